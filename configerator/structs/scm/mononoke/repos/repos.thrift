@@ -188,6 +188,19 @@ struct RawBlobstoreLogging {
 struct RawBlobstorePack {
     1: RawBlobstoreConfig blobstore (rust.box),
 }
+struct RawBlobstoreDiskCache {
+    1: RawBlobstoreConfig blobstore (rust.box),
+    2: string path,
+    3: i64 max_bytes,
+    // Blobs created less than this long ago are not cached. Defaults to 0.
+    4: optional i64 min_admission_age_secs,
+}
+struct RawBlobstoreEncrypted {
+    1: RawBlobstoreConfig blobstore (rust.box),
+    // Each line is a key id and a hex encoded 256 bit key. The last key is
+    // used for new blobs.
+    2: string keyfile,
+}
 struct RawBlobstoreS3 {
     1: string bucket,
     2: string keychain_group,
@@ -213,6 +226,8 @@ union RawBlobstoreConfig {
     9: RawBlobstoreLogging logging,
     10: RawBlobstorePack pack,
     11: RawBlobstoreS3 s3,
+    12: RawBlobstoreDiskCache disk_cache,
+    13: RawBlobstoreEncrypted encrypted,
 }
 
 // A write-mostly blobstore is one that is not read from in normal operation.
@@ -414,6 +429,14 @@ struct RawInfinitepushParams {
 struct RawFilestoreParams {
     1: i64 chunk_size,
     2: i32 concurrency,
+    // If set, chunk boundaries are content-defined, and chunk_size is the
+    // average chunk size
+    3: optional RawFilestoreCdcParams content_defined_chunking,
+}
+
+struct RawFilestoreCdcParams {
+    1: i64 min_chunk_size,
+    2: i64 max_chunk_size,
 }
 
 struct RawCommitSyncSmallRepoConfig {
@@ -423,6 +446,17 @@ struct RawCommitSyncSmallRepoConfig {
     4: string bookmark_prefix,
     5: map<string, string> mapping,
     6: string direction,
+    // Tried in order before the prefix mapping, first match wins
+    7: optional list<RawCommitSyncPathRule> path_rules,
+}
+
+// Either do_not_sync_glob, or both file and target must be set
+struct RawCommitSyncPathRule {
+    // Small repo paths matching this glob are not synced
+    1: optional string do_not_sync_glob,
+    // A single small repo file, synced to target in the large repo
+    2: optional string file,
+    3: optional string target,
 }
 
 struct RawCommitSyncConfig {
@@ -30,7 +30,7 @@ use derived_data_filenodes::FilenodesOnlyPublic;
 use fastlog::RootFastlog;
 use fbinit::FacebookInit;
 use filenodes::Filenodes;
-use filestore::FilestoreConfig;
+use filestore::{ChunkingMethod, FilestoreConfig};
 use fsnodes::RootFsnodeId;
 use futures::{compat::Future01CompatExt, future, try_join};
 use git_types::TreeHandle;
@@ -39,8 +39,8 @@ use memblob::Memblob;
 use mercurial_derived_data::MappedHgChangesetId;
 use mercurial_mutation::{HgMutationStore, SqlHgMutationStoreBuilder};
 use metaconfig_types::{
-    self, CensoredScubaParams, DerivedDataConfig, FilestoreParams, Redaction, RepoConfig,
-    SegmentedChangelogConfig, StorageConfig, UnodeVersion,
+    self, CensoredScubaParams, DerivedDataConfig, FilestoreCdcParams, FilestoreParams, Redaction,
+    RepoConfig, SegmentedChangelogConfig, StorageConfig, UnodeVersion,
 };
 use mononoke_types::RepositoryId;
 use newfilenodes::NewFilenodesBuilder;
@@ -237,10 +237,23 @@ pub async fn open_blobrepo_given_datasources(
             let FilestoreParams {
                 chunk_size,
                 concurrency,
+                content_defined_chunking,
             } = params;
 
+            let chunking = match content_defined_chunking {
+                Some(FilestoreCdcParams {
+                    min_chunk_size,
+                    max_chunk_size,
+                }) => ChunkingMethod::ContentDefined {
+                    min_size: min_chunk_size,
+                    max_size: max_chunk_size,
+                },
+                None => ChunkingMethod::FixedSize,
+            };
+
             FilestoreConfig {
                 chunk_size: Some(chunk_size),
+                chunking,
                 concurrency,
             }
         })
@@ -0,0 +1,135 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+
+struct IndexEntry {
+    size: u64,
+    last_used: u64,
+}
+
+/// In-memory record of what the on-disk cache holds, in least-recently-used order.
+#[derive(Default)]
+pub struct LruIndex {
+    entries: HashMap<String, IndexEntry>,
+    order: BTreeMap<u64, String>,
+    total_bytes: u64,
+    clock: u64,
+}
+
+impl LruIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Mark `key` as most recently used. Returns false if `key` is not in the index.
+    pub fn touch(&mut self, key: &str) -> bool {
+        let now = self.tick();
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                if let Some(key) = self.order.remove(&entry.last_used) {
+                    self.order.insert(now, key);
+                }
+                entry.last_used = now;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Add (or replace) `key` as the most recently used entry.
+    pub fn insert(&mut self, key: String, size: u64) {
+        self.remove(&key);
+        let now = self.tick();
+        self.total_bytes += size;
+        self.order.insert(now, key.clone());
+        self.entries.insert(
+            key,
+            IndexEntry {
+                size,
+                last_used: now,
+            },
+        );
+    }
+
+    /// Drop `key` from the index, returning its size if it was present.
+    pub fn remove(&mut self, key: &str) -> Option<u64> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.last_used);
+        self.total_bytes -= entry.size;
+        Some(entry.size)
+    }
+
+    /// Drop least recently used entries until the total size is no more than `max_bytes`.
+    /// Returns the keys that were dropped, so that the caller can delete them from disk.
+    pub fn evict(&mut self, max_bytes: u64) -> Vec<String> {
+        let mut evicted = vec![];
+        while self.total_bytes > max_bytes {
+            let oldest = match self.order.keys().next() {
+                Some(oldest) => *oldest,
+                None => break,
+            };
+            if let Some(key) = self.order.remove(&oldest) {
+                if let Some(entry) = self.entries.remove(&key) {
+                    self.total_bytes -= entry.size;
+                }
+                evicted.push(key);
+            }
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let mut index = LruIndex::new();
+        index.insert("a".to_string(), 10);
+        index.insert("b".to_string(), 10);
+        index.insert("c".to_string(), 10);
+        assert_eq!(index.total_bytes(), 30);
+
+        assert!(index.touch("a"));
+        assert!(!index.touch("missing"));
+
+        assert_eq!(index.evict(20), vec!["b".to_string()]);
+        assert_eq!(index.evict(5), vec!["c".to_string(), "a".to_string()]);
+        assert_eq!(index.total_bytes(), 0);
+        assert_eq!(index.len(), 0);
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut index = LruIndex::new();
+        index.insert("a".to_string(), 10);
+        index.insert("a".to_string(), 4);
+        assert_eq!(index.total_bytes(), 4);
+        assert_eq!(index.len(), 1);
+        assert_eq!(index.remove("a"), Some(4));
+        assert_eq!(index.remove("a"), None);
+    }
+}
@@ -0,0 +1,416 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+mod index;
+
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use percent_encoding::{percent_decode_str, percent_encode, AsciiSet, CONTROLS};
+use tempfile::NamedTempFile;
+use tokio::{fs::File, io::AsyncWriteExt};
+
+use blobstore::{
+    Blobstore, BlobstoreGetData, BlobstoreMetadata, BlobstorePutOps, OverwriteStatus, PutBehaviour,
+};
+use context::CoreContext;
+use mononoke_types::BlobstoreBytes;
+
+use crate::index::LruIndex;
+
+const PREFIX: &str = "blob-";
+/// https://url.spec.whatwg.org/#fragment-percent-encode-set
+const FRAGMENT: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`');
+/// https://url.spec.whatwg.org/#path-percent-encode-set, plus '/' so that each key is one file
+const PATH: &AsciiSet = &FRAGMENT.add(b'#').add(b'?').add(b'{').add(b'}').add(b'/');
+
+#[derive(Clone, Copy, Debug)]
+pub struct DiskCacheOptions {
+    // The most data to keep on disk. Least recently used blobs are evicted beyond this.
+    pub max_bytes: u64,
+    // Only blobs whose ctime is at least this old are admitted to the cache. Blobs without a
+    // ctime are always admitted.
+    pub min_admission_age: Duration,
+}
+
+impl DiskCacheOptions {
+    pub fn new(max_bytes: u64, min_admission_age: Duration) -> Self {
+        Self {
+            max_bytes,
+            min_admission_age,
+        }
+    }
+
+    fn admits(&self, meta: &BlobstoreMetadata) -> bool {
+        if self.min_admission_age == Duration::from_secs(0) {
+            return true;
+        }
+        match meta.ctime() {
+            Some(ctime) => now_secs() - ctime >= self.min_admission_age.as_secs() as i64,
+            None => true,
+        }
+    }
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .ok()
+        .and_then(|now| i64::try_from(now.as_secs()).ok())
+        .unwrap_or(0)
+}
+
+struct DiskCache {
+    base: PathBuf,
+    options: DiskCacheOptions,
+    index: Mutex<LruIndex>,
+}
+
+impl DiskCache {
+    fn open(base: &Path, options: DiskCacheOptions) -> Result<Self> {
+        fs::create_dir_all(base)?;
+        if !base.is_dir() {
+            bail!("Cache directory {:?} is not a directory", base);
+        }
+
+        // Rebuild the index from what is already on disk, oldest first, so that a restart keeps
+        // the cache warm.
+        let mut found = vec![];
+        for entry in fs::read_dir(base)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let key = name
+                .to_str()
+                .filter(|name| name.starts_with(PREFIX))
+                .and_then(|name| percent_decode_str(&name[PREFIX.len()..]).decode_utf8().ok());
+            match key {
+                Some(key) => {
+                    let meta = entry.metadata()?;
+                    let last_used = meta.accessed().ok().max(meta.modified().ok());
+                    found.push((last_used, key.into_owned(), meta.len()));
+                }
+                None => {
+                    // Leftover from an interrupted write.
+                    let _ = fs::remove_file(entry.path());
+                }
+            }
+        }
+        found.sort();
+
+        let mut index = LruIndex::new();
+        for (_, key, size) in found {
+            index.insert(key, size);
+        }
+
+        let cache = Self {
+            base: base.to_owned(),
+            options,
+            index: Mutex::new(index),
+        };
+        cache.evict();
+        Ok(cache)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let key = percent_encode(key.as_bytes(), PATH);
+        self.base.join(format!("{}{}", PREFIX, key))
+    }
+
+    fn evict(&self) {
+        let evicted = self
+            .index
+            .lock()
+            .expect("lock poisoned")
+            .evict(self.options.max_bytes);
+        for key in evicted {
+            let _ = fs::remove_file(self.path(&key));
+        }
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.index.lock().expect("lock poisoned").contains(key)
+    }
+
+    fn touch(&self, key: &str) -> bool {
+        self.index.lock().expect("lock poisoned").touch(key)
+    }
+
+    fn forget(&self, key: &str) {
+        self.index.lock().expect("lock poisoned").remove(key);
+        let _ = fs::remove_file(self.path(key));
+    }
+
+    async fn get(&self, key: &str) -> Option<BlobstoreGetData> {
+        if !self.touch(key) {
+            return None;
+        }
+        let decoded = tokio::fs::read(self.path(key))
+            .await
+            .ok()
+            .and_then(|bytes| BlobstoreGetData::decode(Bytes::from(bytes)).ok());
+        if decoded.is_none() {
+            // Evicted under us, or damaged. Either way, it's of no use.
+            self.forget(key);
+        }
+        decoded
+    }
+
+    async fn put(&self, key: &str, value: BlobstoreGetData) {
+        if !self.options.admits(value.as_meta()) {
+            // Make sure we do not keep serving an older value.
+            if self.contains(key) {
+                self.forget(key);
+            }
+            return;
+        }
+        let bytes = match value.encode(None) {
+            Ok(bytes) => bytes,
+            Err(()) => return,
+        };
+        let size = bytes.len() as u64;
+        if size > self.options.max_bytes {
+            return;
+        }
+        match self.write(key, bytes).await {
+            Ok(()) => {
+                self.index
+                    .lock()
+                    .expect("lock poisoned")
+                    .insert(key.to_string(), size);
+                self.evict();
+            }
+            Err(_) => self.forget(key),
+        }
+    }
+
+    async fn write(&self, key: &str, bytes: Bytes) -> Result<()> {
+        let tempfile = NamedTempFile::new_in(&self.base)?;
+        let mut file = File::from_std(tempfile.as_file().try_clone()?);
+        file.write_all(bytes.as_ref()).await?;
+        file.flush().await?;
+        tempfile.persist(self.path(key))?;
+        Ok(())
+    }
+}
+
+/// A size-bounded, least-recently-used cache on local disk, in front of another (typically
+/// remote) blobstore. Unlike cachelib and memcache, the cache survives restarts.
+#[derive(Clone)]
+pub struct DiskCacheBlob<T> {
+    inner: T,
+    cache: Arc<DiskCache>,
+}
+
+impl<T> DiskCacheBlob<T> {
+    pub fn open<P: AsRef<Path>>(inner: T, path: P, options: DiskCacheOptions) -> Result<Self> {
+        let cache = DiskCache::open(path.as_ref(), options)?;
+        Ok(Self {
+            inner,
+            cache: Arc::new(cache),
+        })
+    }
+
+    /// Number of blobs and total bytes currently held on disk.
+    pub fn cache_usage(&self) -> (usize, u64) {
+        let index = self.cache.index.lock().expect("lock poisoned");
+        (index.len(), index.total_bytes())
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for DiskCacheBlob<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DiskCacheBlob")
+            .field("inner", &self.inner)
+            .field("base", &self.cache.base)
+            .field("options", &self.cache.options)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<T: BlobstorePutOps> Blobstore for DiskCacheBlob<T> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        if let Some(value) = self.cache.get(key).await {
+            return Ok(Some(value));
+        }
+        let value = self.inner.get(ctx, key).await?;
+        if let Some(ref value) = value {
+            self.cache.put(key, value.clone()).await;
+        }
+        Ok(value)
+    }
+
+    async fn is_present<'a>(&'a self, ctx: &'a CoreContext, key: &'a str) -> Result<bool> {
+        if self.cache.contains(key) {
+            return Ok(true);
+        }
+        self.inner.is_present(ctx, key).await
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        BlobstorePutOps::put_with_status(self, ctx, key, value).await?;
+        Ok(())
+    }
+}
+
+impl<T: BlobstorePutOps> DiskCacheBlob<T> {
+    async fn put_impl<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: Option<PutBehaviour>,
+    ) -> Result<OverwriteStatus> {
+        let status = if let Some(put_behaviour) = put_behaviour {
+            self.inner
+                .put_explicit(ctx, key.clone(), value.clone(), put_behaviour)
+                .await?
+        } else {
+            self.inner
+                .put_with_status(ctx, key.clone(), value.clone())
+                .await?
+        };
+        if status != OverwriteStatus::Prevented {
+            let meta = BlobstoreMetadata::new(Some(now_secs()));
+            self.cache
+                .put(&key, BlobstoreGetData::new(meta, value))
+                .await;
+        }
+        Ok(status)
+    }
+}
+
+#[async_trait]
+impl<T: BlobstorePutOps> BlobstorePutOps for DiskCacheBlob<T> {
+    async fn put_explicit<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: PutBehaviour,
+    ) -> Result<OverwriteStatus> {
+        self.put_impl(ctx, key, value, Some(put_behaviour)).await
+    }
+
+    async fn put_with_status<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<OverwriteStatus> {
+        self.put_impl(ctx, key, value, None).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use borrowed::borrowed;
+    use fbinit::FacebookInit;
+    use memblob::Memblob;
+
+    fn make_value(value: &str) -> BlobstoreBytes {
+        BlobstoreBytes::from_bytes(Bytes::copy_from_slice(value.as_bytes()))
+    }
+
+    #[fbinit::compat_test]
+    async fn test_read_through(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let dir = tempfile::tempdir()?;
+        let inner = Arc::new(Memblob::default());
+        let options = DiskCacheOptions::new(1024 * 1024, Duration::from_secs(0));
+        let store = DiskCacheBlob::open(inner.clone(), dir.path(), options)?;
+
+        inner
+            .put(ctx, "key/1".to_string(), make_value("one"))
+            .await?;
+        assert_eq!(store.cache_usage().0, 0);
+
+        let fetched = store.get(ctx, "key/1").await?.map(|v| v.into_bytes());
+        assert_eq!(fetched, Some(make_value("one")));
+        assert_eq!(store.cache_usage().0, 1);
+
+        // Served from disk, even after the backing store loses it, and after a restart.
+        inner.unlink("key/1".to_string()).await?;
+        let store = DiskCacheBlob::open(inner.clone(), dir.path(), options)?;
+        let fetched = store.get(ctx, "key/1").await?.map(|v| v.into_bytes());
+        assert_eq!(fetched, Some(make_value("one")));
+        assert!(store.is_present(ctx, "key/1").await?);
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_eviction(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let dir = tempfile::tempdir()?;
+        let inner = Arc::new(Memblob::default());
+        let value = make_value(&"x".repeat(100));
+        let entry_size = BlobstoreGetData::from_bytes(value.clone().into_bytes())
+            .encode(None)
+            .map_err(|()| anyhow::format_err!("encode failed"))?
+            .len() as u64;
+        let options = DiskCacheOptions::new(entry_size * 2, Duration::from_secs(0));
+        let store = DiskCacheBlob::open(inner.clone(), dir.path(), options)?;
+
+        for key in &["a", "b", "c"] {
+            inner.put(ctx, key.to_string(), value.clone()).await?;
+        }
+        store.get(ctx, "a").await?;
+        store.get(ctx, "b").await?;
+        // Make "a" the most recently used, so "b" is evicted next.
+        store.get(ctx, "a").await?;
+        store.get(ctx, "c").await?;
+
+        assert_eq!(store.cache_usage(), (2, entry_size * 2));
+        assert!(store.cache.get("a").await.is_some());
+        assert!(store.cache.get("b").await.is_none());
+        assert!(store.cache.get("c").await.is_some());
+        assert!(!dir.path().join("blob-b").exists());
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn test_ctime_admission(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let dir = tempfile::tempdir()?;
+        let inner = Arc::new(Memblob::default());
+        let options = DiskCacheOptions::new(1024 * 1024, Duration::from_secs(3600));
+        let store = DiskCacheBlob::open(inner.clone(), dir.path(), options)?;
+
+        // A fresh write is too young to be cached.
+        store.put(ctx, "new".to_string(), make_value("new")).await?;
+        assert_eq!(store.cache_usage().0, 0);
+
+        let old = BlobstoreGetData::new(
+            BlobstoreMetadata::new(Some(now_secs() - 7200)),
+            make_value("old"),
+        );
+        store.cache.put("old", old).await;
+        assert_eq!(store.cache_usage().0, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{bail, format_err, Error, Result};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use mononoke_types::BlobstoreBytes;
+use openssl::{
+    rand::rand_bytes,
+    symm::{decrypt_aead, encrypt_aead, Cipher},
+};
+use std::{
+    convert::{TryFrom, TryInto},
+    mem::size_of,
+};
+
+use crate::keys::EncryptionKeys;
+
+pub const NONCE_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum HeaderType {
+    Aes256Gcm,
+}
+
+impl TryFrom<u32> for HeaderType {
+    type Error = Error;
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            // We can use other values for other ciphers in future
+            0 => Ok(HeaderType::Aes256Gcm),
+            _ => Err(format_err!(
+                "Unknown header value for encryptedblob {}",
+                value
+            )),
+        }
+    }
+}
+
+impl From<HeaderType> for u32 {
+    fn from(value: HeaderType) -> u32 {
+        match value {
+            HeaderType::Aes256Gcm => 0,
+        }
+    }
+}
+
+/// At-rest form of an encrypted blob:
+///
+/// ```text
+/// u32 header | u8 key id length | key id | nonce | tag | ciphertext
+/// ```
+///
+/// The blobstore key the blob is stored under is used as additional authenticated data, so a
+/// blob copied to a different key will fail to decrypt.
+#[derive(Debug, PartialEq)]
+pub(crate) struct EncryptedEnvelope {
+    header: HeaderType,
+    pub key_id: String,
+    nonce: [u8; NONCE_LEN],
+    tag: [u8; TAG_LEN],
+    ciphertext: Bytes,
+}
+
+impl EncryptedEnvelope {
+    pub fn encrypt(keys: &EncryptionKeys, blobstore_key: &str, plaintext: &[u8]) -> Result<Self> {
+        let (key_id, key) = keys.active();
+        let mut nonce = [0; NONCE_LEN];
+        rand_bytes(&mut nonce)?;
+        let mut tag = [0; TAG_LEN];
+        let ciphertext = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            key,
+            Some(&nonce),
+            blobstore_key.as_bytes(),
+            plaintext,
+            &mut tag,
+        )?;
+        Ok(Self {
+            header: HeaderType::Aes256Gcm,
+            key_id: key_id.to_string(),
+            nonce,
+            tag,
+            ciphertext: Bytes::from(ciphertext),
+        })
+    }
+
+    pub fn decrypt(&self, keys: &EncryptionKeys, blobstore_key: &str) -> Result<Bytes> {
+        let key = keys
+            .get(&self.key_id)
+            .ok_or_else(|| format_err!("Unknown encryption key id {}", self.key_id))?;
+        let plaintext = match self.header {
+            HeaderType::Aes256Gcm => decrypt_aead(
+                Cipher::aes_256_gcm(),
+                key,
+                Some(&self.nonce),
+                blobstore_key.as_bytes(),
+                &self.ciphertext,
+                &self.tag,
+            )
+            .map_err(|_| format_err!("Failed to decrypt {}", blobstore_key))?,
+        };
+        Ok(Bytes::from(plaintext))
+    }
+}
+
+impl TryFrom<BlobstoreBytes> for EncryptedEnvelope {
+    type Error = Error;
+
+    fn try_from(bytes: BlobstoreBytes) -> Result<Self, Error> {
+        let mut bytes = bytes.into_bytes();
+        if bytes.len() < size_of::<u32>() + 1 {
+            bail!("Encrypted blob is truncated");
+        }
+        let header = HeaderType::try_from(bytes.get_u32())?;
+        let key_id_len = bytes.get_u8() as usize;
+        if bytes.len() < key_id_len + NONCE_LEN + TAG_LEN {
+            bail!("Encrypted blob is truncated");
+        }
+        let key_id = String::from_utf8(bytes.split_to(key_id_len).to_vec())?;
+        let nonce = bytes.split_to(NONCE_LEN).as_ref().try_into()?;
+        let tag = bytes.split_to(TAG_LEN).as_ref().try_into()?;
+        Ok(EncryptedEnvelope {
+            header,
+            key_id,
+            nonce,
+            tag,
+            ciphertext: bytes,
+        })
+    }
+}
+
+impl Into<BlobstoreBytes> for EncryptedEnvelope {
+    fn into(self) -> BlobstoreBytes {
+        let mut buf = BytesMut::with_capacity(
+            size_of::<u32>() + 1 + self.key_id.len() + NONCE_LEN + TAG_LEN + self.ciphertext.len(),
+        );
+        buf.put_u32(self.header.into());
+        buf.put_u8(self.key_id.len() as u8);
+        buf.put_slice(self.key_id.as_bytes());
+        buf.put_slice(&self.nonce);
+        buf.put_slice(&self.tag);
+        buf.put_slice(&self.ciphertext);
+        BlobstoreBytes::from_bytes(buf.freeze())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::KEY_LEN;
+
+    fn keys() -> EncryptionKeys {
+        EncryptionKeys::new(vec![("k1".to_string(), vec![7; KEY_LEN])]).unwrap()
+    }
+
+    #[test]
+    fn envelope_roundtrip() -> Result<()> {
+        let keys = keys();
+        let envelope = EncryptedEnvelope::encrypt(&keys, "key", b"hello world!")?;
+        let bytes: BlobstoreBytes =
+            EncryptedEnvelope::encrypt(&keys, "key", b"hello world!")?.into();
+        let parsed = EncryptedEnvelope::try_from(bytes)?;
+        assert_eq!(parsed.key_id, "k1");
+        assert_eq!(parsed.decrypt(&keys, "key")?, Bytes::from("hello world!"));
+        // Fresh nonce every time
+        assert_ne!(envelope.nonce, parsed.nonce);
+        Ok(())
+    }
+
+    #[test]
+    fn envelope_tamper() -> Result<()> {
+        let keys = keys();
+        let envelope = EncryptedEnvelope::encrypt(&keys, "key", b"hello world!")?;
+        assert!(envelope.decrypt(&keys, "other_key").is_err());
+
+        let bytes: BlobstoreBytes = envelope.into();
+        let mut tampered = bytes.into_bytes().to_vec();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        let parsed = EncryptedEnvelope::try_from(BlobstoreBytes::from_bytes(tampered))?;
+        assert!(parsed.decrypt(&keys, "key").is_err());
+
+        assert!(EncryptedEnvelope::try_from(BlobstoreBytes::from_bytes(vec![0, 0, 0, 0])).is_err());
+        Ok(())
+    }
+}
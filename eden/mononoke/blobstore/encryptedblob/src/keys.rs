@@ -0,0 +1,123 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use anyhow::{bail, format_err, Context, Result};
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+/// Length in bytes of an AES-256 key
+pub const KEY_LEN: usize = 32;
+
+/// The keys an `EncryptedBlob` can decrypt with, and the one it encrypts new blobs with.
+///
+/// To rotate keys, add a new key and make it active. Blobs written with older keys can still be
+/// read for as long as their key is kept in the set.
+#[derive(Clone)]
+pub struct EncryptionKeys {
+    keys: HashMap<String, Vec<u8>>,
+    active: String,
+}
+
+impl EncryptionKeys {
+    /// Create a key set from `(key_id, key)` pairs. The last key is the active one.
+    pub fn new(keys: Vec<(String, Vec<u8>)>) -> Result<Self> {
+        let active = match keys.last() {
+            Some((key_id, _)) => key_id.clone(),
+            None => bail!("No encryption keys given"),
+        };
+        let mut map = HashMap::new();
+        for (key_id, key) in keys {
+            if key_id.is_empty() || key_id.len() > u8::max_value() as usize {
+                bail!("Key id {:?} must be between 1 and 255 bytes long", key_id);
+            }
+            if key.len() != KEY_LEN {
+                bail!("Key {} must be {} bytes long", key_id, KEY_LEN);
+            }
+            if map.insert(key_id.clone(), key).is_some() {
+                bail!("Key {} is defined more than once", key_id);
+            }
+        }
+        Ok(Self { keys: map, active })
+    }
+
+    /// Parse a keyfile. Each line is a key id followed by whitespace and the key as hex. Blank
+    /// lines and lines starting with `#` are ignored. The last key in the file is the active one.
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut keys = vec![];
+        for (lineno, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let (key_id, key) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(key_id), Some(key), None) => (key_id, key),
+                _ => bail!("Line {}: expected a key id and a key", lineno + 1),
+            };
+            let key = hex::decode(key)
+                .map_err(|e| format_err!("Line {}: key is not valid hex: {}", lineno + 1, e))?;
+            keys.push((key_id.to_string(), key));
+        }
+        Self::new(keys)
+    }
+
+    pub fn from_keyfile<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("While reading keyfile {:?}", path))?;
+        Self::parse(&content).with_context(|| format!("While parsing keyfile {:?}", path))
+    }
+
+    /// The id and key to encrypt new blobs with.
+    pub fn active(&self) -> (&str, &[u8]) {
+        (&self.active, &self.keys[&self.active])
+    }
+
+    pub fn get(&self, key_id: &str) -> Option<&[u8]> {
+        self.keys.get(key_id).map(|key| key.as_slice())
+    }
+}
+
+impl fmt::Debug for EncryptionKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the keys themselves
+        let mut key_ids: Vec<_> = self.keys.keys().collect();
+        key_ids.sort();
+        f.debug_struct("EncryptionKeys")
+            .field("key_ids", &key_ids)
+            .field("active", &self.active)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_keyfile() -> Result<()> {
+        let content = format!(
+            "# old key\nk1 {}\n\nk2   {}\n",
+            "00".repeat(KEY_LEN),
+            "ff".repeat(KEY_LEN)
+        );
+        let keys = EncryptionKeys::parse(&content)?;
+        assert_eq!(keys.active(), ("k2", &[0xff; KEY_LEN][..]));
+        assert_eq!(keys.get("k1"), Some(&[0; KEY_LEN][..]));
+        assert_eq!(keys.get("k3"), None);
+        assert!(!format!("{:?}", keys).contains("ffff"));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_bad_keyfile() {
+        assert!(EncryptionKeys::parse("").is_err());
+        assert!(EncryptionKeys::parse("k1 abcd").is_err());
+        assert!(EncryptionKeys::parse("k1 zz").is_err());
+        let key = "00".repeat(KEY_LEN);
+        assert!(EncryptionKeys::parse(&format!("k1 {}\nk1 {}", key, key)).is_err());
+    }
+}
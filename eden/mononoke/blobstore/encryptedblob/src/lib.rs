@@ -0,0 +1,15 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+mod envelope;
+mod keys;
+mod store;
+
+pub use keys::EncryptionKeys;
+pub use store::EncryptedBlob;
@@ -0,0 +1,234 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::envelope::EncryptedEnvelope;
+use crate::keys::EncryptionKeys;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use blobstore::{Blobstore, BlobstoreGetData, BlobstorePutOps, OverwriteStatus, PutBehaviour};
+use context::CoreContext;
+use mononoke_types::BlobstoreBytes;
+use std::{convert::TryInto, sync::Arc};
+
+/// A layer over an existing blobstore that encrypts blobs before they reach it, so that the
+/// underlying storage only ever sees ciphertext.
+#[derive(Clone, Debug)]
+pub struct EncryptedBlob<T> {
+    inner: T,
+    keys: Arc<EncryptionKeys>,
+}
+
+impl<T> EncryptedBlob<T> {
+    pub fn new(inner: T, keys: EncryptionKeys) -> Self {
+        Self {
+            inner,
+            keys: Arc::new(keys),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: Blobstore + BlobstorePutOps> Blobstore for EncryptedBlob<T> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        let inner_get_data = match self.inner.get(ctx, key).await? {
+            Some(inner_get_data) => inner_get_data,
+            None => return Ok(None),
+        };
+
+        let meta = inner_get_data.as_meta().clone();
+        let envelope: EncryptedEnvelope = inner_get_data
+            .into_bytes()
+            .try_into()
+            .with_context(|| format!("While parsing encrypted envelope for {:?}", key))?;
+        let plaintext = envelope.decrypt(&self.keys, key)?;
+
+        Ok(Some(BlobstoreGetData::new(
+            meta,
+            BlobstoreBytes::from_bytes(plaintext),
+        )))
+    }
+
+    async fn is_present<'a>(&'a self, ctx: &'a CoreContext, key: &'a str) -> Result<bool> {
+        self.inner.is_present(ctx, key).await
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        BlobstorePutOps::put_with_status(self, ctx, key, value).await?;
+        Ok(())
+    }
+}
+
+impl<T: BlobstorePutOps> EncryptedBlob<T> {
+    async fn put_impl<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: Option<PutBehaviour>,
+    ) -> Result<OverwriteStatus> {
+        let envelope = EncryptedEnvelope::encrypt(&self.keys, &key, value.as_bytes())
+            .with_context(|| format!("While encrypting {:?}", key))?;
+
+        if let Some(put_behaviour) = put_behaviour {
+            self.inner
+                .put_explicit(ctx, key, envelope.into(), put_behaviour)
+                .await
+        } else {
+            self.inner.put_with_status(ctx, key, envelope.into()).await
+        }
+    }
+
+    /// Re-encrypt the blob at `key` with the active key, if it was written with an older one.
+    /// Returns `true` if the blob was rewritten. Use this to migrate blobs after a key rotation,
+    /// before the old key is removed from the keyfile.
+    pub async fn reencrypt<'a>(&'a self, ctx: &'a CoreContext, key: &'a str) -> Result<bool> {
+        let inner_get_data = match self.inner.get(ctx, key).await? {
+            Some(inner_get_data) => inner_get_data,
+            None => return Ok(false),
+        };
+        let envelope: EncryptedEnvelope = inner_get_data.into_bytes().try_into()?;
+        let (active_key_id, _) = self.keys.active();
+        if envelope.key_id == active_key_id {
+            return Ok(false);
+        }
+
+        let plaintext = envelope.decrypt(&self.keys, key)?;
+        self.put_impl(
+            ctx,
+            key.to_string(),
+            BlobstoreBytes::from_bytes(plaintext),
+            Some(PutBehaviour::Overwrite),
+        )
+        .await?;
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl<B: BlobstorePutOps> BlobstorePutOps for EncryptedBlob<B> {
+    async fn put_explicit<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: PutBehaviour,
+    ) -> Result<OverwriteStatus> {
+        self.put_impl(ctx, key, value, Some(put_behaviour)).await
+    }
+
+    async fn put_with_status<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<OverwriteStatus> {
+        self.put_impl(ctx, key, value, None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::KEY_LEN;
+    use borrowed::borrowed;
+    use bytes::Bytes;
+    use fbinit::FacebookInit;
+    use memblob::Memblob;
+
+    fn keys(key_ids: &[&str]) -> EncryptionKeys {
+        EncryptionKeys::new(
+            key_ids
+                .iter()
+                .enumerate()
+                .map(|(i, key_id)| (key_id.to_string(), vec![i as u8; KEY_LEN]))
+                .collect(),
+        )
+        .unwrap()
+    }
+
+    #[fbinit::compat_test]
+    async fn roundtrip_test(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let inner = Arc::new(Memblob::default());
+        let store = EncryptedBlob::new(inner.clone(), keys(&["k1"]));
+
+        let value = BlobstoreBytes::from_bytes(Bytes::copy_from_slice(b"secret data"));
+        store
+            .put(ctx, "repo0000.key".to_string(), value.clone())
+            .await?;
+
+        let fetched = store.get(ctx, "repo0000.key").await?;
+        assert_eq!(fetched.map(|v| v.into_bytes()), Some(value.clone()));
+        assert!(store.is_present(ctx, "repo0000.key").await?);
+        assert_eq!(store.get(ctx, "repo0000.missing").await?, None);
+
+        let raw = inner
+            .get(ctx, "repo0000.key")
+            .await?
+            .expect("inner blob missing")
+            .into_raw_bytes();
+        assert!(!raw
+            .windows(value.len())
+            .any(|window| window == value.as_bytes().as_ref()));
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn key_rotation_test(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let inner = Arc::new(Memblob::default());
+        let value = BlobstoreBytes::from_bytes(Bytes::copy_from_slice(b"secret data"));
+
+        let old_store = EncryptedBlob::new(inner.clone(), keys(&["k1"]));
+        old_store.put(ctx, "key".to_string(), value.clone()).await?;
+
+        // After rotation, old blobs are still readable, and can be migrated to the new key.
+        let new_store = EncryptedBlob::new(inner.clone(), keys(&["k1", "k2"]));
+        let fetched = new_store.get(ctx, "key").await?;
+        assert_eq!(fetched.map(|v| v.into_bytes()), Some(value.clone()));
+        assert!(new_store.reencrypt(ctx, "key").await?);
+        assert!(!new_store.reencrypt(ctx, "key").await?);
+
+        // Now the old key is no longer needed.
+        let rotated_store = EncryptedBlob::new(inner.clone(), keys(&["k0", "k2"]));
+        let fetched = rotated_store.get(ctx, "key").await?;
+        assert_eq!(fetched.map(|v| v.into_bytes()), Some(value.clone()));
+        assert!(old_store.get(ctx, "key").await.is_err());
+        Ok(())
+    }
+
+    #[fbinit::compat_test]
+    async fn moved_blob_test(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let inner = Arc::new(Memblob::default());
+        let store = EncryptedBlob::new(inner.clone(), keys(&["k1"]));
+
+        let value = BlobstoreBytes::from_bytes(Bytes::copy_from_slice(b"secret data"));
+        store.put(ctx, "key".to_string(), value).await?;
+
+        // Ciphertext is bound to its key
+        let raw = inner.get(ctx, "key").await?.expect("inner blob missing");
+        inner
+            .put(ctx, "other_key".to_string(), raw.into_bytes())
+            .await?;
+        assert!(store.get(ctx, "other_key").await.is_err());
+        Ok(())
+    }
+}
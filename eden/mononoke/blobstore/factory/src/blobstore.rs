@@ -13,6 +13,8 @@ use blobstore_sync_queue::SqlBlobstoreSyncQueue;
 use cacheblob::CachelibBlobstoreOptions;
 use cached_config::ConfigStore;
 use chaosblob::{ChaosBlobstore, ChaosOptions};
+use diskcacheblob::{DiskCacheBlob, DiskCacheOptions};
+use encryptedblob::{EncryptedBlob, EncryptionKeys};
 use fbinit::FacebookInit;
 use fileblob::Fileblob;
 use futures::{
@@ -35,6 +37,7 @@ use sqlblob::{CountedSqlblob, Sqlblob};
 use std::num::{NonZeroU64, NonZeroUsize};
 use std::sync::Arc;
 use throttledblob::{ThrottleOptions, ThrottledBlob};
+use validatingblob::ValidatingBlob;
 
 use crate::ReadOnlyStorage;
 
@@ -46,6 +49,7 @@ pub struct BlobstoreOptions {
     pub pack_options: PackOptions,
     pub cachelib_options: CachelibBlobstoreOptions,
     pub put_behaviour: PutBehaviour,
+    pub validate_gets: bool,
 }
 
 impl BlobstoreOptions {
@@ -56,6 +60,7 @@ impl BlobstoreOptions {
         pack_options: PackOptions,
         cachelib_options: CachelibBlobstoreOptions,
         put_behaviour: Option<PutBehaviour>,
+        validate_gets: bool,
     ) -> Self {
         Self {
             chaos_options,
@@ -65,6 +70,7 @@ impl BlobstoreOptions {
             cachelib_options,
             // If not specified, maintain status quo, which is overwrite
             put_behaviour: put_behaviour.unwrap_or(DEFAULT_PUT_BEHAVIOUR),
+            validate_gets,
         }
     }
 }
@@ -78,6 +84,7 @@ impl Default for BlobstoreOptions {
             PackOptions::default(),
             CachelibBlobstoreOptions::default(),
             None,
+            false,
         )
     }
 }
@@ -398,6 +405,46 @@ pub fn make_blobstore_put_ops<'a>(
                 Arc::new(PackBlob::new(store, blobstore_options.pack_options.clone()))
                     as Arc<dyn BlobstorePutOps>
             }
+            DiskCache {
+                blobconfig,
+                path,
+                max_bytes,
+                min_admission_age,
+            } => {
+                let store = make_blobstore_put_ops(
+                    fb,
+                    *blobconfig,
+                    mysql_options,
+                    readonly_storage,
+                    &blobstore_options,
+                    logger,
+                    config_store,
+                )
+                .await?;
+
+                let options = DiskCacheOptions::new(max_bytes, min_admission_age);
+                DiskCacheBlob::open(store, path, options)
+                    .context(ErrorKind::StateOpen)
+                    .map(|store| Arc::new(store) as Arc<dyn BlobstorePutOps>)?
+            }
+            Encrypted {
+                blobconfig,
+                keyfile,
+            } => {
+                let store = make_blobstore_put_ops(
+                    fb,
+                    *blobconfig,
+                    mysql_options,
+                    readonly_storage,
+                    &blobstore_options,
+                    logger,
+                    config_store,
+                )
+                .await?;
+
+                let keys = EncryptionKeys::from_keyfile(keyfile).context(ErrorKind::StateOpen)?;
+                Arc::new(EncryptedBlob::new(store, keys)) as Arc<dyn BlobstorePutOps>
+            }
             S3 {
                 bucket,
                 keychain_group,
@@ -478,6 +525,12 @@ pub async fn make_blobstore_multiplexed<'a>(
         _ => readonly_storage,
     };
 
+    // Components report corrupt blobs to the scrub handler if there is one
+    let validation_handler = match &scrub_args {
+        Some((scrub_handler, _)) => scrub_handler.clone(),
+        None => Arc::new(LoggingScrubHandler::new(false)) as Arc<dyn ScrubHandler>,
+    };
+
     let mut applied_chaos = false;
 
     let components = future::try_join_all(inner_config.into_iter().map({
@@ -495,6 +548,7 @@ pub async fn make_blobstore_multiplexed<'a>(
                 }
             }
 
+            let validation_handler = validation_handler.clone();
             async move {
                 let store = make_blobstore_put_ops(
                     fb,
@@ -507,6 +561,13 @@ pub async fn make_blobstore_multiplexed<'a>(
                 )
                 .await?;
 
+                let store = if blobstore_options.validate_gets {
+                    Arc::new(ValidatingBlob::new(store, blobstoreid, validation_handler))
+                        as Arc<dyn BlobstorePutOps>
+                } else {
+                    store
+                };
+
                 Ok((blobstoreid, store_type, store))
             }
         }
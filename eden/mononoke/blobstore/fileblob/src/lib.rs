@@ -15,17 +15,18 @@ use std::time::SystemTime;
 
 use anyhow::{bail, format_err, Result};
 use async_trait::async_trait;
-use percent_encoding::{percent_encode, AsciiSet, CONTROLS};
+use percent_encoding::{percent_decode_str, percent_encode, AsciiSet, CONTROLS};
 
 use blobstore::{
     Blobstore, BlobstoreEnumerationData, BlobstoreGetData, BlobstoreKeyParam, BlobstoreKeySource,
-    BlobstoreMetadata, BlobstorePutOps, BlobstoreWithLink, OverwriteStatus, PutBehaviour,
+    BlobstoreMetadata, BlobstorePutOps, BlobstoreUnlinkOps, BlobstoreWithLink, OverwriteStatus,
+    PutBehaviour,
 };
 use context::CoreContext;
 use mononoke_types::BlobstoreBytes;
 use tempfile::{NamedTempFile, PersistError};
 use tokio::{
-    fs::{hard_link, File},
+    fs::{hard_link, remove_file, File},
     io::{self, AsyncReadExt, AsyncWriteExt},
 };
 
@@ -194,6 +195,17 @@ impl BlobstoreWithLink for Fileblob {
     }
 }
 
+#[async_trait]
+impl BlobstoreUnlinkOps for Fileblob {
+    async fn unlink<'a>(&'a self, _ctx: &'a CoreContext, key: &'a str) -> Result<()> {
+        match remove_file(self.path(key)).await {
+            Ok(()) => Ok(()),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
 #[async_trait]
 impl BlobstoreKeySource for Fileblob {
     async fn enumerate<'a>(
@@ -207,15 +219,22 @@ impl BlobstoreKeySource for Fileblob {
                     keys: HashSet::new(),
                     next_token: None,
                 };
+                let prefix = format!("{}-", PREFIX);
                 WalkDir::new(&self.base)
+                    .min_depth(1)
+                    .max_depth(1)
                     .into_iter()
                     .filter_map(|v| v.ok())
                     .for_each(|entry| {
-                        let entry = entry.path().to_str();
-                        if let Some(data) = entry {
-                            let key = data.to_string();
-                            if key < range.end_key && key > range.begin_key {
-                                enum_data.keys.insert(key);
+                        // Undo the encoding done by `path` to get back to the key
+                        let key = entry
+                            .file_name()
+                            .to_str()
+                            .and_then(|name| name.strip_prefix(prefix.as_str()))
+                            .and_then(|key| percent_decode_str(key).decode_utf8().ok());
+                        if let Some(key) = key {
+                            if range.contains(&key) {
+                                enum_data.keys.insert(key.into_owned());
                             }
                         }
                     });
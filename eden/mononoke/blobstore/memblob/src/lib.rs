@@ -5,7 +5,7 @@
  * GNU General Public License version 2.
  */
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::sync::{Arc, Mutex};
 
@@ -14,7 +14,8 @@ use async_trait::async_trait;
 use futures::future::{BoxFuture, FutureExt};
 
 use blobstore::{
-    Blobstore, BlobstoreGetData, BlobstorePutOps, BlobstoreWithLink, OverwriteStatus, PutBehaviour,
+    Blobstore, BlobstoreEnumerationData, BlobstoreGetData, BlobstoreKeyParam, BlobstoreKeySource,
+    BlobstorePutOps, BlobstoreUnlinkOps, BlobstoreWithLink, OverwriteStatus, PutBehaviour,
     DEFAULT_PUT_BEHAVIOUR,
 };
 use context::CoreContext;
@@ -179,6 +180,45 @@ impl BlobstoreWithLink for Memblob {
     }
 }
 
+#[async_trait]
+impl BlobstoreUnlinkOps for Memblob {
+    async fn unlink<'a>(&'a self, _ctx: &'a CoreContext, key: &'a str) -> Result<()> {
+        let state = self.state.clone();
+
+        let mut inner = state.lock().expect("lock poison");
+        inner.unlink(key);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BlobstoreKeySource for Memblob {
+    async fn enumerate<'a>(
+        &'a self,
+        _ctx: &'a CoreContext,
+        range: &'a BlobstoreKeyParam,
+    ) -> Result<BlobstoreEnumerationData> {
+        match range {
+            BlobstoreKeyParam::Start(ref range) => {
+                let state = self.state.clone();
+
+                let inner = state.lock().expect("lock poison");
+                let keys: HashSet<String> = inner
+                    .links
+                    .keys()
+                    .filter(|key| range.contains(key))
+                    .cloned()
+                    .collect();
+                Ok(BlobstoreEnumerationData {
+                    keys,
+                    next_token: None,
+                })
+            }
+            _ => Err(format_err!("Memblob does not support token, only ranges")),
+        }
+    }
+}
+
 impl fmt::Debug for Memblob {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Memblob")
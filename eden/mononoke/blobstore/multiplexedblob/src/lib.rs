@@ -9,9 +9,11 @@
 
 pub mod base;
 pub mod queue;
+pub mod range_scrub;
 pub mod scrub;
 
 pub use crate::queue::MultiplexedBlobstore;
+pub use crate::range_scrub::{scrub_range, RangeScrubSummary, StoreScrubSummary};
 pub use crate::scrub::{LoggingScrubHandler, ScrubBlobstore, ScrubHandler};
 
 #[cfg(test)]
@@ -0,0 +1,242 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::scrub::ScrubHandler;
+
+use anyhow::Result;
+use blobstore::{BlobstoreGetData, BlobstoreKeyParam, BlobstoreKeySource};
+use context::CoreContext;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use metaconfig_types::{BlobstoreId, ScrubAction};
+use serde_derive::Serialize;
+use slog::debug;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::Arc;
+
+/// What a range scrub found (and did) for one component of the multiplex.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize)]
+pub struct StoreScrubSummary {
+    /// Keys in the range that this store has.
+    pub present: u64,
+    /// Keys in the range that some other store has, but this one does not.
+    pub missing: u64,
+    /// Missing keys that were written back to this store.
+    pub repaired: u64,
+    /// Total size of the values written back to this store.
+    pub repaired_bytes: u64,
+    /// Missing keys that we failed to write back to this store.
+    pub repair_failed: u64,
+}
+
+/// The outcome of `scrub_range`, suitable for reporting as JSON.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize)]
+pub struct RangeScrubSummary {
+    /// Distinct keys found in the range, across all stores.
+    pub keys: u64,
+    /// Keys that are missing from at least one store.
+    pub inconsistent_keys: u64,
+    /// Per-store details, by blobstore id.
+    pub stores: BTreeMap<String, StoreScrubSummary>,
+    /// Total size of the values written back, across all stores.
+    pub repaired_bytes: u64,
+    /// Keys that are listed by some store, but that no store could give us a value for. These
+    /// cannot be repaired by scrubbing.
+    pub unrecoverable_keys: Vec<String>,
+}
+
+struct KeyOutcome {
+    key: String,
+    missing: Vec<BlobstoreId>,
+    repaired: Vec<(BlobstoreId, bool)>,
+    size: u64,
+    unrecoverable: bool,
+}
+
+/// Enumerate every page of keys in `range` from one store.
+async fn enumerate_all(
+    ctx: &CoreContext,
+    store: &dyn BlobstoreKeySource,
+    range: BlobstoreKeyParam,
+) -> Result<HashSet<String>> {
+    let mut keys = HashSet::new();
+    let mut param = range;
+    loop {
+        let data = store.enumerate(ctx, &param).await?;
+        keys.extend(data.keys);
+        match data.next_token {
+            Some(next) => param = next,
+            None => return Ok(keys),
+        }
+    }
+}
+
+async fn get_from_any(
+    ctx: &CoreContext,
+    stores: &[(BlobstoreId, Arc<dyn BlobstoreKeySource>)],
+    holders: &[BlobstoreId],
+    key: &str,
+) -> Option<BlobstoreGetData> {
+    for (id, store) in stores {
+        if !holders.contains(id) {
+            continue;
+        }
+        match store.get(ctx, key).await {
+            Ok(Some(value)) => return Some(value),
+            Ok(None) => {}
+            Err(e) => {
+                debug!(
+                    ctx.logger(),
+                    "scrub: blobstore_id {:?} failed to get {}: {:?}", id, key, e
+                );
+            }
+        }
+    }
+    None
+}
+
+async fn scrub_key(
+    ctx: &CoreContext,
+    stores: &[(BlobstoreId, Arc<dyn BlobstoreKeySource>)],
+    key: String,
+    holders: Vec<BlobstoreId>,
+    missing: Vec<BlobstoreId>,
+    scrub_action: ScrubAction,
+    scrub_handler: &dyn ScrubHandler,
+) -> Result<KeyOutcome> {
+    let value = match get_from_any(ctx, stores, &holders, &key).await {
+        Some(value) => value,
+        None => {
+            return Ok(KeyOutcome {
+                key,
+                missing,
+                repaired: vec![],
+                size: 0,
+                unrecoverable: true,
+            });
+        }
+    };
+
+    let mut repaired = vec![];
+    for (id, store) in stores {
+        if !missing.contains(id) {
+            continue;
+        }
+        let is_repaired = match scrub_action {
+            ScrubAction::ReportOnly => false,
+            ScrubAction::Repair => store
+                .put(ctx, key.clone(), value.as_bytes().clone())
+                .await
+                .is_ok(),
+        };
+        scrub_handler.on_repair(ctx, *id, &key, is_repaired, value.as_meta());
+        if scrub_action == ScrubAction::Repair {
+            repaired.push((*id, is_repaired));
+        }
+    }
+
+    Ok(KeyOutcome {
+        key,
+        missing,
+        repaired,
+        size: value.as_bytes().len() as u64,
+        unrecoverable: false,
+    })
+}
+
+/// Check that every component of a multiplex has every key in `range`, without needing to know
+/// the keys up front (unlike scrubbing with `ScrubBlobstore`, which only looks at keys as they are
+/// fetched). Keys present in some stores but missing from others are reported to the
+/// `scrub_handler`, and written back to the stores missing them if `scrub_action` says to repair.
+///
+/// Keys written very recently may still be waiting for the healer, and show up as missing.
+pub async fn scrub_range(
+    ctx: &CoreContext,
+    stores: &[(BlobstoreId, Arc<dyn BlobstoreKeySource>)],
+    range: BlobstoreKeyParam,
+    scrub_action: ScrubAction,
+    scrub_handler: &dyn ScrubHandler,
+    concurrency: usize,
+) -> Result<RangeScrubSummary> {
+    let listings = stream::iter(stores.iter())
+        .map(|(id, store)| {
+            let range = range.clone();
+            async move { Ok((*id, enumerate_all(ctx, store.as_ref(), range).await?)) }
+        })
+        .buffered(concurrency)
+        .try_collect::<Vec<(BlobstoreId, HashSet<String>)>>()
+        .await?;
+
+    let mut summary = RangeScrubSummary::default();
+    for (id, keys) in listings.iter() {
+        summary.stores.insert(
+            id.to_string(),
+            StoreScrubSummary {
+                present: keys.len() as u64,
+                ..Default::default()
+            },
+        );
+    }
+
+    let all_keys: BTreeSet<&String> = listings.iter().flat_map(|(_, keys)| keys.iter()).collect();
+    summary.keys = all_keys.len() as u64;
+
+    let inconsistent = all_keys.into_iter().filter_map(|key| {
+        let (holders, missing): (Vec<_>, Vec<_>) = listings
+            .iter()
+            .map(|(id, keys)| (*id, keys.contains(key)))
+            .partition(|(_, present)| *present);
+        if missing.is_empty() {
+            None
+        } else {
+            let holders = holders.into_iter().map(|(id, _)| id).collect();
+            let missing = missing.into_iter().map(|(id, _)| id).collect();
+            Some((key.clone(), holders, missing))
+        }
+    });
+
+    let outcomes = stream::iter(inconsistent)
+        .map(|(key, holders, missing)| {
+            scrub_key(
+                ctx,
+                stores,
+                key,
+                holders,
+                missing,
+                scrub_action,
+                scrub_handler,
+            )
+        })
+        .buffer_unordered(concurrency)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    for outcome in outcomes {
+        summary.inconsistent_keys += 1;
+        for id in outcome.missing.iter() {
+            if let Some(store) = summary.stores.get_mut(&id.to_string()) {
+                store.missing += 1;
+            }
+        }
+        for (id, is_repaired) in outcome.repaired.iter() {
+            if let Some(store) = summary.stores.get_mut(&id.to_string()) {
+                if *is_repaired {
+                    store.repaired += 1;
+                    store.repaired_bytes += outcome.size;
+                    summary.repaired_bytes += outcome.size;
+                } else {
+                    store.repair_failed += 1;
+                }
+            }
+        }
+        if outcome.unrecoverable {
+            summary.unrecoverable_keys.push(outcome.key);
+        }
+    }
+    summary.unrecoverable_keys.sort();
+
+    Ok(summary)
+}
@@ -43,6 +43,16 @@ pub trait ScrubHandler: Send + Sync {
         is_repaired: bool,
         meta: &BlobstoreMetadata,
     );
+
+    /// Called when one of the inner stores returned data that does not match its key.
+    fn on_corrupt(
+        &self,
+        _ctx: &CoreContext,
+        _blobstore_id: BlobstoreId,
+        _key: &str,
+        _meta: &BlobstoreMetadata,
+    ) {
+    }
 }
 
 pub struct LoggingScrubHandler {
@@ -78,6 +88,21 @@ impl ScrubHandler for LoggingScrubHandler {
             }
         }
     }
+
+    fn on_corrupt(
+        &self,
+        ctx: &CoreContext,
+        blobstore_id: BlobstoreId,
+        key: &str,
+        _meta: &BlobstoreMetadata,
+    ) {
+        if !self.quiet {
+            warn!(
+                ctx.logger(),
+                "scrub: blobstore_id {:?} returned corrupt data for {}", &blobstore_id, &key
+            );
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -15,10 +15,14 @@ use std::{
 
 use crate::base::{MultiplexedBlobstoreBase, MultiplexedBlobstorePutHandler};
 use crate::queue::MultiplexedBlobstore;
+use crate::range_scrub::scrub_range;
 use crate::scrub::{LoggingScrubHandler, ScrubBlobstore, ScrubHandler};
 use anyhow::{bail, Result};
 use async_trait::async_trait;
-use blobstore::{Blobstore, BlobstoreGetData, BlobstorePutOps, OverwriteStatus, PutBehaviour};
+use blobstore::{
+    Blobstore, BlobstoreGetData, BlobstoreKeyParam, BlobstoreKeySource, BlobstorePutOps,
+    OverwriteStatus, PutBehaviour,
+};
 use blobstore_sync_queue::{
     BlobstoreSyncQueue, BlobstoreSyncQueueEntry, OperationKey, SqlBlobstoreSyncQueue,
 };
@@ -1370,3 +1374,80 @@ async fn no_handlers(fb: FacebookInit) {
         clear();
     }
 }
+
+#[fbinit::test]
+async fn range_scrubbed(fb: FacebookInit) -> Result<()> {
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx);
+    let scrub_handler = LoggingScrubHandler::new(true);
+
+    let bid0 = BlobstoreId::new(0);
+    let bs0 = Arc::new(Memblob::default());
+    let bid1 = BlobstoreId::new(1);
+    let bs1 = Arc::new(Memblob::default());
+    let bid2 = BlobstoreId::new(2);
+    let bs2 = Arc::new(Memblob::default());
+    let stores = vec![
+        (bid0, bs0.clone() as Arc<dyn BlobstoreKeySource>),
+        (bid1, bs1.clone() as Arc<dyn BlobstoreKeySource>),
+        (bid2, bs2.clone() as Arc<dyn BlobstoreKeySource>),
+    ];
+
+    // k0 is everywhere, k1 is missing from bs1, k2 only in bs2, and out_of_range is
+    // inconsistent but outside the range we scrub.
+    for bs in &[&bs0, &bs1, &bs2] {
+        bs.put(ctx, "k0".to_owned(), make_value("v0")).await?;
+    }
+    bs0.put(ctx, "k1".to_owned(), make_value("v1")).await?;
+    bs2.put(ctx, "k1".to_owned(), make_value("v1")).await?;
+    bs2.put(ctx, "k2".to_owned(), make_value("v22")).await?;
+    bs0.put(ctx, "out_of_range".to_owned(), make_value("v"))
+        .await?;
+
+    let range = BlobstoreKeyParam::from("k".to_owned().."l".to_owned());
+
+    // Report only: we should see the problems, but nothing should change
+    let summary = scrub_range(
+        ctx,
+        &stores,
+        range.clone(),
+        ScrubAction::ReportOnly,
+        &scrub_handler,
+        10,
+    )
+    .await?;
+    assert_eq!(summary.keys, 3);
+    assert_eq!(summary.inconsistent_keys, 2);
+    assert_eq!(summary.repaired_bytes, 0);
+    assert_eq!(summary.stores["0"].present, 2);
+    assert_eq!(summary.stores["0"].missing, 1);
+    assert_eq!(summary.stores["1"].missing, 2);
+    assert_eq!(summary.stores["2"].missing, 0);
+    assert_eq!(summary.stores["1"].repaired, 0);
+    assert!(bs1.get(ctx, "k1").await?.is_none());
+
+    // Repair: everything in range should now be everywhere
+    let summary = scrub_range(
+        ctx,
+        &stores,
+        range.clone(),
+        ScrubAction::Repair,
+        &scrub_handler,
+        10,
+    )
+    .await?;
+    assert_eq!(summary.inconsistent_keys, 2);
+    assert_eq!(summary.stores["0"].repaired, 1);
+    assert_eq!(summary.stores["1"].repaired, 2);
+    assert_eq!(summary.stores["1"].repaired_bytes, 5);
+    assert_eq!(summary.repaired_bytes, 8);
+    assert!(summary.unrecoverable_keys.is_empty());
+    assert_eq!(bs1.get(ctx, "k2").await?, Some(make_value("v22").into()));
+    assert!(bs1.get(ctx, "out_of_range").await?.is_none());
+
+    let summary = scrub_range(ctx, &stores, range, ScrubAction::Repair, &scrub_handler, 10).await?;
+    assert_eq!(summary.keys, 3);
+    assert_eq!(summary.inconsistent_keys, 0);
+
+    Ok(())
+}
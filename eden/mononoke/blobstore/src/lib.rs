@@ -401,6 +401,15 @@ pub trait BlobstoreWithLink: Blobstore {
     ) -> Result<()>;
 }
 
+/// Mixin trait for blobstores that support removing keys, e.g. for garbage collection
+#[async_trait]
+#[auto_impl(Arc, Box)]
+pub trait BlobstoreUnlinkOps: Blobstore {
+    /// Remove `key` from the store. Other keys linked to the same data are unaffected. Removing a
+    /// key that is not present is not an error.
+    async fn unlink<'a>(&'a self, ctx: &'a CoreContext, key: &'a str) -> Result<()>;
+}
+
 /// BlobstoreKeySource Interface
 /// Abstract for use with populate_healer
 #[async_trait]
@@ -419,6 +428,14 @@ pub struct BlobstoreKeyRange {
     pub end_key: String,
 }
 
+impl BlobstoreKeyRange {
+    /// Whether `key` falls in this range. `begin_key` is inclusive and `end_key` exclusive; an
+    /// empty `end_key` means the range is unbounded above.
+    pub fn contains(&self, key: &str) -> bool {
+        key >= self.begin_key.as_str() && (self.end_key.is_empty() || key < self.end_key.as_str())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub enum BlobstoreKeyToken {
     // For fileblob and manifold
@@ -0,0 +1,264 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+#![deny(warnings)]
+
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use bytes::Bytes;
+
+use blobstore::{Blobstore, BlobstoreGetData, BlobstorePutOps, OverwriteStatus, PutBehaviour};
+use context::CoreContext;
+use metaconfig_types::BlobstoreId;
+use mononoke_types::{
+    deleted_files_manifest::DeletedManifest,
+    fastlog_batch::FastlogBatch,
+    fsnode::Fsnode,
+    skeleton_manifest::SkeletonManifest,
+    typed_hash::ChangesetIdContext,
+    unode::{FileUnode, ManifestUnode},
+    Blob, BlobstoreBytes, BlobstoreValue, ChangesetId, ContentChunk, ContentChunkId, ContentId,
+    DeletedManifestId, FastlogBatchId, FileContents, FileUnodeId, FsnodeId, ManifestUnodeId,
+    SkeletonManifestId,
+};
+use multiplexedblob::ScrubHandler;
+
+const HASH_SEPARATOR: &str = ".blake2.";
+
+/// Decode `bytes` as a `V`, and check that encoding it again gives back `id`.
+fn check_roundtrip<V>(id: V::Key, bytes: &Bytes) -> bool
+where
+    V: BlobstoreValue,
+    V::Key: Copy + PartialEq,
+{
+    match V::from_blob(Blob::new(id, bytes.clone())) {
+        Ok(value) => *value.into_blob().id() == id,
+        Err(_) => false,
+    }
+}
+
+fn check_changeset(id: ChangesetId, bytes: &Bytes) -> bool {
+    // A BonsaiChangeset keeps the id it was loaded with, so hash the serialized form directly.
+    let mut context = ChangesetIdContext::new();
+    context.update(bytes);
+    context.finish() == id
+}
+
+/// Check that `bytes` really is the value for the content-addressed `key`. Keys are
+/// `[<repo prefix>.]<type>.blake2.<hash>`. Returns `true` for keys of types that cannot be
+/// checked.
+///
+/// Chunked file contents only record the id of the whole file, so for those we can only check
+/// that they decode.
+pub fn is_valid(key: &str, bytes: &Bytes) -> bool {
+    let split = match key.rfind(HASH_SEPARATOR) {
+        Some(split) => split,
+        None => return true,
+    };
+    let hash = &key[split + HASH_SEPARATOR.len()..];
+    let kind = key[..split].rsplit('.').next().unwrap_or("");
+
+    macro_rules! check {
+        ($id_type: ident, $check: expr) => {
+            match $id_type::from_str(hash) {
+                Ok(id) => $check(id, bytes),
+                // Not a hash we understand
+                Err(_) => true,
+            }
+        };
+    }
+
+    match kind {
+        "changeset" => check!(ChangesetId, check_changeset),
+        "content" => check!(ContentId, check_roundtrip::<FileContents>),
+        "chunk" => check!(ContentChunkId, check_roundtrip::<ContentChunk>),
+        "fileunode" => check!(FileUnodeId, check_roundtrip::<FileUnode>),
+        "manifestunode" => check!(ManifestUnodeId, check_roundtrip::<ManifestUnode>),
+        "deletedmanifest" => check!(DeletedManifestId, check_roundtrip::<DeletedManifest>),
+        "fsnode" => check!(FsnodeId, check_roundtrip::<Fsnode>),
+        "skeletonmanifest" => check!(SkeletonManifestId, check_roundtrip::<SkeletonManifest>),
+        "fastlogbatch" => check!(FastlogBatchId, check_roundtrip::<FastlogBatch>),
+        _ => true,
+    }
+}
+
+/// A layer over a blobstore (usually one component of a multiplex) that checks content-addressed
+/// blobs against the hash in their key on `get`. Corrupt blobs are reported to the scrub handler
+/// and treated as missing, so that the multiplex can fetch them from another component.
+#[derive(Clone)]
+pub struct ValidatingBlob<T> {
+    inner: T,
+    blobstore_id: BlobstoreId,
+    scrub_handler: Arc<dyn ScrubHandler>,
+}
+
+impl<T> ValidatingBlob<T> {
+    pub fn new(inner: T, blobstore_id: BlobstoreId, scrub_handler: Arc<dyn ScrubHandler>) -> Self {
+        Self {
+            inner,
+            blobstore_id,
+            scrub_handler,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ValidatingBlob<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValidatingBlob")
+            .field("inner", &self.inner)
+            .field("blobstore_id", &self.blobstore_id)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<T: BlobstorePutOps> Blobstore for ValidatingBlob<T> {
+    async fn get<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: &'a str,
+    ) -> Result<Option<BlobstoreGetData>> {
+        let value = match self.inner.get(ctx, key).await? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        if is_valid(key, value.as_raw_bytes()) {
+            Ok(Some(value))
+        } else {
+            self.scrub_handler
+                .on_corrupt(ctx, self.blobstore_id, key, value.as_meta());
+            Ok(None)
+        }
+    }
+
+    async fn is_present<'a>(&'a self, ctx: &'a CoreContext, key: &'a str) -> Result<bool> {
+        self.inner.is_present(ctx, key).await
+    }
+
+    async fn put<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<()> {
+        self.inner.put(ctx, key, value).await
+    }
+}
+
+#[async_trait]
+impl<T: BlobstorePutOps> BlobstorePutOps for ValidatingBlob<T> {
+    async fn put_explicit<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+        put_behaviour: PutBehaviour,
+    ) -> Result<OverwriteStatus> {
+        self.inner
+            .put_explicit(ctx, key, value, put_behaviour)
+            .await
+    }
+
+    async fn put_with_status<'a>(
+        &'a self,
+        ctx: &'a CoreContext,
+        key: String,
+        value: BlobstoreBytes,
+    ) -> Result<OverwriteStatus> {
+        self.inner.put_with_status(ctx, key, value).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use blobstore::BlobstoreMetadata;
+    use borrowed::borrowed;
+    use fbinit::FacebookInit;
+    use memblob::Memblob;
+    use mononoke_types::MononokeId;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingScrubHandler {
+        corrupt: Mutex<Vec<(BlobstoreId, String)>>,
+    }
+
+    impl ScrubHandler for RecordingScrubHandler {
+        fn on_repair(
+            &self,
+            _ctx: &CoreContext,
+            _blobstore_id: BlobstoreId,
+            _key: &str,
+            _is_repaired: bool,
+            _meta: &BlobstoreMetadata,
+        ) {
+        }
+
+        fn on_corrupt(
+            &self,
+            _ctx: &CoreContext,
+            blobstore_id: BlobstoreId,
+            key: &str,
+            _meta: &BlobstoreMetadata,
+        ) {
+            self.corrupt
+                .lock()
+                .unwrap()
+                .push((blobstore_id, key.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_is_valid() {
+        let blob = FileContents::new_bytes("hello").into_blob();
+        let key = blob.id().blobstore_key();
+        let data = blob.data().clone();
+        assert!(is_valid(&key, &data));
+        assert!(is_valid(&format!("repo0000.{}", key), &data));
+
+        let other = FileContents::new_bytes("goodbye").into_blob();
+        assert!(!is_valid(&key, other.data()));
+        assert!(!is_valid(&key, &Bytes::from("not thrift")));
+
+        // Keys we don't know how to check are always valid
+        assert!(is_valid("alias.sha1.0000", &data));
+        assert!(is_valid("content.blake2.nothex", &data));
+    }
+
+    #[fbinit::compat_test]
+    async fn test_corrupt_is_missing(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let inner = Arc::new(Memblob::default());
+        let handler = Arc::new(RecordingScrubHandler::default());
+        let store = ValidatingBlob::new(inner.clone(), BlobstoreId::new(3), handler.clone());
+
+        let good = FileContents::new_bytes("hello").into_blob();
+        let good_key = good.id().blobstore_key();
+        let bad = FileContents::new_bytes("goodbye").into_blob();
+        let bad_key = ContentChunk::new_bytes("x")
+            .into_blob()
+            .id()
+            .blobstore_key();
+
+        store.put(ctx, good_key.clone(), good.into()).await?;
+        store.put(ctx, bad_key.clone(), bad.into()).await?;
+
+        assert!(store.get(ctx, &good_key).await?.is_some());
+        assert!(store.get(ctx, &bad_key).await?.is_none());
+        assert!(store.is_present(ctx, &bad_key).await?);
+        assert_eq!(
+            *handler.corrupt.lock().unwrap(),
+            vec![(BlobstoreId::new(3), bad_key)]
+        );
+        Ok(())
+    }
+}
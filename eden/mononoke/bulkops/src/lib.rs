@@ -79,29 +79,50 @@ pub fn fetch_all_public_changesets<'a>(
     }
     .try_flatten_stream()
     .and_then(move |(lower_bound, upper_bound)| async move {
-        let ids: Vec<_> = changesets
-            .get_list_bs_cs_id_in_range_exclusive(repo_id, lower_bound, upper_bound)
-            .compat()
-            .try_collect()
-            .await?;
-        let (entries, public) = try_join(
-            changesets
-                .get_many(ctx.clone(), repo_id, ids.clone())
-                .compat(),
-            phases.get_public_raw(ctx, &ids),
+        let result = fetch_public_changesets_in_range(
+            ctx,
+            repo_id,
+            changesets,
+            phases,
+            lower_bound,
+            upper_bound,
         )
         .await?;
-        let mut entries_map: HashMap<_, _> = entries.into_iter().map(|e| (e.cs_id, e)).collect();
-        let result: Vec<_> = ids
-            .into_iter()
-            .filter(|id| public.contains(&id))
-            .filter_map(|id| entries_map.remove(&id))
-            .collect();
         Ok::<_, Error>(stream::iter(result).map(Ok))
     })
     .try_flatten()
 }
 
+/// Fetch the public changesets whose ids in the changesets table are in
+/// [lower_bound, upper_bound)
+pub async fn fetch_public_changesets_in_range(
+    ctx: &CoreContext,
+    repo_id: RepositoryId,
+    changesets: &SqlChangesets,
+    phases: &SqlPhases,
+    lower_bound: u64,
+    upper_bound: u64,
+) -> Result<Vec<ChangesetEntry>, Error> {
+    let ids: Vec<_> = changesets
+        .get_list_bs_cs_id_in_range_exclusive(repo_id, lower_bound, upper_bound)
+        .compat()
+        .try_collect()
+        .await?;
+    let (entries, public) = try_join(
+        changesets
+            .get_many(ctx.clone(), repo_id, ids.clone())
+            .compat(),
+        phases.get_public_raw(ctx, &ids),
+    )
+    .await?;
+    let mut entries_map: HashMap<_, _> = entries.into_iter().map(|e| (e.cs_id, e)).collect();
+    Ok(ids
+        .into_iter()
+        .filter(|id| public.contains(&id))
+        .filter_map(|id| entries_map.remove(&id))
+        .collect())
+}
+
 fn windows(start: u64, stop: u64, step: u64) -> impl Iterator<Item = (u64, u64)> {
     (0..)
         .map(move |index| (start + index * step, start + (index + 1) * step))
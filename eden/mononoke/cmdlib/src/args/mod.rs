@@ -84,6 +84,7 @@ const WRITE_ZSTD_ARG: &str = "blobstore-write-zstd-level";
 const MANIFOLD_API_KEY_ARG: &str = "manifold-api-key";
 const CACHELIB_ATTEMPT_ZSTD_ARG: &str = "blobstore-cachelib-attempt-zstd";
 const BLOBSTORE_PUT_BEHAVIOUR_ARG: &str = "blobstore-put-behaviour";
+const BLOBSTORE_VALIDATE_GETS_ARG: &str = "blobstore-validate-gets";
 
 // Old version took no args which means it would be no good for overriding default for a binary that defaults to true.
 const READONLY_STORAGE_OLD_ARG: &str = "readonly-storage";
@@ -1085,6 +1086,12 @@ fn add_blobstore_args<'a, 'b>(
     .arg(
       put_arg
     )
+    .arg(
+        Arg::with_name(BLOBSTORE_VALIDATE_GETS_ARG)
+            .long(BLOBSTORE_VALIDATE_GETS_ARG)
+            .required(false)
+            .help("Check content-addressed blobs against their hash when reading them from each component of a multiplexed blobstore. Corrupt blobs are treated as missing."),
+    )
     .arg(
         Arg::with_name(READONLY_STORAGE_OLD_ARG)
             .long(READONLY_STORAGE_OLD_ARG)
@@ -1442,6 +1449,7 @@ pub fn parse_blobstore_options<'a>(matches: &MononokeMatches<'a>) -> BlobstoreOp
         PackOptions::new(write_zstd_level),
         CachelibBlobstoreOptions::new_lazy(Some(attempt_zstd)),
         blobstore_put_behaviour,
+        matches.is_present(BLOBSTORE_VALIDATE_GETS_ARG),
     )
 }
 
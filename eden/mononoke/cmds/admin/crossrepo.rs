@@ -1024,6 +1024,7 @@ mod test {
                 default_action: DefaultSmallToLargeCommitSyncPathAction::Preserve,
                 direction: CommitSyncDirection::SmallToLarge,
                 map: Default::default(),
+                path_rules: vec![],
                 bookmark_prefix: Default::default(),
             };
             let mut commit_sync_config = CommitSyncConfig {
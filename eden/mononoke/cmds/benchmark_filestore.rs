@@ -16,7 +16,7 @@ use clap::{Arg, SubCommand};
 use cmdlib::args::{self, MononokeMatches};
 use context::CoreContext;
 use fbinit::FacebookInit;
-use filestore::{self, FetchKey, FilestoreConfig, StoreRequest};
+use filestore::{self, ChunkingMethod, FetchKey, FilestoreConfig, StoreRequest};
 use futures::{
     compat::Future01CompatExt,
     stream::{self, StreamExt, TryStreamExt},
@@ -123,6 +123,7 @@ async fn run_benchmark_filestore<'a>(
 
     let config = FilestoreConfig {
         chunk_size: Some(chunk_size),
+        chunking: ChunkingMethod::FixedSize,
         concurrency,
     };
 
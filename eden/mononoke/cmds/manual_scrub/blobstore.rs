@@ -7,14 +7,15 @@
 
 use std::sync::Arc;
 
-use anyhow::{Error, Result};
+use anyhow::{bail, Context, Error, Result};
 use cached_config::ConfigStore;
 use fbinit::FacebookInit;
 use slog::Logger;
 
-use blobstore::Blobstore;
+use blobstore::{Blobstore, BlobstoreKeySource, ErrorKind};
 use blobstore_factory::{make_blobstore, BlobstoreOptions};
-use metaconfig_types::{ScrubAction, StorageConfig};
+use fileblob::Fileblob;
+use metaconfig_types::{BlobConfig, BlobstoreId, ScrubAction, StorageConfig};
 use sql_ext::facebook::MysqlOptions;
 
 pub async fn open_blobstore(
@@ -39,3 +40,40 @@ pub async fn open_blobstore(
     .await
     .map_err(Error::from)
 }
+
+fn open_key_source(
+    blobconfig: BlobConfig,
+    blobstore_options: &BlobstoreOptions,
+) -> Result<Arc<dyn BlobstoreKeySource>> {
+    match blobconfig {
+        BlobConfig::Files { path } => {
+            let store = Fileblob::create(path.join("blobs"), blobstore_options.put_behaviour)
+                .context(ErrorKind::StateOpen)?;
+            Ok(Arc::new(store))
+        }
+        _ => bail!("Blobstore type does not support listing keys"),
+    }
+}
+
+/// Open each component of a multiplexed blobstore separately, so that their keys can be listed
+/// and compared with each other.
+pub fn open_key_sources(
+    storage_config: StorageConfig,
+    blobstore_options: &BlobstoreOptions,
+) -> Result<Vec<(BlobstoreId, Arc<dyn BlobstoreKeySource>)>> {
+    let blobstores = match storage_config.blobstore {
+        BlobConfig::Multiplexed { blobstores, .. } | BlobConfig::Scrub { blobstores, .. } => {
+            blobstores
+        }
+        _ => bail!("Range scrub needs a multiplexed blobstore"),
+    };
+
+    blobstores
+        .into_iter()
+        .map(|(blobstore_id, _, config)| {
+            let store = open_key_source(config, blobstore_options)
+                .with_context(|| format!("Opening blobstore {}", blobstore_id))?;
+            Ok((blobstore_id, store))
+        })
+        .collect()
+}
@@ -18,13 +18,19 @@ use tokio::{
     io::{stdin, AsyncBufReadExt, AsyncWriteExt, BufReader},
 };
 
+use blobstore::{BlobstoreKeyParam, BlobstoreKeyRange};
 use cmdlib::args;
 use context::CoreContext;
+use metaconfig_types::ScrubAction;
+use multiplexedblob::{scrub_range, LoggingScrubHandler};
 
 mod blobstore;
 mod scrub;
 
-use crate::{blobstore::open_blobstore, scrub::scrub};
+use crate::{
+    blobstore::{open_blobstore, open_key_sources},
+    scrub::scrub,
+};
 
 const ARG_STORAGE_CONFIG_NAME: &str = "storage-config-name";
 const ARG_SCHEDULED_MAX: &str = "scheduled-max";
@@ -33,6 +39,11 @@ const ARG_SUCCESSFUL_KEYS: &str = "success-keys-output";
 const ARG_MISSING_KEYS: &str = "missing-keys-output";
 const ARG_ERROR_KEYS: &str = "error-keys-output";
 
+const ARG_RANGE_START: &str = "range-start";
+const ARG_RANGE_END: &str = "range-end";
+const ARG_REPORT_ONLY: &str = "report-only";
+const ARG_SUMMARY_OUTPUT: &str = "summary-output";
+
 async fn bridge_to_file(mut file: File, mut recv: mpsc::Receiver<String>) -> Result<()> {
     while let Some(string) = recv.next().await {
         file.write_all(string.as_bytes()).await?;
@@ -78,22 +89,56 @@ fn main(fb: fbinit::FacebookInit) -> Result<()> {
             Arg::with_name(ARG_SUCCESSFUL_KEYS)
                 .long(ARG_SUCCESSFUL_KEYS)
                 .takes_value(true)
-                .required(true)
+                .required_unless(ARG_RANGE_START)
                 .help("A file to write successfully scrubbed key IDs to"),
         )
         .arg(
             Arg::with_name(ARG_MISSING_KEYS)
                 .long(ARG_MISSING_KEYS)
                 .takes_value(true)
-                .required(true)
+                .required_unless(ARG_RANGE_START)
                 .help("A file to write missing data key IDs to"),
         )
         .arg(
             Arg::with_name(ARG_ERROR_KEYS)
                 .long(ARG_ERROR_KEYS)
                 .takes_value(true)
-                .required(true)
+                .required_unless(ARG_RANGE_START)
                 .help("A file to write error fetching data key IDs to"),
+        )
+        .arg(
+            Arg::with_name(ARG_RANGE_START)
+                .long(ARG_RANGE_START)
+                .takes_value(true)
+                .required(false)
+                .help(
+                    "Instead of reading keys from stdin, list the keys in each component of the \
+                    multiplex starting at this key, and repair any that are missing",
+                ),
+        )
+        .arg(
+            Arg::with_name(ARG_RANGE_END)
+                .long(ARG_RANGE_END)
+                .takes_value(true)
+                .required(false)
+                .requires(ARG_RANGE_START)
+                .help("The end of the key range to scrub (exclusive). Default is no end."),
+        )
+        .arg(
+            Arg::with_name(ARG_REPORT_ONLY)
+                .long(ARG_REPORT_ONLY)
+                .takes_value(false)
+                .required(false)
+                .requires(ARG_RANGE_START)
+                .help("Only report missing keys in the range, do not repair them"),
+        )
+        .arg(
+            Arg::with_name(ARG_SUMMARY_OUTPUT)
+                .long(ARG_SUMMARY_OUTPUT)
+                .takes_value(true)
+                .required(false)
+                .requires(ARG_RANGE_START)
+                .help("A file to write the JSON summary of a range scrub to. Default is stdout."),
         );
 
     let matches = app.get_matches();
@@ -117,6 +162,47 @@ fn main(fb: fbinit::FacebookInit) -> Result<()> {
     let blobstore_options = args::parse_blobstore_options(&matches);
     let ctx = CoreContext::new_bulk_with_logger(fb, logger.clone());
 
+    if let Some(range_start) = matches.value_of(ARG_RANGE_START) {
+        let range = BlobstoreKeyParam::Start(BlobstoreKeyRange {
+            begin_key: range_start.to_string(),
+            end_key: matches.value_of(ARG_RANGE_END).unwrap_or("").to_string(),
+        });
+        let scrub_action = if matches.is_present(ARG_REPORT_ONLY) {
+            ScrubAction::ReportOnly
+        } else {
+            ScrubAction::Repair
+        };
+        let summary_file_name = matches.value_of_os(ARG_SUMMARY_OUTPUT);
+        let stores = open_key_sources(storage_config, &blobstore_options)?;
+
+        let scrub = async move {
+            let summary = scrub_range(
+                &ctx,
+                &stores,
+                range,
+                scrub_action,
+                &LoggingScrubHandler::new(false),
+                scheduled_max,
+            )
+            .await
+            .context("Range scrub failed")?;
+
+            let mut summary = serde_json::to_string_pretty(&summary)?;
+            summary.push('\n');
+            match summary_file_name {
+                Some(file_name) => {
+                    let mut file = File::create(file_name).await?;
+                    file.write_all(summary.as_bytes()).await?;
+                    file.flush().await?;
+                }
+                None => print!("{}", summary),
+            }
+            Ok(())
+        };
+
+        return runtime.block_on_std(scrub);
+    }
+
     let success_file_name = matches
         .value_of_os(ARG_SUCCESSFUL_KEYS)
         .context("No successfully scrubbed output file")?;
@@ -8,12 +8,14 @@
 #![deny(warnings)]
 
 use anyhow::{format_err, Error};
+use blobrepo::BlobRepo;
 use blobstore::{Loadable, PutBehaviour};
-use clap::Arg;
+use clap::{Arg, ArgMatches};
 use cloned::cloned;
 use context::CoreContext;
 use fbinit::FacebookInit;
-use futures::stream::{self, TryStreamExt};
+use filestore::{ChunkingMethod, FilestoreConfig, RechunkStats};
+use futures::stream::{self, StreamExt, TryStreamExt};
 
 use mercurial_types::{HgFileNodeId, HgNodeHash};
 use std::str::FromStr;
@@ -23,6 +25,110 @@ use cmdlib::{args, helpers::block_execute};
 const NAME: &str = "rechunker";
 const DEFAULT_NUM_JOBS: usize = 10;
 
+const ARG_CHUNK_SIZE: &str = "chunk-size";
+const ARG_CONTENT_DEFINED: &str = "content-defined";
+const ARG_MIN_CHUNK_SIZE: &str = "min-chunk-size";
+const ARG_MAX_CHUNK_SIZE: &str = "max-chunk-size";
+const ARG_STATS: &str = "stats";
+
+/// Chunking overrides from the command line, to apply on top of the repo's FilestoreConfig.
+struct ChunkingArgs {
+    chunk_size: Option<u64>,
+    content_defined: bool,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+}
+
+impl ChunkingArgs {
+    fn from_matches(matches: &ArgMatches<'_>) -> Result<Self, Error> {
+        let parse = |name| -> Result<Option<u64>, Error> {
+            matches
+                .value_of(name)
+                .map(|v| v.parse().map_err(Error::from))
+                .transpose()
+        };
+
+        Ok(Self {
+            chunk_size: parse(ARG_CHUNK_SIZE)?,
+            content_defined: matches.is_present(ARG_CONTENT_DEFINED),
+            min_size: parse(ARG_MIN_CHUNK_SIZE)?,
+            max_size: parse(ARG_MAX_CHUNK_SIZE)?,
+        })
+    }
+
+    fn apply(&self, repo_config: FilestoreConfig) -> Result<FilestoreConfig, Error> {
+        let chunk_size = self.chunk_size.or(repo_config.chunk_size);
+
+        let chunking = if self.content_defined {
+            let avg_size = chunk_size
+                .ok_or_else(|| format_err!("Content-defined chunking needs a chunk size"))?;
+            let (default_min, default_max) = match ChunkingMethod::content_defined(avg_size) {
+                ChunkingMethod::ContentDefined { min_size, max_size } => (min_size, max_size),
+                ChunkingMethod::FixedSize => unreachable!(),
+            };
+            let min_size = self.min_size.unwrap_or(default_min);
+            let max_size = self.max_size.unwrap_or(default_max);
+            if min_size == 0 || min_size > avg_size || avg_size > max_size {
+                return Err(format_err!(
+                    "Invalid chunk sizes: need 0 < min ({}) <= average ({}) <= max ({})",
+                    min_size,
+                    avg_size,
+                    max_size
+                ));
+            }
+            ChunkingMethod::ContentDefined { min_size, max_size }
+        } else {
+            repo_config.chunking
+        };
+
+        Ok(FilestoreConfig {
+            chunk_size,
+            chunking,
+            concurrency: repo_config.concurrency,
+        })
+    }
+}
+
+/// Compute what storing each file with `config` would produce, and accumulate deduplication
+/// statistics across all of them. Nothing is written.
+async fn compute_stats(
+    ctx: &CoreContext,
+    blobrepo: &BlobRepo,
+    config: FilestoreConfig,
+    filenode_ids: Vec<HgFileNodeId>,
+    jobs: usize,
+) -> Result<RechunkStats, Error> {
+    stream::iter(filenode_ids)
+        .map(|fid| {
+            cloned!(blobrepo, ctx);
+            async move {
+                let env = fid.load(&ctx, blobrepo.blobstore()).await?;
+                filestore::chunk_keys(&blobrepo.get_blobstore(), config, &ctx, env.content_id())
+                    .await
+            }
+        })
+        .buffer_unordered(jobs)
+        .try_fold(RechunkStats::new(), |mut stats, keys| async move {
+            stats.add_file(keys);
+            Ok(stats)
+        })
+        .await
+}
+
+fn print_stats(name: &str, config: &FilestoreConfig, stats: &RechunkStats) {
+    println!("{} ({:?}):", name, config);
+    println!("  files: {}", stats.files);
+    println!(
+        "  chunks: {} ({} unique)",
+        stats.total_chunks, stats.unique_chunks
+    );
+    println!(
+        "  bytes: {} ({} unique)",
+        stats.total_bytes, stats.unique_bytes
+    );
+    println!("  dedup ratio: {:.3}", stats.dedup_ratio());
+}
+
 #[fbinit::main]
 fn main(fb: FacebookInit) -> Result<(), Error> {
     let matches = args::MononokeAppBuilder::new(NAME)
@@ -46,6 +152,38 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
                 .takes_value(true)
                 .help("The number of filenodes to rechunk in parallel"),
         )
+        .arg(
+            Arg::with_name(ARG_CHUNK_SIZE)
+                .long(ARG_CHUNK_SIZE)
+                .takes_value(true)
+                .help(
+                    "Chunk size to use instead of the repo's (the average chunk size for \
+                     content-defined chunking)",
+                ),
+        )
+        .arg(
+            Arg::with_name(ARG_CONTENT_DEFINED)
+                .long(ARG_CONTENT_DEFINED)
+                .help("Use content-defined chunking, regardless of the repo's configuration"),
+        )
+        .arg(
+            Arg::with_name(ARG_MIN_CHUNK_SIZE)
+                .long(ARG_MIN_CHUNK_SIZE)
+                .takes_value(true)
+                .requires(ARG_CONTENT_DEFINED)
+                .help("Smallest chunk size for content-defined chunking"),
+        )
+        .arg(
+            Arg::with_name(ARG_MAX_CHUNK_SIZE)
+                .long(ARG_MAX_CHUNK_SIZE)
+                .takes_value(true)
+                .requires(ARG_CONTENT_DEFINED)
+                .help("Largest chunk size for content-defined chunking"),
+        )
+        .arg(Arg::with_name(ARG_STATS).long(ARG_STATS).help(
+            "Don't rechunk anything. Instead, report the deduplication ratio the new \
+                     chunking would achieve on these files, compared with the repo's",
+        ))
         .get_matches();
 
     args::init_cachelib(fb, &matches);
@@ -70,23 +208,35 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
         })
         .collect();
 
+    let filenode_ids = filenode_ids.into_iter().collect::<Result<Vec<_>, _>>()?;
+    let stats = matches.is_present(ARG_STATS);
+    let chunking_args = ChunkingArgs::from_matches(&matches)?;
+
     let blobrepo = args::open_repo(fb, &logger, &matches);
     let rechunk = async move {
         let blobrepo = blobrepo.await?;
+        let repo_config = blobrepo.filestore_config();
+        let config = chunking_args.apply(repo_config)?;
+
+        if stats {
+            let current =
+                compute_stats(&ctx, &blobrepo, repo_config, filenode_ids.clone(), jobs).await?;
+            let rechunked = compute_stats(&ctx, &blobrepo, config, filenode_ids, jobs).await?;
+            print_stats("current", &repo_config, &current);
+            print_stats("rechunked", &config, &rechunked);
+            return Ok(());
+        }
+
         stream::iter(filenode_ids)
+            .map(Ok)
             .try_for_each_concurrent(jobs, |fid| {
                 cloned!(blobrepo, ctx);
                 async move {
                     let env = fid.load(&ctx, blobrepo.blobstore()).await?;
                     let content_id = env.content_id();
-                    filestore::force_rechunk(
-                        &blobrepo.get_blobstore(),
-                        blobrepo.filestore_config().clone(),
-                        &ctx,
-                        content_id,
-                    )
-                    .await
-                    .map(|_| ())
+                    filestore::force_rechunk(&blobrepo.get_blobstore(), config, &ctx, content_id)
+                        .await
+                        .map(|_| ())
                 }
             })
             .await
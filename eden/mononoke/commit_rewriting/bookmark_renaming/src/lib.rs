@@ -130,6 +130,7 @@ mod test {
         SmallRepoCommitSyncConfig {
             default_action: DefaultSmallToLargeCommitSyncPathAction::Preserve,
             map: hashmap! {},
+            path_rules: vec![],
             bookmark_prefix: AsciiString::from_ascii("b1/".to_string()).unwrap(),
             direction: CommitSyncDirection::LargeToSmall,
         }
@@ -139,6 +140,7 @@ mod test {
         SmallRepoCommitSyncConfig {
             default_action: DefaultSmallToLargeCommitSyncPathAction::PrependPrefix(mp("shifted")),
             map: hashmap! {},
+            path_rules: vec![],
             bookmark_prefix: AsciiString::from_ascii("b2/".to_string()).unwrap(),
             direction: CommitSyncDirection::LargeToSmall,
         }
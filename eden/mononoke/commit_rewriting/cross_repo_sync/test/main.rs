@@ -281,6 +281,7 @@ fn create_commit_sync_config(
     let small_repo_config = SmallRepoCommitSyncConfig {
         default_action: DefaultSmallToLargeCommitSyncPathAction::PrependPrefix(MPath::new(prefix)?),
         map: hashmap! {},
+        path_rules: vec![],
         bookmark_prefix: AsciiString::new(),
         direction: CommitSyncDirection::LargeToSmall,
     };
@@ -1751,6 +1752,7 @@ async fn prepare_commit_syncer_with_mapping_change(
         map: hashmap! {
             MPath::new("tools")? => MPath::new("tools")?,
         },
+        path_rules: vec![],
         bookmark_prefix: AsciiString::new(),
         direction: CommitSyncDirection::LargeToSmall,
     };
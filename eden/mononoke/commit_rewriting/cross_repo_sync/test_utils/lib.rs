@@ -336,6 +336,7 @@ pub fn base_commit_sync_config(large_repo: &BlobRepo, small_repo: &BlobRepo) ->
             MPath::new("prefix").unwrap(),
         ),
         map: hashmap! {},
+        path_rules: vec![],
         bookmark_prefix: AsciiString::new(),
         direction: CommitSyncDirection::SmallToLarge,
     };
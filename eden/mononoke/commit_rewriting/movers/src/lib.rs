@@ -8,10 +8,11 @@
 #![deny(warnings)]
 
 use anyhow::{Context, Error, Result};
+use globset::{GlobBuilder, GlobMatcher};
 use mercurial_types::{MPath, MPathElement};
 use metaconfig_types::{
     CommitSyncConfig, CommitSyncDirection, DefaultSmallToLargeCommitSyncPathAction,
-    SmallRepoCommitSyncConfig,
+    SmallRepoCommitSyncConfig, SmallRepoPathRule,
 };
 use mononoke_types::RepositoryId;
 use std::collections::{HashMap, HashSet};
@@ -30,6 +31,18 @@ pub enum ErrorKind {
     SmallRepoNotFound(RepositoryId),
     #[error("Provided map is not prefix-free (e.g. {0:?} and {1:?})")]
     NonPrefixFreeMap(MPath, MPath),
+    #[error("Invalid path rule glob {0:?}")]
+    InvalidPathRuleGlob(String, #[source] globset::Error),
+    #[error(
+        "Path rules of small repo {0} are not reversible: {1:?} syncs to {2:?}, which syncs back to {3:?}"
+    )]
+    NonReversiblePathRules(RepositoryId, MPath, MPath, Option<MPath>),
+    #[error(
+        "Path rule of small repo {0} moving {1:?} to {2:?} never applies, because an earlier path rule matches {1:?}"
+    )]
+    UnreachablePathRule(RepositoryId, MPath, MPath),
+    #[error("{0:?} is the target of moving {2:?} in small repo {1} and {4:?} in small repo {3}")]
+    ConflictingMovedFiles(MPath, RepositoryId, MPath, RepositoryId, MPath),
 }
 
 /// A function to modify paths during repo sync
@@ -67,6 +80,14 @@ enum PathAction {
     DoNotSync,
 }
 
+/// A path rule, compiled from `SmallRepoPathRule`
+enum PathRule {
+    // Paths matching this glob should not be synced
+    DoNotSync(GlobMatcher),
+    // This exact path should be replaced with a new value
+    MoveFile(MPath, MPath),
+}
+
 /// Default action to apply to a path when syncing between two repos
 #[derive(Debug, Clone)]
 pub enum DefaultAction {
@@ -190,6 +211,57 @@ pub fn mover_factory(
     }))
 }
 
+fn compile_path_rules(path_rules: &[SmallRepoPathRule]) -> Result<Vec<PathRule>> {
+    path_rules
+        .iter()
+        .map(|path_rule| match path_rule {
+            SmallRepoPathRule::DoNotSyncGlob(glob) => {
+                let matcher = GlobBuilder::new(glob)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|e| ErrorKind::InvalidPathRuleGlob(glob.clone(), e))?
+                    .compile_matcher();
+                Ok(PathRule::DoNotSync(matcher))
+            }
+            SmallRepoPathRule::MoveFile(from, to) => {
+                Ok(PathRule::MoveFile(from.clone(), to.clone()))
+            }
+        })
+        .collect()
+}
+
+/// Create a `Mover`, which applies ordered path rules (the first
+/// matching rule wins), and falls back to `fallback_mover` for paths
+/// that match none of them
+fn path_rules_mover_factory(
+    path_rules: &[SmallRepoPathRule],
+    fallback_mover: Mover,
+) -> Result<Mover> {
+    let path_rules = compile_path_rules(path_rules)?;
+    if path_rules.is_empty() {
+        return Ok(fallback_mover);
+    }
+
+    Ok(Arc::new(move |source_path: &MPath| {
+        let source_path_str = String::from_utf8_lossy(&source_path.to_vec()).into_owned();
+        for path_rule in path_rules.iter() {
+            match path_rule {
+                PathRule::DoNotSync(matcher) => {
+                    if matcher.is_match(&source_path_str) {
+                        return Ok(None);
+                    }
+                }
+                PathRule::MoveFile(from, to) => {
+                    if from == source_path {
+                        return Ok(Some(to.clone()));
+                    }
+                }
+            }
+        }
+        fallback_mover(source_path)
+    }))
+}
+
 // Given a full sync config and a small repo id,
 // split it into this repo the rest
 fn get_small_repo_and_others_from_config(
@@ -224,7 +296,20 @@ pub fn get_small_to_large_mover(
         .map(|(k, v)| (k, PrefixAction::Change(v)))
         .collect();
 
-    mover_factory(prefix_map, default_action)
+    let prefix_mover = mover_factory(prefix_map, default_action)?;
+    path_rules_mover_factory(&source_repo_config.path_rules, prefix_mover)
+}
+
+fn moved_files(
+    small_repo_config: &SmallRepoCommitSyncConfig,
+) -> impl Iterator<Item = (&MPath, &MPath)> {
+    small_repo_config
+        .path_rules
+        .iter()
+        .filter_map(|path_rule| match path_rule {
+            SmallRepoPathRule::MoveFile(from, to) => Some((from, to)),
+            SmallRepoPathRule::DoNotSyncGlob(_) => None,
+        })
 }
 
 /// Get a mover for a large-to-small repo sync
@@ -236,6 +321,9 @@ pub fn get_large_to_small_mover(
         get_small_repo_and_others_from_config(commit_sync_config, small_repo_id)?;
 
     let target_repo_right_sides: HashSet<_> = target_repo_config.map.values().collect();
+    let target_repo_moved_files: HashMap<MPath, MPath> = moved_files(target_repo_config)
+        .map(|(from, to)| (to.clone(), from.clone()))
+        .collect();
 
     let other_repo_right_sides: Vec<&MPath> = other_repo_configs
         .iter()
@@ -258,6 +346,16 @@ pub fn get_large_to_small_mover(
         )
         .collect();
 
+    let other_repo_moved_files: Vec<&MPath> = other_repo_configs
+        .iter()
+        .map(|small_repo_config| {
+            moved_files(small_repo_config)
+                .map(|(_, to)| to)
+                .filter(|to| !target_repo_moved_files.contains_key(to))
+        })
+        .flatten()
+        .collect();
+
     // We reverse the direction of all path-to-path mappings
     let mut prefix_map: HashMap<MPath, PrefixAction> = target_repo_config
         .map
@@ -274,6 +372,7 @@ pub fn get_large_to_small_mover(
     other_repo_right_sides
         .into_iter()
         .chain(other_repo_prepended_prefixes.into_iter())
+        .chain(other_repo_moved_files.into_iter())
         .for_each(|v| {
             prefix_map.insert(v.clone(), PrefixAction::DoNotSync);
         });
@@ -310,11 +409,16 @@ pub fn get_large_to_small_mover(
     // If this path is equal to the original path then we consider that default_large_to_small_mover
     // returns correct path, otherwise we return None (i.e. a path from large repo doesn't remap
     // to a path from small repo).
+    // The same check takes care of paths excluded by glob path rules, and of moved files
+    // shadowing paths synced by prefix.
     let default_large_to_small_mover = mover_factory(prefix_map, default_action)?;
 
     let small_to_large_mover = get_small_to_large_mover(commit_sync_config, small_repo_id)?;
     Ok(Arc::new(move |path: &MPath| -> Result<Option<MPath>> {
-        let moved_large_to_small = default_large_to_small_mover(path)?;
+        let moved_large_to_small = match target_repo_moved_files.get(path) {
+            Some(moved_file) => Some(moved_file.clone()),
+            None => default_large_to_small_mover(path)?,
+        };
         match moved_large_to_small {
             Some(moved_large_to_small) => {
                 if small_to_large_mover(&moved_large_to_small)?.as_ref() == Some(&path) {
@@ -346,6 +450,102 @@ pub fn get_movers(
     }
 }
 
+/// Validate that every small repo's mover and reverse mover are inverses
+/// of each other on paths affected by path rules.
+/// Files moved by any small repo's path rules are checked against all small
+/// repos: the moved files themselves, and the paths of every small repo that
+/// sync to the moved files' targets by prefix, must sync back to where they
+/// came from. Without this, a file moved onto a path that's already synced
+/// (by this or another small repo), or two files moved onto the same path,
+/// would make the reverse mover silently pick one of the sources, or drop
+/// the path.
+pub fn validate_movers(commit_sync_config: &CommitSyncConfig) -> Result<()> {
+    let has_path_rules = commit_sync_config
+        .small_repos
+        .values()
+        .any(|small_repo_config| !small_repo_config.path_rules.is_empty());
+    if !has_path_rules {
+        return Ok(());
+    }
+
+    let all_moved_files: Vec<(RepositoryId, &MPath, &MPath)> = commit_sync_config
+        .small_repos
+        .iter()
+        .map(|(small_repo_id, small_repo_config)| {
+            moved_files(small_repo_config).map(move |(from, to)| (*small_repo_id, from, to))
+        })
+        .flatten()
+        .collect();
+
+    let mut moved_file_sources: HashMap<&MPath, (RepositoryId, &MPath)> = HashMap::new();
+    for (small_repo_id, from, to) in all_moved_files.iter() {
+        if let Some((other_repo_id, other_from)) =
+            moved_file_sources.insert(*to, (*small_repo_id, *from))
+        {
+            return Err(ErrorKind::ConflictingMovedFiles(
+                (*to).clone(),
+                other_repo_id,
+                other_from.clone(),
+                *small_repo_id,
+                (*from).clone(),
+            )
+            .into());
+        }
+    }
+
+    // Paths, which sync to the moved files' targets by prefix alone
+    let mut without_path_rules = commit_sync_config.clone();
+    for small_repo_config in without_path_rules.small_repos.values_mut() {
+        small_repo_config.path_rules.clear();
+    }
+
+    for (small_repo_id, small_repo_config) in commit_sync_config.small_repos.iter() {
+        let small_repo_id = *small_repo_id;
+        // This also checks that glob path rules are valid
+        let mover = get_small_to_large_mover(commit_sync_config, small_repo_id)?;
+        let reverse_mover = get_large_to_small_mover(commit_sync_config, small_repo_id)?;
+        let prefix_reverse_mover = get_large_to_small_mover(&without_path_rules, small_repo_id)?;
+
+        // A moved file excluded by an earlier glob (or moved by an earlier
+        // rule) would still be excluded from the other small repos
+        for (from, to) in moved_files(small_repo_config) {
+            if mover(from)?.as_ref() != Some(to) {
+                return Err(ErrorKind::UnreachablePathRule(
+                    small_repo_id,
+                    from.clone(),
+                    to.clone(),
+                )
+                .into());
+            }
+        }
+
+        let mut paths_to_check = vec![];
+        for (moved_by, from, to) in all_moved_files.iter() {
+            if *moved_by == small_repo_id {
+                paths_to_check.push((*from).clone());
+            }
+            paths_to_check.extend(prefix_reverse_mover(to)?);
+        }
+
+        for path in paths_to_check {
+            if let Some(moved) = mover(&path)? {
+                let moved_back = reverse_mover(&moved)?;
+                if moved_back.as_ref() != Some(&path) {
+                    return Err(ErrorKind::NonReversiblePathRules(
+                        small_repo_id,
+                        path,
+                        moved,
+                        moved_back,
+                    )
+                    .into());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -475,6 +675,7 @@ mod test {
             map: hashmap! {
                 mp("preserved2") => mp("repo1-rest/preserved2"),
             },
+            path_rules: vec![],
             bookmark_prefix: AsciiString::from_ascii("b1".to_string()).unwrap(),
             direction: CommitSyncDirection::LargeToSmall,
         }
@@ -488,6 +689,7 @@ mod test {
                 mp("sub1") => mp("repo2-rest/sub1"),
                 mp("sub2") => mp("repo2-rest/sub2"),
             },
+            path_rules: vec![],
             bookmark_prefix: AsciiString::from_ascii("b2".to_string()).unwrap(),
             direction: CommitSyncDirection::LargeToSmall,
         }
@@ -646,6 +848,7 @@ mod test {
                     map: hashmap! {
                         mp("preserved2") => mp("preserved2"),
                     },
+                    path_rules: vec![],
                     bookmark_prefix: AsciiString::from_ascii("b1".to_string()).unwrap(),
                     direction: CommitSyncDirection::LargeToSmall,
                 },
@@ -656,6 +859,7 @@ mod test {
                         mp("sub1") => mp("repo2-rest/sub1"),
                         mp("sub2") => mp("repo2-rest/sub2"),
                     },
+                    path_rules: vec![],
                     bookmark_prefix: AsciiString::from_ascii("b2".to_string()).unwrap(),
                     direction: CommitSyncDirection::LargeToSmall,
                 },
@@ -742,6 +946,7 @@ mod test {
                 mp("sub1") => mp("repo2-rest/sub1"),
                 mp("sub1/preserved") => mp("sub1/preserved"),
             },
+            path_rules: vec![],
             bookmark_prefix: AsciiString::from_ascii("b2".to_string()).unwrap(),
             direction: CommitSyncDirection::LargeToSmall,
         }
@@ -793,6 +998,7 @@ mod test {
                 mp("preserved") => mp("preserved"),
                 mp("preserved/excluded") => mp("shifted/preserved/excluded"),
             },
+            path_rules: vec![],
             bookmark_prefix: AsciiString::from_ascii("b2".to_string()).unwrap(),
             direction: CommitSyncDirection::LargeToSmall,
         }
@@ -830,4 +1036,158 @@ mod test {
 
         Ok(())
     }
+
+    fn get_small_repo_sync_config_with_path_rules(
+        path_rules: Vec<SmallRepoPathRule>,
+    ) -> SmallRepoCommitSyncConfig {
+        SmallRepoCommitSyncConfig {
+            default_action: DefaultSmallToLargeCommitSyncPathAction::PrependPrefix(mp("repo1")),
+            map: hashmap! {
+                mp("lib") => mp("libs/lib"),
+            },
+            path_rules,
+            bookmark_prefix: AsciiString::from_ascii("b1".to_string()).unwrap(),
+            direction: CommitSyncDirection::SmallToLarge,
+        }
+    }
+
+    fn get_large_repo_sync_config_with_path_rules(
+        path_rules: Vec<SmallRepoPathRule>,
+    ) -> CommitSyncConfig {
+        CommitSyncConfig {
+            large_repo_id: RepositoryId::new(0),
+            common_pushrebase_bookmarks: vec![],
+            small_repos: hashmap! {
+                RepositoryId::new(1) => get_small_repo_sync_config_with_path_rules(path_rules),
+                RepositoryId::new(2) => SmallRepoCommitSyncConfig {
+                    default_action: DefaultSmallToLargeCommitSyncPathAction::PrependPrefix(mp("repo2")),
+                    map: hashmap! {},
+                    path_rules: vec![
+                        SmallRepoPathRule::MoveFile(mp("README"), mp("docs/repo2.md")),
+                    ],
+                    bookmark_prefix: AsciiString::from_ascii("b2".to_string()).unwrap(),
+                    direction: CommitSyncDirection::SmallToLarge,
+                },
+            },
+            version_name: CommitSyncConfigVersion("TEST_VERSION_NAME".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_movers_with_path_rules() -> Result<()> {
+        let config = get_large_repo_sync_config_with_path_rules(vec![
+            SmallRepoPathRule::DoNotSyncGlob("**/*.generated.*".to_string()),
+            SmallRepoPathRule::MoveFile(mp("lib/README"), mp("docs/lib.md")),
+            SmallRepoPathRule::MoveFile(mp("BUILD"), mp("build/BUILD")),
+        ]);
+        validate_movers(&config)?;
+        let Movers {
+            mover,
+            reverse_mover,
+        } = get_movers(
+            &config,
+            RepositoryId::new(1),
+            CommitSyncDirection::SmallToLarge,
+        )?;
+
+        // Glob rules exclude paths at any depth, in both directions
+        assert_eq!(mover(&mp("foo.generated.rs"))?, None);
+        assert_eq!(mover(&mp("lib/sub/foo.generated.h"))?, None);
+        assert_eq!(reverse_mover(&mp("libs/lib/sub/foo.generated.h"))?, None);
+        assert_eq!(
+            mover(&mp("lib/sub/foo.rs"))?,
+            Some(mp("libs/lib/sub/foo.rs"))
+        );
+
+        // Moved files sync back to where they came from
+        assert_eq!(mover(&mp("lib/README"))?, Some(mp("docs/lib.md")));
+        assert_eq!(reverse_mover(&mp("docs/lib.md"))?, Some(mp("lib/README")));
+        assert_eq!(reverse_mover(&mp("libs/lib/README"))?, None);
+        assert_eq!(mover(&mp("BUILD"))?, Some(mp("build/BUILD")));
+        assert_eq!(reverse_mover(&mp("build/BUILD"))?, Some(mp("BUILD")));
+        assert_eq!(reverse_mover(&mp("repo1/BUILD"))?, None);
+        assert_eq!(mover(&mp("build/other"))?, Some(mp("repo1/build/other")));
+
+        // A file moved by another small repo doesn't sync to this one
+        assert_eq!(reverse_mover(&mp("docs/repo2.md"))?, None);
+        let repo2_mover = get_large_to_small_mover(&config, RepositoryId::new(2))?;
+        assert_eq!(repo2_mover(&mp("docs/repo2.md"))?, Some(mp("README")));
+        assert_eq!(repo2_mover(&mp("docs/lib.md"))?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_movers_with_path_rules() -> Result<()> {
+        // Two files moved to the same path
+        let config = get_large_repo_sync_config_with_path_rules(vec![
+            SmallRepoPathRule::MoveFile(mp("a"), mp("docs/a")),
+            SmallRepoPathRule::MoveFile(mp("b"), mp("docs/a")),
+        ]);
+        assert!(validate_movers(&config).is_err());
+
+        // A file moved over a path, which is synced by prefix
+        let config = get_large_repo_sync_config_with_path_rules(vec![SmallRepoPathRule::MoveFile(
+            mp("a"),
+            mp("libs/lib/a"),
+        )]);
+        assert!(validate_movers(&config).is_err());
+
+        // The path the file would be moved over is excluded
+        let config = get_large_repo_sync_config_with_path_rules(vec![
+            SmallRepoPathRule::DoNotSyncGlob("lib/a".to_string()),
+            SmallRepoPathRule::MoveFile(mp("a"), mp("libs/lib/a")),
+        ]);
+        validate_movers(&config)?;
+
+        let config =
+            get_large_repo_sync_config_with_path_rules(vec![SmallRepoPathRule::DoNotSyncGlob(
+                "[".to_string(),
+            )]);
+        assert!(validate_movers(&config).is_err());
+
+        // A moved file, which is excluded by an earlier rule
+        let config = get_large_repo_sync_config_with_path_rules(vec![
+            SmallRepoPathRule::DoNotSyncGlob("*.md".to_string()),
+            SmallRepoPathRule::MoveFile(mp("a.md"), mp("docs/a.md")),
+        ]);
+        let err = validate_movers(&config).unwrap_err();
+        assert!(err.to_string().contains("never applies"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_movers_cross_repo() -> Result<()> {
+        // Small repo 1 moves a file into small repo 2's image, without
+        // small repo 2 having any path rules of its own
+        let mut config =
+            get_large_repo_sync_config_with_path_rules(vec![SmallRepoPathRule::MoveFile(
+                mp("a"),
+                mp("repo2/x"),
+            )]);
+        if let Some(repo2) = config.small_repos.get_mut(&RepositoryId::new(2)) {
+            repo2.path_rules.clear();
+        }
+        let err = validate_movers(&config).unwrap_err();
+        assert!(err.to_string().contains("small repo 2"));
+
+        // Both small repos move a file onto the same path
+        let config = get_large_repo_sync_config_with_path_rules(vec![SmallRepoPathRule::MoveFile(
+            mp("a"),
+            mp("docs/repo2.md"),
+        )]);
+        let err = validate_movers(&config).unwrap_err();
+        assert!(err.to_string().contains("is the target of moving"));
+
+        // Files moved by both small repos, to different paths outside of
+        // the other small repo's image
+        let config = get_large_repo_sync_config_with_path_rules(vec![SmallRepoPathRule::MoveFile(
+            mp("a"),
+            mp("docs/lib.md"),
+        )]);
+        validate_movers(&config)?;
+
+        Ok(())
+    }
 }
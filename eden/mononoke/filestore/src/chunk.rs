@@ -12,12 +12,129 @@ use futures::{
     stream::{BoxStream, Stream, StreamExt, TryStreamExt},
     task::{Context, Poll},
 };
+use std::cmp::min;
 use std::convert::TryFrom;
 use std::fmt::{self, Debug};
 use std::pin::Pin;
 
 use crate::expected_size::ExpectedSize;
 
+/// How the Filestore splits large files into chunks.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ChunkingMethod {
+    /// Every chunk is exactly `chunk_size` bytes, except for the last one.
+    FixedSize,
+    /// Chunk boundaries are picked by a rolling hash over the content (FastCDC), so that an
+    /// insertion or deletion only affects the chunks around it. `chunk_size` is the average chunk
+    /// size that we target, and chunks are never smaller than `min_size` (except for the last
+    /// one), nor larger than `max_size`.
+    ContentDefined { min_size: u64, max_size: u64 },
+}
+
+impl Default for ChunkingMethod {
+    fn default() -> Self {
+        ChunkingMethod::FixedSize
+    }
+}
+
+impl ChunkingMethod {
+    /// Content-defined chunking with the customary FastCDC bounds around an average chunk size:
+    /// a quarter of it for the smallest chunk, and four times it for the largest.
+    pub fn content_defined(avg_size: u64) -> Self {
+        ChunkingMethod::ContentDefined {
+            min_size: avg_size / 4,
+            max_size: avg_size * 4,
+        }
+    }
+}
+
+/// Parameters for content-defined chunking, in a form that is cheap to use in the hot loop.
+#[derive(Debug, Copy, Clone)]
+pub struct ContentDefinedChunker {
+    min_size: usize,
+    avg_size: usize,
+    max_size: usize,
+    /// Mask used before we reach avg_size. It has more bits set, which makes a cut less likely.
+    mask_small: u64,
+    /// Mask used after we reach avg_size. It has fewer bits set, which makes a cut more likely.
+    mask_large: u64,
+}
+
+impl ContentDefinedChunker {
+    pub fn new(min_size: usize, avg_size: usize, max_size: usize) -> Self {
+        assert!(min_size > 0);
+        assert!(min_size <= avg_size && avg_size <= max_size);
+
+        // Normalized chunking: we look for avg_size.log2() bits, one more before the average,
+        // one less after it. This narrows the chunk size distribution around avg_size. The masks
+        // use the high bits of the fingerprint, since those depend on the most input bytes.
+        let bits = 63 - (avg_size as u64).leading_zeros();
+        let mask = |bits: u32| match bits {
+            0 => 0,
+            bits if bits >= 64 => !0,
+            bits => !0u64 << (64 - bits),
+        };
+
+        ContentDefinedChunker {
+            min_size,
+            avg_size,
+            max_size,
+            mask_small: mask(bits + 1),
+            mask_large: mask(bits.saturating_sub(1)),
+        }
+    }
+
+    pub fn from_method(chunk_size: u64, min_size: u64, max_size: u64) -> Self {
+        // NOTE: Like elsewhere in the Filestore, we assume sizes fit in a usize.
+        Self::new(
+            usize::try_from(min_size).unwrap(),
+            usize::try_from(chunk_size).unwrap(),
+            usize::try_from(max_size).unwrap(),
+        )
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Find the length of the first chunk in data. This is always at most max_size, and
+    /// data.len() if no boundary is found.
+    pub fn find_boundary(&self, data: &[u8]) -> usize {
+        let len = min(data.len(), self.max_size);
+        if len <= self.min_size {
+            return len;
+        }
+
+        let normal = min(len, self.avg_size);
+        let mut fingerprint: u64 = 0;
+        let mut i = self.min_size;
+
+        while i < normal {
+            fingerprint = (fingerprint << 1).wrapping_add(GEAR[data[i] as usize]);
+            if fingerprint & self.mask_small == 0 {
+                return i + 1;
+            }
+            i += 1;
+        }
+
+        while i < len {
+            fingerprint = (fingerprint << 1).wrapping_add(GEAR[data[i] as usize]);
+            if fingerprint & self.mask_large == 0 {
+                return i + 1;
+            }
+            i += 1;
+        }
+
+        len
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Boundary {
+    Fixed(usize),
+    ContentDefined(ContentDefinedChunker),
+}
+
 #[must_use = "streams do nothing unless polled"]
 #[pin_project::pin_project]
 #[derive(Debug)]
@@ -29,26 +146,69 @@ pub struct ChunkStream<S> {
 
 #[derive(Debug)]
 struct ChunkStreamState {
-    chunk_size: usize,
+    boundary: Boundary,
     buff: BytesMut,
     emitted: bool,
     had_data: bool,
+    exhausted: bool,
     done: bool,
 }
 
+impl ChunkStreamState {
+    fn new(boundary: Boundary, capacity: usize) -> Self {
+        ChunkStreamState {
+            boundary,
+            buff: BytesMut::with_capacity(capacity),
+            emitted: false,
+            had_data: false,
+            exhausted: false,
+            done: false,
+        }
+    }
+
+    /// Returns the size of the next chunk to emit, if we have buffered enough data to know it.
+    fn next_chunk_size(&self) -> Option<usize> {
+        let len = self.buff.len();
+        let flush = self.exhausted && len > 0;
+
+        match self.boundary {
+            Boundary::Fixed(chunk_size) => {
+                if len >= chunk_size {
+                    Some(chunk_size)
+                } else if flush {
+                    Some(len)
+                } else {
+                    None
+                }
+            }
+            Boundary::ContentDefined(ref chunker) => {
+                // We can only pick a boundary once we have max_size bytes buffered (or no more
+                // data is coming): until then, a boundary further down might still be the
+                // first one.
+                if len >= chunker.max_size() || flush {
+                    Some(chunker.find_boundary(&self.buff))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
 impl<S> ChunkStream<S> {
     pub fn new(stream: S, chunk_size: usize) -> ChunkStream<S> {
         assert!(chunk_size > 0);
 
         ChunkStream {
             stream,
-            state: ChunkStreamState {
-                chunk_size,
-                buff: BytesMut::with_capacity(chunk_size),
-                emitted: false,
-                had_data: false,
-                done: false,
-            },
+            state: ChunkStreamState::new(Boundary::Fixed(chunk_size), chunk_size),
+        }
+    }
+
+    pub fn content_defined(stream: S, chunker: ContentDefinedChunker) -> ChunkStream<S> {
+        ChunkStream {
+            stream,
+            state: ChunkStreamState::new(Boundary::ContentDefined(chunker), chunker.max_size()),
         }
     }
 }
@@ -67,13 +227,37 @@ where
         }
 
         loop {
-            if proj.state.buff.len() >= proj.state.chunk_size {
-                // We've buffered more data than we need. Emit some.
+            if let Some(size) = proj.state.next_chunk_size() {
+                // We've buffered enough data to know where the next chunk ends. Emit it.
                 proj.state.emitted = true;
-                let chunk = proj.state.buff.split_to(proj.state.chunk_size).freeze();
+                let chunk = proj.state.buff.split_to(size).freeze();
                 return Poll::Ready(Some(Ok(chunk)));
             }
 
+            if proj.state.exhausted {
+                // No more data is coming, and we've emitted everything we had buffered.
+                proj.state.done = true;
+
+                // However, we need to be a little careful to handle empty data here.
+                //
+                // If we emitted data, that just means all our data was returned in chunks, and we
+                // are done.
+                //
+                // However, if we never emitted, then we have two possible cases to handle:
+                //
+                // - Our underlying stream was empty Bytes. In this case, we should return empty
+                // Bytes too (we're returning a representation of the underlying content, chunked).
+                //
+                // - Our underlying stream was empty. In this case, we shouldn't return anything.
+
+                if proj.state.had_data && !proj.state.emitted {
+                    proj.state.emitted = true;
+                    return Poll::Ready(Some(Ok(Bytes::new())));
+                }
+
+                return Poll::Ready(None);
+            }
+
             // We need more data. Poll for some! Note the as_mut() here is used to reborrow the
             // stream and avoid moving it into the loop iteration.
 
@@ -83,46 +267,15 @@ where
                     // that extend_from slice implicitly extends our BytesMut.
                     proj.state.had_data = true;
                     proj.state.buff.extend_from_slice(&bytes);
-                    continue;
                 }
                 Some(Err(e)) => {
                     return Poll::Ready(Some(Err(e)));
                 }
                 None => {
-                    // Fallthrough
+                    // No more data is coming. Flush whatever we have left.
+                    proj.state.exhausted = true;
                 }
             };
-
-            // No more data is coming.
-
-            proj.state.done = true;
-
-            // Return whatever we have left. However, we need to be a little careful to handle
-            // empty data here.
-            //
-            // If our buffer happens to just be empty, but we emitted data, that just means our
-            // data was disivible by our chunk size, and we are done.
-            //
-            // However, if our buffer is empty, but we never emitted, then we have two possible
-            // cases to handle:
-            //
-            // - Our underlying stream was empty Bytes. In this case, we should return empty Bytes
-            // too (we're returning a representation of the underlying content, chunked).
-            //
-            // - Our underlying stream was empty. In this case, we shouldn't return anything.
-
-            let out = if !proj.state.buff.is_empty() || (proj.state.had_data && !proj.state.emitted)
-            {
-                // We did have some buffered data. Emit that.
-                proj.state.emitted = true;
-                let chunk = std::mem::replace(&mut proj.state.buff, BytesMut::new()).freeze();
-                Poll::Ready(Some(Ok(chunk)))
-            } else {
-                // We have no more buffered data. We're done.
-                Poll::Ready(None)
-            };
-
-            return out;
         }
     }
 }
@@ -142,11 +295,12 @@ impl Debug for Chunks<'_> {
 }
 
 /// Chunk a stream of incoming data for storage. We use the incoming size hint to decide whether
-/// to chunk.
+/// to chunk, and chunking to decide where chunk boundaries go.
 pub fn make_chunks<'a, S>(
     data: S,
     expected_size: ExpectedSize,
     chunk_size: Option<u64>,
+    chunking: ChunkingMethod,
 ) -> Chunks<'a>
 where
     S: Stream<Item = Result<Bytes, Error>> + Send + 'a,
@@ -171,7 +325,14 @@ where
 
     match chunk_size {
         Some(chunk_size) if expected_size.should_chunk(chunk_size) => {
-            let stream = ChunkStream::new(data, chunk_size as usize);
+            let stream = match chunking {
+                ChunkingMethod::FixedSize => ChunkStream::new(data, chunk_size as usize),
+                ChunkingMethod::ContentDefined { min_size, max_size } => {
+                    let chunker =
+                        ContentDefinedChunker::from_method(chunk_size, min_size, max_size);
+                    ChunkStream::content_defined(data, chunker)
+                }
+            };
             Chunks::Chunked(expected_size, stream.boxed())
         }
         _ => {
@@ -190,6 +351,77 @@ where
     }
 }
 
+/// Random values for the Gear rolling hash used by content-defined chunking, one per byte value.
+/// These were produced by a splitmix64 generator, and must never change: doing so would move
+/// every chunk boundary and defeat deduplication against existing content.
+#[rustfmt::skip]
+static GEAR: [u64; 256] = [
+    0x61cd86c91c1158ec, 0x90bb8b05c97dc3c8, 0x4df8f52c45664788, 0xaaae68704e5ef4e2,
+    0x1e6dc1b7e5e96c8d, 0x998eba5d70c6e01e, 0xadfa08745ff79dca, 0x4ec4f8cbfa2e300c,
+    0x590ecf187c9a1b52, 0x98207540e1f9eedd, 0xd6c2ecf550f88ece, 0x702816b3a7ebca64,
+    0x80b0273e708fa53f, 0xa035cb542ea8a42e, 0x5f6ee9454d34c6b0, 0x19f494b13853112e,
+    0x62af29923ff81e09, 0x16016a2b1277559f, 0xd9a005bbca0e8b21, 0xe1485c121145e9c2,
+    0xd0a610aa43b850ca, 0x94f2ef859c087c4b, 0xb3c6c0f891552526, 0x1c4e1bfc18950ac8,
+    0x80868ed92c6f5242, 0xef94c68b6f4f1c64, 0xcfb54cf845438690, 0xb0971f4fd3cd582e,
+    0x1d8dfb7cf25e7011, 0x1183553bd3383a97, 0xe73e0657f22bcdfe, 0xf86238d785e774cf,
+    0x6af0d248d339ba8e, 0x2fad94a1fc96593e, 0xc65e5e6d24008216, 0x2a8f5b7094e6d2a6,
+    0xce079acaf860eaa0, 0xd5269c27515c41d8, 0xf185fea0013027bd, 0x7b1cb79cd311acd3,
+    0x3a86357af7400cf4, 0x6c0b719c4b747212, 0x0cc592ecddd1a297, 0xddd26a32eb3b5895,
+    0xfad34d83a1f601f2, 0xe005a69c53c602fb, 0xb3d19610254f8ed0, 0xced773898d7d7e58,
+    0xb61fd7e5b137ce0f, 0xcf62917df15274c3, 0xe224301e1e5c5da5, 0xa8d8f8f650fda461,
+    0x3d359abd071596ca, 0x25b519b01061e8da, 0xa2b5736e6a9da707, 0x46db63fe2243bc4a,
+    0x0f491056738870e6, 0x8ab23a0672daa984, 0x0db4330d1bad7cc3, 0xf21fa8a4fc3e42b4,
+    0xcefe2af39137c61e, 0x2572a3ddc910314f, 0xce2561906fe3482b, 0x1c26940f00835465,
+    0x5a53dba37bd8e1b7, 0x7c03104bc283c9c3, 0x021234752c695d8e, 0x5c6cb39e525a1ad5,
+    0x2194dbd6a9fda074, 0x51627fa4e2c097b0, 0xbb4f2a2cb24048ea, 0x1306d55de7b312b8,
+    0xf7946a32a6fc832d, 0xa3a23e18dfd1ed59, 0x992d2869edf4efea, 0xb7ea0bdb19713d72,
+    0x1fa74a1b9192e38b, 0x6ab4400fd475e627, 0xd6310fc3fa5bd517, 0x8118997921c855b5,
+    0x17802007ef22a9d5, 0xe661ff5a8ddcb57f, 0xf3937f78e2e64d00, 0xb6c624a660d0dcf6,
+    0xa0d479217b8d30fc, 0xba0b939b88fde18e, 0xaff8414d119fee5c, 0x1f79b84597b40ed7,
+    0x36188374e6515cf9, 0xdbfff099128c0cc2, 0xea4119dd83e44f7b, 0xeb7392e7a6e0ebdd,
+    0x000f1b5a26693804, 0xd2851e6c28495bc2, 0xa50c1b72c9f204dc, 0x23dee5bab129ad08,
+    0x1fd868a8e32bf414, 0x89216d58b7f41e41, 0x252c4097a8d82125, 0x4460d0fcb60c95da,
+    0xa9514cf17827f51c, 0xe697805b4f0d406f, 0x8c29837d40732d52, 0xf57bccc795ad56fc,
+    0x4dc25f7acbae14d7, 0xf4900f6fa828ef92, 0xf6e5f032911b0a58, 0x3467ec4f2d6e67e1,
+    0xdf02b4d86a4b05c8, 0x2155ba140f271682, 0x3aa270226e6f63e1, 0x19c800e574370c3b,
+    0xb899e3d8c90464a9, 0xb6d750321b18927b, 0x1cd9b2fe60bc06d7, 0x2ee5b0b06216aee5,
+    0x6ce40fc248709b75, 0x704f3568ea4b950c, 0x0d7620de64faabe6, 0xcc990fbaae7042e0,
+    0x6f2baffe49f59e70, 0xcfb04c84117ad30f, 0xd0f68ac36097dbfc, 0x0002c7d38180dbc2,
+    0xea9fd696c366b7d9, 0xb5e83243ee4688c5, 0x51f2df8ee3320b5a, 0xbb827fe2eb9e832d,
+    0x4552222d6a11dce3, 0x1cf01e14e4b21d01, 0xe881c534ca235af3, 0x56cb984e49662471,
+    0x118a83cc446ed0e9, 0xfb655e06ba6be1e1, 0x84459a87f835cb5e, 0xe34820c1f381f130,
+    0xdd48db3449b85bde, 0xb35dc96dd2f1213b, 0x116fb5c982d886f0, 0x78a7ee832b0c695b,
+    0x14696711c3cba2b4, 0x3ceef67a31b5902b, 0x2b5a1cba5c4177ca, 0x5bba70244874b18c,
+    0x809cfa961e5f6b22, 0x9ec58c5593faad67, 0xcfbe3ffe8f5aa00c, 0x5b2865fc2d37eef7,
+    0x034963f1e0596da4, 0xed4cdb0e746bff23, 0xbad3ea6b9e499dd3, 0x19fd1f3e0a1f457d,
+    0x9f21019687e57416, 0x2b6685581ba92e52, 0xf4b211262bdccdca, 0xac473f6119d61736,
+    0x5928b3d7c2bc2191, 0xa8202d6a6f1c4a4b, 0x9ce6d1f02a469aef, 0x0e73577d85fa1721,
+    0xa5593dc273cae662, 0xdd6e7a10e0869675, 0x3e8dd96cbb253c3f, 0xf4b786455afc3c95,
+    0xde75f4c473c9794e, 0x5bff3f5afe531f79, 0x0ed83393982e449d, 0x12671636963346df,
+    0xf611083dc6ef7670, 0x32e2abb9bf285a80, 0xe83e3359fc1ee60e, 0xebbfb219250dfbce,
+    0x88c95a487635c902, 0xd1e57b00fd574f68, 0x6c86d1bb2eb594e8, 0x92859155b9d7b272,
+    0x7f51bf876cdb5749, 0xc221a6579ac9b16c, 0x7f665043ca5f83a8, 0xbf454c9917d068b4,
+    0x0ce0efd86779d38f, 0x10c4f03ab77b9ddf, 0x2aaea94ab6aed342, 0x5cdcc57f8a196e25,
+    0xd0a820ec1053af55, 0x845f8d436593e17e, 0x534745ac5bfbf296, 0xb29dbab60a8d0a4a,
+    0x0d83b5ebb055697e, 0x427bd3ea4c6d89c8, 0x0281a51dd8b188dc, 0x49a005a13583400b,
+    0x419dc1ab4290bf22, 0xd1deecc6d61f8083, 0xf947f76ed5f2ae98, 0x1af1f8dfefd83845,
+    0x2ceaab613931d428, 0x182a508413732905, 0xce37dc823d1fa29c, 0x48aacc6f9ae7ae78,
+    0x594683b6b65d2356, 0x710f56507d49f855, 0xe21638d6e6688994, 0xb3199ccf2750b3a4,
+    0x9ee6a8e4a07f0e62, 0x93315c8cd7c8adc7, 0xc314f9f60188aa91, 0x456fd5deef1baf98,
+    0x62bbb1dae9e99e7f, 0x08787f234fc6a6bd, 0xb0a76766f1efea22, 0x0c45184169f674b0,
+    0x0d93390e7c6ab501, 0x7a3abf25fa29e087, 0xf4b0df1fc21aacf3, 0xa31a5eafe2cb105d,
+    0x4d95bf7212f54784, 0x596c0cbc74fac687, 0xd2c9525bdd98f7bc, 0x67eaaaee8b3b81e8,
+    0x6d48f3b6596a2a06, 0x734e7e45bd850a9e, 0x725ae8c207be30ac, 0x39185ca4a3f2407d,
+    0x3391e7ff27123266, 0xa392557e8f89da68, 0xd23890e39b23f52a, 0xf785dada861f6910,
+    0x5bfeff5628d63cb2, 0x022f412a8931dd03, 0x804f13f141f0db11, 0x20745b4fba73dd14,
+    0xf827ce06646060ae, 0x383448731a161cd3, 0x1e7fc95b34e4025b, 0x27f31aa5906d1db9,
+    0x073fb66539e59f74, 0x9eddd5429602270b, 0x3bddf524abdd04ed, 0xfceb19090477b195,
+    0x907b071b3c8f5b29, 0x1236b850980f53d1, 0x0e4d84032bfef0c9, 0x8b9f365162dcd313,
+    0x7d57e01f66c6d2cb, 0x0c005603f2cb3a78, 0x2d88d795c953ad3d, 0x777c10c3c78b2fcd,
+    0x39a1c257a59f8cf9, 0xaca63755f6a21b21, 0xddd3eee72ed68289, 0xbaff5c81fb4fcbb7,
+    0x75ad08ca60503c0b, 0xae8ae3853a42e27b, 0x30072b6581f0aa6d, 0x15bded91f74b8d37,
+];
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -197,13 +429,20 @@ mod test {
     use assert_matches::assert_matches;
     use futures::stream;
     use quickcheck::quickcheck;
+    use rand::{rngs::SmallRng, RngCore, SeedableRng};
+    use std::collections::HashSet;
     use tokio_compat::runtime::Runtime;
 
     #[test]
     fn test_make_chunks_no_chunk_size() {
         let in_stream = stream::empty();
 
-        match make_chunks(in_stream, ExpectedSize::new(10), None) {
+        match make_chunks(
+            in_stream,
+            ExpectedSize::new(10),
+            None,
+            ChunkingMethod::FixedSize,
+        ) {
             Chunks::Inline(_) => {}
             c => panic!("Did not expect {:?}", c),
         };
@@ -213,7 +452,12 @@ mod test {
     fn test_make_chunks_no_chunking() {
         let in_stream = stream::empty();
 
-        match make_chunks(in_stream, ExpectedSize::new(10), Some(100)) {
+        match make_chunks(
+            in_stream,
+            ExpectedSize::new(10),
+            Some(100),
+            ChunkingMethod::FixedSize,
+        ) {
             Chunks::Inline(_) => {}
             c => panic!("Did not expect {:?}", c),
         };
@@ -223,7 +467,12 @@ mod test {
     fn test_make_chunks_no_chunking_limit() {
         let in_stream = stream::empty();
 
-        match make_chunks(in_stream, ExpectedSize::new(100), Some(100)) {
+        match make_chunks(
+            in_stream,
+            ExpectedSize::new(100),
+            Some(100),
+            ChunkingMethod::FixedSize,
+        ) {
             Chunks::Inline(_) => {}
             c => panic!("Did not expect {:?}", c),
         };
@@ -233,7 +482,12 @@ mod test {
     fn test_make_chunks_chunking() {
         let in_stream = stream::empty();
 
-        match make_chunks(in_stream, ExpectedSize::new(1000), Some(100)) {
+        match make_chunks(
+            in_stream,
+            ExpectedSize::new(1000),
+            Some(100),
+            ChunkingMethod::FixedSize,
+        ) {
             Chunks::Chunked(h, _) if h.check_equals(1000).is_ok() => {}
             c => panic!("Did not expect {:?}", c),
         };
@@ -252,7 +506,12 @@ mod test {
         ];
         let in_stream = stream::iter(chunks).map(Ok);
 
-        let fut = match make_chunks(in_stream, ExpectedSize::new(10), Some(100)) {
+        let fut = match make_chunks(
+            in_stream,
+            ExpectedSize::new(10),
+            Some(100),
+            ChunkingMethod::FixedSize,
+        ) {
             c @ Chunks::Chunked(..) => panic!("Did not expect {:?}", c),
             Chunks::Inline(fut) => fut,
         };
@@ -273,7 +532,12 @@ mod test {
         ];
         let in_stream = stream::iter(chunks).map(Ok);
 
-        let fut = match make_chunks(in_stream, ExpectedSize::new(10), Some(1)) {
+        let fut = match make_chunks(
+            in_stream,
+            ExpectedSize::new(10),
+            Some(1),
+            ChunkingMethod::FixedSize,
+        ) {
             Chunks::Chunked(_, stream) => stream.try_collect::<Vec<_>>(),
             c @ Chunks::Inline(..) => panic!("Did not expect {:?}", c),
         };
@@ -430,7 +694,132 @@ mod test {
         true
     }
 
+    #[test]
+    fn test_make_chunks_content_defined() {
+        let in_stream = stream::empty();
+
+        match make_chunks(
+            in_stream,
+            ExpectedSize::new(1000),
+            Some(100),
+            ChunkingMethod::content_defined(100),
+        ) {
+            Chunks::Chunked(h, _) if h.check_equals(1000).is_ok() => {}
+            c => panic!("Did not expect {:?}", c),
+        };
+    }
+
+    #[test]
+    fn test_content_defined_boundary_bounds() {
+        let chunker = ContentDefinedChunker::new(16, 64, 256);
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let mut data = vec![0u8; 1000];
+            rng.fill_bytes(&mut data);
+            let boundary = chunker.find_boundary(&data);
+            assert!((16..=256).contains(&boundary), "boundary at {}", boundary);
+        }
+
+        // Data that is too short to be cut is returned whole.
+        assert_eq!(chunker.find_boundary(&[1; 10]), 10);
+        assert_eq!(chunker.find_boundary(&[]), 0);
+    }
+
+    #[tokio::test]
+    async fn test_content_defined_resynchronizes_after_insert() {
+        // Inserting a byte near the start of the content should only change the chunks around
+        // the insertion: chunk boundaries further down depend only on the content around them.
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut data = vec![0u8; 1024 * 1024];
+        rng.fill_bytes(&mut data);
+
+        let mut edited = data.clone();
+        edited.insert(100, 42);
+
+        let chunker = ContentDefinedChunker::new(1024, 4096, 16384);
+        let chunk = |data: Vec<u8>| {
+            let in_stream = stream::iter(vec![Bytes::from(data)]).map(Result::<_, ()>::Ok);
+            ChunkStream::content_defined(in_stream, chunker).try_collect::<Vec<_>>()
+        };
+
+        let before = chunk(data).await.unwrap();
+        let after = chunk(edited).await.unwrap();
+
+        let before_set: HashSet<_> = before.iter().collect();
+        let changed = after.iter().filter(|c| !before_set.contains(c)).count();
+
+        assert!(before.len() > 100);
+        assert!(changed <= 2, "{} chunks changed", changed);
+    }
+
+    async fn do_check_content_defined_stream(
+        in_chunks: Vec<Vec<u8>>,
+        chunker: ContentDefinedChunker,
+    ) -> bool {
+        let in_chunks: Vec<Bytes> = in_chunks.into_iter().map(Bytes::from).collect();
+        let expected_bytes = in_chunks
+            .iter()
+            .fold(BytesMut::new(), |mut bytes, chunk| {
+                bytes.extend_from_slice(&chunk);
+                bytes
+            })
+            .freeze();
+
+        let out_chunks =
+            ChunkStream::content_defined(stream::iter(in_chunks).map(Result::<_, ()>::Ok), chunker)
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+
+        // How the input was split must not affect where chunk boundaries go.
+        let single_chunks = ChunkStream::content_defined(
+            stream::iter(vec![expected_bytes.clone()]).map(Result::<_, ()>::Ok),
+            chunker,
+        )
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+
+        if !expected_bytes.is_empty() && out_chunks != single_chunks {
+            return false;
+        }
+
+        let got_bytes = out_chunks
+            .iter()
+            .fold(BytesMut::new(), |mut bytes, chunk| {
+                bytes.extend_from_slice(&chunk);
+                bytes
+            })
+            .freeze();
+
+        if expected_bytes != got_bytes {
+            return false;
+        }
+
+        if out_chunks.len() <= 1 {
+            return true;
+        }
+
+        // All chunks except for the last one must be within bounds. The last one can be smaller.
+        for chunk in out_chunks[0..out_chunks.len() - 1].iter() {
+            if chunk.len() < chunker.min_size || chunk.len() > chunker.max_size {
+                return false;
+            }
+        }
+
+        out_chunks[out_chunks.len() - 1].len() <= chunker.max_size
+    }
+
     quickcheck! {
+        fn check_content_defined_stream(in_chunks: Vec<Vec<u8>>, min_size: usize) -> bool {
+            // Keep chunks small so that quickcheck's inputs actually get cut.
+            let min_size = min_size % 8 + 1;
+            let chunker = ContentDefinedChunker::new(min_size, min_size * 2, min_size * 4);
+            let mut rt = Runtime::new().unwrap();
+            rt.block_on_std(do_check_content_defined_stream(in_chunks, chunker))
+        }
+
         fn check_chunk_stream(in_chunks: Vec<Vec<u8>>, size: usize) -> bool {
             let size = size + 1; // Don't allow 0 as the size.
             let mut rt = Runtime::new().unwrap();
@@ -450,7 +839,7 @@ mod test {
 
             let len = expected_bytes.len() as u64;
 
-            let fut = match make_chunks(in_stream, ExpectedSize::new(len), Some(len)) {
+            let fut = match make_chunks(in_stream, ExpectedSize::new(len), Some(len), ChunkingMethod::FixedSize) {
                 Chunks::Inline(fut) => fut,
                 c => panic!("Did not expect {:?}", c),
             };
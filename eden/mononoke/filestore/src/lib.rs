@@ -36,8 +36,9 @@ mod prepare;
 mod rechunk;
 mod streamhash;
 
+pub use chunk::ChunkingMethod;
 pub use fetch_key::{Alias, AliasBlob, FetchKey};
-pub use rechunk::{force_rechunk, rechunk};
+pub use rechunk::{chunk_keys, force_rechunk, rechunk, RechunkStats};
 
 #[cfg(test)]
 mod test;
@@ -63,6 +64,7 @@ mod test;
 #[derive(Debug, Copy, Clone)]
 pub struct FilestoreConfig {
     pub chunk_size: Option<u64>,
+    pub chunking: ChunkingMethod,
     pub concurrency: usize,
 }
 
@@ -70,6 +72,7 @@ impl Default for FilestoreConfig {
     fn default() -> Self {
         FilestoreConfig {
             chunk_size: None,
+            chunking: ChunkingMethod::FixedSize,
             concurrency: 1,
         }
     }
@@ -170,6 +173,18 @@ pub async fn get_metadata<B: Blobstore>(
     }
 }
 
+/// Recompute the metadata for a stream of file contents without trusting anything stored about
+/// it, e.g. to check stored content for corruption.
+pub async fn compute_metadata<S>(
+    expected_size: u64,
+    content_stream: S,
+) -> Result<ContentMetadata, Error>
+where
+    S: Stream<Item = Result<Bytes, Error>> + Send,
+{
+    metadata::compute_metadata(expected_size, content_stream).await
+}
+
 /// Fetch the metadata for the underlying content. This will return None if the content does
 /// not exist, Some(None) if the metadata does not exist, and Some(Some(ContentMetadata))
 /// when metadata found. It will not recompute metadata on the fly
@@ -397,19 +412,20 @@ pub async fn store<B: Blobstore + Clone + 'static>(
 ) -> Result<ContentMetadata, Error> {
     use chunk::Chunks;
 
-    let prepared = match chunk::make_chunks(data, req.expected_size, config.chunk_size) {
-        Chunks::Inline(fut) => prepare::prepare_bytes(fut.await?),
-        Chunks::Chunked(expected_size, chunks) => {
-            prepare::prepare_chunked(
-                ctx.clone(),
-                blobstore.clone(),
-                expected_size,
-                chunks,
-                config.concurrency,
-            )
-            .await?
-        }
-    };
+    let prepared =
+        match chunk::make_chunks(data, req.expected_size, config.chunk_size, config.chunking) {
+            Chunks::Inline(fut) => prepare::prepare_bytes(fut.await?),
+            Chunks::Chunked(expected_size, chunks) => {
+                prepare::prepare_chunked(
+                    ctx.clone(),
+                    blobstore.clone(),
+                    expected_size,
+                    chunks,
+                    config.concurrency,
+                )
+                .await?
+            }
+        };
 
     finalize::finalize(blobstore, ctx, Some(&req), prepared).await
 }
@@ -7,13 +7,21 @@
 
 use anyhow::Error;
 use blobstore::{Blobstore, Loadable, LoadableError, Storable};
+use bytes::Bytes;
 use context::CoreContext;
+use futures::{
+    future,
+    stream::{Stream, StreamExt},
+};
 use mononoke_types::{BlobstoreValue, ContentId, ContentMetadata, ContentMetadataId};
 use thiserror::Error;
 
-use crate::alias::alias_stream;
+use crate::alias::{add_aliases_to_multiplexer, alias_stream};
 use crate::expected_size::ExpectedSize;
 use crate::fetch;
+use crate::incremental_hash::ContentIdIncrementalHasher;
+use crate::multiplexer::Multiplexer;
+use crate::streamhash::hash_stream;
 
 #[derive(Debug, Error)]
 pub enum RebuildBackmappingError {
@@ -125,3 +133,41 @@ async fn rebuild_metadata<B: Blobstore>(
 
     Ok(metadata)
 }
+
+/// Compute metadata for a stream of file contents from scratch, including its ContentId. The
+/// returned total_size is the size that was observed, but the Git SHA-1 is computed with the
+/// expected size, as it is part of what is hashed.
+pub async fn compute_metadata<S>(
+    expected_size: u64,
+    content_stream: S,
+) -> Result<ContentMetadata, Error>
+where
+    S: Stream<Item = Result<Bytes, Error>> + Send,
+{
+    let mut multiplexer = Multiplexer::<Bytes>::new();
+    let content_id =
+        multiplexer.add(|stream| hash_stream(ContentIdIncrementalHasher::new(), stream));
+    let total_size = multiplexer.add(|stream| {
+        stream.fold(0, |size, bytes: Bytes| {
+            future::ready(size + bytes.len() as u64)
+        })
+    });
+    let aliases = add_aliases_to_multiplexer(&mut multiplexer, ExpectedSize::new(expected_size));
+
+    multiplexer
+        .drain(content_stream)
+        .await
+        .map_err(|e| -> Error { e.into() })?;
+
+    let content_id = content_id.await?;
+    let total_size = total_size.await?;
+    let (sha1, sha256, git_sha1) = aliases.await?.redeem(expected_size)?;
+
+    Ok(ContentMetadata {
+        total_size,
+        content_id,
+        sha1,
+        sha256,
+        git_sha1,
+    })
+}
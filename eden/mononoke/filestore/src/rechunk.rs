@@ -6,14 +6,20 @@
  */
 
 use anyhow::Error;
-use futures::future::TryFutureExt;
+use futures::{future::TryFutureExt, stream::TryStreamExt};
 use slog::debug;
+use std::collections::HashSet;
 use thiserror::Error;
 
 use blobstore::{Blobstore, Loadable, LoadableError};
 use context::CoreContext;
-use mononoke_types::{ChunkedFileContents, ContentId, ContentMetadata, FileContents};
+use mononoke_types::{
+    content_chunk::new_blob_and_pointer, ChunkedFileContents, ContentId, ContentMetadata,
+    FileContents, MononokeId,
+};
 
+use crate::chunk::{make_chunks, ChunkingMethod, Chunks};
+use crate::expected_size::ExpectedSize;
 use crate::{fetch, get_metadata, store, FetchKey, FilestoreConfig, StoreRequest};
 
 #[derive(Debug, Error)]
@@ -64,8 +70,10 @@ pub async fn rechunk<B: Blobstore + Clone + 'static>(
         None => return Err(ErrorKind::ContentNotFound(content_id).into()),
     };
 
-    match chunk_size {
-        Some(chunk_size) if content_metadata.total_size > chunk_size => {
+    match (chunk_size, filestore_config.chunking) {
+        (Some(chunk_size), ChunkingMethod::FixedSize)
+            if content_metadata.total_size > chunk_size =>
+        {
             let r: Result<(ContentMetadata, bool), Error> = rechunk_if_uses_larger_chunk_size(
                 blobstore,
                 chunk_size,
@@ -77,6 +85,11 @@ pub async fn rechunk<B: Blobstore + Clone + 'static>(
 
             r
         }
+        (Some(chunk_size), ChunkingMethod::ContentDefined { .. })
+            if content_metadata.total_size > chunk_size =>
+        {
+            rechunk_if_not_content_defined(blobstore, filestore_config, ctx, content_metadata).await
+        }
         _ => Ok((content_metadata, false)),
     }
 }
@@ -154,6 +167,7 @@ async fn rechunk_if_uses_larger_chunk_size<B: Blobstore + Clone + 'static>(
     if should_rechunk {
         let filestore_config = FilestoreConfig {
             chunk_size: Some(expected_chunk_size),
+            chunking: ChunkingMethod::FixedSize,
             concurrency,
         };
 
@@ -167,6 +181,145 @@ async fn rechunk_if_uses_larger_chunk_size<B: Blobstore + Clone + 'static>(
     }
 }
 
+/// For content, represented by `content_metadata`, rechunk it if its
+/// chunks are not the ones content-defined chunking with `filestore_config`
+/// would produce (e.g. it was written with fixed-size chunks, or with
+/// different content-defined chunking parameters).
+/// Note: finding this out requires reading the content, so this is about as
+/// expensive as rechunking if the content turns out to need it.
+async fn rechunk_if_not_content_defined<B: Blobstore + Clone + 'static>(
+    blobstore: &B,
+    filestore_config: FilestoreConfig,
+    ctx: &CoreContext,
+    content_metadata: ContentMetadata,
+) -> Result<(ContentMetadata, bool), Error> {
+    let content_id = content_metadata.content_id.clone();
+
+    let file_contents: FileContents = content_id
+        .load(ctx, blobstore)
+        .map_err(move |err| {
+            match err {
+                LoadableError::Error(err) => err,
+                LoadableError::Missing(_) => ErrorKind::ContentNotFound(content_id).into(),
+            }
+        })
+        .await?;
+
+    let should_rechunk = match file_contents {
+        FileContents::Bytes(_) => true,
+        FileContents::Chunked(ref chunked_file_contents) => {
+            let existing: Vec<_> = chunked_file_contents
+                .iter_chunks()
+                .map(|pointer| (pointer.chunk_id().blobstore_key(), pointer.size()))
+                .collect();
+            let wanted =
+                chunk_file_contents(blobstore, filestore_config, ctx, file_contents.clone())
+                    .await?;
+            existing != wanted
+        }
+    };
+
+    if should_rechunk {
+        let content_metadata: ContentMetadata =
+            do_rechunk_file_contents(blobstore, filestore_config, ctx, file_contents, content_id)
+                .await?;
+
+        Ok((content_metadata, true))
+    } else {
+        Ok((content_metadata, false))
+    }
+}
+
+/// Compute the blobstore keys (and sizes) of the blobs that storing the
+/// content for `content_id` with `filestore_config` would produce, without
+/// writing anything. Content that would not be chunked is returned as a
+/// single content blob.
+pub async fn chunk_keys<B: Blobstore + Clone + 'static>(
+    blobstore: &B,
+    filestore_config: FilestoreConfig,
+    ctx: &CoreContext,
+    content_id: ContentId,
+) -> Result<Vec<(String, u64)>, Error> {
+    let file_contents: FileContents = content_id
+        .load(ctx, blobstore)
+        .map_err(move |err| {
+            match err {
+                LoadableError::Error(err) => err,
+                LoadableError::Missing(_) => ErrorKind::ContentNotFound(content_id).into(),
+            }
+        })
+        .await?;
+    chunk_file_contents(blobstore, filestore_config, ctx, file_contents).await
+}
+
+async fn chunk_file_contents<B: Blobstore + Clone + 'static>(
+    blobstore: &B,
+    filestore_config: FilestoreConfig,
+    ctx: &CoreContext,
+    file_contents: FileContents,
+) -> Result<Vec<(String, u64)>, Error> {
+    let content_id = file_contents.content_id();
+    let size = file_contents.size();
+    let file_stream = fetch::stream_file_bytes(blobstore, ctx, file_contents, fetch::Range::All);
+
+    match make_chunks(
+        file_stream,
+        ExpectedSize::new(size),
+        filestore_config.chunk_size,
+        filestore_config.chunking,
+    ) {
+        Chunks::Inline(_) => Ok(vec![(content_id.blobstore_key(), size)]),
+        Chunks::Chunked(_, chunks) => {
+            chunks
+                .map_ok(|bytes| {
+                    let (blob, pointer) = new_blob_and_pointer(bytes);
+                    (blob.id().blobstore_key(), pointer.size())
+                })
+                .try_collect()
+                .await
+        }
+    }
+}
+
+/// Deduplication statistics for a set of files, as they would be stored
+/// with a given `FilestoreConfig`. Feed it the output of `chunk_keys`.
+#[derive(Debug, Default)]
+pub struct RechunkStats {
+    pub files: u64,
+    pub total_chunks: u64,
+    pub total_bytes: u64,
+    pub unique_chunks: u64,
+    pub unique_bytes: u64,
+    seen: HashSet<String>,
+}
+
+impl RechunkStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_file(&mut self, keys: Vec<(String, u64)>) {
+        self.files += 1;
+        for (key, size) in keys {
+            self.total_chunks += 1;
+            self.total_bytes += size;
+            if self.seen.insert(key) {
+                self.unique_chunks += 1;
+                self.unique_bytes += size;
+            }
+        }
+    }
+
+    /// How many bytes of content we have for each byte we'd need to store.
+    pub fn dedup_ratio(&self) -> f64 {
+        if self.unique_bytes == 0 {
+            1.0
+        } else {
+            self.total_bytes as f64 / self.unique_bytes as f64
+        }
+    }
+}
+
 /// Unconditionally rechunk `file_contents` using the `filestore_config`
 /// NOTE: This could actually unchunk a file if the chunk size threshold
 /// is increased after the file is written.
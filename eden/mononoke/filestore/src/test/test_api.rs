@@ -7,7 +7,7 @@
 
 use super::{canonical, chunk, request};
 use crate as filestore;
-use crate::{errors, Alias, FetchKey, FilestoreConfig, StoreRequest};
+use crate::{errors, Alias, ChunkingMethod, FetchKey, FilestoreConfig, StoreRequest};
 
 use super::failing_blobstore::{FailingBlobstore, FailingBlobstoreError};
 use anyhow::{Error, Result};
@@ -29,6 +29,7 @@ const HELLO_WORLD: &'static [u8] = b"hello, world";
 const HELLO_WORLD_LENGTH: u64 = 12;
 const DEFAULT_CONFIG: FilestoreConfig = FilestoreConfig {
     chunk_size: None,
+    chunking: ChunkingMethod::FixedSize,
     concurrency: 1,
 };
 
@@ -215,6 +216,7 @@ async fn filestore_chunked_put_get(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(1),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
 
@@ -244,10 +246,12 @@ async fn filestore_chunked_put_get_nested(fb: FacebookInit) -> Result<()> {
 
     let small = FilestoreConfig {
         chunk_size: Some(1),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let large = FilestoreConfig {
         chunk_size: Some(3),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -308,6 +312,7 @@ async fn filestore_chunk_not_found(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(3),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -349,6 +354,7 @@ async fn filestore_put_invalid_size(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(3),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -378,6 +384,7 @@ async fn filestore_put_content_id(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(3),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -421,6 +428,7 @@ async fn filestore_put_sha1(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(3),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -463,6 +471,7 @@ async fn filestore_put_git_sha1(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(3),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -509,6 +518,7 @@ async fn filestore_put_sha256(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(3),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -598,6 +608,7 @@ async fn filestore_get_range(fb: FacebookInit) -> Result<()> {
 async fn filestore_get_chunked_range(fb: FacebookInit) -> Result<()> {
     let small = FilestoreConfig {
         chunk_size: Some(3),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
 
@@ -793,6 +804,7 @@ async fn filestore_test_chunked_peek(fb: FacebookInit) -> Result<()> {
 
     let small = FilestoreConfig {
         chunk_size: Some(1),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
 
@@ -902,6 +914,7 @@ async fn filestore_store_error(fb: FacebookInit) -> Result<()> {
 
     let config = FilestoreConfig {
         chunk_size: Some(1),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
 
@@ -928,10 +941,12 @@ async fn filestore_test_rechunk(fb: FacebookInit) -> Result<()> {
 
     let small = FilestoreConfig {
         chunk_size: Some(1),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let large = FilestoreConfig {
         chunk_size: Some(3),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -965,10 +980,12 @@ async fn filestore_test_rechunk_larger(fb: FacebookInit) -> Result<()> {
 
     let small = FilestoreConfig {
         chunk_size: Some(1),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let large = FilestoreConfig {
         chunk_size: Some(3),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -1002,11 +1019,13 @@ async fn filestore_test_rechunk_unchunked(fb: FacebookInit) -> Result<()> {
 
     let small = FilestoreConfig {
         chunk_size: Some(1),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     // This is large enough that the data we upload won't be chunked.
     let large = FilestoreConfig {
         chunk_size: Some(100),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -1038,6 +1057,7 @@ async fn filestore_test_rechunk_missing_content(fb: FacebookInit) -> Result<()>
 
     let conf = FilestoreConfig {
         chunk_size: Some(1),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -1066,6 +1086,7 @@ async fn filestore_chunked_put_get_with_size(fb: FacebookInit) -> Result<()> {
     let blob = memblob::Memblob::default();
     let config = FilestoreConfig {
         chunk_size: Some(1),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
 
@@ -1109,10 +1130,12 @@ async fn filestore_test_rechunk_if_needed_tiny_unchunked_file(fb: FacebookInit)
 
     let large1 = FilestoreConfig {
         chunk_size: Some(100),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let large2 = FilestoreConfig {
         chunk_size: Some(200),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -1152,10 +1175,12 @@ async fn filestore_test_rechunk_if_needed_large_unchunked_file(fb: FacebookInit)
 
     let large = FilestoreConfig {
         chunk_size: Some(100),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let small = FilestoreConfig {
         chunk_size: Some(1),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -1190,10 +1215,12 @@ async fn filestore_test_rechunk_if_needed_large_chunks(fb: FacebookInit) -> Resu
 
     let large = FilestoreConfig {
         chunk_size: Some(5),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let small = FilestoreConfig {
         chunk_size: Some(1),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -1228,6 +1255,7 @@ async fn filestore_test_rechunk_if_needed_tiny_chunks(fb: FacebookInit) -> Resul
 
     let large = FilestoreConfig {
         chunk_size: Some(4),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -1306,3 +1334,153 @@ async fn filestore_exists(fb: FacebookInit) -> Result<()> {
 
     Ok(())
 }
+
+#[fbinit::compat_test]
+async fn filestore_content_defined_put_get(fb: FacebookInit) -> Result<()> {
+    let req = request(HELLO_WORLD);
+    let content_id = canonical(HELLO_WORLD);
+
+    let blob = memblob::Memblob::default();
+    let config = FilestoreConfig {
+        chunk_size: Some(2),
+        chunking: ChunkingMethod::ContentDefined {
+            min_size: 1,
+            max_size: 4,
+        },
+        concurrency: 5,
+    };
+
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx, blob, req);
+
+    filestore::store(
+        blob,
+        config,
+        ctx,
+        req,
+        stream::once(future::ready(Ok(Bytes::from(HELLO_WORLD)))),
+    )
+    .await?;
+
+    let res = filestore::fetch_concat_opt(blob, ctx, &FetchKey::Canonical(content_id)).await;
+
+    println!("res = {:#?}", res);
+
+    assert_eq!(res?, Some(Bytes::from(HELLO_WORLD)));
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn filestore_test_rechunk_if_needed_content_defined(fb: FacebookInit) -> Result<()> {
+    let blob = memblob::Memblob::new(PutBehaviour::Overwrite);
+
+    let fixed = FilestoreConfig {
+        chunk_size: Some(4),
+        chunking: ChunkingMethod::FixedSize,
+        concurrency: 5,
+    };
+    let cdc = FilestoreConfig {
+        chunk_size: Some(4),
+        chunking: ChunkingMethod::ContentDefined {
+            min_size: 2,
+            max_size: 8,
+        },
+        concurrency: 5,
+    };
+    let ctx = CoreContext::test_mock(fb);
+
+    let full_data = &b"foobarbazquxquuxcorgegraultgarply"[..];
+    let full_key = request(full_data);
+    let full_id = canonical(full_data);
+    borrowed!(ctx, blob, full_key);
+
+    // Chunk with fixed-size chunks
+    filestore::store(
+        blob,
+        fixed,
+        ctx,
+        full_key,
+        stream::once(future::ready(Ok(Bytes::from(full_data)))),
+    )
+    .await?;
+
+    // The chunks we want are the content-defined ones, so we expect a rechunk
+    let (_, rechunked) = filestore::rechunk::rechunk(blob, cdc, ctx, full_id).await?;
+    assert!(rechunked);
+
+    let res = filestore::fetch_concat_opt(blob, ctx, &FetchKey::Canonical(full_id)).await?;
+    assert_eq!(res, Some(Bytes::from(full_data)));
+
+    // Now that the content is chunked as we want, we expect no rechunk
+    let (_, rechunked) = filestore::rechunk::rechunk(blob, cdc, ctx, full_id).await?;
+    assert!(!rechunked);
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn filestore_test_rechunk_stats(fb: FacebookInit) -> Result<()> {
+    let blob = memblob::Memblob::default();
+    let config = FilestoreConfig {
+        chunk_size: Some(3),
+        chunking: ChunkingMethod::FixedSize,
+        concurrency: 5,
+    };
+    let ctx = CoreContext::test_mock(fb);
+    borrowed!(ctx, blob);
+
+    let mut stats = filestore::RechunkStats::new();
+
+    for data in &[&b"foofoofoo"[..], &b"foobar"[..]] {
+        filestore::store(
+            blob,
+            config,
+            ctx,
+            &request(data),
+            stream::once(future::ready(Ok(Bytes::from(*data)))),
+        )
+        .await?;
+        stats.add_file(filestore::chunk_keys(blob, config, ctx, canonical(data)).await?);
+    }
+
+    // "foo" x 4 and "bar" once: 15 bytes, of which only 6 are unique.
+    assert_eq!(stats.files, 2);
+    assert_eq!(stats.total_chunks, 5);
+    assert_eq!(stats.unique_chunks, 2);
+    assert_eq!(stats.total_bytes, 15);
+    assert_eq!(stats.unique_bytes, 6);
+    assert_eq!(stats.dedup_ratio(), 2.5);
+
+    Ok(())
+}
+
+#[fbinit::compat_test]
+async fn filestore_compute_metadata(_fb: FacebookInit) -> Result<()> {
+    let chunks = vec![
+        Ok(Bytes::from(&HELLO_WORLD[..5])),
+        Ok(Bytes::from(&HELLO_WORLD[5..])),
+    ];
+    let res = filestore::compute_metadata(HELLO_WORLD_LENGTH, stream::iter(chunks)).await?;
+
+    assert_eq!(
+        res,
+        ContentMetadata {
+            total_size: HELLO_WORLD_LENGTH,
+            content_id: canonical(HELLO_WORLD),
+            sha1: *HELLO_WORLD_SHA1,
+            git_sha1: *HELLO_WORLD_GIT_SHA1,
+            sha256: *HELLO_WORLD_SHA256
+        }
+    );
+
+    // Content that is shorter than claimed is reported with its real size and content id.
+    let res = filestore::compute_metadata(
+        HELLO_WORLD_LENGTH,
+        stream::once(future::ready(Ok(Bytes::from(&HELLO_WORLD[..5])))),
+    )
+    .await?;
+    assert_eq!(res.total_size, 5);
+    assert_eq!(res.content_id, canonical(&HELLO_WORLD[..5]));
+
+    Ok(())
+}
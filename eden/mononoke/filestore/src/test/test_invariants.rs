@@ -24,7 +24,7 @@ use crate::incremental_hash::{
     hash_bytes, ContentIdIncrementalHasher, GitSha1IncrementalHasher, Sha1IncrementalHasher,
     Sha256IncrementalHasher,
 };
-use crate::{Alias, FetchKey, FilestoreConfig};
+use crate::{Alias, ChunkingMethod, FetchKey, FilestoreConfig};
 
 use super::failing_blobstore::FailingBlobstore;
 use super::request;
@@ -93,6 +93,7 @@ fn test_invariants(fb: FacebookInit) -> Result<()> {
     let blob = FailingBlobstore::new(memblob.clone(), 0.75, 0.75);
     let config = FilestoreConfig {
         chunk_size: Some(16),
+        chunking: ChunkingMethod::FixedSize,
         concurrency: 5,
     };
     let ctx = CoreContext::test_mock(fb);
@@ -139,16 +140,19 @@ fn test_store_bytes_consistency(fb: FacebookInit) -> Result<(), Error> {
 
             let no_chunking = FilestoreConfig {
                 chunk_size: None,
+                chunking: ChunkingMethod::FixedSize,
                 concurrency: 1,
             };
 
             let chunked = FilestoreConfig {
                 chunk_size: Some(std::cmp::max(1, (bytes.len() as u64) / 2)),
+                chunking: ChunkingMethod::FixedSize,
                 concurrency: 1,
             };
 
             let too_small_to_chunk = FilestoreConfig {
                 chunk_size: Some(std::cmp::max(1, (bytes.len() as u64) * 2)),
+                chunking: ChunkingMethod::FixedSize,
                 concurrency: 1,
             };
 
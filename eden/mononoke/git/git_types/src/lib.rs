@@ -20,6 +20,6 @@ mod store;
 mod tree;
 
 pub use crate::blob::BlobHandle;
-pub use crate::tree::{Tree, TreeBuilder, TreeHandle, TreeMember, Treeish};
+pub use crate::tree::{tree_blobstore_key, Tree, TreeBuilder, TreeHandle, TreeMember, Treeish};
 pub use derive_tree::TreeMapping;
 pub use object::ObjectKind;
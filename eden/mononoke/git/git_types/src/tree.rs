@@ -14,7 +14,10 @@ use std::io::{self, Write};
 use std::iter::Iterator;
 
 use ::manifest::Entry;
-use mononoke_types::{hash::RichGitSha1, MPathElement};
+use mononoke_types::{
+    hash::{GitSha1, RichGitSha1},
+    MPathElement,
+};
 
 use crate::errors::ErrorKind;
 use crate::mode;
@@ -36,10 +39,15 @@ impl TreeHandle {
     }
 
     pub fn blobstore_key(&self) -> String {
-        format!("git.tree.{}", self.oid)
+        tree_blobstore_key(&self.oid.sha1())
     }
 }
 
+/// Trees are stored by their Git SHA-1 alone, so they can be looked up without knowing their size.
+pub fn tree_blobstore_key(sha1: &GitSha1) -> String {
+    format!("git.tree.{}", sha1)
+}
+
 impl TryFrom<thrift::TreeHandle> for TreeHandle {
     type Error = Error;
 
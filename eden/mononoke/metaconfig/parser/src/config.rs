@@ -441,8 +441,8 @@ mod test {
         PushParams, PushrebaseFlags, PushrebaseParams, RemoteDatabaseConfig,
         RemoteMetadataDatabaseConfig, RepoClientKnobs, SegmentedChangelogConfig,
         ShardableRemoteDatabaseConfig, ShardedRemoteDatabaseConfig, SmallRepoCommitSyncConfig,
-        SourceControlServiceMonitoring, SourceControlServiceParams, UnodeVersion,
-        WireprotoLoggingConfig,
+        SmallRepoPathRule, SourceControlServiceMonitoring, SourceControlServiceParams,
+        UnodeVersion, WireprotoLoggingConfig,
     };
     use mononoke_types::MPath;
     use nonzero_ext::nonzero;
@@ -521,6 +521,7 @@ mod test {
                 small_repos: hashmap! {
                     RepositoryId::new(2) => SmallRepoCommitSyncConfig {
                         default_action: DefaultSmallToLargeCommitSyncPathAction::Preserve,
+                        path_rules: vec![],
                         bookmark_prefix: AsciiString::from_str("repo2").unwrap(),
                         map: hashmap! {
                             MPath::new("p1").unwrap() => MPath::new(".r2-legacy/p1").unwrap(),
@@ -530,6 +531,7 @@ mod test {
                     },
                     RepositoryId::new(3) => SmallRepoCommitSyncConfig {
                         default_action: DefaultSmallToLargeCommitSyncPathAction::PrependPrefix(MPath::new("subdir").unwrap()),
+                        path_rules: vec![],
                         bookmark_prefix: AsciiString::from_str("repo3").unwrap(),
                         map: hashmap! {
                             MPath::new("p1").unwrap() => MPath::new("p1").unwrap(),
@@ -648,6 +650,123 @@ mod test {
         assert!(msg.contains("One bookmark prefix starts with another, which is prohibited"));
     }
 
+    #[test]
+    fn test_commit_sync_config_path_rules() {
+        let commit_sync_config = r#"
+            [mega]
+            large_repo_id = 1
+            common_pushrebase_bookmarks = ["master"]
+
+                [[mega.small_repos]]
+                repoid = 2
+                bookmark_prefix = "repo2"
+                default_action = "prepend_prefix"
+                default_prefix = "repo2"
+                direction = "small_to_large"
+
+                    [mega.small_repos.mapping]
+                    "p1" = "p1"
+
+                    [[mega.small_repos.path_rules]]
+                    do_not_sync_glob = "**/*.generated.*"
+
+                    [[mega.small_repos.path_rules]]
+                    file = "README"
+                    target = "docs/repo2.md"
+        "#;
+
+        let paths = btreemap! {
+            "common/commitsyncmap.toml" => commit_sync_config
+        };
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let tmp_dir = write_files(&paths);
+        let raw_config = crate::raw::read_raw_configs(tmp_dir.path(), &config_store)
+            .expect("expect to read configs");
+        let commit_sync = parse_commit_sync_config(raw_config.commit_sync)
+            .expect("expected to get a commit sync config");
+
+        let small_repo = &commit_sync["mega"].small_repos[&RepositoryId::new(2)];
+        assert_eq!(
+            small_repo.path_rules,
+            vec![
+                SmallRepoPathRule::DoNotSyncGlob("**/*.generated.*".to_string()),
+                SmallRepoPathRule::MoveFile(
+                    MPath::new("README").unwrap(),
+                    MPath::new("docs/repo2.md").unwrap()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_commit_sync_config_non_reversible_path_rules() {
+        let commit_sync_config = r#"
+            [mega]
+            large_repo_id = 1
+            common_pushrebase_bookmarks = ["master"]
+
+                [[mega.small_repos]]
+                repoid = 2
+                bookmark_prefix = "repo2"
+                default_action = "prepend_prefix"
+                default_prefix = "repo2"
+                direction = "small_to_large"
+
+                    [[mega.small_repos.path_rules]]
+                    file = "README"
+                    target = "repo2/docs/README"
+        "#;
+
+        let paths = btreemap! {
+            "common/commitsyncmap.toml" => commit_sync_config
+        };
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(tmp_dir.path(), &config_store);
+        let msg = format!("{:#?}", res);
+        println!("res = {}", msg);
+        assert!(res.is_err());
+        assert!(msg.contains("are not reversible"));
+    }
+
+    #[test]
+    fn test_commit_sync_config_cross_repo_path_rules_collision() {
+        let commit_sync_config = r#"
+            [mega]
+            large_repo_id = 1
+            common_pushrebase_bookmarks = ["master"]
+
+                [[mega.small_repos]]
+                repoid = 2
+                bookmark_prefix = "repo2"
+                default_action = "prepend_prefix"
+                default_prefix = "repo2"
+                direction = "small_to_large"
+
+                [[mega.small_repos]]
+                repoid = 3
+                bookmark_prefix = "repo3"
+                default_action = "prepend_prefix"
+                default_prefix = "repo3"
+                direction = "small_to_large"
+
+                    [[mega.small_repos.path_rules]]
+                    file = "README"
+                    target = "repo2/README"
+        "#;
+
+        let paths = btreemap! {
+            "common/commitsyncmap.toml" => commit_sync_config
+        };
+        let config_store = ConfigStore::new(Arc::new(TestSource::new()), None, None);
+        let tmp_dir = write_files(&paths);
+        let res = load_repo_configs(tmp_dir.path(), &config_store);
+        let msg = format!("{:#?}", res);
+        println!("res = {}", msg);
+        assert!(res.is_err());
+        assert!(msg.contains("Path rules of small repo 2 are not reversible"));
+    }
+
     #[test]
     fn test_duplicated_repo_ids() {
         let www_content = r#"
@@ -1009,6 +1128,7 @@ mod test {
                 filestore: Some(FilestoreParams {
                     chunk_size: 768,
                     concurrency: 48,
+                    content_defined_chunking: None,
                 }),
                 commit_sync_config: None,
                 hipster_acl: Some("foo/test".to_string()),
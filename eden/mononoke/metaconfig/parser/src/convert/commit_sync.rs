@@ -14,10 +14,10 @@ use bookmarks_types::BookmarkName;
 use itertools::Itertools;
 use metaconfig_types::{
     CommitSyncConfig, CommitSyncConfigVersion, CommitSyncDirection,
-    DefaultSmallToLargeCommitSyncPathAction, SmallRepoCommitSyncConfig,
+    DefaultSmallToLargeCommitSyncPathAction, SmallRepoCommitSyncConfig, SmallRepoPathRule,
 };
 use mononoke_types::{MPath, RepositoryId};
-use repos::{RawCommitSyncConfig, RawCommitSyncSmallRepoConfig};
+use repos::{RawCommitSyncConfig, RawCommitSyncPathRule, RawCommitSyncSmallRepoConfig};
 
 use crate::convert::Convert;
 
@@ -52,6 +52,9 @@ fn check_no_duplicate_small_repos(small_repos: &[RawCommitSyncSmallRepoConfig])
 /// mean potentail bookmark name collisions.
 ///
 /// - Check that large repo from this config is not the same as any of the small repos
+///
+/// - Check that the movers built from this config sync paths named in it back and
+/// forth without change, so that path rules are reversible.
 fn validate_commit_sync_config(commit_sync_config: &CommitSyncConfig) -> Result<()> {
     if commit_sync_config
         .small_repos
@@ -82,6 +85,8 @@ fn validate_commit_sync_config(commit_sync_config: &CommitSyncConfig) -> Result<
         }
     }
 
+    movers::validate_movers(commit_sync_config)?;
+
     Ok(())
 }
 
@@ -143,6 +148,7 @@ impl Convert for RawCommitSyncSmallRepoConfig {
             bookmark_prefix,
             mapping,
             direction,
+            path_rules,
             ..
         } = self;
 
@@ -167,6 +173,12 @@ impl Convert for RawCommitSyncSmallRepoConfig {
             .map(|(k, v)| Ok((MPath::new(k)?, MPath::new(v)?)))
             .collect::<Result<HashMap<_, _>>>()?;
 
+        let path_rules = path_rules
+            .unwrap_or_default()
+            .into_iter()
+            .map(|path_rule| path_rule.convert())
+            .collect::<Result<Vec<_>>>()?;
+
         let bookmark_prefix = AsciiString::from_str(&bookmark_prefix)
             .map_err(|_| anyhow!("failed to parse ascii string from: {:?}", bookmark_prefix))?;
 
@@ -179,8 +191,26 @@ impl Convert for RawCommitSyncSmallRepoConfig {
         Ok(SmallRepoCommitSyncConfig {
             default_action,
             map,
+            path_rules,
             bookmark_prefix,
             direction,
         })
     }
 }
+
+impl Convert for RawCommitSyncPathRule {
+    type Output = SmallRepoPathRule;
+
+    fn convert(self) -> Result<Self::Output> {
+        match (self.do_not_sync_glob, self.file, self.target) {
+            (Some(glob), None, None) => Ok(SmallRepoPathRule::DoNotSyncGlob(glob)),
+            (None, Some(file), Some(target)) => Ok(SmallRepoPathRule::MoveFile(
+                MPath::new(file)?,
+                MPath::new(target)?,
+            )),
+            _ => Err(anyhow!(
+                "path rule must set either do_not_sync_glob, or both file and target"
+            )),
+        }
+    }
+}
@@ -12,16 +12,16 @@ use std::time::Duration;
 
 use anyhow::{anyhow, Result};
 use metaconfig_types::{
-    BlobConfig, BlobstoreId, DatabaseConfig, FilestoreParams, LocalDatabaseConfig,
-    MetadataDatabaseConfig, MultiplexId, MultiplexedStoreType, RemoteDatabaseConfig,
-    RemoteMetadataDatabaseConfig, ShardableRemoteDatabaseConfig, ShardedRemoteDatabaseConfig,
-    StorageConfig,
+    BlobConfig, BlobstoreId, DatabaseConfig, FilestoreCdcParams, FilestoreParams,
+    LocalDatabaseConfig, MetadataDatabaseConfig, MultiplexId, MultiplexedStoreType,
+    RemoteDatabaseConfig, RemoteMetadataDatabaseConfig, ShardableRemoteDatabaseConfig,
+    ShardedRemoteDatabaseConfig, StorageConfig,
 };
 use nonzero_ext::nonzero;
 use repos::{
     RawBlobstoreConfig, RawDbConfig, RawDbLocal, RawDbRemote, RawDbShardableRemote,
-    RawDbShardedRemote, RawFilestoreParams, RawMetadataConfig, RawMultiplexedStoreType,
-    RawStorageConfig,
+    RawDbShardedRemote, RawFilestoreCdcParams, RawFilestoreParams, RawMetadataConfig,
+    RawMultiplexedStoreType, RawStorageConfig,
 };
 
 use crate::convert::Convert;
@@ -116,6 +116,18 @@ impl Convert for RawBlobstoreConfig {
             RawBlobstoreConfig::pack(raw) => BlobConfig::Pack {
                 blobconfig: Box::new(raw.blobstore.convert()?),
             },
+            RawBlobstoreConfig::disk_cache(raw) => BlobConfig::DiskCache {
+                blobconfig: Box::new(raw.blobstore.convert()?),
+                path: PathBuf::from(raw.path),
+                max_bytes: raw.max_bytes.try_into()?,
+                min_admission_age: Duration::from_secs(
+                    raw.min_admission_age_secs.unwrap_or(0).try_into()?,
+                ),
+            },
+            RawBlobstoreConfig::encrypted(raw) => BlobConfig::Encrypted {
+                blobconfig: Box::new(raw.blobstore.convert()?),
+                keyfile: PathBuf::from(raw.keyfile),
+            },
             RawBlobstoreConfig::s3(raw) => BlobConfig::S3 {
                 bucket: raw.bucket,
                 keychain_group: raw.keychain_group,
@@ -233,9 +245,38 @@ impl Convert for RawFilestoreParams {
     type Output = FilestoreParams;
 
     fn convert(self) -> Result<Self::Output> {
+        let chunk_size = self.chunk_size.try_into()?;
+        let content_defined_chunking = self.content_defined_chunking.convert()?;
+
+        if let Some(cdc) = &content_defined_chunking {
+            if cdc.min_chunk_size == 0
+                || cdc.min_chunk_size > chunk_size
+                || chunk_size > cdc.max_chunk_size
+            {
+                return Err(anyhow!(
+                    "invalid content-defined chunking sizes: need 0 < min ({}) <= chunk_size ({}) <= max ({})",
+                    cdc.min_chunk_size,
+                    chunk_size,
+                    cdc.max_chunk_size,
+                ));
+            }
+        }
+
         Ok(FilestoreParams {
-            chunk_size: self.chunk_size.try_into()?,
+            chunk_size,
             concurrency: self.concurrency.try_into()?,
+            content_defined_chunking,
+        })
+    }
+}
+
+impl Convert for RawFilestoreCdcParams {
+    type Output = FilestoreCdcParams;
+
+    fn convert(self) -> Result<Self::Output> {
+        Ok(FilestoreCdcParams {
+            min_chunk_size: self.min_chunk_size.try_into()?,
+            max_chunk_size: self.max_chunk_size.try_into()?,
         })
     }
 }
@@ -823,6 +823,25 @@ pub enum BlobConfig {
         /// The config for the blobstore that is wrapped.
         blobconfig: Box<BlobConfig>,
     },
+    /// A size-bounded cache on local disk in front of another blobstore
+    DiskCache {
+        /// The config for the blobstore that is cached.
+        blobconfig: Box<BlobConfig>,
+        /// Directory to keep the cached blobs in
+        path: PathBuf,
+        /// Maximum total size of the cached blobs, beyond which the least recently used are
+        /// evicted
+        max_bytes: u64,
+        /// Only cache blobs created at least this long ago
+        min_admission_age: Duration,
+    },
+    /// An encrypting blobstore that wraps another blobstore
+    Encrypted {
+        /// The config for the blobstore that is wrapped.
+        blobconfig: Box<BlobConfig>,
+        /// Path to the file holding the encryption keys
+        keyfile: PathBuf,
+    },
     /// Store in a S3 compatible storage
     S3 {
         /// Bucket to connect to
@@ -851,6 +870,8 @@ impl BlobConfig {
                 .all(BlobConfig::is_local),
             Logging { blobconfig, .. } => blobconfig.is_local(),
             Pack { blobconfig, .. } => blobconfig.is_local(),
+            DiskCache { blobconfig, .. } => blobconfig.is_local(),
+            Encrypted { blobconfig, .. } => blobconfig.is_local(),
         }
     }
 
@@ -1088,6 +1109,17 @@ pub struct FilestoreParams {
     pub chunk_size: u64,
     /// Max number of concurrent chunk uploads to perform in the Filestore.
     pub concurrency: usize,
+    /// If set, chunk boundaries are content-defined, and `chunk_size` is the average chunk size.
+    pub content_defined_chunking: Option<FilestoreCdcParams>,
+}
+
+/// Content-defined chunking configuration for the Filestore.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct FilestoreCdcParams {
+    /// Smallest chunk size, in bytes. Only the last chunk of a file may be smaller.
+    pub min_chunk_size: u64,
+    /// Largest chunk size, in bytes.
+    pub max_chunk_size: u64,
 }
 
 /// Default path action to perform when syncing commits
@@ -1110,12 +1142,25 @@ pub struct SmallRepoCommitSyncConfig {
     pub default_action: DefaultSmallToLargeCommitSyncPathAction,
     /// A map of prefix replacements when syncing
     pub map: HashMap<MPath, MPath>,
+    /// Per-path rules, tried in order before the `map`
+    pub path_rules: Vec<SmallRepoPathRule>,
     /// Bookmark prefix to use in the large repo
     pub bookmark_prefix: AsciiString,
     /// Commit sync direction
     pub direction: CommitSyncDirection,
 }
 
+/// A rule for individual small repo paths when syncing. The first
+/// matching rule wins, and paths that match no rule are synced
+/// according to the prefix `map` and the default action
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SmallRepoPathRule {
+    /// Do not sync small repo paths matching this glob
+    DoNotSyncGlob(String),
+    /// Sync a single small repo file to a given large repo path
+    MoveFile(MPath, MPath),
+}
+
 /// Commit sync direction
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum CommitSyncDirection {
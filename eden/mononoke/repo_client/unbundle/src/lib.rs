@@ -28,8 +28,8 @@ pub use hooks::CrossRepoPushSource;
 pub use processing::{get_pushrebase_hooks, run_post_resolve_action};
 pub use push_redirector::{PushRedirector, PushRedirectorArgs};
 pub use resolver::{
-    resolve, BundleResolverError, BundleResolverResultExt, Changesets, CommonHeads,
-    InfiniteBookmarkPush, NonFastForwardPolicy, PlainBookmarkPush, PostResolveAction,
+    resolve, upload_changegroup_bundle, BundleResolverError, BundleResolverResultExt, Changesets,
+    CommonHeads, InfiniteBookmarkPush, NonFastForwardPolicy, PlainBookmarkPush, PostResolveAction,
     PostResolveBookmarkOnlyPushRebase, PostResolveInfinitePush, PostResolvePush,
     PostResolvePushRebase, PushrebaseBookmarkSpec, UploadedBonsais, UploadedHgChangesetIds,
 };
@@ -356,6 +356,30 @@ pub async fn resolve<'a>(
     }
 }
 
+/// Upload the changesets from a bundle2, which carries a changegroup and its treegroup,
+/// like the ones `hg bundle` writes. Unlike `resolve`, this doesn't expect the parts of a
+/// push, and doesn't move any bookmarks. Parts after the treegroup are ignored.
+pub async fn upload_changegroup_bundle<'a>(
+    ctx: &'a CoreContext,
+    repo: &'a BlobRepo,
+    bundle2: BoxStream<'static, Result<Bundle2Item<'static>>>,
+    pushrebase_flags: PushrebaseFlags,
+) -> Result<UploadedBonsais, Error> {
+    let resolver = Bundle2Resolver::new(ctx, repo, false, pushrebase_flags);
+    let (_, bundle2) = resolver.resolve_stream_params(bundle2).await?;
+    let (cg_push, bundle2) = resolver
+        .maybe_resolve_changegroup(bundle2, || true)
+        .await
+        .context("While resolving Changegroup")?;
+    let cg_push = cg_push.ok_or_else(|| format_err!("Bundle doesn't contain a changegroup"))?;
+    let (manifests, _) = resolver
+        .resolve_b2xtreegroup2(bundle2)
+        .await
+        .context("While resolving B2xTreegroup2")?;
+    let (uploaded_bonsais, _) = resolver.upload_changesets(cg_push, manifests).await?;
+    Ok(uploaded_bonsais)
+}
+
 fn report_unbundle_type(
     ctx: &CoreContext,
     repo: &BlobRepo,
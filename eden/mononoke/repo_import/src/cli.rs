@@ -5,15 +5,19 @@
  * GNU General Public License version 2.
  */
 
-use crate::{ImportStage, RecoveryFields};
-use anyhow::Error;
-use clap::{Arg, ArgMatches, SubCommand};
+use crate::{ImportSource, ImportStage, RecoveryFields};
+use anyhow::{format_err, Error};
+use clap::{Arg, ArgGroup, ArgMatches, SubCommand};
 use cmdlib::args::{self, MononokeClapApp};
 use mononoke_types::DateTime;
 use std::num::NonZeroUsize;
 
 pub const IMPORT: &str = "import";
 pub const ARG_GIT_REPOSITORY_PATH: &str = "git-repository-path";
+pub const ARG_HG_BUNDLE_PATH: &str = "hg-bundle-path";
+pub const ARG_SOURCE_REPO_NAME: &str = "source-repo-name";
+pub const ARG_SOURCE_BOOKMARK: &str = "source-bookmark";
+pub const IMPORT_SOURCE: &str = "import-source";
 pub const ARG_DEST_PATH: &str = "dest-path";
 pub const ARG_BATCH_SIZE: &str = "batch-size";
 pub const ARG_BOOKMARK_SUFFIX: &str = "bookmark-suffix";
@@ -65,9 +69,34 @@ pub fn setup_app<'a, 'b>() -> MononokeClapApp<'a, 'b> {
                 .about("Run the whole repo_import process")
                 .arg(
                     Arg::with_name(ARG_GIT_REPOSITORY_PATH)
-                        .required(true)
                         .help("Path to a git repository to import"),
                 )
+                .arg(
+                    Arg::with_name(ARG_HG_BUNDLE_PATH)
+                        .long(ARG_HG_BUNDLE_PATH)
+                        .takes_value(true)
+                        .help("Path to a Mercurial bundle2 file (e.g. from hg bundle --all) to import. \
+                        The parents of all commits in the bundle must be in the bundle too."),
+                )
+                .arg(
+                    Arg::with_name(ARG_SOURCE_REPO_NAME)
+                        .long(ARG_SOURCE_REPO_NAME)
+                        .takes_value(true)
+                        .requires(ARG_SOURCE_BOOKMARK)
+                        .help("Name of another Mononoke repo to import commits from"),
+                )
+                .arg(
+                    Arg::with_name(ARG_SOURCE_BOOKMARK)
+                        .long(ARG_SOURCE_BOOKMARK)
+                        .takes_value(true)
+                        .requires(ARG_SOURCE_REPO_NAME)
+                        .help("Bookmark in the source Mononoke repo, whose ancestors we import"),
+                )
+                .group(
+                    ArgGroup::with_name(IMPORT_SOURCE)
+                        .args(&[ARG_GIT_REPOSITORY_PATH, ARG_HG_BUNDLE_PATH, ARG_SOURCE_REPO_NAME])
+                        .required(true),
+                )
                 .arg(
                     Arg::with_name(ARG_DEST_PATH)
                         .long(ARG_DEST_PATH)
@@ -166,9 +195,9 @@ pub fn setup_app<'a, 'b>() -> MononokeClapApp<'a, 'b> {
 }
 
 pub fn setup_import_args(matches: &ArgMatches<'_>) -> Result<RecoveryFields, Error> {
-    let import_stage = ImportStage::GitImport;
+    let import_stage = ImportStage::ImportCommits;
     let recovery_file_path = matches.value_of(ARG_RECOVERY_FILE_PATH).unwrap();
-    let git_repo_path = matches.value_of(ARG_GIT_REPOSITORY_PATH).unwrap();
+    let import_source = setup_import_source(matches)?;
     let dest_path = matches.value_of(ARG_DEST_PATH).unwrap();
     let bookmark_suffix = matches.value_of(ARG_BOOKMARK_SUFFIX).unwrap();
     let batch_size = matches.value_of(ARG_BATCH_SIZE).unwrap();
@@ -189,7 +218,7 @@ pub fn setup_import_args(matches: &ArgMatches<'_>) -> Result<RecoveryFields, Err
     Ok(RecoveryFields {
         import_stage,
         recovery_file_path: recovery_file_path.to_string(),
-        git_repo_path: git_repo_path.to_string(),
+        import_source,
         dest_path: dest_path.to_string(),
         bookmark_suffix: bookmark_suffix.to_string(),
         batch_size,
@@ -203,7 +232,26 @@ pub fn setup_import_args(matches: &ArgMatches<'_>) -> Result<RecoveryFields, Err
         commit_message: commit_message.to_string(),
         datetime,
         shifted_bcs_ids: None,
-        gitimport_bcs_ids: None,
+        imported_bcs_ids: None,
         merged_cs_id: None,
     })
 }
+
+fn setup_import_source(matches: &ArgMatches<'_>) -> Result<ImportSource, Error> {
+    if let Some(git_repo_path) = matches.value_of(ARG_GIT_REPOSITORY_PATH) {
+        return Ok(ImportSource::Git(git_repo_path.to_string()));
+    }
+    if let Some(hg_bundle_path) = matches.value_of(ARG_HG_BUNDLE_PATH) {
+        return Ok(ImportSource::HgBundle(hg_bundle_path.to_string()));
+    }
+    match (
+        matches.value_of(ARG_SOURCE_REPO_NAME),
+        matches.value_of(ARG_SOURCE_BOOKMARK),
+    ) {
+        (Some(repo_name), Some(bookmark)) => Ok(ImportSource::MononokeRepo {
+            repo_name: repo_name.to_string(),
+            bookmark: bookmark.to_string(),
+        }),
+        _ => Err(format_err!("No source to import from")),
+    }
+}
@@ -18,23 +18,25 @@ use cmdlib::args::{self, MononokeMatches};
 use cmdlib::helpers::block_execute;
 use context::CoreContext;
 use cross_repo_sync::{
-    create_commit_syncers, find_toposorted_unsynced_ancestors, rewrite_commit,
+    create_commit_syncers, find_toposorted_unsynced_ancestors, rewrite_commit, upload_commits,
     CandidateSelectionHint, CommitSyncContext, CommitSyncOutcome, CommitSyncer, Syncers,
 };
 use derived_data_utils::derived_data_utils;
 use fbinit::FacebookInit;
 use futures::{
-    compat::Future01CompatExt,
+    compat::{Future01CompatExt, Stream01CompatExt},
     future::{self, TryFutureExt},
     stream::{self, StreamExt, TryStreamExt},
 };
+use futures_old::stream::Stream as OldStream;
 use import_tools::{GitimportPreferences, GitimportTarget};
 use itertools::Itertools;
 use live_commit_sync_config::{CfgrLiveCommitSyncConfig, LiveCommitSyncConfig};
 use manifest::ManifestOps;
 use maplit::hashset;
+use mercurial_bundles::bundle2::{Bundle2Stream, StreamEvent};
 use mercurial_types::{HgChangesetId, MPath};
-use metaconfig_types::{CommitSyncConfigVersion, RepoConfig};
+use metaconfig_types::{CommitSyncConfigVersion, PushrebaseFlags, RepoConfig};
 use mononoke_hg_sync_job_helper_lib::wait_for_latest_log_id_to_be_synced;
 use mononoke_types::{BonsaiChangeset, BonsaiChangesetMut, ChangesetId, DateTime};
 use movers::{DefaultAction, Mover};
@@ -44,6 +46,7 @@ use serde::{Deserialize, Serialize};
 use serde_json;
 use slog::info;
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Cursor;
 use std::path::Path;
 use std::sync::Arc;
 use synced_commit_mapping::{SqlSyncedCommitMapping, SyncedCommitMapping};
@@ -113,7 +116,9 @@ struct SmallRepoBackSyncVars {
 
 #[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
 enum ImportStage {
-    GitImport,
+    // Named after the git-only import that it replaced, to keep recovery files compatible
+    #[serde(rename = "GitImport")]
+    ImportCommits,
     RewritePaths,
     DeriveBonsais,
     MoveBookmark,
@@ -121,6 +126,17 @@ enum ImportStage {
     PushCommit,
 }
 
+/// Where the imported commits come from
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum ImportSource {
+    /// Path to a git repository, imported with gitimport
+    Git(String),
+    /// Path to a Mercurial bundle2 file with a changegroup
+    HgBundle(String),
+    /// Ancestors of a bookmark in another Mononoke repo
+    MononokeRepo { repo_name: String, bookmark: String },
+}
+
 /*
     Most fields can be found with 'repo_import --help'
 */
@@ -129,7 +145,7 @@ pub struct RecoveryFields {
     /// Indicates which stage we will recover from in case of recovery process
     import_stage: ImportStage,
     recovery_file_path: String,
-    git_repo_path: String,
+    import_source: ImportSource,
     dest_path: String,
     bookmark_suffix: String,
     batch_size: usize,
@@ -144,23 +160,149 @@ pub struct RecoveryFields {
     datetime: DateTime,
     /// ChangesetId of the merged commit we make to merge the imported commits into dest_bookmark
     merged_cs_id: Option<ChangesetId>,
-    /// ChangesetIds created after shifting the file paths of the gitimported commits
+    /// ChangesetIds created after shifting the file paths of the imported commits
     shifted_bcs_ids: Option<Vec<ChangesetId>>,
-    /// ChangesetIds of the gitimported commits
-    gitimport_bcs_ids: Option<Vec<ChangesetId>>,
+    /// ChangesetIds of the imported commits
+    #[serde(rename = "gitimport_bcs_ids")]
+    imported_bcs_ids: Option<Vec<ChangesetId>>,
+}
+
+async fn import_git_repo(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    git_repo_path: &str,
+) -> Result<Vec<ChangesetId>, Error> {
+    let path = Path::new(git_repo_path);
+    let prefs = GitimportPreferences::default();
+    let target = GitimportTarget::FullRepo;
+    info!(ctx.logger(), "Started importing git commits to Mononoke");
+    let import_map = import_tools::gitimport(&ctx, &repo, &path, target, prefs).await?;
+    info!(ctx.logger(), "Added commits to Mononoke");
+
+    let bonsai_values: Vec<(ChangesetId, BonsaiChangeset)> = import_map.values().cloned().collect();
+    let gitimport_bcs: Vec<BonsaiChangeset> =
+        bonsai_values.iter().map(|(_, bcs)| bcs.clone()).collect();
+    let gitimport_bcs_ids: Vec<ChangesetId> =
+        bonsai_values.iter().map(|(id, _)| id.clone()).collect();
+
+    info!(ctx.logger(), "Saving gitimported bonsai changesets");
+    save_bonsai_changesets(gitimport_bcs.clone(), ctx.clone(), repo.clone()).await?;
+    info!(ctx.logger(), "Saved gitimported bonsai changesets");
+    Ok(gitimport_bcs_ids)
+}
+
+/// Upload the commits of a Mercurial bundle2 (as written by `hg bundle`) and return them
+/// sorted topologically. The bundle must be self-contained: the paths of the imported
+/// commits get rewritten, so a commit whose parent isn't in the bundle is rejected
+/// instead of being attached to a commit that would keep its old paths.
+async fn import_hg_bundle(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    hg_bundle_path: &str,
+    pushrebase_flags: PushrebaseFlags,
+) -> Result<Vec<ChangesetId>, Error> {
+    info!(ctx.logger(), "Started importing hg bundle to Mononoke");
+    let bundle = fs::read(hg_bundle_path).await?;
+    let bundle_stream =
+        Bundle2Stream::new(ctx.logger().clone(), Cursor::new(bundle)).filter_map(|e| match e {
+            StreamEvent::Next(item) => Some(item),
+            StreamEvent::Done(..) => None,
+        });
+    let uploaded_bcs = unbundle::upload_changegroup_bundle(
+        &ctx,
+        &repo,
+        bundle_stream.compat().boxed(),
+        pushrebase_flags,
+    )
+    .await?;
+    info!(
+        ctx.logger(),
+        "Added {} commits from hg bundle to Mononoke",
+        uploaded_bcs.len()
+    );
+
+    let uploaded_bcs_ids: HashSet<_> = uploaded_bcs
+        .iter()
+        .map(|bcs| bcs.get_changeset_id())
+        .collect();
+    for bcs in &uploaded_bcs {
+        if let Some(parent) = bcs.parents().find(|p| !uploaded_bcs_ids.contains(p)) {
+            return Err(format_err!(
+                "Commit {} from the hg bundle has parent {}, which is not in the bundle",
+                bcs.get_changeset_id(),
+                parent
+            ));
+        }
+    }
+
+    let uploaded_bcs: Vec<BonsaiChangeset> = uploaded_bcs.into_iter().collect();
+    let sorted_bcs = sort_bcs(&uploaded_bcs)?;
+    Ok(get_cs_ids(&sorted_bcs))
+}
+
+async fn import_mononoke_repo(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    source_repo: &BlobRepo,
+    source_bookmark: &BookmarkName,
+) -> Result<Vec<ChangesetId>, Error> {
+    let head = source_repo
+        .get_bonsai_bookmark(ctx.clone(), source_bookmark)
+        .await?
+        .ok_or_else(|| {
+            format_err!(
+                "Bookmark {} doesn't exist in {}",
+                source_bookmark,
+                source_repo.name()
+            )
+        })?;
+    info!(
+        ctx.logger(),
+        "Started importing ancestors of {} from {}",
+        head,
+        source_repo.name()
+    );
+
+    let mut visited = hashset! {head};
+    let mut to_load = vec![head];
+    let mut source_bcs = vec![];
+    while !to_load.is_empty() {
+        let loaded = stream::iter(to_load.drain(..).map(|bcs_id| async move {
+            let bcs = bcs_id.load(ctx, &source_repo.get_blobstore()).await?;
+            Result::<_, Error>::Ok(bcs)
+        }))
+        .buffered(100)
+        .try_collect::<Vec<_>>()
+        .await?;
+        for bcs in loaded {
+            to_load.extend(bcs.parents().filter(|parent| visited.insert(*parent)));
+            source_bcs.push(bcs);
+        }
+    }
+
+    let sorted_bcs = sort_bcs(&source_bcs)?;
+    let bcs_ids = get_cs_ids(&sorted_bcs);
+    info!(
+        ctx.logger(),
+        "Copying {} commits to Mononoke",
+        bcs_ids.len()
+    );
+    upload_commits(ctx, sorted_bcs, source_repo, repo).await?;
+    info!(ctx.logger(), "Copied commits to Mononoke");
+    Ok(bcs_ids)
 }
 
 async fn rewrite_file_paths(
     ctx: &CoreContext,
     repo: &BlobRepo,
     mover: &Mover,
-    gitimport_bcs_ids: &[ChangesetId],
+    imported_bcs_ids: &[ChangesetId],
 ) -> Result<Vec<ChangesetId>, Error> {
     let mut remapped_parents: HashMap<ChangesetId, ChangesetId> = HashMap::new();
     let mut bonsai_changesets = vec![];
 
-    let len = gitimport_bcs_ids.len();
-    let gitimport_changesets = stream::iter(gitimport_bcs_ids.iter().map(|bcs_id| async move {
+    let len = imported_bcs_ids.len();
+    let imported_changesets = stream::iter(imported_bcs_ids.iter().map(|bcs_id| async move {
         let bcs = bcs_id.load(ctx, &repo.get_blobstore()).await?;
         Result::<_, Error>::Ok(bcs)
     }))
@@ -168,7 +310,7 @@ async fn rewrite_file_paths(
     .try_collect::<Vec<_>>()
     .await?;
 
-    for (index, bcs) in gitimport_changesets.iter().enumerate() {
+    for (index, bcs) in imported_changesets.iter().enumerate() {
         let bcs_id = bcs.get_changeset_id();
         let rewritten_bcs_opt = rewrite_commit(
             &ctx,
@@ -880,6 +1022,20 @@ async fn save_importing_state(recovery_fields: &RecoveryFields) -> Result<(), Er
     Ok(())
 }
 
+/// Recovery files written before other import sources were supported only have a
+/// "git_repo_path" instead of an "import_source"
+fn parse_recovery_fields(serialized: &str) -> Result<RecoveryFields, Error> {
+    let mut value: serde_json::Value = serde_json::from_str(serialized)?;
+    if let Some(fields) = value.as_object_mut() {
+        if let Some(git_repo_path) = fields.remove("git_repo_path") {
+            fields
+                .entry("import_source")
+                .or_insert_with(|| serde_json::json!({ "Git": git_repo_path }));
+        }
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
 async fn fetch_recovery_state(
     ctx: &CoreContext,
     saved_recovery_file_paths: &str,
@@ -890,7 +1046,7 @@ async fn fetch_recovery_state(
     saved_proc_recovery_file
         .read_to_string(&mut serialized)
         .await?;
-    let recovery_fields: RecoveryFields = serde_json::from_str(&serialized)?;
+    let recovery_fields = parse_recovery_fields(&serialized)?;
     info!(
         ctx.logger(),
         "Fetched the recovery stage for importing.\nStarting from stage: {:?}",
@@ -906,8 +1062,6 @@ async fn repo_import(
     fb: FacebookInit,
     matches: &MononokeMatches<'_>,
 ) -> Result<(), Error> {
-    let arg_git_repo_path = recovery_fields.git_repo_path.clone();
-    let path = Path::new(&arg_git_repo_path);
     let dest_path_prefix = MPath::new(&recovery_fields.dest_path)?;
     let importing_bookmark = get_importing_bookmark(&recovery_fields.bookmark_suffix)?;
     if !is_valid_bookmark_suffix(&recovery_fields.bookmark_suffix) {
@@ -1036,36 +1190,45 @@ async fn repo_import(
         args::open_sql::<SqlMutableCounters>(ctx.fb, config_store, &matches).await?;
 
     // Importing process starts here
-    if recovery_fields.import_stage == ImportStage::GitImport {
-        let prefs = GitimportPreferences::default();
-        let target = GitimportTarget::FullRepo;
-        info!(ctx.logger(), "Started importing git commits to Mononoke");
-        let import_map = import_tools::gitimport(&ctx, &repo, &path, target, prefs).await?;
-        info!(ctx.logger(), "Added commits to Mononoke");
-
-        let bonsai_values: Vec<(ChangesetId, BonsaiChangeset)> =
-            import_map.values().cloned().collect();
-        let gitimport_bcs: Vec<BonsaiChangeset> =
-            bonsai_values.iter().map(|(_, bcs)| bcs.clone()).collect();
-        let gitimport_bcs_ids: Vec<ChangesetId> =
-            bonsai_values.iter().map(|(id, _)| id.clone()).collect();
-
-        info!(ctx.logger(), "Saving gitimported bonsai changesets");
-        save_bonsai_changesets(gitimport_bcs.clone(), ctx.clone(), repo.clone()).await?;
-        info!(ctx.logger(), "Saved gitimported bonsai changesets");
+    if recovery_fields.import_stage == ImportStage::ImportCommits {
+        let imported_bcs_ids = match &recovery_fields.import_source {
+            ImportSource::Git(git_repo_path) => import_git_repo(&ctx, &repo, git_repo_path).await?,
+            ImportSource::HgBundle(hg_bundle_path) => {
+                import_hg_bundle(&ctx, &repo, hg_bundle_path, repo_config.pushrebase.flags).await?
+            }
+            ImportSource::MononokeRepo {
+                repo_name,
+                bookmark,
+            } => {
+                let (_, source_repo_config) = configs
+                    .repos
+                    .iter()
+                    .find(|(name, _)| *name == repo_name)
+                    .ok_or_else(|| format_err!("Unknown source repo {}", repo_name))?;
+                let source_repo = args::open_repo_with_repo_id(
+                    fb,
+                    ctx.logger(),
+                    source_repo_config.repoid,
+                    &matches,
+                )
+                .await?;
+                let source_bookmark = BookmarkName::new(bookmark)?;
+                import_mononoke_repo(&ctx, &repo, &source_repo, &source_bookmark).await?
+            }
+        };
 
         recovery_fields.import_stage = ImportStage::RewritePaths;
-        recovery_fields.gitimport_bcs_ids = Some(gitimport_bcs_ids);
+        recovery_fields.imported_bcs_ids = Some(imported_bcs_ids);
         save_importing_state(&recovery_fields).await?;
     }
 
     if recovery_fields.import_stage == ImportStage::RewritePaths {
-        let gitimport_bcs_ids = recovery_fields
-            .gitimport_bcs_ids
+        let imported_bcs_ids = recovery_fields
+            .imported_bcs_ids
             .as_ref()
-            .ok_or_else(|| format_err!("gitimported changeset ids are not found"))?;
+            .ok_or_else(|| format_err!("imported changeset ids are not found"))?;
         let shifted_bcs_ids =
-            rewrite_file_paths(&ctx, &repo, &combined_mover, &gitimport_bcs_ids).await?;
+            rewrite_file_paths(&ctx, &repo, &combined_mover, &imported_bcs_ids).await?;
         recovery_fields.import_stage = ImportStage::DeriveBonsais;
         recovery_fields.shifted_bcs_ids = Some(shifted_bcs_ids);
         save_importing_state(&recovery_fields).await?;
@@ -10,8 +10,9 @@ mod tests {
     use crate::{
         back_sync_commits_to_small_repo, check_dependent_systems, derive_bonsais_single_repo,
         find_mapping_version, get_large_repo_config_if_pushredirected, get_large_repo_setting,
-        merge_imported_commit, move_bookmark, push_merge_commit, rewrite_file_paths, ChangesetArgs,
-        CheckerFlags, ImportStage, RecoveryFields, RepoImportSetting,
+        import_mononoke_repo, merge_imported_commit, move_bookmark, parse_recovery_fields,
+        push_merge_commit, rewrite_file_paths, ChangesetArgs, CheckerFlags, ImportSource,
+        ImportStage, RecoveryFields, RepoImportSetting,
     };
     use anyhow::Result;
     use ascii::AsciiString;
@@ -90,9 +91,9 @@ mod tests {
 
     fn create_mock_recovery_fields() -> RecoveryFields {
         RecoveryFields {
-            import_stage: ImportStage::GitImport,
+            import_stage: ImportStage::ImportCommits,
             recovery_file_path: "recovery_path".to_string(),
-            git_repo_path: "git_repo_path".to_string(),
+            import_source: ImportSource::Git("git_repo_path".to_string()),
             dest_path: "dest_path".to_string(),
             bookmark_suffix: "bookmark_suffix".to_string(),
             batch_size: 2,
@@ -106,11 +107,36 @@ mod tests {
             commit_message: "commit_message".to_string(),
             datetime: DateTime::now(),
             shifted_bcs_ids: None,
-            gitimport_bcs_ids: None,
+            imported_bcs_ids: None,
             merged_cs_id: None,
         }
     }
 
+    #[test]
+    fn test_parse_recovery_fields() -> Result<()> {
+        let mut recovery_fields = create_mock_recovery_fields();
+        recovery_fields.imported_bcs_ids = Some(vec![MON_CSID]);
+        let mut value = serde_json::to_value(&recovery_fields)?;
+        assert_eq!(value["import_stage"], "GitImport");
+        assert_eq!(value["gitimport_bcs_ids"][0], MON_CSID.to_string());
+
+        let parsed = parse_recovery_fields(&value.to_string())?;
+        assert_eq!(parsed.import_stage, ImportStage::ImportCommits);
+        assert_eq!(parsed.import_source, recovery_fields.import_source);
+        assert_eq!(parsed.imported_bcs_ids, Some(vec![MON_CSID]));
+
+        // Recovery files from before import sources only have a git repo path
+        let fields = value.as_object_mut().unwrap();
+        fields.remove("import_source");
+        fields.insert("git_repo_path".to_string(), "old_git_repo_path".into());
+        let parsed = parse_recovery_fields(&value.to_string())?;
+        assert_eq!(
+            parsed.import_source,
+            ImportSource::Git("old_git_repo_path".to_string())
+        );
+        Ok(())
+    }
+
     #[fbinit::compat_test]
     async fn test_move_bookmark(fb: FacebookInit) -> Result<()> {
         let ctx = CoreContext::test_mock(fb);
@@ -523,6 +549,7 @@ mod tests {
             map: hashmap! {
                 mp("dest_path_prefix/B") => mp("random_dir/B"),
             },
+            path_rules: vec![],
             bookmark_prefix: AsciiString::from_ascii("large_repo_bookmark/".to_string()).unwrap(),
             direction: CommitSyncDirection::SmallToLarge,
         }
@@ -537,6 +564,7 @@ mod tests {
                 mp("dest_path_prefix/B") => mp("random_dir/B"),
                 mp("dest_path_prefix/C") => mp("random_dir/C"),
             },
+            path_rules: vec![],
             bookmark_prefix: AsciiString::from_ascii("large_repo_bookmark/".to_string()).unwrap(),
             direction: CommitSyncDirection::SmallToLarge,
         }
@@ -548,6 +576,7 @@ mod tests {
             map: hashmap! {
                 mp("dest_path_prefix_2") => mp("dpp2"),
             },
+            path_rules: vec![],
             bookmark_prefix: AsciiString::from_ascii("large_repo_bookmark_2/".to_string()).unwrap(),
             direction: CommitSyncDirection::SmallToLarge,
         }
@@ -991,4 +1020,50 @@ mod tests {
 
         Ok(())
     }
+
+    #[fbinit::compat_test]
+    async fn test_import_mononoke_repo(fb: FacebookInit) -> Result<()> {
+        let ctx = CoreContext::test_mock(fb);
+        let repo = create_repo(0)?;
+        let source_repo = create_repo(1)?;
+        let changesets = create_from_dag(
+            &ctx,
+            &source_repo,
+            r##"
+                A-B-C-D
+            "##,
+        )
+        .await?;
+        bookmark(&ctx, &source_repo, "master")
+            .set_to(changesets["C"])
+            .await?;
+
+        let imported_cs_ids =
+            import_mononoke_repo(&ctx, &repo, &source_repo, &create_bookmark_name("master"))
+                .await?;
+        assert_eq!(
+            imported_cs_ids,
+            vec![changesets["A"], changesets["B"], changesets["C"]]
+        );
+
+        let wc = list_working_copy_utf8(&ctx, &repo, changesets["C"]).await?;
+        assert_eq!(
+            wc,
+            hashmap! {
+                mp("A") => "A".to_string(),
+                mp("B") => "B".to_string(),
+                mp("C") => "C".to_string(),
+            }
+        );
+
+        let res = import_mononoke_repo(
+            &ctx,
+            &repo,
+            &source_repo,
+            &create_bookmark_name("nonexistent"),
+        )
+        .await;
+        assert!(res.is_err());
+        Ok(())
+    }
 }
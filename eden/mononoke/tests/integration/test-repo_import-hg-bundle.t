@@ -0,0 +1,143 @@
+# Copyright (c) Facebook, Inc. and its affiliates.
+#
+# This software may be used and distributed according to the terms of the
+# GNU General Public License found in the LICENSE file in the root
+# directory of this source tree.
+
+  $ . "${TEST_FIXTURES}/library.sh"
+  $ setup_common_config
+  $ BUNDLE_REPO="${TESTTMP}/repo-bundle"
+  $ BLOB_TYPE="blob_files" default_setup
+  hg repo
+  o  C [draft;rev=2;26805aba1e60]
+  │
+  o  B [draft;rev=1;112478962961]
+  │
+  o  A [draft;rev=0;426bada5c675]
+  $
+  blobimporting
+  starting Mononoke
+  cloning repo in hg client 'repo2'
+  $ setup_configerator_configs
+  $ cat > "$PUSHREDIRECT_CONF/enable" <<EOF
+  > {
+  > "per_repo": {
+  >   "0": {
+  >      "draft_push": false,
+  >      "public_push": false
+  >    }
+  >   }
+  > }
+  > EOF
+
+# Setup the hg repository to bundle
+  $ cd "$TESTTMP"
+  $ hginit_treemanifest repo-bundle
+  $ cd "$BUNDLE_REPO"
+  $ echo "this is file1" > file1
+  $ mkdir file2_repo
+  $ echo "this is file2" > file2_repo/file2
+  $ hg commit -Aqm "Add file1 and file2"
+  $ mkdir file3_repo
+  $ echo "this is file3" > file3_repo/file3
+  $ hg commit -Aqm "Add file3"
+  $ hg bundle -q --all "$TESTTMP/all.hg"
+  $ hg bundle -q -r tip --base "tip^" "$TESTTMP/tip.hg"
+
+# A bundle whose commits have parents outside of it can't be imported
+  $ cd "$TESTTMP"
+  $ repo_import \
+  > --local-configerator-path="$TESTTMP/configerator" \
+  > import \
+  > --hg-bundle-path "$TESTTMP/tip.hg" \
+  > --dest-path "new_dir/new_repo" \
+  > --batch-size 3 \
+  > --bookmark-suffix "new_repo" \
+  > --disable-phabricator-check \
+  > --disable-hg-sync-check \
+  > --dest-bookmark master_bookmark \
+  > --commit-author user \
+  > --commit-message "merging" \
+  > --recovery-file-path "$TESTTMP/tip_recovery_file.json"
+  * using repo "repo" repoid RepositoryId(0) (glob)
+  * Initializing CfgrLiveCommitSyncConfig (glob)
+  * Done initializing CfgrLiveCommitSyncConfig (glob)
+  * Started importing hg bundle to Mononoke (glob)
+  * Added 1 commits from hg bundle to Mononoke (glob)
+  * Execution error: Commit * from the hg bundle has parent *, which is not in the bundle (glob)
+  Error: Execution failed
+  [1]
+
+# Import the whole bundle
+  $ repo_import \
+  > --local-configerator-path="$TESTTMP/configerator" \
+  > import \
+  > --hg-bundle-path "$TESTTMP/all.hg" \
+  > --dest-path "new_dir/new_repo" \
+  > --batch-size 3 \
+  > --bookmark-suffix "new_repo" \
+  > --disable-phabricator-check \
+  > --disable-hg-sync-check \
+  > --dest-bookmark master_bookmark \
+  > --commit-author user \
+  > --commit-message "merging" \
+  > --recovery-file-path "$TESTTMP/recovery_file.json"
+  * using repo "repo" repoid RepositoryId(0) (glob)
+  * Initializing CfgrLiveCommitSyncConfig (glob)
+  * Done initializing CfgrLiveCommitSyncConfig (glob)
+  * Started importing hg bundle to Mononoke (glob)
+  * Added 2 commits from hg bundle to Mononoke (glob)
+  * Remapped ChangesetId(Blake2(*)) => ChangesetId(Blake2(*)) (glob)
+  * Remapped ChangesetId(Blake2(*)) => ChangesetId(Blake2(*)) (glob)
+  * Saving shifted bonsai changesets (glob)
+  * Saved shifted bonsai changesets (glob)
+  * Start deriving data types (glob)
+  * Finished deriving data types (glob)
+  * Start moving the bookmark (glob)
+  * Created bookmark BookmarkName { bookmark: "repo_import_new_repo" } pointing to * (glob)
+  * Set bookmark BookmarkName { bookmark: "repo_import_new_repo" } to * (glob)
+  * Finished moving the bookmark (glob)
+  * Merging the imported commits into given bookmark, master_bookmark (glob)
+  * Done checking path conflicts (glob)
+  * Creating a merge bonsai changeset with parents: *, * (glob)
+  * Created merge bonsai: * and changeset: * (glob)
+  * Finished merging (glob)
+  * Running pushrebase (glob)
+  * Finished pushrebasing to * (glob)
+
+  $ jq -c '.import_source' "$TESTTMP/recovery_file.json"
+  {"HgBundle":"$TESTTMP/all.hg"}
+  $ jq '.imported_bcs_ids | length' "$TESTTMP/recovery_file.json"
+  2
+
+# Start Mononoke
+  $ mononoke
+  $ wait_for_mononoke
+
+# Clone the repository
+  $ cd "$TESTTMP"
+  $ hgclone_treemanifest ssh://user@dummy/repo-hg repo1 --noupdate -q
+  $ cd repo1
+  $ hgmn pull -q
+  $ hgmn up -q master_bookmark
+
+  $ log -r "all()"
+  @    merging [draft;rev=5;*] (glob)
+  ├─╮
+  │ o  Add file3 [draft;rev=4;*] (glob)
+  │ │
+  │ o  Add file1 and file2 [draft;rev=3;*] (glob)
+  │
+  o  C [draft;rev=2;26805aba1e60]
+  │
+  o  B [draft;rev=1;112478962961]
+  │
+  o  A [draft;rev=0;426bada5c675]
+  $
+
+  $ cat "new_dir/new_repo/file1"
+  this is file1
+  $ cat "new_dir/new_repo/file2_repo/file2"
+  this is file2
+  $ cat "new_dir/new_repo/file3_repo/file3"
+  this is file3
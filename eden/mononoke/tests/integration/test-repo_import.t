@@ -175,12 +175,14 @@
     "datetime": * (glob)
     "dest_bookmark_name": "master_bookmark",
     "dest_path": "new_dir/new_repo",
-    "git_repo_path": "$TESTTMP/repo-git",
     "gitimport_bcs_ids": [
       "f7cbf75d9c08ff96896ed2cebd0327aa514e58b1dd9901d50129b9e08f4aa062",
       "f7708ed066b1c23591f862148e0386ec704a450e572154cc52f87ca0e394a0fb"
     ],
     "hg_sync_check_disabled": true,
+    "import_source": {
+      "Git": "$TESTTMP/repo-git"
+    },
     "import_stage": "PushCommit",
     "merged_cs_id": * (glob)
     "move_bookmark_commits_done": 1,
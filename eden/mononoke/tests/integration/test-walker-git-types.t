@@ -0,0 +1,64 @@
+# Copyright (c) Facebook, Inc. and its affiliates.
+#
+# This software may be used and distributed according to the terms of the
+# GNU General Public License found in the LICENSE file in the root
+# directory of this source tree.
+
+  $ . "${TEST_FIXTURES}/library.sh"
+
+setup configuration
+  $ ENABLED_DERIVED_DATA='["filenodes", "fsnodes", "git_trees", "hgchangesets"]' default_setup_pre_blobimport "blob_files"
+  hg repo
+  o  C [draft;rev=2;26805aba1e60]
+  │
+  o  B [draft;rev=1;112478962961]
+  │
+  o  A [draft;rev=0;426bada5c675]
+  $
+  $ blobimport repo-hg/.hg repo --derived-data-type=git_trees
+
+shallow walk across git trees
+  $ mononoke_walker scrub -q --bookmark master_bookmark -I shallow -i bonsai -i derived_git_trees 2>&1 | strip_glog
+  Walking roots * (glob)
+  Walking edge types [BookmarkToChangeset, ChangesetToGitTreeMapping, GitTreeMappingToRootGitTree, GitTreeToGitTreeChild]
+  Walking node types [Bookmark, Changeset, GitTree, GitTreeMapping]
+  Final count: (4, 4)
+  Bytes/s,* (glob)
+  * Type:Walked,Checks,Children Bookmark:1,1,1 Changeset:1,* GitTree:1,* GitTreeMapping:1,* (glob)
+
+deep walk across git trees
+  $ mononoke_walker scrub -q --bookmark master_bookmark -I deep -i bonsai -i derived_git_trees 2>&1 | strip_glog
+  Walking roots * (glob)
+  Walking edge types [BookmarkToChangeset, ChangesetToBonsaiParent, ChangesetToGitTreeMapping, GitTreeMappingToRootGitTree, GitTreeToGitTreeChild]
+  Walking node types [Bookmark, Changeset, GitTree, GitTreeMapping]
+  Final count: (10, 10)
+  Bytes/s,* (glob)
+  * Type:Walked,Checks,Children Bookmark:1,1,1 Changeset:3,* GitTree:3,* GitTreeMapping:3,* (glob)
+
+shallow walk from git trees to the blob content via the git sha1 alias
+  $ mononoke_walker scrub -q --bookmark master_bookmark -I shallow -i bonsai -i derived_git_trees -i AliasContentMapping -i FileContent -X ChangesetToFileContent 2>&1 | strip_glog
+  Walking roots * (glob)
+  Walking edge types [AliasContentMappingToFileContent, BookmarkToChangeset, ChangesetToGitTreeMapping, GitTreeMappingToRootGitTree, GitTreeToGitBlob, GitTreeToGitTreeChild]
+  Walking node types [AliasContentMapping, Bookmark, Changeset, FileContent, GitTree, GitTreeMapping]
+  Final count: (10, 10)
+  Bytes/s,* (glob)
+  * Type:Walked,Checks,Children AliasContentMapping:3,* Bookmark:1,1,1 Changeset:1,* FileContent:3,* GitTree:1,* GitTreeMapping:1,* (glob)
+
+hg repos have no git or globalrev mappings, so the walk stops there
+  $ mononoke_walker scrub -q --bookmark master_bookmark -I shallow -i bonsai -i BonsaiGitMapping -i BonsaiGlobalrevMapping 2>&1 | strip_glog
+  Walking roots * (glob)
+  Walking edge types [BookmarkToChangeset, ChangesetToBonsaiGitMapping, ChangesetToBonsaiGlobalrevMapping]
+  Walking node types [BonsaiGitMapping, BonsaiGlobalrevMapping, Bookmark, Changeset]
+  Final count: (4, 4)
+  Bytes/s,* (glob)
+  * Type:Walked,Checks,Children BonsaiGitMapping:1,* BonsaiGlobalrevMapping:1,* Bookmark:1,1,1 Changeset:1,* (glob)
+
+validate the git trees hash to their ids
+  $ mononoke_walker validate -I deep -q -i bonsai -i derived_git_trees --bookmark master_bookmark -c GitTreeHashMatches 2>&1 | strip_glog
+  Walking roots * (glob)
+  Walking edge types * (glob)
+  Walking node types * (glob)
+  Performing check types [GitTreeHashMatches]
+  Final count: * (glob)
+  Walked* (glob)
+  Nodes,Pass,Fail:*,3,0; EdgesChecked:*; CheckType:Pass,Fail Total:3,0 GitTreeHashMatches:3,0 (glob)
@@ -27,6 +27,16 @@ validate, expecting all valid
   Walked* (glob)
   Nodes,Pass,Fail:40,3,0; EdgesChecked:9; CheckType:Pass,Fail Total:3,0 HgLinkNodePopulated:3,0
 
+validate, expecting all valid, with the checks that reload data
+  $ mononoke_walker validate -I deep -q --bookmark master_bookmark -c FileContentMatchesMetadata -c FsnodeSummaryMatches -c BonsaiHgMappingRoundTrips -c FileChangeSizeMatchesMetadata 2>&1 | strip_glog
+  Walking roots * (glob)
+  Walking edge types * (glob)
+  Walking node types * (glob)
+  Performing check types [BonsaiHgMappingRoundTrips, FileChangeSizeMatchesMetadata, FileContentMatchesMetadata, FsnodeSummaryMatches]
+  Final count: * (glob)
+  Walked* (glob)
+  Nodes,Pass,Fail:*,0; EdgesChecked:*; CheckType:Pass,Fail Total:*,0 BonsaiHgMappingRoundTrips:3,0 FileChangeSizeMatchesMetadata:3,0 FileContentMatchesMetadata:3,0 FsnodeSummaryMatches:3,0 (glob)
+
 
 validate, check route is logged on unexpected error (forced with chaos blob)
   $ mononoke_walker --blobstore-read-chaos-rate=1 --cachelib-only-blobstore validate -I deep -q --bookmark master_bookmark --scuba-log-file scuba-error.json 2>&1 | strip_glog
@@ -0,0 +1,35 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+CREATE TABLE IF NOT EXISTS walker_checkpoints (
+  repo_id INT UNSIGNED NOT NULL,
+  checkpoint_name VARCHAR(255) NOT NULL,
+  -- Changesets table ids [lower_bound, upper_bound) are covered by the walk
+  lower_bound BIGINT NOT NULL,
+  upper_bound BIGINT NOT NULL,
+  -- Changesets table ids [lower_bound, completed_bound) have been walked
+  completed_bound BIGINT NOT NULL,
+  create_timestamp BIGINT NOT NULL,
+  update_timestamp BIGINT NOT NULL,
+  PRIMARY KEY (repo_id, checkpoint_name)
+);
+
+CREATE TABLE IF NOT EXISTS walker_checkpoint_bookmarks (
+  repo_id INT UNSIGNED NOT NULL,
+  checkpoint_name VARCHAR(255) NOT NULL,
+  name VARCHAR(512) NOT NULL,
+  changeset_id VARBINARY(32) NOT NULL,
+  PRIMARY KEY (repo_id, checkpoint_name, name)
+);
+
+CREATE TABLE IF NOT EXISTS walker_checkpoint_counters (
+  repo_id INT UNSIGNED NOT NULL,
+  checkpoint_name VARCHAR(255) NOT NULL,
+  node_type VARCHAR(255) NOT NULL,
+  walked BIGINT NOT NULL,
+  PRIMARY KEY (repo_id, checkpoint_name, node_type)
+);
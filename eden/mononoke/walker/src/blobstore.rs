@@ -8,13 +8,14 @@
 use crate::validate::{CHECK_FAIL, CHECK_TYPE, NODE_KEY, REPO};
 
 use anyhow::{format_err, Error};
-use blobstore::{Blobstore, BlobstoreMetadata};
+use blobstore::{Blobstore, BlobstoreMetadata, PutBehaviour};
 use blobstore_factory::{
     make_blobstore_multiplexed, make_blobstore_put_ops, BlobstoreOptions, ReadOnlyStorage,
 };
 use cached_config::ConfigStore;
 use context::CoreContext;
 use fbinit::FacebookInit;
+use fileblob::Fileblob;
 use inlinable_string::InlinableString;
 use metaconfig_types::{BlobConfig, BlobstoreId, ScrubAction};
 use multiplexedblob::{LoggingScrubHandler, ScrubHandler};
@@ -31,6 +32,7 @@ define_stats! {
     prefix = "mononoke.walker";
     scrub_repaired: dynamic_timeseries("{}.blobstore.{}.{}.repaired", (subcommand: &'static str, blobstore_id: String, repo: String); Rate, Sum),
     scrub_repair_required: dynamic_timeseries("{}.blobstore.{}.{}.repair_required", (subcommand: &'static str, blobstore_id: String, repo: String); Rate, Sum),
+    scrub_corrupt: dynamic_timeseries("{}.blobstore.{}.{}.corrupt", (subcommand: &'static str, blobstore_id: String, repo: String); Rate, Sum),
 }
 
 pub const BLOBSTORE_ID: &'static str = "blobstore_id";
@@ -107,6 +109,40 @@ impl ScrubHandler for StatsScrubHandler {
             );
         }
     }
+
+    fn on_corrupt(
+        &self,
+        ctx: &CoreContext,
+        blobstore_id: BlobstoreId,
+        key: &str,
+        meta: &BlobstoreMetadata,
+    ) {
+        self.inner.on_corrupt(ctx, blobstore_id, key, meta);
+
+        let ctime = match meta.ctime() {
+            Some(ctime) => ScubaValue::from(ctime),
+            None => ScubaValue::Null(NullScubaValue::Int),
+        };
+
+        self.scuba
+            .clone()
+            .add(REPO, self.repo_stats_key.clone())
+            .add(BLOBSTORE_ID, blobstore_id)
+            .add(NODE_KEY, key)
+            .add(CHECK_TYPE, "scrub_corrupt")
+            .add(CHECK_FAIL, 1)
+            .add("session", ctx.session().metadata().session_id().to_string())
+            .add("ctime", ctime)
+            .log();
+        STATS::scrub_corrupt.add_value(
+            1,
+            (
+                self.subcommand_stats_key,
+                blobstore_id.to_string(),
+                self.repo_stats_key.clone(),
+            ),
+        );
+    }
 }
 
 fn get_blobconfig(
@@ -139,6 +175,22 @@ fn get_blobconfig(
     }
 }
 
+/// Open the store that gc sweeps, which needs to be able to enumerate and remove its keys.
+/// This is the store itself rather than a multiplex, so pass inner_blobstore_id to sweep one side
+/// of a multiplex at a time.
+pub fn open_sweep_blobstore(
+    blob_config: BlobConfig,
+    inner_blobstore_id: Option<u64>,
+) -> Result<Fileblob, Error> {
+    match get_blobconfig(blob_config, inner_blobstore_id)? {
+        BlobConfig::Files { path } => Fileblob::open(path.join("blobs"), PutBehaviour::Overwrite),
+        blobconfig => Err(format_err!(
+            "gc does not support sweeping blobstore {:?}",
+            blobconfig
+        )),
+    }
+}
+
 pub async fn open_blobstore(
     fb: FacebookInit,
     mysql_options: MysqlOptions,
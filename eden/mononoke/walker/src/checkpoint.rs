@@ -0,0 +1,366 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+// Checkpoints let a long running chunked walk (e.g. a deep scrub) be resumed after a restart.
+// A checkpoint records the range of changesets table ids the walk covers, how far through that
+// range it has got, the bookmarks it started from and how many nodes of each type it has walked.
+
+use crate::graph::NodeType;
+
+use anyhow::Error;
+use bookmarks::BookmarkName;
+use futures::compat::Future01CompatExt;
+use mononoke_types::{ChangesetId, RepositoryId, Timestamp};
+use sql::{queries, Connection};
+use sql_construct::{SqlConstruct, SqlConstructFromMetadataDatabaseConfig};
+use sql_ext::SqlConnections;
+use std::{cmp, collections::HashMap, str::FromStr};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    // Changesets table ids in [lower_bound, upper_bound) are covered by the walk
+    pub lower_bound: u64,
+    pub upper_bound: u64,
+    // Changesets table ids in [lower_bound, completed_bound) have been walked
+    pub completed_bound: u64,
+    pub create_timestamp: Timestamp,
+    pub update_timestamp: Timestamp,
+    // Where the publishing bookmarks pointed when the walk started
+    pub bookmarks: Vec<(BookmarkName, ChangesetId)>,
+    pub walked_by_type: HashMap<NodeType, u64>,
+}
+
+impl Checkpoint {
+    pub fn new(
+        lower_bound: u64,
+        upper_bound: u64,
+        bookmarks: Vec<(BookmarkName, ChangesetId)>,
+    ) -> Self {
+        let now = Timestamp::now();
+        Self {
+            lower_bound,
+            upper_bound,
+            completed_bound: lower_bound,
+            create_timestamp: now,
+            update_timestamp: now,
+            bookmarks,
+            walked_by_type: HashMap::new(),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.completed_bound >= self.upper_bound
+    }
+
+    /// The id ranges still to walk, in ascending order so that parents are walked before their
+    /// children.
+    pub fn remaining_chunks(&self, chunk_size: u64) -> Vec<(u64, u64)> {
+        let mut chunks = vec![];
+        let mut low = self.completed_bound;
+        while low < self.upper_bound {
+            let high = cmp::min(low.saturating_add(chunk_size), self.upper_bound);
+            chunks.push((low, high));
+            low = high;
+        }
+        chunks
+    }
+}
+
+queries! {
+    write ReplaceCheckpoint(
+        repo_id: RepositoryId,
+        checkpoint_name: &str,
+        lower_bound: u64,
+        upper_bound: u64,
+        completed_bound: u64,
+        create_timestamp: Timestamp,
+        update_timestamp: Timestamp
+    ) {
+        none,
+        "REPLACE INTO walker_checkpoints (repo_id, checkpoint_name, lower_bound, upper_bound, completed_bound, create_timestamp, update_timestamp)
+        VALUES ({repo_id}, {checkpoint_name}, {lower_bound}, {upper_bound}, {completed_bound}, {create_timestamp}, {update_timestamp})"
+    }
+
+    write UpdateCompletedBound(
+        repo_id: RepositoryId,
+        checkpoint_name: &str,
+        completed_bound: u64,
+        update_timestamp: Timestamp
+    ) {
+        none,
+        "UPDATE walker_checkpoints SET completed_bound = {completed_bound}, update_timestamp = {update_timestamp}
+        WHERE repo_id = {repo_id} AND checkpoint_name = {checkpoint_name}"
+    }
+
+    write DeleteBookmarks(repo_id: RepositoryId, checkpoint_name: &str) {
+        none,
+        "DELETE FROM walker_checkpoint_bookmarks WHERE repo_id = {repo_id} AND checkpoint_name = {checkpoint_name}"
+    }
+
+    write InsertBookmarks(
+        values: (repo_id: RepositoryId, checkpoint_name: &str, name: BookmarkName, changeset_id: ChangesetId)
+    ) {
+        none,
+        "INSERT INTO walker_checkpoint_bookmarks (repo_id, checkpoint_name, name, changeset_id) VALUES {values}"
+    }
+
+    write DeleteCounters(repo_id: RepositoryId, checkpoint_name: &str) {
+        none,
+        "DELETE FROM walker_checkpoint_counters WHERE repo_id = {repo_id} AND checkpoint_name = {checkpoint_name}"
+    }
+
+    write ReplaceCounters(
+        values: (repo_id: RepositoryId, checkpoint_name: &str, node_type: &str, walked: u64)
+    ) {
+        none,
+        "REPLACE INTO walker_checkpoint_counters (repo_id, checkpoint_name, node_type, walked) VALUES {values}"
+    }
+
+    read SelectCheckpoint(repo_id: RepositoryId, checkpoint_name: &str) -> (u64, u64, u64, Timestamp, Timestamp) {
+        "SELECT lower_bound, upper_bound, completed_bound, create_timestamp, update_timestamp
+        FROM walker_checkpoints
+        WHERE repo_id = {repo_id} AND checkpoint_name = {checkpoint_name}"
+    }
+
+    read SelectBookmarks(repo_id: RepositoryId, checkpoint_name: &str) -> (BookmarkName, ChangesetId) {
+        "SELECT name, changeset_id
+        FROM walker_checkpoint_bookmarks
+        WHERE repo_id = {repo_id} AND checkpoint_name = {checkpoint_name}
+        ORDER BY name"
+    }
+
+    read SelectCounters(repo_id: RepositoryId, checkpoint_name: &str) -> (String, u64) {
+        "SELECT node_type, walked
+        FROM walker_checkpoint_counters
+        WHERE repo_id = {repo_id} AND checkpoint_name = {checkpoint_name}"
+    }
+}
+
+#[derive(Clone)]
+pub struct SqlCheckpoints {
+    write_connection: Connection,
+    read_master_connection: Connection,
+}
+
+impl SqlConstruct for SqlCheckpoints {
+    const LABEL: &'static str = "walker_checkpoints";
+
+    const CREATION_QUERY: &'static str = include_str!("../schemas/sqlite-checkpoints.sql");
+
+    fn from_sql_connections(connections: SqlConnections) -> Self {
+        Self {
+            write_connection: connections.write_connection,
+            read_master_connection: connections.read_master_connection,
+        }
+    }
+}
+
+impl SqlConstructFromMetadataDatabaseConfig for SqlCheckpoints {}
+
+impl SqlCheckpoints {
+    pub async fn load(
+        &self,
+        repo_id: RepositoryId,
+        checkpoint_name: &str,
+    ) -> Result<Option<Checkpoint>, Error> {
+        let rows =
+            SelectCheckpoint::query(&self.read_master_connection, &repo_id, &checkpoint_name)
+                .compat()
+                .await?;
+        let (lower_bound, upper_bound, completed_bound, create_timestamp, update_timestamp) =
+            match rows.into_iter().next() {
+                Some(row) => row,
+                None => return Ok(None),
+            };
+
+        let bookmarks =
+            SelectBookmarks::query(&self.read_master_connection, &repo_id, &checkpoint_name)
+                .compat()
+                .await?;
+
+        let mut walked_by_type = HashMap::new();
+        for (node_type, walked) in
+            SelectCounters::query(&self.read_master_connection, &repo_id, &checkpoint_name)
+                .compat()
+                .await?
+        {
+            walked_by_type.insert(NodeType::from_str(&node_type)?, walked);
+        }
+
+        Ok(Some(Checkpoint {
+            lower_bound,
+            upper_bound,
+            completed_bound,
+            create_timestamp,
+            update_timestamp,
+            bookmarks,
+            walked_by_type,
+        }))
+    }
+
+    /// Store `checkpoint` as the start of a new walk, replacing any previous checkpoint of the
+    /// same name.
+    pub async fn insert(
+        &self,
+        repo_id: RepositoryId,
+        checkpoint_name: &str,
+        checkpoint: &Checkpoint,
+    ) -> Result<(), Error> {
+        let txn = self.write_connection.start_transaction().compat().await?;
+        let (txn, _) = ReplaceCheckpoint::query_with_transaction(
+            txn,
+            &repo_id,
+            &checkpoint_name,
+            &checkpoint.lower_bound,
+            &checkpoint.upper_bound,
+            &checkpoint.completed_bound,
+            &checkpoint.create_timestamp,
+            &checkpoint.update_timestamp,
+        )
+        .compat()
+        .await?;
+        let (txn, _) = DeleteBookmarks::query_with_transaction(txn, &repo_id, &checkpoint_name)
+            .compat()
+            .await?;
+        let (txn, _) = DeleteCounters::query_with_transaction(txn, &repo_id, &checkpoint_name)
+            .compat()
+            .await?;
+        let txn = if checkpoint.bookmarks.is_empty() {
+            txn
+        } else {
+            let values: Vec<_> = checkpoint
+                .bookmarks
+                .iter()
+                .map(|(name, cs_id)| (&repo_id, &checkpoint_name, name, cs_id))
+                .collect();
+            let (txn, _) = InsertBookmarks::query_with_transaction(txn, values.as_slice())
+                .compat()
+                .await?;
+            txn
+        };
+        let txn = self
+            .replace_counters(txn, repo_id, checkpoint_name, &checkpoint.walked_by_type)
+            .await?;
+        txn.commit().compat().await?;
+        Ok(())
+    }
+
+    /// Record that the walk has completed up to `completed_bound`, and the walked counts so far
+    pub async fn update(
+        &self,
+        repo_id: RepositoryId,
+        checkpoint_name: &str,
+        completed_bound: u64,
+        walked_by_type: &HashMap<NodeType, u64>,
+    ) -> Result<(), Error> {
+        let txn = self.write_connection.start_transaction().compat().await?;
+        let txn = self
+            .replace_counters(txn, repo_id, checkpoint_name, walked_by_type)
+            .await?;
+        let (txn, _) = UpdateCompletedBound::query_with_transaction(
+            txn,
+            &repo_id,
+            &checkpoint_name,
+            &completed_bound,
+            &Timestamp::now(),
+        )
+        .compat()
+        .await?;
+        txn.commit().compat().await?;
+        Ok(())
+    }
+
+    async fn replace_counters(
+        &self,
+        txn: sql::Transaction,
+        repo_id: RepositoryId,
+        checkpoint_name: &str,
+        walked_by_type: &HashMap<NodeType, u64>,
+    ) -> Result<sql::Transaction, Error> {
+        if walked_by_type.is_empty() {
+            return Ok(txn);
+        }
+        let node_types: Vec<(&'static str, &u64)> = walked_by_type
+            .iter()
+            .map(|(node_type, walked)| (Into::<&'static str>::into(node_type), walked))
+            .collect();
+        let values: Vec<_> = node_types
+            .iter()
+            .map(|(node_type, walked)| (&repo_id, &checkpoint_name, node_type, *walked))
+            .collect();
+        let (txn, _) = ReplaceCounters::query_with_transaction(txn, values.as_slice())
+            .compat()
+            .await?;
+        Ok(txn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fbinit::FacebookInit;
+    use mononoke_types_mocks::changesetid::{ONES_CSID, TWOS_CSID};
+
+    #[test]
+    fn test_remaining_chunks() {
+        let mut checkpoint = Checkpoint::new(1, 10, vec![]);
+        assert_eq!(
+            checkpoint.remaining_chunks(4),
+            vec![(1, 5), (5, 9), (9, 10)]
+        );
+        checkpoint.completed_bound = 9;
+        assert_eq!(checkpoint.remaining_chunks(4), vec![(9, 10)]);
+        assert!(!checkpoint.is_complete());
+        checkpoint.completed_bound = 10;
+        assert!(checkpoint.remaining_chunks(4).is_empty());
+        assert!(checkpoint.is_complete());
+    }
+
+    #[fbinit::compat_test]
+    async fn test_checkpoint_roundtrip(_fb: FacebookInit) -> Result<(), Error> {
+        let store = SqlCheckpoints::with_sqlite_in_memory()?;
+        let repo_id = RepositoryId::new(0);
+        assert_eq!(store.load(repo_id, "scrub").await?, None);
+
+        let mut checkpoint = Checkpoint::new(
+            1,
+            100,
+            vec![
+                (BookmarkName::new("main")?, ONES_CSID),
+                (BookmarkName::new("release")?, TWOS_CSID),
+            ],
+        );
+        store.insert(repo_id, "scrub", &checkpoint).await?;
+        let loaded = store.load(repo_id, "scrub").await?.expect("no checkpoint");
+        assert_eq!(loaded.bookmarks, checkpoint.bookmarks);
+        assert_eq!(loaded.completed_bound, 1);
+
+        checkpoint.walked_by_type.insert(NodeType::Changeset, 5);
+        checkpoint.walked_by_type.insert(NodeType::FileContent, 7);
+        store
+            .update(repo_id, "scrub", 51, &checkpoint.walked_by_type)
+            .await?;
+        let loaded = store.load(repo_id, "scrub").await?.expect("no checkpoint");
+        assert_eq!(loaded.completed_bound, 51);
+        assert_eq!(loaded.upper_bound, 100);
+        assert_eq!(loaded.walked_by_type, checkpoint.walked_by_type);
+
+        // Other repos and names are separate
+        assert_eq!(store.load(RepositoryId::new(1), "scrub").await?, None);
+        assert_eq!(store.load(repo_id, "other").await?, None);
+
+        // Starting again replaces the old state
+        store
+            .insert(repo_id, "scrub", &Checkpoint::new(1, 200, vec![]))
+            .await?;
+        let loaded = store.load(repo_id, "scrub").await?.expect("no checkpoint");
+        assert_eq!(loaded.upper_bound, 200);
+        assert!(loaded.bookmarks.is_empty());
+        assert!(loaded.walked_by_type.is_empty());
+        Ok(())
+    }
+}
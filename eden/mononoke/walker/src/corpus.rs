@@ -156,6 +156,8 @@ fn dump_with_extension(node_type: NodeType) -> bool {
         NodeType::BonsaiHgMapping => false,
         NodeType::PhaseMapping => false,
         NodeType::PublishedBookmarks => false,
+        NodeType::BonsaiGitMapping => false,
+        NodeType::BonsaiGlobalrevMapping => false,
         // Hg
         NodeType::HgBonsaiMapping => false,
         NodeType::HgChangeset => false,
@@ -177,6 +179,8 @@ fn dump_with_extension(node_type: NodeType) -> bool {
         NodeType::FastlogFile => false,
         NodeType::Fsnode => false,
         NodeType::FsnodeMapping => false,
+        NodeType::GitTree => false,
+        NodeType::GitTreeMapping => false,
         NodeType::SkeletonManifest => false,
         NodeType::SkeletonManifestMapping => false,
         NodeType::UnodeFile => false,
@@ -438,6 +442,7 @@ pub async fn corpus<'a>(
         walk_state,
         make_sink,
         true,
+        None,
     )
     .await
 }
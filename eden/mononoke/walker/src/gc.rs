@@ -0,0 +1,549 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::blobstore::open_sweep_blobstore;
+use crate::graph::{EdgeType, FileContentData, Node, NodeData, NodeType};
+use crate::progress::{progress_stream, report_state};
+use crate::setup::{
+    setup_common, ARCHIVE_PREFIX_ARG, GC, INNER_BLOBSTORE_ID_ARG, RETAIN_KEY_PREFIX_ARG,
+    RETAIN_SCRATCH_PREFIX_ARG, UNREACHABLE_KEYS_OUTPUT_ARG,
+};
+use crate::state::WalkState;
+use crate::tail::{walk_exact_tail, RepoWalkRun};
+use crate::walk::{EmptyRoute, OutgoingEdge};
+
+use anyhow::{format_err, Error};
+use blobstore::{
+    Blobstore, BlobstoreKeyParam, BlobstoreKeyRange, BlobstoreKeySource, BlobstoreUnlinkOps,
+};
+use bookmarks::{BookmarkKind, BookmarkPagination, BookmarkPrefix, Freshness};
+use clap::ArgMatches;
+use cloned::cloned;
+use cmdlib::args::{self, MononokeMatches};
+use context::CoreContext;
+use dashmap::DashMap;
+use fbinit::FacebookInit;
+use futures::{
+    future::{self, FutureExt},
+    stream::{self, Stream, StreamExt, TryStreamExt},
+    TryFutureExt,
+};
+use mononoke_types::BlobstoreBytes;
+use samplingblob::SamplingHandler;
+use slog::{info, Logger};
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufWriter, Write},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Pushes upload their blobs before moving a bookmark, so blobs a little older than the walk may
+// be about to become reachable.
+const SWEEP_GRACE_PERIOD_SECS: i64 = 24 * 60 * 60;
+
+/// Records every key the walk loaded from the blobstore, i.e. the reachable keys.
+#[derive(Debug, Default)]
+pub struct ReachableKeys {
+    keys: DashMap<String, ()>,
+}
+
+impl ReachableKeys {
+    pub fn contains(&self, key: &str) -> bool {
+        self.keys.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+}
+
+impl SamplingHandler for ReachableKeys {
+    fn sample_get(
+        &self,
+        _ctx: &CoreContext,
+        key: &str,
+        value: Option<&BlobstoreBytes>,
+    ) -> Result<(), Error> {
+        if value.is_some() {
+            self.keys.insert(key.to_owned(), ());
+        }
+        Ok(())
+    }
+
+    fn sample_is_present(&self, _ctx: &CoreContext, key: &str, value: bool) -> Result<(), Error> {
+        if value {
+            self.keys.insert(key.to_owned(), ());
+        }
+        Ok(())
+    }
+}
+
+/// The key families (after the repo prefix) that the walk loads when it steps to a node of this
+/// type. Only these families can be swept.
+fn node_type_key_prefixes(node_type: NodeType) -> &'static [&'static str] {
+    match node_type {
+        NodeType::Root => &[],
+        // Bonsai
+        NodeType::Bookmark => &[],
+        NodeType::Changeset => &["changeset.blake2."],
+        NodeType::BonsaiHgMapping => &[],
+        NodeType::PhaseMapping => &[],
+        NodeType::PublishedBookmarks => &[],
+        NodeType::BonsaiGitMapping => &[],
+        NodeType::BonsaiGlobalrevMapping => &[],
+        // Hg
+        NodeType::HgBonsaiMapping => &[],
+        NodeType::HgChangeset => &["hgchangeset.sha1."],
+        NodeType::HgManifest => &["hgmanifest.sha1."],
+        NodeType::HgFileEnvelope => &["hgfilenode.sha1."],
+        NodeType::HgFileNode => &[],
+        // Content
+        NodeType::FileContent => &["content.blake2.", "chunk.blake2."],
+        NodeType::FileContentMetadata => &["content_metadata.blake2."],
+        NodeType::AliasContentMapping => &["alias.sha1.", "alias.sha256.", "alias.gitsha1."],
+        // Derived data
+        NodeType::Blame => &["blame."],
+        NodeType::ChangesetInfo => &[],
+        NodeType::ChangesetInfoMapping => &["changeset_info.blake2."],
+        NodeType::DeletedManifest => &["deletedmanifest.blake2."],
+        NodeType::DeletedManifestMapping => &["derived_root_deleted_manifest."],
+        NodeType::FastlogBatch => &["fastlogbatch."],
+        NodeType::FastlogDir => &[],
+        NodeType::FastlogFile => &[],
+        NodeType::Fsnode => &["fsnode.blake2."],
+        NodeType::FsnodeMapping => &["derived_root_fsnode."],
+        NodeType::GitTree => &["git.tree."],
+        NodeType::GitTreeMapping => &["git.derived_root."],
+        NodeType::SkeletonManifest => &["skeletonmanifest.blake2."],
+        NodeType::SkeletonManifestMapping => &["derived_root_skeletonmanifest."],
+        NodeType::UnodeFile => &["fileunode.blake2."],
+        NodeType::UnodeManifest => &["manifestunode.blake2."],
+        NodeType::UnodeMapping => &["derived_root_unode.", "derived_root_unode_v2."],
+    }
+}
+
+/// The key families that a walk over `node_types` marks completely
+pub fn sweepable_key_prefixes<'a>(
+    node_types: impl IntoIterator<Item = &'a NodeType>,
+) -> Vec<String> {
+    let mut prefixes: Vec<String> = node_types
+        .into_iter()
+        .flat_map(|t| node_type_key_prefixes(*t).iter())
+        .map(|prefix| prefix.to_string())
+        .collect();
+    prefixes.sort();
+    prefixes.dedup();
+    prefixes
+}
+
+#[derive(Clone, Debug)]
+pub struct SweepParams {
+    /// The keys to sweep, usually everything under the repo's prefix
+    pub range: BlobstoreKeyRange,
+    /// Only keys starting with one of these (after the repo prefix) are swept, any other key is
+    /// not in the walked graph so cannot have been marked
+    pub sweep_prefixes: Vec<String>,
+    /// Keys starting with one of these are never swept
+    pub retain_prefixes: Vec<String>,
+    /// Blobs created at or after this time (in seconds since the epoch) are kept, as they may
+    /// have been written after the mark phase started
+    pub created_before: i64,
+    /// Where to move unreachable keys to. None for a dry run.
+    pub archive_prefix: Option<String>,
+    pub concurrency: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SweepSummary {
+    pub enumerated_keys: u64,
+    pub reachable_keys: u64,
+    pub retained_keys: u64,
+    pub recent_keys: u64,
+    pub unreachable_keys: u64,
+    pub unreachable_bytes: u64,
+    pub archived_keys: u64,
+}
+
+enum SweepStatus {
+    // Removed since it was enumerated
+    Gone,
+    Recent,
+    Unreachable(u64),
+}
+
+async fn enumerate_keys<S: BlobstoreKeySource>(
+    ctx: &CoreContext,
+    store: &S,
+    range: &BlobstoreKeyRange,
+) -> Result<Vec<String>, Error> {
+    let mut keys = vec![];
+    let mut param = BlobstoreKeyParam::Start(range.clone());
+    loop {
+        let data = store.enumerate(ctx, &param).await?;
+        keys.extend(data.keys.into_iter().filter(|key| range.contains(key)));
+        match data.next_token {
+            Some(next_token) => param = next_token,
+            None => break,
+        }
+    }
+    keys.sort();
+    Ok(keys)
+}
+
+/// Sweep the keys in `store` that were not marked as reachable. Returns a summary, and the
+/// unreachable keys. Unreachable keys are only moved if there is an archive prefix.
+pub async fn sweep<S>(
+    ctx: &CoreContext,
+    store: &S,
+    reachable: &ReachableKeys,
+    params: &SweepParams,
+) -> Result<(SweepSummary, Vec<String>), Error>
+where
+    S: BlobstoreKeySource + BlobstoreUnlinkOps,
+{
+    let mut summary = SweepSummary::default();
+    let mut candidates = vec![];
+    for key in enumerate_keys(ctx, store, &params.range).await? {
+        summary.enumerated_keys += 1;
+        let unprefixed = if key.starts_with(&params.range.begin_key) {
+            &key[params.range.begin_key.len()..]
+        } else {
+            key.as_str()
+        };
+        if reachable.contains(&key) {
+            summary.reachable_keys += 1;
+        } else if !params
+            .sweep_prefixes
+            .iter()
+            .any(|prefix| unprefixed.starts_with(prefix))
+            || params
+                .retain_prefixes
+                .iter()
+                .any(|prefix| unprefixed.starts_with(prefix))
+            || params
+                .archive_prefix
+                .as_ref()
+                .map_or(false, |prefix| key.starts_with(prefix))
+        {
+            summary.retained_keys += 1;
+        } else {
+            candidates.push(key);
+        }
+    }
+
+    let swept = stream::iter(candidates)
+        .map(|key| async move {
+            let status = match store.get(ctx, &key).await? {
+                None => SweepStatus::Gone,
+                Some(value)
+                    if value
+                        .as_meta()
+                        .ctime()
+                        .map_or(false, |ctime| ctime >= params.created_before) =>
+                {
+                    SweepStatus::Recent
+                }
+                Some(value) => {
+                    let size = value.as_raw_bytes().len() as u64;
+                    if let Some(archive_prefix) = &params.archive_prefix {
+                        let archive_key = format!("{}{}", archive_prefix, key);
+                        store.put(ctx, archive_key, value.into_bytes()).await?;
+                        store.unlink(ctx, &key).await?;
+                    }
+                    SweepStatus::Unreachable(size)
+                }
+            };
+            Ok::<_, Error>((key, status))
+        })
+        .buffered(params.concurrency)
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let mut unreachable = vec![];
+    for (key, status) in swept {
+        match status {
+            SweepStatus::Gone => {}
+            SweepStatus::Recent => summary.recent_keys += 1,
+            SweepStatus::Unreachable(size) => {
+                summary.unreachable_keys += 1;
+                summary.unreachable_bytes += size;
+                if params.archive_prefix.is_some() {
+                    summary.archived_keys += 1;
+                }
+                unreachable.push(key);
+            }
+        }
+    }
+    Ok((summary, unreachable))
+}
+
+// Force load of file contents so that their chunks are marked as reachable
+fn loading_stream<InStream, SS>(
+    scheduled_max: usize,
+    s: InStream,
+) -> impl Stream<Item = Result<(Node, Option<NodeData>, Option<SS>), Error>>
+where
+    InStream: Stream<Item = Result<(Node, Option<NodeData>, Option<SS>), Error>> + 'static + Send,
+    SS: 'static + Send,
+{
+    s.map_ok(move |(n, nd, ss)| match nd {
+        Some(NodeData::FileContent(FileContentData::ContentStream(file_bytes_stream))) => {
+            file_bytes_stream
+                .try_fold(0, |acc, file_bytes| future::ok(acc + file_bytes.size()))
+                .map_ok(move |num_bytes| {
+                    (
+                        n,
+                        Some(NodeData::FileContent(FileContentData::Consumed(num_bytes))),
+                        ss,
+                    )
+                })
+                .map_err(|e| e.context(format_err!("While marking file content stream")))
+                .left_future()
+        }
+        nd => future::ok((n, nd, ss)).right_future(),
+    })
+    .try_buffer_unordered(scheduled_max)
+}
+
+// Marks by walking the graph, then sweeps by enumerating the blobstore
+pub async fn gc<'a>(
+    fb: FacebookInit,
+    logger: Logger,
+    matches: &'a MononokeMatches<'a>,
+    sub_m: &'a ArgMatches<'a>,
+) -> Result<(), Error> {
+    let reachable = Arc::new(ReachableKeys::default());
+    let (datasources, mut walk_params) =
+        setup_common(GC, fb, &logger, Some(reachable.clone()), matches, sub_m).await?;
+
+    if walk_params.tail_secs.is_some() {
+        return Err(format_err!(
+            "gc cannot tail, it needs a complete walk before it can sweep"
+        ));
+    }
+
+    let inner_blobstore_id = args::get_u64_opt(&sub_m, INNER_BLOBSTORE_ID_ARG);
+    let sweep_store = open_sweep_blobstore(datasources.blobconfig.clone(), inner_blobstore_id)?;
+
+    let ctx = CoreContext::new_with_logger(fb, logger.clone());
+    if let Some(prefixes) = sub_m.values_of(RETAIN_SCRATCH_PREFIX_ARG) {
+        for prefix in prefixes {
+            let mut scratch_roots = datasources
+                .blobrepo
+                .bookmarks()
+                .list(
+                    ctx.clone(),
+                    Freshness::MostRecent,
+                    &BookmarkPrefix::new(prefix)?,
+                    &[BookmarkKind::Scratch],
+                    &BookmarkPagination::FromStart,
+                    std::u64::MAX,
+                )
+                .map_ok(|(bookmark, _cs_id)| {
+                    OutgoingEdge::new(EdgeType::RootToBookmark, Node::Bookmark(bookmark.name))
+                })
+                .try_collect::<Vec<_>>()
+                .await?;
+            info!(
+                logger,
+                "Retaining {} scratch bookmarks with prefix {}",
+                scratch_roots.len(),
+                prefix
+            );
+            walk_params.walk_roots.append(&mut scratch_roots);
+        }
+    }
+
+    let repo_prefix = datasources.blobrepo.get_repoid().prefix();
+    let retain_prefixes = sub_m
+        .values_of(RETAIN_KEY_PREFIX_ARG)
+        .map_or_else(Vec::new, |values| values.map(|v| v.to_string()).collect());
+    let archive_prefix = sub_m.value_of(ARCHIVE_PREFIX_ARG).map(|v| v.to_string());
+    let unreachable_keys_output = sub_m.value_of(UNREACHABLE_KEYS_OUTPUT_ARG);
+
+    let started = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    let scheduled_max = walk_params.scheduled_max;
+    let quiet = walk_params.quiet;
+    let progress_state = walk_params.progress_state.clone();
+
+    let make_sink = move |run: RepoWalkRun| {
+        cloned!(run.ctx);
+        async move |walk_output| {
+            let walk_progress = progress_stream(quiet, &progress_state, walk_output);
+            let loading = loading_stream(scheduled_max, walk_progress);
+            report_state(ctx, progress_state, loading).await
+        }
+    };
+
+    let sweep_prefixes = sweepable_key_prefixes(&walk_params.include_node_types);
+    info!(logger, "Sweeping key prefixes {:?}", sweep_prefixes);
+
+    let walk_state = Arc::new(WalkState::new(
+        walk_params.include_node_types.clone(),
+        walk_params.include_edge_types.clone(),
+        HashSet::new(),
+        walk_params.enable_derive,
+    ));
+    walk_exact_tail::<_, _, _, _, _, EmptyRoute>(
+        fb,
+        logger.clone(),
+        datasources,
+        walk_params,
+        &[NodeType::FileContent],
+        None,
+        walk_state,
+        make_sink,
+        false,
+        None,
+    )
+    .await?;
+    info!(logger, "Marked {} reachable keys", reachable.len());
+
+    let params = SweepParams {
+        range: BlobstoreKeyRange {
+            begin_key: repo_prefix.clone(),
+            // The repo prefix ends in '.', and '/' sorts just after it
+            end_key: format!("{}/", repo_prefix.trim_end_matches('.')),
+        },
+        sweep_prefixes,
+        retain_prefixes,
+        created_before: started - SWEEP_GRACE_PERIOD_SECS,
+        archive_prefix,
+        concurrency: scheduled_max,
+    };
+    let (summary, unreachable) = sweep(&ctx, &sweep_store, &reachable, &params).await?;
+
+    if let Some(path) = unreachable_keys_output {
+        let mut out = BufWriter::new(File::create(path)?);
+        for key in &unreachable {
+            writeln!(out, "{}", key)?;
+        }
+        out.flush()?;
+    }
+
+    let dry_run = if params.archive_prefix.is_none() {
+        " (dry run)"
+    } else {
+        ""
+    };
+    info!(
+        logger,
+        "Swept {} keys{}: {} reachable, {} retained, {} too recent, {} unreachable using {} bytes, {} archived",
+        summary.enumerated_keys,
+        dry_run,
+        summary.reachable_keys,
+        summary.retained_keys,
+        summary.recent_keys,
+        summary.unreachable_keys,
+        summary.unreachable_bytes,
+        summary.archived_keys,
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borrowed::borrowed;
+    use memblob::Memblob;
+
+    fn params(archive_prefix: Option<&str>) -> SweepParams {
+        SweepParams {
+            range: BlobstoreKeyRange {
+                begin_key: "repo0000.".to_string(),
+                end_key: "repo0000/".to_string(),
+            },
+            sweep_prefixes: sweepable_key_prefixes(&[NodeType::Changeset]),
+            retain_prefixes: vec!["changeset.blake2.keep".to_string()],
+            created_before: std::i64::MAX,
+            archive_prefix: archive_prefix.map(|p| p.to_string()),
+            concurrency: 10,
+        }
+    }
+
+    #[fbinit::compat_test]
+    async fn test_sweep(fb: FacebookInit) -> Result<(), Error> {
+        let ctx = CoreContext::test_mock(fb);
+        borrowed!(ctx);
+        let store = Memblob::default();
+        for key in &[
+            "repo0000.changeset.blake2.reachable",
+            "repo0000.changeset.blake2.unreachable",
+            "repo0000.changeset.blake2.keep_this",
+            "repo0000.segmented_changelog_iddag.blake2.not_in_graph",
+            "repo0001.other_repo",
+        ] {
+            store
+                .put(ctx, key.to_string(), BlobstoreBytes::from_bytes("abc"))
+                .await?;
+        }
+        let reachable = ReachableKeys::default();
+        reachable.sample_get(
+            ctx,
+            "repo0000.changeset.blake2.reachable",
+            Some(&BlobstoreBytes::from_bytes("abc")),
+        )?;
+
+        // A dry run changes nothing
+        let (summary, unreachable) = sweep(ctx, &store, &reachable, &params(None)).await?;
+        assert_eq!(
+            unreachable,
+            vec!["repo0000.changeset.blake2.unreachable".to_string()]
+        );
+        assert_eq!(
+            summary,
+            SweepSummary {
+                enumerated_keys: 4,
+                reachable_keys: 1,
+                retained_keys: 2,
+                recent_keys: 0,
+                unreachable_keys: 1,
+                unreachable_bytes: 3,
+                archived_keys: 0,
+            }
+        );
+        assert!(
+            store
+                .is_present(ctx, "repo0000.changeset.blake2.unreachable")
+                .await?
+        );
+
+        let (summary, _) = sweep(ctx, &store, &reachable, &params(Some("archive."))).await?;
+        assert_eq!(summary.archived_keys, 1);
+        assert!(
+            !store
+                .is_present(ctx, "repo0000.changeset.blake2.unreachable")
+                .await?
+        );
+        assert!(
+            store
+                .is_present(ctx, "archive.repo0000.changeset.blake2.unreachable")
+                .await?
+        );
+        assert!(
+            store
+                .is_present(ctx, "repo0000.changeset.blake2.reachable")
+                .await?
+        );
+        assert!(store.is_present(ctx, "repo0001.other_repo").await?);
+        assert!(
+            store
+                .is_present(
+                    ctx,
+                    "repo0000.segmented_changelog_iddag.blake2.not_in_graph"
+                )
+                .await?
+        );
+
+        // Nothing left to sweep
+        let (summary, _) = sweep(ctx, &store, &reachable, &params(Some("archive."))).await?;
+        assert_eq!(summary.unreachable_keys, 0);
+        Ok(())
+    }
+}
@@ -19,6 +19,7 @@ use filenodes::FilenodeInfo;
 use filestore::Alias;
 use fsnodes::RootFsnodeId;
 use futures::stream::BoxStream;
+use git_types::{tree_blobstore_key, Tree, TreeHandle};
 use hash_memo::EagerHashMemoizer;
 use internment::ArcIntern;
 use manifest::Entry;
@@ -32,11 +33,12 @@ use mononoke_types::{
     deleted_files_manifest::DeletedManifest,
     fastlog_batch::FastlogBatch,
     fsnode::Fsnode,
+    hash::GitSha1,
     skeleton_manifest::SkeletonManifest,
     unode::{FileUnode, ManifestUnode},
     BlameId, BonsaiChangeset, ChangesetId, ContentId, ContentMetadata, DeletedManifestId,
-    FastlogBatchId, FileUnodeId, FsnodeId, MPath, MPathHash, ManifestUnodeId, MononokeId,
-    SkeletonManifestId,
+    FastlogBatchId, FileUnodeId, FsnodeId, Globalrev, MPath, MPathHash, ManifestUnodeId,
+    MononokeId, SkeletonManifestId,
 };
 use once_cell::sync::OnceCell;
 use phases::Phase;
@@ -262,6 +264,8 @@ create_graph!(
             BonsaiHgMapping,
             PhaseMapping,
             PublishedBookmarks,
+            BonsaiGitMapping,
+            BonsaiGlobalrevMapping,
             // Hg
             HgBonsaiMapping,
             HgChangeset,
@@ -283,6 +287,8 @@ create_graph!(
             FastlogFile,
             Fsnode,
             FsnodeMapping,
+            GitTree,
+            GitTreeMapping,
             SkeletonManifest,
             SkeletonManifestMapping,
             UnodeFile,
@@ -305,7 +311,10 @@ create_graph!(
             DeletedManifestMapping,
             FsnodeMapping,
             SkeletonManifestMapping,
-            UnodeMapping
+            UnodeMapping,
+            BonsaiGitMapping,
+            BonsaiGlobalrevMapping,
+            GitTreeMapping
         ]
     ),
     (BonsaiHgMapping, ChangesetId, [HgChangeset]),
@@ -315,6 +324,8 @@ create_graph!(
         UnitKey,
         [Changeset, BonsaiHgMapping]
     ),
+    (BonsaiGitMapping, ChangesetId, []),
+    (BonsaiGlobalrevMapping, ChangesetId, []),
     // Hg
     (HgBonsaiMapping, HgChangesetId, [Changeset]),
     (
@@ -393,6 +404,12 @@ create_graph!(
         [Changeset, PreviousBatch(FastlogBatch)]
     ),
     (FsnodeMapping, ChangesetId, [RootFsnode(Fsnode)]),
+    (
+        GitTree,
+        GitSha1,
+        [GitTreeChild(GitTree), GitBlob(AliasContentMapping)]
+    ),
+    (GitTreeMapping, ChangesetId, [RootGitTree(GitTree)]),
     (
         SkeletonManifest,
         SkeletonManifestId,
@@ -431,6 +448,8 @@ impl NodeType {
             NodeType::BonsaiHgMapping => Some(FilenodesOnlyPublic::NAME),
             NodeType::PhaseMapping => None,
             NodeType::PublishedBookmarks => None,
+            NodeType::BonsaiGitMapping => None,
+            NodeType::BonsaiGlobalrevMapping => None,
             // Hg
             NodeType::HgBonsaiMapping => Some(MappedHgChangesetId::NAME),
             NodeType::HgChangeset => Some(MappedHgChangesetId::NAME),
@@ -452,6 +471,8 @@ impl NodeType {
             NodeType::FastlogFile => Some(RootFastlog::NAME),
             NodeType::Fsnode => Some(RootFsnodeId::NAME),
             NodeType::FsnodeMapping => Some(RootFsnodeId::NAME),
+            NodeType::GitTree => Some(TreeHandle::NAME),
+            NodeType::GitTreeMapping => Some(TreeHandle::NAME),
             NodeType::SkeletonManifest => Some(RootSkeletonManifestId::NAME),
             NodeType::SkeletonManifestMapping => Some(RootSkeletonManifestId::NAME),
             NodeType::UnodeFile => Some(RootUnodeManifestId::NAME),
@@ -592,6 +613,8 @@ pub enum NodeData {
     BonsaiHgMapping(Option<HgChangesetId>),
     PhaseMapping(Option<Phase>),
     PublishedBookmarks,
+    BonsaiGitMapping(Option<GitSha1>),
+    BonsaiGlobalrevMapping(Option<Globalrev>),
     // Hg
     HgBonsaiMapping(Option<ChangesetId>),
     HgChangeset(HgBlobChangeset),
@@ -613,6 +636,8 @@ pub enum NodeData {
     FastlogFile(Option<FastlogBatch>),
     Fsnode(Fsnode),
     FsnodeMapping(Option<FsnodeId>),
+    GitTree(Tree),
+    GitTreeMapping(Option<GitSha1>),
     SkeletonManifest(Option<SkeletonManifest>),
     SkeletonManifestMapping(Option<SkeletonManifestId>),
     UnodeFile(FileUnode),
@@ -630,6 +655,8 @@ impl Node {
             Node::BonsaiHgMapping(k) => k.blobstore_key(),
             Node::PhaseMapping(k) => k.blobstore_key(),
             Node::PublishedBookmarks(_) => "published_bookmarks".to_string(),
+            Node::BonsaiGitMapping(k) => k.blobstore_key(),
+            Node::BonsaiGlobalrevMapping(k) => k.blobstore_key(),
             // Hg
             Node::HgBonsaiMapping(k) => k.blobstore_key(),
             Node::HgChangeset(k) => k.blobstore_key(),
@@ -651,6 +678,8 @@ impl Node {
             Node::FastlogFile(k) => k.blobstore_key(),
             Node::Fsnode(k) => k.blobstore_key(),
             Node::FsnodeMapping(k) => k.blobstore_key(),
+            Node::GitTree(k) => tree_blobstore_key(k),
+            Node::GitTreeMapping(k) => k.blobstore_key(),
             Node::SkeletonManifest(k) => k.blobstore_key(),
             Node::SkeletonManifestMapping(k) => k.blobstore_key(),
             Node::UnodeFile(k) => k.blobstore_key(),
@@ -668,6 +697,8 @@ impl Node {
             Node::BonsaiHgMapping(_) => None,
             Node::PhaseMapping(_) => None,
             Node::PublishedBookmarks(_) => None,
+            Node::BonsaiGitMapping(_) => None,
+            Node::BonsaiGlobalrevMapping(_) => None,
             // Hg
             Node::HgBonsaiMapping(_) => None,
             Node::HgChangeset(_) => None,
@@ -689,6 +720,8 @@ impl Node {
             Node::FastlogFile(_) => None,
             Node::Fsnode(_) => None,
             Node::FsnodeMapping(_) => None,
+            Node::GitTree(_) => None,
+            Node::GitTreeMapping(_) => None,
             Node::SkeletonManifest(_) => None,
             Node::SkeletonManifestMapping(_) => None,
             Node::UnodeFile(_) => None,
@@ -707,6 +740,8 @@ impl Node {
             Node::BonsaiHgMapping(k) => Some(k.sampling_fingerprint()),
             Node::PhaseMapping(k) => Some(k.sampling_fingerprint()),
             Node::PublishedBookmarks(_) => None,
+            Node::BonsaiGitMapping(k) => Some(k.sampling_fingerprint()),
+            Node::BonsaiGlobalrevMapping(k) => Some(k.sampling_fingerprint()),
             // Hg
             Node::HgBonsaiMapping(k) => Some(k.sampling_fingerprint()),
             Node::HgChangeset(k) => Some(k.sampling_fingerprint()),
@@ -728,6 +763,8 @@ impl Node {
             Node::FastlogFile(k) => Some(k.sampling_fingerprint()),
             Node::Fsnode(k) => Some(k.sampling_fingerprint()),
             Node::FsnodeMapping(k) => Some(k.sampling_fingerprint()),
+            Node::GitTree(k) => Some(k.sampling_fingerprint()),
+            Node::GitTreeMapping(k) => Some(k.sampling_fingerprint()),
             Node::SkeletonManifest(k) => Some(k.sampling_fingerprint()),
             Node::SkeletonManifestMapping(k) => Some(k.sampling_fingerprint()),
             Node::UnodeFile(k) => Some(k.sampling_fingerprint()),
@@ -820,8 +857,7 @@ mod tests {
         // If you are adding a new derived data type, please add it to the walker graph rather than to this
         // list, otherwise it won't get scrubbed and thus you would be unaware of different representation
         // in different stores
-        let grandfathered: HashSet<&'static str> =
-            HashSet::from_iter(vec!["git_trees"].into_iter());
+        let grandfathered: HashSet<&'static str> = HashSet::from_iter(vec![].into_iter());
         let mut missing = HashSet::new();
         for t in &a {
             if s.contains(t.as_str()) {
@@ -18,7 +18,9 @@ use cmdlib::{
 };
 
 mod blobstore;
+mod checkpoint;
 mod corpus;
+mod gc;
 #[macro_use]
 mod graph;
 mod parse_node;
@@ -53,6 +55,7 @@ fn main(fb: FacebookInit) -> Result<(), Error> {
             sizing::compression_benefit(fb, logger.clone(), &matches, sub_m).boxed()
         }
         (setup::CORPUS, Some(sub_m)) => corpus::corpus(fb, logger.clone(), &matches, sub_m).boxed(),
+        (setup::GC, Some(sub_m)) => gc::gc(fb, logger.clone(), &matches, sub_m).boxed(),
         (setup::SCRUB, Some(sub_m)) => {
             scrub::scrub_objects(fb, logger.clone(), &matches, sub_m).boxed()
         }
@@ -205,6 +205,18 @@ mod tests {
                     format!("{:?}", parse_node("PublishedBookmarks:garbage"))
                 );
             }
+            NodeType::BonsaiGitMapping => assert_eq!(
+                node_type,
+                &parse_node(&format!("BonsaiGitMapping{}{}", NODE_SEP, SAMPLE_BLAKE2))?.get_type()
+            ),
+            NodeType::BonsaiGlobalrevMapping => assert_eq!(
+                node_type,
+                &parse_node(&format!(
+                    "BonsaiGlobalrevMapping{}{}",
+                    NODE_SEP, SAMPLE_BLAKE2
+                ))?
+                .get_type()
+            ),
             // Hg
             NodeType::HgBonsaiMapping => assert_eq!(
                 node_type,
@@ -331,6 +343,19 @@ mod tests {
                     &parse_node(&format!("FsnodeMapping{}{}", NODE_SEP, SAMPLE_BLAKE2))?.get_type()
                 );
             }
+            NodeType::GitTree => {
+                assert_eq!(
+                    node_type,
+                    &parse_node(&format!("GitTree{}{}", NODE_SEP, SAMPLE_SHA1))?.get_type()
+                );
+            }
+            NodeType::GitTreeMapping => {
+                assert_eq!(
+                    node_type,
+                    &parse_node(&format!("GitTreeMapping{}{}", NODE_SEP, SAMPLE_BLAKE2))?
+                        .get_type()
+                );
+            }
             NodeType::SkeletonManifest => {
                 assert_eq!(
                     node_type,
@@ -387,6 +387,23 @@ impl<Inner> Clone for ProgressStateMutex<Inner> {
     }
 }
 
+impl<SS, T> ProgressStateMutex<ProgressStateCountByType<SS, T>>
+where
+    SS: Add<SS, Output = SS> + Default,
+{
+    /// How many nodes of each type have been recorded so far
+    pub fn walked_by_type(&self) -> HashMap<NodeType, u64> {
+        self.inner
+            .lock()
+            .unwrap()
+            .work_stats
+            .stats_by_type
+            .iter()
+            .map(|(node_type, (walked, _))| (*node_type, *walked))
+            .collect()
+    }
+}
+
 // Log some status update, passing on all data unchanged
 pub fn progress_stream<InStream, PS, Payload, SS, K>(
     quiet: bool,
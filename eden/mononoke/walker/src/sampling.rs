@@ -102,6 +102,8 @@ fn filter_repo_path(node_type: NodeType, path: Option<&'_ WrappedPath>) -> Optio
         NodeType::BonsaiHgMapping => None,
         NodeType::PhaseMapping => None,
         NodeType::PublishedBookmarks => None,
+        NodeType::BonsaiGitMapping => None,
+        NodeType::BonsaiGlobalrevMapping => None,
         // Hg
         NodeType::HgBonsaiMapping => None,
         NodeType::HgChangeset => None,
@@ -123,6 +125,8 @@ fn filter_repo_path(node_type: NodeType, path: Option<&'_ WrappedPath>) -> Optio
         NodeType::FastlogFile => path,
         NodeType::Fsnode => path,
         NodeType::FsnodeMapping => None,
+        NodeType::GitTree => path,
+        NodeType::GitTreeMapping => None,
         NodeType::SkeletonManifest => path,
         NodeType::SkeletonManifestMapping => None,
         NodeType::UnodeFile => path,
@@ -5,6 +5,7 @@
  * GNU General Public License version 2.
  */
 
+use crate::checkpoint::SqlCheckpoints;
 use crate::graph::{FileContentData, Node, NodeData, NodeType};
 use crate::progress::{
     progress_stream, report_state, ProgressReporter, ProgressReporterUnprotected,
@@ -12,14 +13,15 @@ use crate::progress::{
 };
 use crate::sampling::{SamplingWalkVisitor, WalkSampleMapping};
 use crate::setup::{
-    parse_node_types, setup_common, OutputFormat, DEFAULT_INCLUDE_NODE_TYPES,
-    EXCLUDE_OUTPUT_NODE_TYPE_ARG, EXCLUDE_SAMPLE_NODE_TYPE_ARG, INCLUDE_OUTPUT_NODE_TYPE_ARG,
-    INCLUDE_SAMPLE_NODE_TYPE_ARG, LIMIT_DATA_FETCH_ARG, OUTPUT_FORMAT_ARG, PROGRESS_INTERVAL_ARG,
-    PROGRESS_SAMPLE_DURATION_S, PROGRESS_SAMPLE_RATE, PROGRESS_SAMPLE_RATE_ARG, SAMPLE_OFFSET_ARG,
-    SAMPLE_RATE_ARG, SCRUB,
+    parse_node_types, setup_common, OutputFormat, CHECKPOINT_NAME_ARG, CHUNK_SIZE_ARG,
+    DEFAULT_CHUNK_SIZE, DEFAULT_INCLUDE_NODE_TYPES, EXCLUDE_OUTPUT_NODE_TYPE_ARG,
+    EXCLUDE_SAMPLE_NODE_TYPE_ARG, INCLUDE_OUTPUT_NODE_TYPE_ARG, INCLUDE_SAMPLE_NODE_TYPE_ARG,
+    LIMIT_DATA_FETCH_ARG, OUTPUT_FORMAT_ARG, PROGRESS_INTERVAL_ARG, PROGRESS_SAMPLE_DURATION_S,
+    PROGRESS_SAMPLE_RATE, PROGRESS_SAMPLE_RATE_ARG, RESUME_ARG, SAMPLE_OFFSET_ARG, SAMPLE_RATE_ARG,
+    SCRUB,
 };
 use crate::sizing::SizingSample;
-use crate::tail::{walk_exact_tail, RepoWalkRun};
+use crate::tail::{walk_exact_tail, ChunkingParams, RepoWalkRun};
 use crate::validate::TOTAL;
 use crate::walk::EmptyRoute;
 
@@ -345,6 +347,22 @@ pub async fn scrub_objects<'a>(
 
     let repo_stats_key = args::get_repo_name(config_store, &matches)?;
 
+    let chunking = match sub_m.value_of(CHECKPOINT_NAME_ARG) {
+        Some(checkpoint_name) => {
+            let chunk_size = args::get_u64(&sub_m, CHUNK_SIZE_ARG, DEFAULT_CHUNK_SIZE);
+            if chunk_size == 0 {
+                return Err(format_err!("--{} must be positive", CHUNK_SIZE_ARG));
+            }
+            Some(ChunkingParams {
+                checkpoints: args::open_sql::<SqlCheckpoints>(fb, config_store, matches).await?,
+                checkpoint_name: checkpoint_name.to_string(),
+                chunk_size,
+                resume: sub_m.is_present(RESUME_ARG),
+            })
+        }
+        None => None,
+    };
+
     let sample_rate = args::get_u64_opt(&sub_m, SAMPLE_RATE_ARG).unwrap_or(1);
     let sample_offset = args::get_u64_opt(&sub_m, SAMPLE_OFFSET_ARG).unwrap_or(0);
     let progress_interval_secs = args::get_u64_opt(&sub_m, PROGRESS_INTERVAL_ARG);
@@ -443,6 +461,7 @@ pub async fn scrub_objects<'a>(
         walk_state,
         make_sink,
         false,
+        chunking,
     )
     .await
 }
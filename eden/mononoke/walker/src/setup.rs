@@ -6,7 +6,7 @@
  */
 
 use crate::blobstore;
-use crate::graph::{EdgeType, Node, NodeType};
+use crate::graph::{EdgeType, Node, NodeType, UnitKey};
 use crate::parse_node::parse_node;
 use crate::progress::{
     sort_by_string, ProgressStateCountByType, ProgressStateMutex, ProgressSummary,
@@ -28,7 +28,7 @@ use futures::{
     future::{self, Future},
 };
 use itertools::{process_results, Itertools};
-use metaconfig_types::{Redaction, ScrubAction};
+use metaconfig_types::{BlobConfig, Redaction, ScrubAction};
 use once_cell::sync::Lazy;
 use samplingblob::SamplingHandler;
 use scuba_ext::MononokeScubaSampleBuilder;
@@ -47,6 +47,7 @@ use strum_macros::{AsRefStr, EnumString, EnumVariantNames};
 
 pub struct RepoWalkDatasources {
     pub blobrepo: BlobRepo,
+    pub blobconfig: BlobConfig,
     pub scuba_builder: MononokeScubaSampleBuilder,
 }
 
@@ -66,12 +67,14 @@ pub struct RepoWalkParams {
 
 pub const PROGRESS_SAMPLE_RATE: u64 = 1000;
 pub const PROGRESS_SAMPLE_DURATION_S: u64 = 5;
+pub const DEFAULT_CHUNK_SIZE: u64 = 100000;
 
 // Sub commands
 pub const SCRUB: &str = "scrub";
 pub const COMPRESSION_BENEFIT: &str = "compression-benefit";
 pub const VALIDATE: &str = "validate";
 pub const CORPUS: &str = "corpus";
+pub const GC: &str = "gc";
 
 // Subcommand args
 const QUIET_ARG: &str = "quiet";
@@ -86,7 +89,7 @@ const EXCLUDE_EDGE_TYPE_ARG: &str = "exclude-edge-type";
 const INCLUDE_EDGE_TYPE_ARG: &str = "include-edge-type";
 const BOOKMARK_ARG: &str = "bookmark";
 const WALK_ROOT_ARG: &str = "walk-root";
-const INNER_BLOBSTORE_ID_ARG: &str = "inner-blobstore-id";
+pub const INNER_BLOBSTORE_ID_ARG: &str = "inner-blobstore-id";
 const SCRUB_BLOBSTORE_ACTION_ARG: &str = "scrub-blobstore-action";
 const ENABLE_DERIVE_ARG: &str = "enable-derive";
 pub const PROGRESS_SAMPLE_RATE_ARG: &str = "progress-sample-rate";
@@ -104,6 +107,13 @@ pub const EXCLUDE_OUTPUT_NODE_TYPE_ARG: &str = "exclude-output-node-type";
 pub const INCLUDE_OUTPUT_NODE_TYPE_ARG: &str = "include-output-node-type";
 pub const OUTPUT_FORMAT_ARG: &str = "output-format";
 pub const OUTPUT_DIR_ARG: &str = "output-dir";
+pub const RETAIN_SCRATCH_PREFIX_ARG: &str = "retain-scratch-prefix";
+pub const RETAIN_KEY_PREFIX_ARG: &str = "retain-key-prefix";
+pub const ARCHIVE_PREFIX_ARG: &str = "archive-prefix";
+pub const UNREACHABLE_KEYS_OUTPUT_ARG: &str = "unreachable-keys-output";
+pub const CHECKPOINT_NAME_ARG: &str = "checkpoint-name";
+pub const RESUME_ARG: &str = "resume";
+pub const CHUNK_SIZE_ARG: &str = "chunk-size";
 const SCUBA_TABLE_ARG: &str = "scuba-table";
 const SCUBA_LOG_FILE_ARG: &str = "scuba-log-file";
 
@@ -189,6 +199,9 @@ const DEEP_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     EdgeType::ChangesetToFsnodeMapping,
     EdgeType::ChangesetToSkeletonManifestMapping,
     EdgeType::ChangesetToUnodeMapping,
+    EdgeType::ChangesetToBonsaiGitMapping,
+    EdgeType::ChangesetToBonsaiGlobalrevMapping,
+    EdgeType::ChangesetToGitTreeMapping,
     // Hg
     EdgeType::HgBonsaiMappingToChangeset,
     EdgeType::HgChangesetToHgParent,
@@ -223,6 +236,9 @@ const DEEP_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     EdgeType::FsnodeMappingToRootFsnode,
     EdgeType::FsnodeToChildFsnode,
     EdgeType::FsnodeToFileContent,
+    EdgeType::GitTreeMappingToRootGitTree,
+    EdgeType::GitTreeToGitBlob,
+    EdgeType::GitTreeToGitTreeChild,
     EdgeType::SkeletonManifestMappingToRootSkeletonManifest,
     EdgeType::SkeletonManifestToSkeletonManifestChild,
     EdgeType::UnodeFileToBlame,
@@ -252,6 +268,9 @@ const SHALLOW_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     EdgeType::ChangesetToFsnodeMapping,
     EdgeType::ChangesetToSkeletonManifestMapping,
     EdgeType::ChangesetToUnodeMapping,
+    EdgeType::ChangesetToBonsaiGitMapping,
+    EdgeType::ChangesetToBonsaiGlobalrevMapping,
+    EdgeType::ChangesetToGitTreeMapping,
     // Hg
     EdgeType::HgBonsaiMappingToChangeset,
     EdgeType::HgChangesetToHgManifest,
@@ -275,6 +294,9 @@ const SHALLOW_INCLUDE_EDGE_TYPES: &[EdgeType] = &[
     EdgeType::FsnodeToChildFsnode,
     EdgeType::FsnodeToFileContent,
     EdgeType::FsnodeMappingToRootFsnode,
+    EdgeType::GitTreeMappingToRootGitTree,
+    EdgeType::GitTreeToGitBlob,
+    EdgeType::GitTreeToGitTreeChild,
     EdgeType::SkeletonManifestMappingToRootSkeletonManifest,
     EdgeType::SkeletonManifestToSkeletonManifestChild,
     EdgeType::UnodeFileToBlame,
@@ -440,6 +462,28 @@ pub fn setup_toplevel_app<'a, 'b>(
                 .default_value(OutputFormat::PrettyDebug.as_ref())
                 .required(false)
                 .help("Set the output format"),
+        )
+        .arg(
+            Arg::with_name(CHECKPOINT_NAME_ARG)
+                .long(CHECKPOINT_NAME_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("Walk the public changesets in chunks instead of from the walk roots, saving progress to the named checkpoint after each chunk"),
+        )
+        .arg(
+            Arg::with_name(RESUME_ARG)
+                .long(RESUME_ARG)
+                .takes_value(false)
+                .required(false)
+                .requires(CHECKPOINT_NAME_ARG)
+                .help("Continue from the last saved checkpoint, if it is not complete"),
+        )
+        .arg(
+            Arg::with_name(CHUNK_SIZE_ARG)
+                .long(CHUNK_SIZE_ARG)
+                .takes_value(true)
+                .required(false)
+                .help("How many changesets table ids to walk per chunk when checkpointing, defaults to 100000"),
         );
 
     let compression_benefit = setup_subcommand_args(
@@ -491,6 +535,42 @@ pub fn setup_toplevel_app<'a, 'b>(
             .help("Check types to include, defaults to all possible values"),
     );
 
+    let gc = setup_subcommand_args(
+        SubCommand::with_name(GC).about("mark all blobs reachable from the repo's bookmarks, then sweep the blobstore for unreachable ones. Publishing bookmarks are always walked, and all node and edge types are always included."),
+    )
+    .arg(
+        Arg::with_name(RETAIN_SCRATCH_PREFIX_ARG)
+            .long(RETAIN_SCRATCH_PREFIX_ARG)
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .required(false)
+            .help("Also walk from scratch bookmarks with this prefix, so that what they point to is retained"),
+    )
+    .arg(
+        Arg::with_name(RETAIN_KEY_PREFIX_ARG)
+            .long(RETAIN_KEY_PREFIX_ARG)
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .required(false)
+            .help("Never sweep keys starting with this prefix (after the repo prefix). Keys outside the walker graph are always retained."),
+    )
+    .arg(
+        Arg::with_name(ARCHIVE_PREFIX_ARG)
+            .long(ARCHIVE_PREFIX_ARG)
+            .takes_value(true)
+            .required(false)
+            .help("Move unreachable keys under this prefix. Default is to do a dry run that only reports them."),
+    )
+    .arg(
+        Arg::with_name(UNREACHABLE_KEYS_OUTPUT_ARG)
+            .long(UNREACHABLE_KEYS_OUTPUT_ARG)
+            .takes_value(true)
+            .required(false)
+            .help("File to write unreachable keys to, one per line"),
+    );
+
     app_template.build()
         .about("Walks the mononoke commit and/or derived data graphs, with option of performing validations and modifications")
         .arg(
@@ -502,6 +582,7 @@ pub fn setup_toplevel_app<'a, 'b>(
         )
         .subcommand(compression_benefit)
         .subcommand(corpus)
+        .subcommand(gc)
         .subcommand(scrub_objects)
         .subcommand(validate)
 }
@@ -824,19 +905,40 @@ pub fn setup_common<'a>(
 
         let caching = matches.parse_and_init_cachelib(fb);
 
-        let include_edge_types = parse_edge_types(
-            sub_m,
-            INCLUDE_EDGE_TYPE_ARG,
-            EXCLUDE_EDGE_TYPE_ARG,
-            DEEP_INCLUDE_EDGE_TYPES,
-        )?;
-
-        let mut include_node_types = parse_node_types(
-            sub_m,
-            INCLUDE_NODE_TYPE_ARG,
-            EXCLUDE_NODE_TYPE_ARG,
-            DEFAULT_INCLUDE_NODE_TYPES,
-        )?;
+        let (mut include_edge_types, mut include_node_types) = if walk_stats_key == GC {
+            // Anything the walk does not step to would not be marked, so gc walks the whole graph
+            for arg in &[
+                INCLUDE_EDGE_TYPE_ARG,
+                EXCLUDE_EDGE_TYPE_ARG,
+                INCLUDE_NODE_TYPE_ARG,
+                EXCLUDE_NODE_TYPE_ARG,
+                ERROR_AS_DATA_NODE_TYPE_ARG,
+                ERROR_AS_DATA_EDGE_TYPE_ARG,
+            ] {
+                if sub_m.is_present(arg) {
+                    return Err(format_err!(
+                        "gc always walks all node and edge types, --{} is not supported",
+                        arg
+                    ));
+                }
+            }
+            (EdgeType::iter().collect(), NodeType::iter().collect())
+        } else {
+            (
+                parse_edge_types(
+                    sub_m,
+                    INCLUDE_EDGE_TYPE_ARG,
+                    EXCLUDE_EDGE_TYPE_ARG,
+                    DEEP_INCLUDE_EDGE_TYPES,
+                )?,
+                parse_node_types(
+                    sub_m,
+                    INCLUDE_NODE_TYPE_ARG,
+                    EXCLUDE_NODE_TYPE_ARG,
+                    DEFAULT_INCLUDE_NODE_TYPES,
+                )?,
+            )
+        };
 
         // Only walk derived node types that the repo is configured to contain
         include_node_types.retain(|t| {
@@ -878,7 +980,32 @@ pub fn setup_common<'a>(
             walk_roots.append(&mut roots);
         }
 
-        if walk_roots.is_empty() {
+        let mut root_node_types: HashSet<_> =
+            walk_roots.iter().map(|e| e.label.outgoing_type()).collect();
+
+        if walk_stats_key == GC {
+            // Anything reachable from a publishing bookmark must be kept
+            walk_roots.push(OutgoingEdge::new(
+                EdgeType::RootToPublishedBookmarks,
+                Node::PublishedBookmarks(UnitKey()),
+            ));
+            root_node_types.insert(NodeType::PublishedBookmarks);
+            // Retained scratch bookmarks are added as roots once the repo is open
+            if sub_m.is_present(RETAIN_SCRATCH_PREFIX_ARG) {
+                root_node_types.insert(NodeType::Bookmark);
+            }
+        }
+
+        // Chunked walks find their roots from the changesets table. Each chunk walks only its own
+        // changesets, so don't follow parents into history.
+        let chunked = sub_m.is_present(CHECKPOINT_NAME_ARG);
+        if chunked {
+            root_node_types.insert(NodeType::Changeset);
+            include_edge_types.remove(&EdgeType::ChangesetToBonsaiParent);
+            include_edge_types.remove(&EdgeType::HgChangesetToHgParent);
+        }
+
+        if walk_roots.is_empty() && !chunked {
             return Err(format_err!(
                 "No walk roots provided, pass with --{} or --{}",
                 BOOKMARK_ARG,
@@ -888,9 +1015,6 @@ pub fn setup_common<'a>(
 
         info!(logger, "Walking roots {:?} ", walk_roots);
 
-        let root_node_types: HashSet<_> =
-            walk_roots.iter().map(|e| e.label.outgoing_type()).collect();
-
         let (include_edge_types, include_node_types) =
             reachable_graph_elements(include_edge_types, include_node_types, root_node_types);
         info!(
@@ -971,7 +1095,7 @@ pub fn setup_common<'a>(
         let blobstore = blobstore::open_blobstore(
             fb,
             mysql_options,
-            storage_config.blobstore,
+            storage_config.blobstore.clone(),
             inner_blobstore_id,
             None,
             readonly_storage,
@@ -1032,6 +1156,7 @@ pub fn setup_common<'a>(
         Ok((
             RepoWalkDatasources {
                 blobrepo,
+                blobconfig: storage_config.blobstore,
                 scuba_builder,
             },
             RepoWalkParams {
@@ -379,6 +379,7 @@ pub async fn compression_benefit<'a>(
         walk_state,
         make_sink,
         true,
+        None,
     )
     .await
 }
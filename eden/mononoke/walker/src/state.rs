@@ -17,8 +17,8 @@ use dashmap::{mapref::one::Ref, DashMap};
 use futures::future::TryFutureExt;
 use mercurial_types::{HgChangesetId, HgFileNodeId, HgManifestId};
 use mononoke_types::{
-    ChangesetId, ContentId, DeletedManifestId, FastlogBatchId, FileUnodeId, FsnodeId, MPathHash,
-    ManifestUnodeId, SkeletonManifestId,
+    hash::GitSha1, ChangesetId, ContentId, DeletedManifestId, FastlogBatchId, FileUnodeId,
+    FsnodeId, MPathHash, ManifestUnodeId, SkeletonManifestId,
 };
 use phases::{Phase, Phases};
 use std::{
@@ -162,6 +162,8 @@ pub struct WalkState {
     visited_bcs_mapping: StateMap<InternedId<ChangesetId>>,
     public_not_visited: StateMap<InternedId<ChangesetId>>,
     visited_bcs_phase: StateMap<InternedId<ChangesetId>>,
+    visited_bcs_git_mapping: StateMap<InternedId<ChangesetId>>,
+    visited_bcs_globalrev_mapping: StateMap<InternedId<ChangesetId>>,
     visited_file: StateMap<ContentId>,
     visited_hg_cs: StateMap<InternedId<HgChangesetId>>,
     visited_hg_cs_mapping: StateMap<InternedId<HgChangesetId>>,
@@ -179,6 +181,8 @@ pub struct WalkState {
     visited_fastlog_file: StateMap<InternedId<FileUnodeId>>,
     visited_fsnode: StateMap<FsnodeId>,
     visited_fsnode_mapping: StateMap<InternedId<ChangesetId>>,
+    visited_git_tree: StateMap<GitSha1>,
+    visited_git_tree_mapping: StateMap<InternedId<ChangesetId>>,
     visited_skeleton_manifest: StateMap<SkeletonManifestId>,
     visited_skeleton_manifest_mapping: StateMap<InternedId<ChangesetId>>,
     visited_unode_file: StateMap<UnodeInterned<FileUnodeId>>,
@@ -215,6 +219,8 @@ impl WalkState {
             visited_bcs_mapping: StateMap::with_hasher(fac.clone()),
             public_not_visited: StateMap::with_hasher(fac.clone()),
             visited_bcs_phase: StateMap::with_hasher(fac.clone()),
+            visited_bcs_git_mapping: StateMap::with_hasher(fac.clone()),
+            visited_bcs_globalrev_mapping: StateMap::with_hasher(fac.clone()),
             visited_file: StateMap::with_hasher(fac.clone()),
             visited_hg_cs: StateMap::with_hasher(fac.clone()),
             visited_hg_cs_mapping: StateMap::with_hasher(fac.clone()),
@@ -232,6 +238,8 @@ impl WalkState {
             visited_fastlog_file: StateMap::with_hasher(fac.clone()),
             visited_fsnode: StateMap::with_hasher(fac.clone()),
             visited_fsnode_mapping: StateMap::with_hasher(fac.clone()),
+            visited_git_tree: StateMap::with_hasher(fac.clone()),
+            visited_git_tree_mapping: StateMap::with_hasher(fac.clone()),
             visited_skeleton_manifest: StateMap::with_hasher(fac.clone()),
             visited_skeleton_manifest_mapping: StateMap::with_hasher(fac.clone()),
             visited_unode_file: StateMap::with_hasher(fac.clone()),
@@ -282,6 +290,21 @@ impl WalkState {
                 // Save some memory, no need to keep an entry in public_not_visited now its in visited_bcs_phase
                 self.public_not_visited.remove(&id);
             }
+            (Node::BonsaiGitMapping(bcs_id), Some(NodeData::BonsaiGitMapping(Some(_)))) => {
+                self.record(
+                    &self.visited_bcs_git_mapping,
+                    &self.bcs_ids.interned(bcs_id),
+                );
+            }
+            (
+                Node::BonsaiGlobalrevMapping(bcs_id),
+                Some(NodeData::BonsaiGlobalrevMapping(Some(_))),
+            ) => {
+                self.record(
+                    &self.visited_bcs_globalrev_mapping,
+                    &self.bcs_ids.interned(bcs_id),
+                );
+            }
             // Hg
             (Node::BonsaiHgMapping(bcs_id), Some(NodeData::BonsaiHgMapping(Some(_)))) => {
                 self.record(&self.visited_bcs_mapping, &self.bcs_ids.interned(bcs_id));
@@ -305,6 +328,12 @@ impl WalkState {
             (Node::FsnodeMapping(bcs_id), Some(NodeData::FsnodeMapping(Some(_)))) => {
                 self.record(&self.visited_fsnode_mapping, &self.bcs_ids.interned(bcs_id));
             }
+            (Node::GitTreeMapping(bcs_id), Some(NodeData::GitTreeMapping(Some(_)))) => {
+                self.record(
+                    &self.visited_git_tree_mapping,
+                    &self.bcs_ids.interned(bcs_id),
+                );
+            }
             (
                 Node::SkeletonManifestMapping(bcs_id),
                 Some(NodeData::SkeletonManifestMapping(Some(_))),
@@ -403,6 +432,20 @@ impl VisitOne for WalkState {
                     true
                 }
             }
+            Node::BonsaiGitMapping(bcs_id) => {
+                if let Some(id) = self.bcs_ids.get(bcs_id) {
+                    !self.visited_bcs_git_mapping.contains_key(&id) // Does not insert, see record_resolved_visit
+                } else {
+                    true
+                }
+            }
+            Node::BonsaiGlobalrevMapping(bcs_id) => {
+                if let Some(id) = self.bcs_ids.get(bcs_id) {
+                    !self.visited_bcs_globalrev_mapping.contains_key(&id) // Does not insert, see record_resolved_visit
+                } else {
+                    true
+                }
+            }
             // Hg
             Node::HgBonsaiMapping(hg_cs_id) => self.record(
                 &self.visited_hg_cs_mapping,
@@ -467,6 +510,14 @@ impl VisitOne for WalkState {
                     true
                 }
             }
+            Node::GitTree(sha1) => self.record(&self.visited_git_tree, &sha1),
+            Node::GitTreeMapping(bcs_id) => {
+                if let Some(id) = self.bcs_ids.get(bcs_id) {
+                    !self.visited_git_tree_mapping.contains_key(&id) // Does not insert, see record_resolved_visit
+                } else {
+                    true
+                }
+            }
             Node::SkeletonManifest(id) => self.record(&self.visited_skeleton_manifest, &id),
             Node::SkeletonManifestMapping(bcs_id) => {
                 if let Some(id) = self.bcs_ids.get(bcs_id) {
@@ -5,18 +5,33 @@
  * GNU General Public License version 2.
  */
 
-use crate::graph::{EdgeType, NodeType};
+use crate::checkpoint::{Checkpoint, SqlCheckpoints};
+use crate::graph::{EdgeType, Node, NodeType};
+use crate::progress::{ProgressStateCountByType, ProgressStateMutex, ProgressSummary};
 use crate::setup::{RepoWalkDatasources, RepoWalkParams};
-use crate::walk::{walk_exact, StepRoute, WalkVisitor};
+use crate::state::StepStats;
+use crate::walk::{walk_exact, OutgoingEdge, StepRoute, WalkVisitor};
 
 use anyhow::Error;
+use blobrepo::BlobRepo;
+use bookmarks::{BookmarkKind, BookmarkPagination, BookmarkPrefix, Freshness};
+use bulkops::fetch_public_changesets_in_range;
+use changesets::Changesets;
 use cloned::cloned;
 use context::CoreContext;
 use fbinit::FacebookInit;
-use futures::{future::Future, stream::BoxStream};
+use futures::{
+    compat::Future01CompatExt,
+    future::Future,
+    stream::{BoxStream, TryStreamExt},
+};
+use phases::Phases;
 use scuba_ext::MononokeScubaSampleBuilder;
-use slog::Logger;
-use std::{collections::HashSet, iter::FromIterator};
+use slog::{info, Logger};
+use std::{
+    collections::{HashMap, HashSet},
+    iter::FromIterator,
+};
 use tokio::time::{Duration, Instant};
 
 #[derive(Clone)]
@@ -25,6 +40,17 @@ pub struct RepoWalkRun {
     pub scuba_builder: MononokeScubaSampleBuilder,
 }
 
+// Walk the public changesets in chunks of changesets table ids rather than from the walk roots,
+// saving a checkpoint after each chunk.
+#[derive(Clone)]
+pub struct ChunkingParams {
+    pub checkpoints: SqlCheckpoints,
+    pub checkpoint_name: String,
+    pub chunk_size: u64,
+    // Continue from the last checkpoint rather than starting again
+    pub resume: bool,
+}
+
 pub async fn walk_exact_tail<RunFac, SinkFac, SinkOut, V, VOut, Route>(
     fb: FacebookInit,
     logger: Logger,
@@ -35,6 +61,7 @@ pub async fn walk_exact_tail<RunFac, SinkFac, SinkOut, V, VOut, Route>(
     visitor: V,
     make_run: RunFac,
     keep_edge_paths: bool,
+    chunking: Option<ChunkingParams>,
 ) -> Result<(), Error>
 where
     RunFac: 'static + Clone + Send + Sync + FnOnce(RepoWalkRun) -> SinkFac,
@@ -54,35 +81,92 @@ where
     };
     let required_node_data_types =
         HashSet::from_iter(required_node_data_types.into_iter().cloned());
+    let mut is_first_run = true;
 
     loop {
-        cloned!(make_run, repo, mut scuba_builder, visitor,);
-
         let ctx = CoreContext::new_with_logger(fb, logger.clone());
-        scuba_builder.add("session", ctx.session().metadata().session_id().to_string());
-        let walk_run = RepoWalkRun {
-            ctx: ctx.clone(),
-            scuba_builder: scuba_builder.clone(),
+
+        let walk_one = |walk_roots: Vec<OutgoingEdge>| {
+            cloned!(ctx, make_run, repo, mut scuba_builder, visitor);
+            scuba_builder.add("session", ctx.session().metadata().session_id().to_string());
+            let walk_run = RepoWalkRun {
+                ctx: ctx.clone(),
+                scuba_builder: scuba_builder.clone(),
+            };
+
+            let walk_output = walk_exact(
+                ctx,
+                repo,
+                walk_params.enable_derive,
+                walk_roots,
+                visitor,
+                walk_params.scheduled_max,
+                walk_params.error_as_data_node_types.clone(),
+                walk_params.error_as_data_edge_types.clone(),
+                walk_params.include_edge_types.clone(),
+                always_emit_edge_types.clone(),
+                required_node_data_types.clone(),
+                scuba_builder,
+                keep_edge_paths,
+            );
+
+            let make_sink = make_run(walk_run);
+            make_sink(walk_output)
         };
 
-        let walk_output = walk_exact(
-            ctx,
-            repo,
-            walk_params.enable_derive,
-            walk_params.walk_roots.clone(),
-            visitor,
-            walk_params.scheduled_max,
-            walk_params.error_as_data_node_types.clone(),
-            walk_params.error_as_data_edge_types.clone(),
-            walk_params.include_edge_types.clone(),
-            always_emit_edge_types.clone(),
-            required_node_data_types.clone(),
-            scuba_builder,
-            keep_edge_paths,
-        );
-
-        let make_sink = make_run(walk_run);
-        make_sink(walk_output).await?;
+        match &chunking {
+            None => walk_one(walk_params.walk_roots.clone()).await?,
+            Some(chunking) => {
+                let resume = chunking.resume || !is_first_run;
+                let mut checkpoint =
+                    start_checkpoint(&ctx, &logger, &repo, chunking, resume).await?;
+                let walked_before = checkpoint.walked_by_type.clone();
+                let progress_before = walk_params.progress_state.walked_by_type();
+                let chunks = checkpoint.remaining_chunks(chunking.chunk_size);
+                for (lower_bound, upper_bound) in chunks {
+                    let mut walk_roots = chunk_roots(&ctx, &repo, lower_bound, upper_bound).await?;
+                    if upper_bound == checkpoint.upper_bound {
+                        // Bookmarked changesets may not be marked public in the changesets
+                        // range yet, so make sure the snapshot heads are covered.
+                        walk_roots.extend(checkpoint.bookmarks.iter().map(|(_name, cs_id)| {
+                            OutgoingEdge::new(EdgeType::RootToChangeset, Node::Changeset(*cs_id))
+                        }));
+                    }
+                    info!(
+                        logger,
+                        "Walking chunk [{}, {}) of checkpoint {} with {} roots",
+                        lower_bound,
+                        upper_bound,
+                        chunking.checkpoint_name,
+                        walk_roots.len()
+                    );
+                    walk_one(walk_roots).await?;
+
+                    checkpoint.completed_bound = upper_bound;
+                    checkpoint.walked_by_type = walked_since(
+                        &walked_before,
+                        &progress_before,
+                        &walk_params.progress_state,
+                    );
+                    chunking
+                        .checkpoints
+                        .update(
+                            repo.get_repoid(),
+                            &chunking.checkpoint_name,
+                            checkpoint.completed_bound,
+                            &checkpoint.walked_by_type,
+                        )
+                        .await?;
+                }
+                info!(
+                    logger,
+                    "Completed checkpoint {}, started at {}",
+                    chunking.checkpoint_name,
+                    checkpoint.create_timestamp.timestamp_seconds()
+                );
+            }
+        }
+        is_first_run = false;
 
         match tail_secs {
             Some(interval) => {
@@ -94,3 +178,120 @@ where
         }
     }
 }
+
+// Load the checkpoint to resume from, or snapshot the bookmarks and changesets range and save
+// them as a new checkpoint.
+async fn start_checkpoint(
+    ctx: &CoreContext,
+    logger: &Logger,
+    repo: &BlobRepo,
+    chunking: &ChunkingParams,
+    resume: bool,
+) -> Result<Checkpoint, Error> {
+    let repo_id = repo.get_repoid();
+    if resume {
+        match chunking
+            .checkpoints
+            .load(repo_id, &chunking.checkpoint_name)
+            .await?
+        {
+            Some(checkpoint) if !checkpoint.is_complete() => {
+                info!(
+                    logger,
+                    "Resuming checkpoint {} at {} of [{}, {}), started at {}",
+                    chunking.checkpoint_name,
+                    checkpoint.completed_bound,
+                    checkpoint.lower_bound,
+                    checkpoint.upper_bound,
+                    checkpoint.create_timestamp.timestamp_seconds()
+                );
+                return Ok(checkpoint);
+            }
+            Some(_) => info!(
+                logger,
+                "Checkpoint {} is complete, starting again", chunking.checkpoint_name
+            ),
+            None => info!(
+                logger,
+                "No checkpoint {} to resume, starting from scratch", chunking.checkpoint_name
+            ),
+        }
+    }
+
+    let bookmarks = repo
+        .bookmarks()
+        .list(
+            ctx.clone(),
+            Freshness::MostRecent,
+            &BookmarkPrefix::empty(),
+            BookmarkKind::ALL_PUBLISHING,
+            &BookmarkPagination::FromStart,
+            std::u64::MAX,
+        )
+        .map_ok(|(bookmark, cs_id)| (bookmark.name, cs_id))
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let (lower_bound, upper_bound) = match repo
+        .get_changesets_object()
+        .get_sql_changesets()
+        .get_changesets_ids_bounds(repo_id)
+        .compat()
+        .await?
+    {
+        (Some(start), Some(stop)) => (start, stop + 1),
+        _ => (0, 0),
+    };
+
+    let checkpoint = Checkpoint::new(lower_bound, upper_bound, bookmarks);
+    chunking
+        .checkpoints
+        .insert(repo_id, &chunking.checkpoint_name, &checkpoint)
+        .await?;
+    info!(
+        logger,
+        "Started checkpoint {} for [{}, {}) with {} bookmarks",
+        chunking.checkpoint_name,
+        lower_bound,
+        upper_bound,
+        checkpoint.bookmarks.len()
+    );
+    Ok(checkpoint)
+}
+
+async fn chunk_roots(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    lower_bound: u64,
+    upper_bound: u64,
+) -> Result<Vec<OutgoingEdge>, Error> {
+    let changesets = repo.get_changesets_object();
+    let phases = repo.get_phases();
+    let entries = fetch_public_changesets_in_range(
+        ctx,
+        repo.get_repoid(),
+        changesets.get_sql_changesets(),
+        phases.get_sql_phases(),
+        lower_bound,
+        upper_bound,
+    )
+    .await?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| OutgoingEdge::new(EdgeType::RootToChangeset, Node::Changeset(entry.cs_id)))
+        .collect())
+}
+
+// Counts in the checkpoint are what it was loaded with, plus what this process has walked since.
+fn walked_since(
+    walked_before: &HashMap<NodeType, u64>,
+    progress_before: &HashMap<NodeType, u64>,
+    progress_state: &ProgressStateMutex<ProgressStateCountByType<StepStats, ProgressSummary>>,
+) -> HashMap<NodeType, u64> {
+    let mut walked = walked_before.clone();
+    for (node_type, count) in progress_state.walked_by_type() {
+        let before = progress_before.get(&node_type).cloned().unwrap_or(0);
+        *walked.entry(node_type).or_insert(0) += count.saturating_sub(before);
+    }
+    walked
+}
@@ -11,6 +11,8 @@
 //  2. Add CheckType::node_type() and CheckType::enum_type() cases for the new variant
 //  3. Add a new validation method
 //  4. Add the method to the match/case in ValidatingVisitor::visit()
+// Checks that need to load more data return a future from their validation method, which is
+// resolved in the sink before the result is recorded.
 
 use crate::graph::{EdgeType, Node, NodeData, NodeType};
 use crate::progress::{
@@ -27,15 +29,27 @@ use crate::walk::{EmptyRoute, OutgoingEdge, StepRoute, VisitOne, WalkVisitor};
 
 use anyhow::Error;
 use async_trait::async_trait;
+use blobrepo::BlobRepo;
+use blobrepo_hg::BlobRepoHg;
 use clap::ArgMatches;
 use cloned::cloned;
 use cmdlib::args::{self, MononokeMatches};
 use context::CoreContext;
 use derive_more::AddAssign;
 use fbinit::FacebookInit;
-use futures::{future::TryFutureExt, stream::TryStreamExt};
+use filestore::{self, FetchKey};
+use futures::{
+    compat::Future01CompatExt,
+    future::{self, BoxFuture, FutureExt, TryFutureExt},
+    stream::{self, StreamExt, TryStreamExt},
+};
+use git_types::{Tree, TreeBuilder, Treeish};
 use itertools::Itertools;
-use mononoke_types::{ChangesetId, MPath};
+use mononoke_types::{
+    fsnode::{Fsnode, FsnodeEntry},
+    skeleton_manifest::{SkeletonManifest, SkeletonManifestEntry},
+    ChangesetId, ContentId, MPath,
+};
 use phases::{Phase, Phases};
 use scuba_ext::MononokeScubaSampleBuilder;
 use slog::{info, warn, Logger};
@@ -71,6 +85,9 @@ const VIA_NODE_KEY: &'static str = "via_node_key";
 const VIA_NODE_TYPE: &'static str = "via_node_type";
 const VIA_NODE_PATH: &'static str = "via_node_path";
 
+// How many file changes of a single changeset to look up metadata for at once
+const FILE_CHANGE_CONCURRENCY: usize = 100;
+
 define_stats! {
     prefix = "mononoke.walker.validate";
     // e.g. mononoke.walker.validate.testrepo.hg_link_node_populated.pass
@@ -110,6 +127,14 @@ define_type_enum! {
 enum CheckType {
     ChangesetPhaseIsPublic,
     HgLinkNodePopulated,
+    FileContentMatchesMetadata,
+    FsnodeSummaryMatches,
+    SkeletonManifestSummaryMatches,
+    BonsaiHgMappingRoundTrips,
+    FileChangeSizeMatchesMetadata,
+    BonsaiGitMappingRoundTrips,
+    BonsaiGlobalrevMappingRoundTrips,
+    GitTreeHashMatches,
 }
 }
 
@@ -118,12 +143,28 @@ impl CheckType {
         match self {
             CheckType::ChangesetPhaseIsPublic => "bonsai_phase_is_public",
             CheckType::HgLinkNodePopulated => "hg_link_node_populated",
+            CheckType::FileContentMatchesMetadata => "file_content_matches_metadata",
+            CheckType::FsnodeSummaryMatches => "fsnode_summary_matches",
+            CheckType::SkeletonManifestSummaryMatches => "skeleton_manifest_summary_matches",
+            CheckType::BonsaiHgMappingRoundTrips => "bonsai_hg_mapping_round_trips",
+            CheckType::FileChangeSizeMatchesMetadata => "file_change_size_matches_metadata",
+            CheckType::BonsaiGitMappingRoundTrips => "bonsai_git_mapping_round_trips",
+            CheckType::BonsaiGlobalrevMappingRoundTrips => "bonsai_globalrev_mapping_round_trips",
+            CheckType::GitTreeHashMatches => "git_tree_hash_matches",
         }
     }
     pub fn node_type(&self) -> NodeType {
         match self {
             CheckType::ChangesetPhaseIsPublic => NodeType::PhaseMapping,
             CheckType::HgLinkNodePopulated => NodeType::HgFileNode,
+            CheckType::FileContentMatchesMetadata => NodeType::FileContentMetadata,
+            CheckType::FsnodeSummaryMatches => NodeType::Fsnode,
+            CheckType::SkeletonManifestSummaryMatches => NodeType::SkeletonManifest,
+            CheckType::BonsaiHgMappingRoundTrips => NodeType::BonsaiHgMapping,
+            CheckType::FileChangeSizeMatchesMetadata => NodeType::Changeset,
+            CheckType::BonsaiGitMappingRoundTrips => NodeType::BonsaiGitMapping,
+            CheckType::BonsaiGlobalrevMappingRoundTrips => NodeType::BonsaiGlobalrevMapping,
+            CheckType::GitTreeHashMatches => NodeType::GitTree,
         }
     }
 }
@@ -148,6 +189,7 @@ impl CheckOutput {
 
 struct ValidatingVisitor {
     repo_stats_key: String,
+    repo: BlobRepo,
     inner: WalkState,
     checks_by_node_type: HashMap<NodeType, HashSet<CheckType>>,
 }
@@ -155,6 +197,7 @@ struct ValidatingVisitor {
 impl ValidatingVisitor {
     pub fn new(
         repo_stats_key: String,
+        repo: BlobRepo,
         include_node_types: HashSet<NodeType>,
         include_edge_types: HashSet<EdgeType>,
         include_checks: HashSet<CheckType>,
@@ -163,6 +206,7 @@ impl ValidatingVisitor {
     ) -> Self {
         Self {
             repo_stats_key,
+            repo,
             inner: WalkState::new(
                 include_node_types,
                 include_edge_types,
@@ -249,6 +293,301 @@ fn check_linknode_populated(
     }
 }
 
+// Failure info for checks where the most interesting thing on the route is the changeset we came
+// from.
+fn failure_via_changeset(route: Option<&ValidateRoute>) -> FailureInfo {
+    let via = route.and_then(|r| r.via.last().cloned());
+    FailureInfo::new(route.map(|r| r.src_node.clone()), via)
+}
+
+fn check_fsnode_summary(node_data: Option<&NodeData>, failure: FailureInfo) -> CheckStatus {
+    let fsnode: &Fsnode = match node_data {
+        Some(NodeData::Fsnode(fsnode)) => fsnode,
+        _ => return CheckStatus::Fail(failure),
+    };
+    let summary = fsnode.summary();
+    let mut child_files_count = 0;
+    let mut child_files_total_size = 0;
+    let mut child_dirs_count = 0;
+    let mut descendant_files_count = 0;
+    let mut descendant_files_total_size = 0;
+    for (_elem, entry) in fsnode.list() {
+        match entry {
+            FsnodeEntry::File(file) => {
+                child_files_count += 1;
+                child_files_total_size += file.size();
+                descendant_files_count += 1;
+                descendant_files_total_size += file.size();
+            }
+            FsnodeEntry::Directory(dir) => {
+                let sub = dir.summary();
+                child_dirs_count += 1;
+                descendant_files_count += sub.descendant_files_count;
+                descendant_files_total_size += sub.descendant_files_total_size;
+            }
+        }
+    }
+    if summary.child_files_count == child_files_count
+        && summary.child_files_total_size == child_files_total_size
+        && summary.child_dirs_count == child_dirs_count
+        && summary.descendant_files_count == descendant_files_count
+        && summary.descendant_files_total_size == descendant_files_total_size
+    {
+        CheckStatus::Pass
+    } else {
+        CheckStatus::Fail(failure)
+    }
+}
+
+fn check_skeleton_manifest_summary(
+    node_data: Option<&NodeData>,
+    failure: FailureInfo,
+) -> CheckStatus {
+    let manifest: &SkeletonManifest = match node_data {
+        Some(NodeData::SkeletonManifest(Some(manifest))) => manifest,
+        // Not derived yet, so nothing to check
+        Some(NodeData::SkeletonManifest(None)) => return CheckStatus::Pass,
+        _ => return CheckStatus::Fail(failure),
+    };
+    let summary = manifest.summary();
+    let mut child_files_count = 0;
+    let mut child_dirs_count = 0;
+    let mut descendant_files_count = 0;
+    let mut descendant_dirs_count = 0;
+    let mut max_path_len = 0;
+    let mut descendant_case_conflicts = false;
+    let mut descendant_non_utf8_filenames = false;
+    let mut descendant_invalid_windows_filenames = false;
+    for (elem, entry) in manifest.list() {
+        let elem_len = elem.len() as u32;
+        match entry {
+            SkeletonManifestEntry::File => {
+                child_files_count += 1;
+                descendant_files_count += 1;
+                max_path_len = max_path_len.max(elem_len);
+            }
+            SkeletonManifestEntry::Directory(dir) => {
+                let sub = dir.summary();
+                child_dirs_count += 1;
+                descendant_files_count += sub.descendant_files_count;
+                descendant_dirs_count += sub.descendant_dirs_count + 1;
+                max_path_len = max_path_len.max(elem_len + 1 + sub.max_path_len);
+                descendant_case_conflicts |=
+                    sub.child_case_conflicts | sub.descendant_case_conflicts;
+                descendant_non_utf8_filenames |=
+                    sub.child_non_utf8_filenames | sub.descendant_non_utf8_filenames;
+                descendant_invalid_windows_filenames |=
+                    sub.child_invalid_windows_filenames | sub.descendant_invalid_windows_filenames;
+            }
+        }
+    }
+    if summary.child_files_count == child_files_count
+        && summary.child_dirs_count == child_dirs_count
+        && summary.descendant_files_count == descendant_files_count
+        && summary.descendant_dirs_count == descendant_dirs_count
+        && summary.max_path_len == max_path_len
+        && summary.descendant_case_conflicts == descendant_case_conflicts
+        && summary.descendant_non_utf8_filenames == descendant_non_utf8_filenames
+        && summary.descendant_invalid_windows_filenames == descendant_invalid_windows_filenames
+    {
+        CheckStatus::Pass
+    } else {
+        CheckStatus::Fail(failure)
+    }
+}
+
+// Recompute the metadata from the stored content, and compare with both the content id we
+// looked it up by and the stored metadata.
+fn check_file_content_matches_metadata(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    node: &Node,
+    node_data: Option<&NodeData>,
+    failure: FailureInfo,
+) -> BoxFuture<'static, Result<CheckStatus, Error>> {
+    let (content_id, stored): (ContentId, _) = match (node, node_data) {
+        (Node::FileContentMetadata(content_id), Some(NodeData::FileContentMetadata(stored))) => {
+            (*content_id, stored.clone())
+        }
+        _ => return future::ok(CheckStatus::Fail(failure)).boxed(),
+    };
+    cloned!(ctx);
+    let blobstore = repo.get_blobstore();
+    async move {
+        let (content, size) =
+            match filestore::fetch_with_size(blobstore, ctx, &FetchKey::Canonical(content_id))
+                .await?
+            {
+                Some(fetched) => fetched,
+                None => return Ok(CheckStatus::Fail(failure)),
+            };
+        let computed = filestore::compute_metadata(size, content).await?;
+        let matches = computed.content_id == content_id
+            && computed.total_size == size
+            && stored.map_or(true, |stored| stored == computed);
+        Ok(if matches {
+            CheckStatus::Pass
+        } else {
+            CheckStatus::Fail(failure)
+        })
+    }
+    .boxed()
+}
+
+// The hg changeset a bonsai maps to should map back to the same bonsai
+fn check_bonsai_hg_mapping_round_trips(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    node: &Node,
+    node_data: Option<&NodeData>,
+    failure: FailureInfo,
+) -> BoxFuture<'static, Result<CheckStatus, Error>> {
+    let (bcs_id, hg_cs_id) = match (node, node_data) {
+        (Node::BonsaiHgMapping(bcs_id), Some(NodeData::BonsaiHgMapping(Some(hg_cs_id)))) => {
+            (*bcs_id, *hg_cs_id)
+        }
+        // Not derived yet, so nothing to check
+        (Node::BonsaiHgMapping(_), Some(NodeData::BonsaiHgMapping(None))) => {
+            return future::ok(CheckStatus::Pass).boxed();
+        }
+        _ => return future::ok(CheckStatus::Fail(failure)).boxed(),
+    };
+    cloned!(ctx, repo);
+    async move {
+        let maybe_bcs_id = repo.get_bonsai_from_hg(ctx, hg_cs_id).compat().await?;
+        Ok(if maybe_bcs_id == Some(bcs_id) {
+            CheckStatus::Pass
+        } else {
+            CheckStatus::Fail(failure)
+        })
+    }
+    .boxed()
+}
+
+// The git commit a bonsai maps to should map back to the same bonsai
+fn check_bonsai_git_mapping_round_trips(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    node: &Node,
+    node_data: Option<&NodeData>,
+    failure: FailureInfo,
+) -> BoxFuture<'static, Result<CheckStatus, Error>> {
+    let (bcs_id, git_sha1) = match (node, node_data) {
+        (Node::BonsaiGitMapping(bcs_id), Some(NodeData::BonsaiGitMapping(Some(git_sha1)))) => {
+            (*bcs_id, *git_sha1)
+        }
+        // Not a git commit, so nothing to check
+        (Node::BonsaiGitMapping(_), Some(NodeData::BonsaiGitMapping(None))) => {
+            return future::ok(CheckStatus::Pass).boxed();
+        }
+        _ => return future::ok(CheckStatus::Fail(failure)).boxed(),
+    };
+    cloned!(ctx, repo);
+    async move {
+        let maybe_bcs_id = repo
+            .bonsai_git_mapping()
+            .get_bonsai_from_git_sha1(&ctx, git_sha1)
+            .await?;
+        Ok(if maybe_bcs_id == Some(bcs_id) {
+            CheckStatus::Pass
+        } else {
+            CheckStatus::Fail(failure)
+        })
+    }
+    .boxed()
+}
+
+// The globalrev a bonsai maps to should map back to the same bonsai
+fn check_bonsai_globalrev_mapping_round_trips(
+    repo: &BlobRepo,
+    node: &Node,
+    node_data: Option<&NodeData>,
+    failure: FailureInfo,
+) -> BoxFuture<'static, Result<CheckStatus, Error>> {
+    let (bcs_id, globalrev) = match (node, node_data) {
+        (
+            Node::BonsaiGlobalrevMapping(bcs_id),
+            Some(NodeData::BonsaiGlobalrevMapping(Some(globalrev))),
+        ) => (*bcs_id, *globalrev),
+        // No globalrev assigned, so nothing to check
+        (Node::BonsaiGlobalrevMapping(_), Some(NodeData::BonsaiGlobalrevMapping(None))) => {
+            return future::ok(CheckStatus::Pass).boxed();
+        }
+        _ => return future::ok(CheckStatus::Fail(failure)).boxed(),
+    };
+    cloned!(repo);
+    async move {
+        let maybe_bcs_id = repo.get_bonsai_from_globalrev(globalrev).await?;
+        Ok(if maybe_bcs_id == Some(bcs_id) {
+            CheckStatus::Pass
+        } else {
+            CheckStatus::Fail(failure)
+        })
+    }
+    .boxed()
+}
+
+// Reserializing the tree's members should give back the id and size it is stored with
+fn check_git_tree_hash(node_data: Option<&NodeData>, failure: FailureInfo) -> CheckStatus {
+    let tree: &Tree = match node_data {
+        Some(NodeData::GitTree(tree)) => tree,
+        _ => return CheckStatus::Fail(failure),
+    };
+    let rebuilt: Tree = TreeBuilder::new(tree.members().clone()).into();
+    if rebuilt.handle() == tree.handle() {
+        CheckStatus::Pass
+    } else {
+        CheckStatus::Fail(failure)
+    }
+}
+
+// The size recorded in each file change should be the size of the content it points to. File
+// changes whose content has no metadata yet are not checked.
+fn check_file_change_sizes(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    node_data: Option<&NodeData>,
+    failure: FailureInfo,
+) -> BoxFuture<'static, Result<CheckStatus, Error>> {
+    let sizes: Vec<(ContentId, u64)> = match node_data {
+        Some(NodeData::Changeset(bcs)) => bcs
+            .file_changes()
+            .filter_map(|(_path, fc)| fc.map(|fc| (fc.content_id(), fc.size())))
+            .collect(),
+        _ => return future::ok(CheckStatus::Fail(failure)).boxed(),
+    };
+    cloned!(ctx);
+    let blobstore = repo.get_blobstore();
+    async move {
+        let mismatches = stream::iter(sizes)
+            .map(|(content_id, size)| {
+                cloned!(ctx, blobstore);
+                async move {
+                    let metadata = filestore::get_metadata_readonly(
+                        &blobstore,
+                        &ctx,
+                        &FetchKey::Canonical(content_id),
+                    )
+                    .await?;
+                    Ok::<_, Error>(match metadata {
+                        Some(Some(metadata)) => metadata.total_size != size,
+                        _ => false,
+                    })
+                }
+            })
+            .buffer_unordered(FILE_CHANGE_CONCURRENCY)
+            .try_filter(|mismatch| future::ready(*mismatch))
+            .try_collect::<Vec<_>>()
+            .await?;
+        Ok(if mismatches.is_empty() {
+            CheckStatus::Pass
+        } else {
+            CheckStatus::Fail(failure)
+        })
+    }
+    .boxed()
+}
+
 #[derive(AddAssign, Clone, Copy, Default, Debug)]
 struct CheckStats {
     pass: u64,
@@ -258,9 +597,26 @@ struct CheckStats {
 
 struct CheckData {
     checked: Vec<CheckOutput>,
+    // Checks that need to load data, counted into checked and stats once resolved
+    pending: Vec<BoxFuture<'static, Result<CheckOutput, Error>>>,
     stats: CheckStats,
 }
 
+impl CheckData {
+    async fn resolve_pending(mut self) -> Result<Self, Error> {
+        for pending in std::mem::take(&mut self.pending) {
+            let output = pending.await?;
+            if output.status == CheckStatus::Pass {
+                self.stats.pass += 1;
+            } else {
+                self.stats.fail += 1;
+            }
+            self.checked.push(output);
+        }
+        Ok(self)
+    }
+}
+
 #[derive(Clone, Debug)]
 struct ValidateRoute {
     src_node: Node,
@@ -337,6 +693,7 @@ impl WalkVisitor<(Node, Option<CheckData>, Option<StepStats>), ValidateRoute>
         let mut num_edges: u64 = 1;
         let mut pass = 0;
         let mut fail = 0;
+        let mut pending = vec![];
         let checked: Vec<_> = checks_to_do
             .map(|set| {
                 set.iter().filter_map(|check| {
@@ -351,6 +708,76 @@ impl WalkVisitor<(Node, Option<CheckData>, Option<StepStats>), ValidateRoute>
                             num_edges += outgoing.len() as u64;
                             check_linknode_populated(&outgoing, route.as_ref())
                         }
+                        CheckType::FsnodeSummaryMatches => check_fsnode_summary(
+                            node_data.as_ref(),
+                            failure_via_changeset(route.as_ref()),
+                        ),
+                        CheckType::SkeletonManifestSummaryMatches => {
+                            check_skeleton_manifest_summary(
+                                node_data.as_ref(),
+                                failure_via_changeset(route.as_ref()),
+                            )
+                        }
+                        CheckType::GitTreeHashMatches => check_git_tree_hash(
+                            node_data.as_ref(),
+                            failure_via_changeset(route.as_ref()),
+                        ),
+                        CheckType::FileContentMatchesMetadata
+                        | CheckType::BonsaiHgMappingRoundTrips
+                        | CheckType::FileChangeSizeMatchesMetadata
+                        | CheckType::BonsaiGitMappingRoundTrips
+                        | CheckType::BonsaiGlobalrevMappingRoundTrips => {
+                            let failure = failure_via_changeset(route.as_ref());
+                            let fut = match check {
+                                CheckType::FileContentMatchesMetadata => {
+                                    check_file_content_matches_metadata(
+                                        ctx,
+                                        &self.repo,
+                                        &resolved.target,
+                                        node_data.as_ref(),
+                                        failure,
+                                    )
+                                }
+                                CheckType::BonsaiHgMappingRoundTrips => {
+                                    check_bonsai_hg_mapping_round_trips(
+                                        ctx,
+                                        &self.repo,
+                                        &resolved.target,
+                                        node_data.as_ref(),
+                                        failure,
+                                    )
+                                }
+                                CheckType::BonsaiGitMappingRoundTrips => {
+                                    check_bonsai_git_mapping_round_trips(
+                                        ctx,
+                                        &self.repo,
+                                        &resolved.target,
+                                        node_data.as_ref(),
+                                        failure,
+                                    )
+                                }
+                                CheckType::BonsaiGlobalrevMappingRoundTrips => {
+                                    check_bonsai_globalrev_mapping_round_trips(
+                                        &self.repo,
+                                        &resolved.target,
+                                        node_data.as_ref(),
+                                        failure,
+                                    )
+                                }
+                                _ => check_file_change_sizes(
+                                    ctx,
+                                    &self.repo,
+                                    node_data.as_ref(),
+                                    failure,
+                                ),
+                            };
+                            let check = *check;
+                            pending.push(
+                                fut.map_ok(move |status| CheckOutput::new(check, status))
+                                    .boxed(),
+                            );
+                            return None;
+                        }
                     };
                     if status == CheckStatus::Pass {
                         pass += 1;
@@ -380,11 +807,12 @@ impl WalkVisitor<(Node, Option<CheckData>, Option<StepStats>), ValidateRoute>
 
         let vout = (
             node.clone(),
-            if checked.is_empty() {
+            if checked.is_empty() && pending.is_empty() {
                 None
             } else {
                 Some(CheckData {
                     checked,
+                    pending,
                     stats: CheckStats {
                         pass,
                         fail,
@@ -656,17 +1084,26 @@ pub async fn validate<'a>(
         Duration::from_secs(PROGRESS_SAMPLE_DURATION_S),
     ));
 
-    cloned!(walk_params.progress_state, walk_params.quiet);
+    cloned!(
+        walk_params.progress_state,
+        walk_params.quiet,
+        walk_params.scheduled_max
+    );
     let make_sink = move |run: RepoWalkRun| {
         cloned!(run.ctx);
         validate_progress_state.set_sample_builder(run.scuba_builder);
         async move |walk_output| {
             cloned!(ctx, progress_state, validate_progress_state);
             let walk_progress = progress_stream(quiet, &progress_state.clone(), walk_output)
-                .map_ok(|(n, d, s)| {
-                    // swap stats and data round
-                    (n, s, d)
-                });
+                .map_ok(|(n, d, s)| async move {
+                    // swap stats and data round, resolving any checks that need to load data
+                    let d = match d {
+                        Some(d) => Some(d.resolve_pending().await?),
+                        None => None,
+                    };
+                    Ok::<_, Error>((n, s, d))
+                })
+                .try_buffer_unordered(scheduled_max);
 
             let validate_progress =
                 progress_stream(quiet, &validate_progress_state.clone(), walk_progress);
@@ -685,8 +1122,16 @@ pub async fn validate<'a>(
     let always_emit_edge_types =
         HashSet::from_iter(vec![EdgeType::HgFileNodeToLinkedHgChangeset].into_iter());
 
+    // Only load the data the included checks look at
+    let required_node_data_types: Vec<NodeType> = include_check_types
+        .iter()
+        .map(|t| t.node_type())
+        .unique()
+        .collect();
+
     let stateful_visitor = Arc::new(ValidatingVisitor::new(
         repo_stats_key,
+        datasources.blobrepo.clone(),
         include_node_types,
         include_edge_types,
         include_check_types,
@@ -699,11 +1144,12 @@ pub async fn validate<'a>(
         logger,
         datasources,
         walk_params,
-        &[NodeType::PhaseMapping],
+        &required_node_data_types,
         Some(always_emit_edge_types),
         stateful_visitor,
         make_sink,
         false,
+        None,
     )
     .await
 }
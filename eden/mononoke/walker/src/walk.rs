@@ -17,7 +17,7 @@ use auto_impl::auto_impl;
 use blame::BlameRoot;
 use blobrepo::BlobRepo;
 use blobrepo_hg::BlobRepoHg;
-use blobstore::Loadable;
+use blobstore::{Blobstore, Loadable, LoadableError};
 use bookmarks::{BookmarkKind, BookmarkName, BookmarkPagination, BookmarkPrefix, Freshness};
 use bounded_traversal::bounded_traversal_stream;
 use changeset_info::ChangesetInfo;
@@ -35,14 +35,16 @@ use futures::{
     stream::{BoxStream, StreamExt, TryStreamExt},
 };
 use futures_old::Future as Future01;
+use git_types::{tree_blobstore_key, Tree, TreeHandle, TreeMember, Treeish};
 use itertools::{Either, Itertools};
 use manifest::{Entry, Manifest};
 use mercurial_derived_data::MappedHgChangesetId;
 use mercurial_types::{FileBytes, HgChangesetId, HgFileNodeId, HgManifestId, RepoPath};
 use mononoke_types::{
-    blame::BlameMaybeRejected, fsnode::FsnodeEntry, skeleton_manifest::SkeletonManifestEntry,
-    unode::UnodeEntry, BlameId, ChangesetId, ContentId, DeletedManifestId, FastlogBatchId,
-    FileUnodeId, FsnodeId, MPath, ManifestUnodeId, SkeletonManifestId,
+    blame::BlameMaybeRejected, fsnode::FsnodeEntry, hash::GitSha1,
+    skeleton_manifest::SkeletonManifestEntry, unode::UnodeEntry, BlameId, ChangesetId, ContentId,
+    DeletedManifestId, FastlogBatchId, FileUnodeId, FsnodeId, MPath, ManifestUnodeId,
+    SkeletonManifestId,
 };
 use phases::{HeadsFetcher, Phase, Phases};
 use scuba_ext::MononokeScubaSampleBuilder;
@@ -50,6 +52,7 @@ use skeleton_manifest::RootSkeletonManifestId;
 use slog::warn;
 use std::{
     collections::{HashMap, HashSet},
+    convert::TryFrom,
     fmt::Debug,
     iter::{IntoIterator, Iterator},
     sync::Arc,
@@ -466,6 +469,19 @@ async fn bonsai_changeset_step<V: VisitOne>(
     checker.add_edge(&mut edges, EdgeType::ChangesetToPhaseMapping, || {
         Node::PhaseMapping(*bcs_id)
     });
+    // Git tree mapping is 1:1 but from there expands considerably
+    checker.add_edge(&mut edges, EdgeType::ChangesetToGitTreeMapping, || {
+        Node::GitTreeMapping(*bcs_id)
+    });
+    // Git and globalrev mappings are 1:[0|1] with no further steps
+    checker.add_edge(&mut edges, EdgeType::ChangesetToBonsaiGitMapping, || {
+        Node::BonsaiGitMapping(*bcs_id)
+    });
+    checker.add_edge(
+        &mut edges,
+        EdgeType::ChangesetToBonsaiGlobalrevMapping,
+        || Node::BonsaiGlobalrevMapping(*bcs_id),
+    );
 
     Ok(StepOutput(
         checker.step_data(NodeType::Changeset, || NodeData::Changeset(bcs)),
@@ -616,6 +632,42 @@ async fn hg_to_bonsai_mapping_step<V: VisitOne>(
     }
 }
 
+async fn bonsai_to_git_mapping_step<V: VisitOne>(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    checker: &Checker<V>,
+    bcs_id: ChangesetId,
+) -> Result<StepOutput, Error> {
+    let maybe_git_sha1 = repo
+        .bonsai_git_mapping()
+        .get_git_sha1_from_bonsai(ctx, bcs_id)
+        .await?;
+    Ok(StepOutput(
+        checker.step_data(NodeType::BonsaiGitMapping, || {
+            NodeData::BonsaiGitMapping(maybe_git_sha1)
+        }),
+        vec![],
+    ))
+}
+
+async fn bonsai_to_globalrev_mapping_step<V: VisitOne>(
+    repo: &BlobRepo,
+    checker: &Checker<V>,
+    bcs_id: ChangesetId,
+) -> Result<StepOutput, Error> {
+    let maybe_globalrev = repo
+        .bonsai_globalrev_mapping()
+        .get_globalrev_from_bonsai(repo.get_repoid(), bcs_id)
+        .compat()
+        .await?;
+    Ok(StepOutput(
+        checker.step_data(NodeType::BonsaiGlobalrevMapping, || {
+            NodeData::BonsaiGlobalrevMapping(maybe_globalrev)
+        }),
+        vec![],
+    ))
+}
+
 async fn hg_changeset_step<V: VisitOne>(
     ctx: &CoreContext,
     repo: &BlobRepo,
@@ -909,6 +961,101 @@ async fn fsnode_step<V: VisitOne>(
     ))
 }
 
+async fn bonsai_to_git_tree_mapping_step<V: VisitOne>(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    checker: &Checker<V>,
+    bcs_id: ChangesetId,
+    enable_derive: bool,
+) -> Result<StepOutput, Error> {
+    let root_tree = maybe_derived::<TreeHandle>(ctx, repo, bcs_id, enable_derive).await?;
+
+    if let Some(root_tree) = root_tree {
+        let sha1 = root_tree.oid().sha1();
+        let mut edges = vec![];
+        checker.add_edge_with_path(
+            &mut edges,
+            EdgeType::GitTreeMappingToRootGitTree,
+            || Node::GitTree(sha1),
+            || Some(WrappedPath::Root),
+        );
+        Ok(StepOutput(
+            checker.step_data(NodeType::GitTreeMapping, || {
+                NodeData::GitTreeMapping(Some(sha1))
+            }),
+            edges,
+        ))
+    } else {
+        Ok(StepOutput(
+            checker.step_data(NodeType::GitTreeMapping, || NodeData::GitTreeMapping(None)),
+            vec![],
+        ))
+    }
+}
+
+async fn git_tree_step<V: VisitOne>(
+    ctx: &CoreContext,
+    repo: &BlobRepo,
+    checker: &Checker<V>,
+    sha1: GitSha1,
+    path: Option<&WrappedPath>,
+) -> Result<StepOutput, Error> {
+    let key = tree_blobstore_key(&sha1);
+    let bytes = repo
+        .blobstore()
+        .get(ctx, &key)
+        .await?
+        .ok_or_else(|| LoadableError::Missing(key.clone()))?;
+    let tree = Tree::try_from(bytes)?;
+    if tree.handle().oid().sha1() != sha1 {
+        return Err(format_err!(
+            "Git tree stored at {} has id {}",
+            key,
+            tree.handle().oid()
+        ));
+    }
+
+    let mut blob_edges = vec![];
+    let mut tree_edges = vec![];
+    for (child, member) in tree.members() {
+        match member {
+            TreeMember::Tree(subtree) => {
+                checker.add_edge_with_path(
+                    &mut tree_edges,
+                    EdgeType::GitTreeToGitTreeChild,
+                    || Node::GitTree(subtree.oid().sha1()),
+                    || {
+                        path.map(|p| {
+                            WrappedPath::from(MPath::join_element_opt(p.as_ref(), Some(child)))
+                        })
+                    },
+                );
+            }
+            TreeMember::Blob(blob) => {
+                // Blobs are only addressable by their Git SHA-1 via the alias
+                checker.add_edge_with_path(
+                    &mut blob_edges,
+                    EdgeType::GitTreeToGitBlob,
+                    || Node::AliasContentMapping(AliasKey(Alias::GitSha1(blob.oid().sha1()))),
+                    || {
+                        path.map(|p| {
+                            WrappedPath::from(MPath::join_element_opt(p.as_ref(), Some(child)))
+                        })
+                    },
+                );
+            }
+        }
+    }
+
+    // Ordering to reduce queue depth
+    tree_edges.append(&mut blob_edges);
+
+    Ok(StepOutput(
+        checker.step_data(NodeType::GitTree, || NodeData::GitTree(tree)),
+        tree_edges,
+    ))
+}
+
 async fn bonsai_to_unode_mapping_step<V: VisitOne>(
     ctx: &CoreContext,
     repo: &BlobRepo,
@@ -1566,6 +1713,12 @@ where
         Node::PublishedBookmarks(_) => {
             published_bookmarks_step(published_bookmarks.clone(), &checker).await
         }
+        Node::BonsaiGitMapping(bcs_id) => {
+            bonsai_to_git_mapping_step(&ctx, &repo, &checker, bcs_id).await
+        }
+        Node::BonsaiGlobalrevMapping(bcs_id) => {
+            bonsai_to_globalrev_mapping_step(&repo, &checker, bcs_id).await
+        }
         // Hg
         Node::HgBonsaiMapping(hg_csid) => {
             hg_to_bonsai_mapping_step(ctx.clone(), &repo, &checker, hg_csid).await
@@ -1624,6 +1777,12 @@ where
         Node::FsnodeMapping(bcs_id) => {
             bonsai_to_fsnode_mapping_step(&ctx, &repo, &checker, bcs_id, enable_derive).await
         }
+        Node::GitTree(sha1) => {
+            git_tree_step(&ctx, &repo, &checker, sha1, walk_item.path.as_ref()).await
+        }
+        Node::GitTreeMapping(bcs_id) => {
+            bonsai_to_git_tree_mapping_step(&ctx, &repo, &checker, bcs_id, enable_derive).await
+        }
         Node::SkeletonManifest(id) => {
             skeleton_manifest_step(&ctx, &repo, &checker, &id, walk_item.path.as_ref()).await
         }
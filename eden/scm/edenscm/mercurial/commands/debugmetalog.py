@@ -6,9 +6,9 @@
 from __future__ import absolute_import
 
 import collections
-from typing import Set, Tuple
+from typing import List, Set, Tuple
 
-from .. import cmdutil, graphmod, phases, pycompat, util
+from .. import cmdutil, error, graphmod, phases, pycompat, util
 from ..i18n import _
 from ..node import bin, hex, short
 from .cmdtable import command
@@ -154,3 +154,111 @@ def debugmetalogroots(ui, repo, **opts):
         )
         fm.data(root=hexroot, date=timestamp, desc=desc, index=i)
     fm.end()
+
+
+@command(
+    "debugmetalogdiff",
+    [("", "stat", None, _("only show names of changed keys"))] + cmdutil.templateopts,
+    _("[ROOT1 [ROOT2]]"),
+)
+def debugmetalogdiff(ui, repo, *roots, **opts):
+    """show changes between metalog roots
+
+    ROOT is an index, as shown by :hg:`debugmetalogroots`, or a (prefix of)
+    root id.
+
+    Without ROOT, show changes made by the latest root. With one ROOT, show
+    changes made by that root. With two ROOTs, show changes from ROOT1 to
+    ROOT2.
+    """
+    metalog = repo.svfs.metalog
+    metalogpath = repo.svfs.join("metalog")
+    allroots = metalog.listroots(metalogpath)
+    if len(roots) > 2:
+        raise error.Abort(_("at most two roots can be specified"))
+    if len(roots) == 2:
+        roota = _resolveroot(allroots, roots[0])
+        rootb = _resolveroot(allroots, roots[1])
+    else:
+        if roots:
+            rootb = _resolveroot(allroots, roots[0])
+        else:
+            rootb = allroots[-1]
+        index = allroots.index(rootb)
+        roota = allroots[index - 1] if index > 0 else rootb
+
+    ui.pager("debugmetalogdiff")
+    fm = ui.formatter("debugmetalogdiff", opts)
+    for key, old, new, textdiff in metalog.diff(roota, rootb):
+        if old is None:
+            status, label = "added", "diff.inserted"
+        elif new is None:
+            status, label = "removed", "diff.deleted"
+        else:
+            status, label = "changed", "diff.changed"
+        fm.startitem()
+        fm.write("key status", "%s: %s\n", key, status, label=label)
+        fm.data(diff=textdiff)
+        if opts.get("stat"):
+            continue
+        if textdiff is None:
+            fm.plain(
+                _("binary value changed (%d bytes -> %d bytes)\n")
+                % (len(old or b""), len(new or b""))
+            )
+            continue
+        for line in textdiff.splitlines(True):
+            if line.startswith("@@"):
+                linelabel = "diff.hunk"
+            elif line.startswith("+"):
+                linelabel = "diff.inserted"
+            elif line.startswith("-"):
+                linelabel = "diff.deleted"
+            else:
+                linelabel = ""
+            fm.plain(line, label=linelabel)
+    fm.end()
+
+
+@command("debugmetalogrestore", [], _("ROOT"))
+def debugmetalogrestore(ui, repo, root):
+    """restore metalog to the state of an older root
+
+    ROOT is an index, as shown by :hg:`debugmetalogroots`, or a (prefix of)
+    root id.
+
+    Bookmarks, remote names and visible heads are reset to what they were in
+    ROOT. The restored state is written as a new root, so the history is
+    kept and the restoration can be undone by restoring the previous root.
+    """
+    with repo.wlock(), repo.lock():
+        metalog = repo.svfs.metalog
+        metalogpath = repo.svfs.join("metalog")
+        root = _resolveroot(metalog.listroots(metalogpath), root)
+        message = "debugmetalogrestore %s" % hex(root)
+        newroot = metalog.restore(root, message, int(util.timer()))
+
+        # Keep legacy copies of metalog-backed files in sync.
+        for name in repo.svfs.metapaths:
+            data = metalog.get(name)
+            if data is not None:
+                util.replacefile(repo.svfs.join(name), data)
+
+        # Reload metalog-backed state.
+        repo.svfs.__dict__.pop("metalog", None)
+        repo.invalidate(clearfilecache=True)
+
+    ui.status(_("restored metalog to %s as %s\n") % (short(root), short(newroot)))
+
+
+def _resolveroot(roots, spec):
+    # type: (List[bytes], str) -> bytes
+    """Resolve a root by its index in roots, or a prefix of its hex"""
+    if spec.isdigit() and int(spec) < len(roots):
+        return roots[int(spec)]
+    matched = [r for r in roots if hex(r).startswith(spec)]
+    if not matched:
+        raise error.Abort(_("unknown metalog root: %s") % spec)
+    if len(set(matched)) > 1:
+        raise error.Abort(_("ambiguous metalog root: %s") % spec)
+    return matched[0]
@@ -566,8 +566,8 @@ class dirstate(object):
         # Normalize "" to "None"
         return tsmap.getmetadata().get(name) or None
 
-    def _addpath(self, f, state, mode, size, mtime):
-        # type: (str, str, int, int, int) -> None
+    def _addpath(self, f, state, mode, size, mtime, mtimensec=0):
+        # type: (str, str, int, int, int, int) -> None
         oldstate = self[f]
         if state == "a" or oldstate == "r":
             scmutil.checkfilename(f)
@@ -590,14 +590,20 @@ class dirstate(object):
                     raise error.Abort(_("file %r in dirstate clashes with %r") % (d, f))
         self._dirty = True
         self._updatedfiles.add(f)
-        self._map.addfile(f, oldstate, state, mode, size, mtime)
+        self._map.addfile(f, oldstate, state, mode, size, mtime, mtimensec)
 
     def normal(self, f):
         # type: (str) -> None
         """Mark a file normal and clean."""
         s = util.lstat(self._join(f))
         mtime = s.st_mtime
-        self._addpath(f, "n", s.st_mode, s.st_size & _rangemask, mtime & _rangemask)
+        if self._istreestate:
+            # treestate stores 64-bit sizes and mtimes, and the sub-second part
+            # of mtime.
+            mtimensec = getattr(s, "st_mtime_ns", 0) % 1000000000
+            self._addpath(f, "n", s.st_mode, s.st_size, mtime, mtimensec)
+        else:
+            self._addpath(f, "n", s.st_mode, s.st_size & _rangemask, mtime & _rangemask)
         if not self._istreestate:
             self._map.copymap.pop(f, None)
             if f in self._map.nonnormalset:
@@ -906,7 +912,9 @@ class dirstate(object):
             self._origpl = None
         # use the modification time of the newly created temporary file as the
         # filesystem's notion of 'now'
-        now = util.fstat(st).st_mtime & _rangemask
+        now = util.fstat(st).st_mtime
+        if not self._istreestate:
+            now &= _rangemask
 
         # enough 'delaywrite' prevents 'pack_dirstate' from dropping
         # timestamp of each entries in dirstate, because of 'now > mtime'
@@ -1485,9 +1493,12 @@ class dirstatemap(object):
         """Loads the underlying data, if it's not already loaded"""
         self._map
 
-    def addfile(self, f, oldstate, state, mode, size, mtime):
-        # type: (str, str, str, int, int, int) -> None
-        """Add a tracked file to the dirstate."""
+    def addfile(self, f, oldstate, state, mode, size, mtime, mtimensec=0):
+        # type: (str, str, str, int, int, int, int) -> None
+        """Add a tracked file to the dirstate.
+
+        mtimensec is ignored since the dirstate format cannot store it.
+        """
         if oldstate in "?r" and "_dirs" in self.__dict__:
             self._dirs.addpath(f)
         if oldstate == "?" and "_alldirs" in self.__dict__:
@@ -445,6 +445,31 @@ Environment variables and ``~user`` constructs are expanded in
 
 to include a different configuration file on each computer you use.
 
+Including a file that does not exist is not an error. ``%include?``
+can be used to make that explicit. If ``file`` contains ``*``, ``?``
+or ``[``, it is a glob pattern and all matching files are included in
+sorted order::
+
+  %include? ~/.hgrc.d/$HOST.rc
+  %include ~/.hgrc.d/*.rc
+
+A section header can have conditions, separated by spaces. Values in the
+section are ignored unless all conditions match. Supported conditions are
+``platform``, ``hostname``, ``repo`` and ``tier``. Their values are
+comma-separated glob patterns::
+
+  [ui if platform=linux,osx hostname=devvm*]
+  editor = vim
+
+Values can refer to other configuration values using ``${section.name}``,
+and to environment variables using ``${env:NAME}``. Other ``${...}``
+forms, like ``${HG_NODE}`` in shell hooks, are kept as-is. Use ``$${``
+for a literal ``${``::
+
+  [paths]
+  base = ${env:HOME}/repos
+  default = ${paths.base}/main
+
 A line with ``%unset name`` will remove ``name`` from the current
 section, if it has been set previously.
 
@@ -239,7 +239,7 @@ class treedirstatemap(object):
         """
         return self.hastrackeddir(dirname) or self.hasremoveddir(dirname)
 
-    def addfile(self, f, oldstate, state, mode, size, mtime):
+    def addfile(self, f, oldstate, state, mode, size, mtime, mtimensec=0):
         self._rmap.addfile(
             f,
             pycompat.encodeutf8(oldstate),
@@ -186,7 +186,7 @@ class treestatemap(object):
     def preload(self):
         pass
 
-    def addfile(self, f, oldstate, state, mode, size, mtime):
+    def addfile(self, f, oldstate, state, mode, size, mtime, mtimensec=0):
         if state == "n":
             if size == -2:
                 state = treestate.EXIST_P2 | treestate.EXIST_NEXT
@@ -199,7 +199,7 @@ class treestatemap(object):
         else:
             raise error.ProgrammingError("unknown addfile state: %s" % state)
         # TODO: figure out whether "copied" needs to be preserved here.
-        self._tree.insert(f, state, mode, size, mtime, None)
+        self._tree.insert(f, state, mode, size, mtime, None, mtimensec)
 
     def removefile(self, f, oldstate, size):
         existing = self._tree.get(f, None)
@@ -234,11 +234,11 @@ class treestatemap(object):
         Removes the state marking a file as tracked, but leaves it in the
         treestate for future inspection.
         """
-        if not self._clock:
-            # If watchman clock is not set, watchman is not used, drop
-            # untracked files directly. This is also correct if watchman
-            # clock is reset to empty, since the next query will do a full
-            # crawl.
+        if not self._clock and not self.getmetadata().get("journal"):
+            # If neither the watchman clock nor the change journal cursor is
+            # set, no watcher is used, drop untracked files directly. This is
+            # also correct if the clock is reset to empty, since the next
+            # query will do a full crawl.
             return self._tree.remove(f)
         else:
             # If watchman is used, treestate tracks "untracked" files before
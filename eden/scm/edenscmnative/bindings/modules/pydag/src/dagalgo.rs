@@ -7,8 +7,11 @@
 
 use crate::Names;
 use cpython::*;
+use cpython_ext::AnyhowResultExt;
 use cpython_ext::ResultPyErrExt;
 use cpython_ext::Str;
+use dag::errors::BackendError;
+use dag::revset::Evaluator;
 use dag::DagAlgorithm;
 use dag::Vertex;
 use std::sync::Arc;
@@ -114,6 +117,55 @@ py_class!(pub class dagalgo |py| {
         Ok(Names(result))
     }
 
+    /// Evaluate a revset expression.
+    ///
+    /// `symbols` is an optional function that takes a name and returns a set,
+    /// or None if the name is unknown.
+    ///
+    /// `functions` is an optional dict from names to functions. A function
+    /// takes a list of arguments and returns a set. Arguments are strings for
+    /// symbols and quoted strings, or sets for other expressions.
+    def revset(&self, expr: &str, symbols: Option<PyObject> = None, functions: Option<PyDict> = None) -> PyResult<Names> {
+        let dag = self.dag(py);
+        let mut evaluator = Evaluator::new(dag.as_ref());
+        if let Some(symbols) = symbols {
+            evaluator = evaluator.with_symbol_func(move |name| {
+                symbols
+                    .call(py, (name,), None)
+                    .and_then(|obj| {
+                        if obj.is_none(py) {
+                            Ok(None)
+                        } else {
+                            Ok(Some(obj.extract::<Names>(py)?.0))
+                        }
+                    })
+                    .into_anyhow_result()
+                    .map_err(|e| BackendError::Other(e).into())
+            });
+        }
+        if let Some(functions) = functions {
+            for (name, func) in functions.items(py) {
+                let name = name.extract::<String>(py)?;
+                evaluator = evaluator.with_function(name, move |evaluator, args| {
+                    let mut py_args = Vec::with_capacity(args.len());
+                    for arg in args {
+                        let obj = match arg.as_str() {
+                            Some(s) => s.to_py_object(py).into_object(),
+                            None => Names(evaluator.eval(arg)?).to_py_object(py).into_object(),
+                        };
+                        py_args.push(obj);
+                    }
+                    func.call(py, (py_args,), None)
+                        .and_then(|obj| obj.extract::<Names>(py))
+                        .map(|names| names.0)
+                        .into_anyhow_result()
+                        .map_err(|e| BackendError::Other(e).into())
+                });
+            }
+        }
+        Ok(Names(evaluator.eval_str(expr).map_pyerr(py)?))
+    }
+
     /// Beautify the graph so `render` might look better.
     def beautify(&self, mainbranch: Option<Names> = None) -> PyResult<Self> {
         let dag = self.dag(py).beautify(mainbranch.map(|h| h.0)).map_pyerr(py)?;
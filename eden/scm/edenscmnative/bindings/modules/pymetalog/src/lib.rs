@@ -7,7 +7,7 @@
 
 #![allow(non_camel_case_types)]
 
-use ::metalog::{CommitOptions, Id20, MetaLog, Repair};
+use ::metalog::{CommitOptions, Id20, KeyChange, MetaLog, Repair};
 use cpython::*;
 use cpython_ext::{Bytes, PyNone, ResultPyErrExt, Str};
 use std::cell::RefCell;
@@ -94,6 +94,47 @@ py_class!(pub class metalog |py| {
         Ok(Bytes::from(id.as_ref().to_vec()))
     }
 
+    /// Compare two roots.
+    ///
+    /// Return [(key, old, new, textdiff)], sorted by key. `old` or `new` is
+    /// None if the key is missing. `textdiff` is a unified diff without
+    /// headers, or None if the values are not text.
+    def diff(&self, roota: Bytes, rootb: Bytes, context: usize = 3) -> PyResult<Vec<(Str, Option<PyBytes>, Option<PyBytes>, Option<Str>)>> {
+        let roota = Id20::from_slice(roota.as_ref()).map_pyerr(py)?;
+        let rootb = Id20::from_slice(rootb.as_ref()).map_pyerr(py)?;
+        let diffs = self.log(py).borrow().diff(roota, rootb).map_pyerr(py)?;
+        let result = diffs.into_iter().map(|d| {
+            let textdiff = d.text_diff(context).map(Str::from);
+            let (old, new) = match &d.change {
+                KeyChange::Added(new) => (None, Some(new)),
+                KeyChange::Removed(old) => (Some(old), None),
+                KeyChange::Changed { old, new } => (Some(old), Some(new)),
+            };
+            (
+                Str::from(d.key),
+                old.map(|v| PyBytes::new(py, v)),
+                new.map(|v| PyBytes::new(py, v)),
+                textdiff,
+            )
+        }).collect();
+        Ok(result)
+    }
+
+    /// Write a new root with the same content as the given root.
+    /// Return the new root id. Raise if there are uncommitted changes.
+    def restore(&self, root: Bytes, message: &str, time: Option<u64> = None) -> PyResult<Bytes> {
+        let root = Id20::from_slice(root.as_ref()).map_pyerr(py)?;
+        let mut opts = CommitOptions::default();
+        opts.timestamp = time.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs()).unwrap_or(0)
+        });
+        opts.message = message;
+        let id = self.log(py).borrow_mut().restore(root, opts).map_pyerr(py)?;
+        Ok(Bytes::from(id.as_ref().to_vec()))
+    }
+
     /// Export to a git respository
     def exportgit(&self, path: String) -> PyResult<PyNone> {
         let log = self.log(py).borrow();
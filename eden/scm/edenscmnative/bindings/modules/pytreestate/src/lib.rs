@@ -485,7 +485,7 @@ py_class!(pub class treestate |py| {
         })
     }
 
-    def get(&self, path: &PyPath, default: Option<(u16, u32, i32, i32, Option<PyPathBuf>)>) -> PyResult<Option<(u16, u32, i32, i32, Option<PyPathBuf>)>> {
+    def get(&self, path: &PyPath, default: Option<(u16, u32, i64, i64, Option<PyPathBuf>)>) -> PyResult<Option<(u16, u32, i64, i64, Option<PyPathBuf>)>> {
         let mut state = self.state(py).lock();
         let path = path.as_utf8_bytes();
 
@@ -500,8 +500,17 @@ py_class!(pub class treestate |py| {
                      file.copied.as_ref().map(|path| PyPathBuf::from_utf8_bytes(path.to_vec()).unwrap())))))
     }
 
+    def getmtimensec(&self, path: &PyPath) -> PyResult<Option<u32>> {
+        // The sub-second part of mtime. It is not returned by "get" to keep the tuple
+        // compatible with the legacy dirstate.
+        let mut state = self.state(py).lock();
+        let file = convert_result(py, state.get(path.as_utf8_bytes()))?;
+        Ok(file.map(|file| file.mtime_nsec))
+    }
+
     def insert(
-        &self, path: &PyPath, bits: u16, mode: u32, size: i32, mtime: i32, copied: Option<PyPathBuf>
+        &self, path: &PyPath, bits: u16, mode: u32, size: i64, mtime: i64, copied: Option<PyPathBuf>,
+        mtimensec: u32 = 0
     ) -> PyResult<PyObject> {
         let mut flags = StateFlags::from_bits_truncate(bits);
         // For special mtime or size, mark them as "NEED_CHECK" automatically.
@@ -516,7 +525,7 @@ py_class!(pub class treestate |py| {
             flags -= StateFlags::COPIED;
         };
 
-        let file = FileStateV2 { mode, size, mtime, copied: copied.map(|copied| copied.as_utf8_bytes().to_vec().into_boxed_slice()), state: flags };
+        let file = FileStateV2 { mode, size, mtime, mtime_nsec: mtimensec, copied: copied.map(|copied| copied.as_utf8_bytes().to_vec().into_boxed_slice()), state: flags };
         let path = path.as_utf8_bytes();
         let mut state = self.state(py).lock();
         convert_result(py, state.insert(path, &file))?;
@@ -684,8 +693,8 @@ py_class!(pub class treestate |py| {
             let data = item_tuple.get_item(py, 1).extract::<PySequence>(py)?;
             let state = data.get_item(py, 0)?.extract::<PyString>(py)?.data(py).to_string(py)?.bytes().next().unwrap();
             let mode = data.get_item(py, 1)?.extract::<u32>(py)?;
-            let size = data.get_item(py, 2)?.extract::<i32>(py)?;
-            let mtime = data.get_item(py, 3)?.extract::<i32>(py)?;
+            let size = data.get_item(py, 2)?.extract::<i64>(py)?;
+            let mtime = data.get_item(py, 3)?.extract::<i64>(py)?;
             // Mercurial uses special "size"s to represent "otherparent" if state is "n".
             // See "size = -2" in mercurial/dirstate.py
             let flags = match size {
@@ -700,14 +709,14 @@ py_class!(pub class treestate |py| {
                 _ => StateFlags::empty(),
             };
             if !flags.is_empty() {
-                let file = FileStateV2 { mode, size, mtime, copied: None, state: flags };
+                let file = FileStateV2 { mode, size, mtime, mtime_nsec: 0, copied: None, state: flags };
                 convert_result(py, tree.insert(path.as_utf8_bytes(), &file))?;
             }
         }
         Ok(None)
     }
 
-    def invalidatemtime(&self, fsnow: i32) -> PyResult<PyObject> {
+    def invalidatemtime(&self, fsnow: i64) -> PyResult<PyObject> {
         // Distrust changed files with a mtime of `fsnow`. Rewrite their mtime to -1.
         // See mercurial/pure/parsers.py:pack_dirstate in core Mercurial for motivation.
         // Basically, this is required for the following case:
@@ -735,6 +744,7 @@ py_class!(pub class treestate |py| {
             &mut |_, state| {
                 if state.mtime >= fsnow {
                     state.mtime = -1;
+                    state.mtime_nsec = 0;
                     state.state |= StateFlags::NEED_CHECK;
                     Ok(VisitorResult::Changed)
                 } else {
@@ -25,3 +25,4 @@ pub use match_pattern::{capture_pattern, match_pattern};
 pub use serde_json::{self, json, Value};
 
 pub mod event;
+pub mod query;
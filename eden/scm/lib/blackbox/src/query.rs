@@ -0,0 +1,411 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Query blackbox entries by time, event type, duration and patterns, and
+//! export them as JSON lines or CSV.
+
+use crate::event::Event;
+use crate::{capture_pattern, json, Blackbox, Entry, ToValue, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::ops::Range;
+
+/// Filters and ordering used by [`Blackbox::query`].
+///
+/// Example: network operations slower than 5 seconds in a time window,
+/// slowest first:
+///
+/// ```
+/// # use blackbox::query::{Order, Query};
+/// let query = Query::new()
+///     .time_range(1_600_000_000_000..1_600_600_000_000)
+///     .event_types(&["network"])
+///     .min_duration_ms(5000)
+///     .order(Order::Duration)
+///     .limit(100);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Query {
+    time_range_ms: Range<u64>,
+    event_types: BTreeSet<String>,
+    min_duration_ms: Option<u64>,
+    pattern: Option<Value>,
+    order: Order,
+    limit: Option<usize>,
+}
+
+/// Ordering of query results.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Order {
+    /// Oldest first.
+    Time,
+    /// Newest first.
+    TimeReversed,
+    /// Longest first. Entries without durations are ordered by time after
+    /// entries with durations.
+    Duration,
+}
+
+/// A [`Entry`] returned by [`Blackbox::query`].
+#[derive(Debug)]
+pub struct QueryRow {
+    pub entry: Entry,
+
+    /// Type of the event, as used by patterns. For example, `"network"`.
+    pub event_type: String,
+
+    /// The `duration_ms` field of the event, if present.
+    pub duration_ms: Option<u64>,
+
+    /// Human-friendly JSON form of the event. Payload of `TracingData` is
+    /// omitted.
+    pub value: Value,
+
+    /// Values captured by `["capture", name, pattern]` in the query pattern.
+    pub captures: BTreeMap<String, Value>,
+}
+
+impl Query {
+    /// A query that matches all entries, oldest first.
+    pub fn new() -> Self {
+        Self {
+            time_range_ms: 0..u64::MAX,
+            event_types: Default::default(),
+            min_duration_ms: None,
+            pattern: None,
+            order: Order::Time,
+            limit: None,
+        }
+    }
+
+    /// Only include entries logged in `range`, in milliseconds since epoch.
+    /// The end is exclusive, like ranges returned by `HgTime::parse_range`.
+    pub fn time_range(mut self, range: Range<u64>) -> Self {
+        self.time_range_ms = range;
+        self
+    }
+
+    /// Only include events of the given types (ex. `"network"`, `"finish"`).
+    /// Can be called multiple times to include more types.
+    pub fn event_types(mut self, types: &[impl AsRef<str>]) -> Self {
+        self.event_types
+            .extend(types.iter().map(|t| t.as_ref().to_string()));
+        self
+    }
+
+    /// Only include events with `duration_ms` that is at least `ms`.
+    pub fn min_duration_ms(mut self, ms: u64) -> Self {
+        self.min_duration_ms = Some(ms);
+        self
+    }
+
+    /// Only include events matching the pattern. See `match_pattern` for the
+    /// syntax. Values captured by `["capture", name, pattern]` are returned
+    /// in [`QueryRow::captures`].
+    pub fn pattern(mut self, pattern: Value) -> Self {
+        self.pattern = Some(pattern);
+        self
+    }
+
+    pub fn order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Return at most `limit` entries (after ordering).
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Test an entry. Return the [`QueryRow`] if it matches.
+    /// The time range is checked by [`Blackbox::query`] before decoding.
+    fn check(&self, entry: Entry) -> Option<QueryRow> {
+        let value = match entry.data {
+            Event::TracingData { .. } => json!({"tracing_data": {}}),
+            _ => entry.data.to_value(),
+        };
+        let (event_type, fields) = match value.as_object().and_then(|o| o.iter().next()) {
+            Some((name, fields)) => (name.clone(), fields),
+            None => return None,
+        };
+        if !self.event_types.is_empty() && !self.event_types.contains(&event_type) {
+            return None;
+        }
+        let duration_ms = match fields.get("duration_ms") {
+            Some(v) => v.as_u64(),
+            // Some events (ex. "network") omit zero durations.
+            None if fields.is_object() && event_has_duration(&event_type) => Some(0),
+            None => None,
+        };
+        if let Some(min_duration_ms) = self.min_duration_ms {
+            if duration_ms.unwrap_or(0) < min_duration_ms {
+                return None;
+            }
+        }
+        let captures = match &self.pattern {
+            Some(pattern) => capture_pattern(&value, pattern)?
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+            None => Default::default(),
+        };
+        Some(QueryRow {
+            entry,
+            event_type,
+            duration_ms,
+            value,
+            captures,
+        })
+    }
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Event types that have a `duration_ms` field.
+fn event_has_duration(event_type: &str) -> bool {
+    matches!(event_type, "blocked" | "finish" | "network" | "watchman")
+}
+
+impl Blackbox {
+    /// Find entries matching a [`Query`].
+    ///
+    /// Unlike `session_ids_by_pattern`, filters apply to individual entries,
+    /// and all entries are scanned. Entries that cannot be read or
+    /// deserialized are ignored silently.
+    pub fn query(&self, query: &Query) -> Vec<QueryRow> {
+        let mut rows: Vec<QueryRow> = self
+            .log
+            .iter()
+            .filter_map(|bytes| bytes.ok())
+            .filter(|bytes| match Entry::timestamp_from_slice(bytes) {
+                Some(ts) => query.time_range_ms.contains(&ts),
+                None => false,
+            })
+            .filter_map(|bytes| Entry::from_slice(&bytes))
+            .filter_map(|entry| query.check(entry))
+            .collect();
+
+        // Sorts are stable. Entries with the same timestamp keep their
+        // logged order.
+        rows.sort_by_key(|r| r.entry.timestamp);
+        match query.order {
+            Order::Time => {}
+            Order::TimeReversed => rows.reverse(),
+            Order::Duration => rows.sort_by_key(|r| std::cmp::Reverse(r.duration_ms)),
+        }
+        if let Some(limit) = query.limit {
+            rows.truncate(limit);
+        }
+        rows
+    }
+}
+
+/// Write rows as JSON lines. Each line is an object with `timestamp_ms`,
+/// `session_id`, `type`, `duration_ms`, `event` and `captures`.
+pub fn write_jsonl(rows: &[QueryRow], out: &mut dyn Write) -> io::Result<()> {
+    for row in rows {
+        let obj = json!({
+            "timestamp_ms": row.entry.timestamp,
+            "session_id": row.entry.session_id,
+            "type": row.event_type,
+            "duration_ms": row.duration_ms,
+            "event": row.value,
+            "captures": row.captures,
+        });
+        serde_json::to_writer(&mut *out, &obj)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Write rows as CSV with a header line.
+///
+/// Columns are `timestamp_ms`, `session_id`, `type`, `duration_ms`,
+/// `message`, followed by one column per captured name.
+pub fn write_csv(rows: &[QueryRow], out: &mut dyn Write) -> io::Result<()> {
+    let capture_names: BTreeSet<&str> = rows
+        .iter()
+        .flat_map(|r| r.captures.keys().map(|k| k.as_str()))
+        .collect();
+    let mut header = vec![
+        "timestamp_ms",
+        "session_id",
+        "type",
+        "duration_ms",
+        "message",
+    ];
+    header.extend(capture_names.iter());
+    write_csv_line(out, header.into_iter().map(|s| s.to_string()))?;
+
+    for row in rows {
+        let message = match row.entry.data {
+            Event::TracingData { .. } => "[tracing_data]".to_string(),
+            _ => row.entry.data.to_string().trim().to_string(),
+        };
+        let mut fields = vec![
+            row.entry.timestamp.to_string(),
+            row.entry.session_id.to_string(),
+            row.event_type.clone(),
+            row.duration_ms.map(|d| d.to_string()).unwrap_or_default(),
+            message,
+        ];
+        fields.extend(
+            capture_names
+                .iter()
+                .map(|name| match row.captures.get(*name) {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(v) => v.to_string(),
+                    None => String::new(),
+                }),
+        );
+        write_csv_line(out, fields.into_iter())?;
+    }
+    Ok(())
+}
+
+/// Write a CSV record. Quote fields as specified by RFC 4180.
+fn write_csv_line(out: &mut dyn Write, fields: impl Iterator<Item = String>) -> io::Result<()> {
+    let line: Vec<String> = fields
+        .map(|field| {
+            if field.contains(&[',', '"', '\n', '\r'][..]) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect();
+    out.write_all(line.join(",").as_bytes())?;
+    out.write_all(b"\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::NetworkOp;
+    use crate::BlackboxOptions;
+
+    fn network(duration_ms: u64) -> Event {
+        Event::Network {
+            op: NetworkOp::SshGetFiles,
+            read_bytes: 0,
+            write_bytes: 0,
+            calls: 1,
+            duration_ms,
+            latency_ms: 0,
+            result: None,
+            url: String::new(),
+            session_id: String::new(),
+        }
+    }
+
+    fn test_blackbox() -> Blackbox {
+        let mut blackbox = BlackboxOptions::new().create_in_memory().unwrap();
+        blackbox.log(&network(6000));
+        blackbox.log(&Event::Alias {
+            from: "a".to_string(),
+            to: "b, \"c\"".to_string(),
+        });
+        blackbox.log(&network(0));
+        blackbox.log(&network(9000));
+        blackbox
+    }
+
+    fn durations(rows: &[QueryRow]) -> Vec<Option<u64>> {
+        rows.iter().map(|r| r.duration_ms).collect()
+    }
+
+    #[test]
+    fn test_query_filters() {
+        let blackbox = test_blackbox();
+
+        let rows = blackbox.query(&Query::new());
+        assert_eq!(durations(&rows), [Some(6000), None, Some(0), Some(9000)]);
+        assert_eq!(rows[1].event_type, "alias");
+
+        let rows = blackbox.query(&Query::new().event_types(&["network"]));
+        assert_eq!(durations(&rows), [Some(6000), Some(0), Some(9000)]);
+
+        let rows = blackbox.query(&Query::new().min_duration_ms(5000));
+        assert_eq!(durations(&rows), [Some(6000), Some(9000)]);
+
+        let rows = blackbox.query(&Query::new().time_range(0..1));
+        assert!(rows.is_empty());
+
+        let first = blackbox.query(&Query::new())[0].entry.timestamp;
+        let rows = blackbox.query(&Query::new().time_range(first..first + 1));
+        assert_eq!(rows[0].entry.timestamp, first);
+        let rows = blackbox.query(&Query::new().time_range(0..first));
+        assert!(rows.iter().all(|r| r.entry.timestamp < first));
+
+        let rows = blackbox.query(&Query::new().pattern(json!({"alias": "_"})));
+        assert_eq!(rows.len(), 1);
+    }
+
+    #[test]
+    fn test_query_order_limit_captures() {
+        let blackbox = test_blackbox();
+
+        let query = Query::new().order(Order::Duration).limit(3);
+        assert_eq!(
+            durations(&blackbox.query(&query)),
+            [Some(9000), Some(6000), Some(0)]
+        );
+
+        let query = Query::new().order(Order::TimeReversed).limit(2);
+        assert_eq!(durations(&blackbox.query(&query)), [Some(9000), Some(0)]);
+
+        let query = Query::new().pattern(json!({"network": {
+            "duration_ms": ["capture", "D", ["range", 1, 10000]]
+        }}));
+        let rows = blackbox.query(&query);
+        let captured: Vec<&Value> = rows.iter().map(|r| &r.captures["D"]).collect();
+        assert_eq!(captured, [&json!(6000), &json!(9000)]);
+    }
+
+    #[test]
+    fn test_export() {
+        let blackbox = test_blackbox();
+        let query = Query::new()
+            .pattern(json!(["or", {"alias": {"to": ["capture", "TO", "_"]}}, {"network": "_"}]))
+            .limit(2);
+        let rows = blackbox.query(&query);
+
+        let mut out = Vec::new();
+        write_jsonl(&rows, &mut out).unwrap();
+        let lines: Vec<Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["type"], "network");
+        assert_eq!(lines[0]["duration_ms"], 6000);
+        assert_eq!(lines[0]["event"]["network"]["op"], "ssh_getfiles");
+        assert_eq!(lines[1]["duration_ms"], Value::Null);
+        assert_eq!(lines[1]["captures"], json!({"TO": "b, \"c\""}));
+
+        let mut out = Vec::new();
+        write_csv(&rows, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.split("\r\n").collect();
+        assert_eq!(
+            lines[0],
+            "timestamp_ms,session_id,type,duration_ms,message,TO"
+        );
+        assert!(lines[1].ends_with(
+            r#",network,6000,"[network] SshGetFiles finished in 1 calls, duration 6000 ms, latency 0 ms, read 0 bytes, write 0 bytes, session id , url","#
+        ));
+        assert!(lines[2].ends_with(
+            r##",alias,,"[command_alias] ""a"" expands to ""b, \""c\""""","b, ""c""""##
+        ));
+    }
+}
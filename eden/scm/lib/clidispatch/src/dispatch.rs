@@ -15,8 +15,10 @@ use cliparser::alias::{expand_aliases, find_command_name};
 use cliparser::parser::{ParseError, ParseOptions, ParseOutput, StructFlags};
 use configparser::config::ConfigSet;
 use configparser::hg::ConfigSetHgExt;
+use configparser::schema::{self, ConfigItem, ValueType};
 use std::convert::TryInto;
 use std::sync::atomic::Ordering::SeqCst;
+use std::sync::Once;
 use std::{env, path::Path};
 
 type Result<T, E = Error> = std::result::Result<T, E>;
@@ -103,15 +105,13 @@ fn initialize_blackbox(optional_repo: &OptionalRepo) -> Result<()> {
     if let OptionalRepo::Some(repo) = optional_repo {
         let config = repo.config();
         let max_size = config
-            .get_or("blackbox", "maxsize", || {
-                configparser::hg::ByteCount::from(1u64 << 12)
-            })?
+            .get_or_schema_default::<configparser::hg::ByteCount>("blackbox", "maxsize")?
             .value();
-        let max_files = config.get_or("blackbox", "maxfiles", || 3)?;
+        let max_files: u8 = config.get_or_schema_default("blackbox", "maxfiles")?;
         let path = repo.shared_path().join(".hg/blackbox/v1");
         if let Ok(blackbox) = ::blackbox::BlackboxOptions::new()
             .max_bytes_per_log(max_size)
-            .max_log_count(max_files as u8)
+            .max_log_count(max_files)
             .open(path)
         {
             ::blackbox::init(blackbox);
@@ -122,30 +122,62 @@ fn initialize_blackbox(optional_repo: &OptionalRepo) -> Result<()> {
 
 fn initialize_indexedlog(config: &ConfigSet) -> Result<()> {
     if cfg!(unix) {
-        let chmod_file = config.get_or("permissions", "chmod-file", || -1)?;
+        let chmod_file = config.get_or_schema_default("permissions", "chmod-file")?;
         if chmod_file >= 0 {
             indexedlog::utils::CHMOD_FILE.store(chmod_file, SeqCst);
         }
 
-        let chmod_dir = config.get_or("permissions", "chmod-dir", || -1)?;
+        let chmod_dir = config.get_or_schema_default("permissions", "chmod-dir")?;
         if chmod_dir >= 0 {
             indexedlog::utils::CHMOD_DIR.store(chmod_dir, SeqCst);
         }
 
         let use_symlink_atomic_write: bool =
-            config.get_or_default("format", "use-symlink-atomic-write")?;
+            config.get_or_schema_default("format", "use-symlink-atomic-write")?;
         indexedlog::utils::SYMLINK_ATOMIC_WRITE.store(use_symlink_atomic_write, SeqCst);
     }
 
     Ok(())
 }
 
+/// Describe configs read by this crate in the config schema.
+fn register_config_schema() {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        schema::register(vec![
+            ConfigItem::new("blackbox", "maxsize", ValueType::ByteCount)
+                .default("4 KB")
+                .doc("maximum size of a blackbox log file before it gets rotated"),
+            ConfigItem::new("blackbox", "maxfiles", ValueType::Int)
+                .default("3")
+                .doc("number of blackbox log files to keep"),
+            ConfigItem::new("format", "use-symlink-atomic-write", ValueType::Bool)
+                .default("false")
+                .doc("use symlinks to atomically write small files"),
+            ConfigItem::new("pager", "interface", ValueType::String)
+                .default("hybrid")
+                .doc("interface mode of the built-in pager"),
+            ConfigItem::new("pager", "scroll-past-eof", ValueType::Bool)
+                .default("false")
+                .doc("allow the built-in pager to scroll past the end of the output"),
+            ConfigItem::new("permissions", "chmod-dir", ValueType::Int)
+                .default("-1")
+                .doc("mode of directories created by indexedlog, or -1 to not chmod"),
+            ConfigItem::new("permissions", "chmod-file", ValueType::Int)
+                .default("-1")
+                .doc("mode of files created by indexedlog, or -1 to not chmod"),
+        ]);
+    });
+}
+
 pub fn parse_global_opts(args: &[String]) -> Result<HgGlobalOpts> {
     let early_result = early_parse(args)?;
     early_result.try_into()
 }
 
 pub fn dispatch(command_table: &CommandTable, args: &[String], io: &mut IO) -> Result<u8> {
+    register_config_schema();
+
     let early_result = early_parse(args)?;
     let global_opts: HgGlobalOpts = early_result.clone().try_into()?;
 
@@ -108,18 +108,16 @@ impl IO {
         let mut pager =
             Pager::new_using_stdio().map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
 
-        // Configure the pager.
-        // The Hybrid mode is similar to "-FX" from "less".
-        let mut interface_mode = InterfaceMode::Hybrid;
-        // Similar to "less" default.
-        let mut scroll_past_eof = false;
-        if let Some(mode_str) = config.get("pager", "interface") {
-            let mode = InterfaceMode::from(mode_str.as_ref());
-            interface_mode = mode;
-        }
-        if let Ok(Some(past_eof)) = config.get_opt("pager", "scroll-past-eof") {
-            scroll_past_eof = past_eof;
-        }
+        // Configure the pager. Defaults are in the config schema.
+        // The "hybrid" mode is similar to "-FX" from "less".
+        let to_io_error = |e: anyhow::Error| io::Error::new(io::ErrorKind::Other, e);
+        let interface_mode: String = config
+            .get_or_schema_default("pager", "interface")
+            .map_err(to_io_error)?;
+        let interface_mode = InterfaceMode::from(interface_mode.as_ref());
+        let scroll_past_eof: bool = config
+            .get_or_schema_default("pager", "scroll-past-eof")
+            .map_err(to_io_error)?;
         pager
             .set_scroll_past_eof(scroll_past_eof)
             .set_interface_mode(interface_mode);
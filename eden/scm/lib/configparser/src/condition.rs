@@ -0,0 +1,169 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Facts about the running environment used by conditional sections.
+//!
+//! A section header like `[section if platform=linux hostname=dev*]` is only
+//! effective if all of its conditions match the [`Conditions`] passed to the
+//! config loading functions.
+
+use std::collections::HashSet;
+use std::env;
+
+use glob::{MatchOptions, Pattern};
+use lazy_static::lazy_static;
+
+use crate::dynamicconfig::{get_hostname, get_platform, get_tiers, Platform};
+
+lazy_static! {
+    static ref SYSTEM_CONDITIONS: Conditions = Conditions::detect_system();
+}
+
+/// Values that `[section if key=value]` conditions are tested against.
+#[derive(Clone, Debug, Default)]
+pub struct Conditions {
+    platforms: Vec<String>,
+    hostname: Option<String>,
+    repo: Option<String>,
+    tiers: HashSet<String>,
+}
+
+impl Conditions {
+    /// Return an empty `Conditions`. Only an empty condition specification matches it.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Return `Conditions` describing the current system: platform,
+    /// hostname and tiers. The repo name is not set.
+    ///
+    /// The system information is only computed once per process.
+    pub fn from_system() -> Self {
+        SYSTEM_CONDITIONS.clone()
+    }
+
+    fn detect_system() -> Self {
+        let mut conditions = Conditions::new()
+            .with_platform(env::consts::OS)
+            .with_platform(env::consts::FAMILY);
+        let platform = get_platform();
+        if platform != Platform::Unknown {
+            conditions = conditions.with_platform(platform.to_str());
+        }
+        if let Ok(hostname) = get_hostname() {
+            conditions = conditions.with_hostname(hostname);
+        }
+        if let Ok(tiers) = get_tiers() {
+            for tier in tiers {
+                conditions = conditions.with_tier(tier);
+            }
+        }
+        conditions
+    }
+
+    /// Add a platform name, like "linux", "unix" or "centos".
+    pub fn with_platform(mut self, platform: impl ToString) -> Self {
+        self.platforms.push(platform.to_string());
+        self
+    }
+
+    /// Set the hostname.
+    pub fn with_hostname(mut self, hostname: impl ToString) -> Self {
+        self.hostname = Some(hostname.to_string());
+        self
+    }
+
+    /// Set the repo name.
+    pub fn with_repo(mut self, repo: impl ToString) -> Self {
+        self.repo = Some(repo.to_string());
+        self
+    }
+
+    /// Add a tier.
+    pub fn with_tier(mut self, tier: impl ToString) -> Self {
+        self.tiers.insert(tier.to_string());
+        self
+    }
+
+    /// Test a condition specification like `platform=linux,osx repo=foo*`.
+    ///
+    /// Each `key=patterns` term matches if any of the comma-separated glob
+    /// patterns matches (case-insensitively). The specification matches if
+    /// all terms match. Supported keys are `platform`, `hostname`, `repo` and
+    /// `tier`.
+    ///
+    /// Return an error message if the specification is malformed.
+    pub fn matches(&self, spec: &str) -> Result<bool, String> {
+        let mut result = true;
+        for term in spec.split_whitespace() {
+            let (key, values) = match term.find('=') {
+                Some(index) => (&term[..index], &term[index + 1..]),
+                None => return Err(format!("condition {:?} lacks '='", term)),
+            };
+            let candidates: Vec<&str> = match key {
+                "platform" => self.platforms.iter().map(|s| s.as_str()).collect(),
+                "hostname" => self.hostname.iter().map(|s| s.as_str()).collect(),
+                "repo" => self.repo.iter().map(|s| s.as_str()).collect(),
+                "tier" => self.tiers.iter().map(|s| s.as_str()).collect(),
+                _ => return Err(format!("unknown condition {:?}", key)),
+            };
+            let mut matched = false;
+            for value in values.split(',').filter(|v| !v.is_empty()) {
+                let pattern = Pattern::new(value)
+                    .map_err(|e| format!("invalid pattern {:?}: {}", value, e))?;
+                if candidates
+                    .iter()
+                    .any(|c| pattern.matches_with(c, MATCH_OPTIONS))
+                {
+                    matched = true;
+                }
+            }
+            result = result && matched;
+        }
+        Ok(result)
+    }
+}
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let conditions = Conditions::new()
+            .with_platform("linux")
+            .with_platform("unix")
+            .with_hostname("devvm123.example.com")
+            .with_repo("fbsource")
+            .with_tier("sandcastle");
+
+        assert_eq!(conditions.matches(""), Ok(true));
+        assert_eq!(conditions.matches("platform=linux"), Ok(true));
+        assert_eq!(conditions.matches("platform=windows,unix"), Ok(true));
+        assert_eq!(conditions.matches("platform=windows"), Ok(false));
+        assert_eq!(conditions.matches("hostname=DEVVM*"), Ok(true));
+        assert_eq!(conditions.matches("repo=fbs?urce tier=sand*"), Ok(true));
+        assert_eq!(conditions.matches("repo=fbsource tier=prod"), Ok(false));
+        assert_eq!(
+            conditions.matches("os=linux"),
+            Err("unknown condition \"os\"".to_string())
+        );
+        assert_eq!(
+            conditions.matches("linux"),
+            Err("condition \"linux\" lacks '='".to_string())
+        );
+
+        let empty = Conditions::new();
+        assert_eq!(empty.matches("repo=*"), Ok(false));
+    }
+}
@@ -8,6 +8,7 @@
 use std::collections::hash_map::RandomState;
 use std::collections::HashSet;
 use std::convert::AsRef;
+use std::env;
 use std::fs;
 use std::iter::FromIterator;
 use std::ops::Range;
@@ -20,8 +21,10 @@ use minibytes::Text;
 use pest::{self, Parser, Span};
 use util::path::expand_path;
 
+use crate::condition::Conditions;
 use crate::error::Error;
 use crate::parser::{ConfigParser, Rule};
+use crate::schema;
 
 type Pair<'a> = pest::iterators::Pair<'a, Rule>;
 
@@ -43,6 +46,29 @@ pub struct ValueSource {
     value: Option<Text>,
     source: Text, // global, user, repo, "--config", or an extension name, etc.
     location: Option<ValueLocation>,
+    interpolation: Option<Box<Interpolation>>,
+}
+
+/// Explains how `${...}` references in a raw config value were expanded.
+#[derive(Clone, Debug)]
+pub struct Interpolation {
+    raw: Text,
+    references: Vec<Reference>,
+}
+
+/// A `${...}` reference used by an interpolated config value.
+#[derive(Clone, Debug)]
+pub enum Reference {
+    /// `${section.name}`. `sources` is what `get_sources` returns for the
+    /// referred config. It is empty if the config is not set.
+    Config {
+        section: Text,
+        name: Text,
+        sources: Vec<ValueSource>,
+    },
+
+    /// `${env:NAME}`. `value` is `None` if the environment variable is not set.
+    Env { name: Text, value: Option<Text> },
 }
 
 /// The on-disk file name and byte offsets that provide the config value.
@@ -60,6 +86,7 @@ struct ValueLocation {
 pub struct Options {
     source: Text,
     filters: Vec<Arc<Box<dyn Fn(Text, Text, Option<Text>) -> Option<(Text, Text, Option<Text>)>>>>,
+    conditions: Option<Arc<Conditions>>,
 }
 
 impl ConfigSet {
@@ -92,6 +119,10 @@ impl ConfigSet {
     ///
     /// After loading `1.rc`. `x` is set to 3 and `y` is set to 2.
     ///
+    /// Including a missing file is not an error. `%include?` is an alias of `%include` that makes
+    /// this explicit. If the include path contains glob characters (`*`, `?` or `[`), all
+    /// matching files are loaded in sorted order.
+    ///
     /// Loading a file that is already parsed or being parsed by this `load_path` call is ignored,
     /// to avoid infinite loop. A separate `load_path` call would not ignore files loaded by
     /// other `load_path` calls.
@@ -132,24 +163,188 @@ impl ConfigSet {
 
     /// Get config value for a given config.
     /// Return `None` if the config item does not exist or is unset.
+    ///
+    /// `${section.name}` and `${env:NAME}` references in the value are expanded.
+    /// See [`ConfigSet::get_raw`] for the value without expansion.
     pub fn get(&self, section: impl AsRef<str>, name: impl AsRef<str>) -> Option<Text> {
-        self.sections.get(section.as_ref()).and_then(|section| {
-            section
-                .items
-                .get(name.as_ref())
-                .and_then(|values| values.last().and_then(|value| value.value.clone()))
-        })
+        let (section, name) = (section.as_ref(), name.as_ref());
+        let value = self.get_raw(section, name)?;
+        if !value.contains("${") {
+            return Some(value);
+        }
+        let mut stack = vec![(section.to_string(), name.to_string())];
+        let expanded = self.interpolate(&value, &mut stack, &mut Vec::new(), None);
+        Some(Text::from(expanded))
+    }
+
+    /// Get config value for a given config, without expanding `${...}` references.
+    /// Return `None` if the config item does not exist or is unset.
+    ///
+    /// If the config item does not exist, deprecated names registered using
+    /// [`crate::schema::ConfigItem::alias`] are checked instead.
+    pub fn get_raw(&self, section: impl AsRef<str>, name: impl AsRef<str>) -> Option<Text> {
+        let (section, name) = (section.as_ref(), name.as_ref());
+        let values = match self.get_values(section, name) {
+            Some(values) => values,
+            None => {
+                // Like `ConfigItem::sources`, the last set alias is effective.
+                let aliases = schema::registered_aliases(section, name);
+                aliases
+                    .iter()
+                    .rev()
+                    .find_map(|(section, name)| self.get_values(section, name))?
+            }
+        };
+        values.last().and_then(|value| value.value.clone())
+    }
+
+    fn get_values(&self, section: &str, name: &str) -> Option<&Vec<ValueSource>> {
+        self.sections
+            .get(section)
+            .and_then(|section| section.items.get(name))
     }
 
     /// Get detailed sources of a given config, including overrides, and source information.
     /// The last item in the returned vector is the latest value that is considered effective.
     ///
+    /// Values are interpolated like [`ConfigSet::get`]. For values that contain `${...}`
+    /// references, [`ValueSource::interpolation`] explains where the expanded parts come from.
+    ///
     /// Return an emtpy vector if the config does not exist.
     pub fn get_sources(&self, section: impl AsRef<str>, name: impl AsRef<str>) -> Vec<ValueSource> {
-        self.sections
-            .get(section.as_ref())
-            .and_then(|section| section.items.get(name.as_ref()).cloned())
-            .unwrap_or_default()
+        let (section, name) = (section.as_ref(), name.as_ref());
+        let mut stack = vec![(section.to_string(), name.to_string())];
+        self.get_sources_internal(section, name, &mut stack)
+    }
+
+    fn get_sources_internal(
+        &self,
+        section: &str,
+        name: &str,
+        stack: &mut Vec<(String, String)>,
+    ) -> Vec<ValueSource> {
+        let mut sources = self
+            .sections
+            .get(section)
+            .and_then(|section| section.items.get(name).cloned())
+            .unwrap_or_default();
+        for source in sources.iter_mut() {
+            if let Some(raw) = source.value.clone() {
+                if raw.contains("${") {
+                    let mut references = Vec::new();
+                    let expanded =
+                        self.interpolate(&raw, stack, &mut Vec::new(), Some(&mut references));
+                    source.value = Some(Text::from(expanded));
+                    source.interpolation = Some(Box::new(Interpolation { raw, references }));
+                }
+            }
+        }
+        sources
+    }
+
+    /// Check `${section.name}` references of all config values. Return an error for each
+    /// config whose value refers to itself, directly or indirectly.
+    ///
+    /// References forming a cycle are not expanded by [`ConfigSet::get`].
+    pub fn check_interpolation(&self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for (section_name, section) in self.sections.iter() {
+            for (name, values) in section.items.iter() {
+                if let Some(Some(value)) = values.last().map(|v| &v.value) {
+                    if value.contains("${") {
+                        let mut stack = vec![(section_name.to_string(), name.to_string())];
+                        let mut cycles = Vec::new();
+                        self.interpolate(value, &mut stack, &mut cycles, None);
+                        if let Some(cycle) = cycles.into_iter().next() {
+                            errors.push(Error::Interpolation(
+                                format!("{}.{}", stack[0].0, stack[0].1),
+                                format!("reference cycle: {}", cycle),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        errors
+    }
+
+    /// Expand `${section.name}`, `${env:NAME}` and `$${` in `raw`.
+    ///
+    /// `stack` contains the configs being expanded. A reference to one of them is a cycle.
+    /// It is recorded in `cycles` and left unexpanded. If `references` is set, referred configs
+    /// and environment variables are appended to it.
+    fn interpolate(
+        &self,
+        raw: &str,
+        stack: &mut Vec<(String, String)>,
+        cycles: &mut Vec<String>,
+        mut references: Option<&mut Vec<Reference>>,
+    ) -> String {
+        let mut result = String::with_capacity(raw.len());
+        let mut rest = raw;
+        while let Some(index) = rest.find("${") {
+            if index > 0 && rest[..index].ends_with('$') {
+                // "$${" is an escaped "${".
+                result.push_str(&rest[..index - 1]);
+                result.push_str("${");
+                rest = &rest[index + 2..];
+                continue;
+            }
+            result.push_str(&rest[..index]);
+            let end = match rest[index..].find('}') {
+                Some(end) => index + end,
+                None => {
+                    rest = &rest[index..];
+                    break;
+                }
+            };
+            let reference = &rest[index..=end];
+            let inner = &rest[index + 2..end];
+            rest = &rest[end + 1..];
+
+            if inner.starts_with("env:") && is_env_name(&inner[4..]) {
+                let name = &inner[4..];
+                let value = env::var(name).ok();
+                result.push_str(value.as_deref().unwrap_or(""));
+                if let Some(references) = references.as_mut() {
+                    references.push(Reference::Env {
+                        name: Text::copy_from_slice(name),
+                        value: value.map(Text::from),
+                    });
+                }
+            } else if let (Some(dot), true) = (inner.find('.'), is_config_reference(inner)) {
+                let key = (inner[..dot].to_string(), inner[dot + 1..].to_string());
+                if let Some(position) = stack.iter().position(|k| k == &key) {
+                    let mut cycle: Vec<String> = stack[position..]
+                        .iter()
+                        .map(|(s, n)| format!("{}.{}", s, n))
+                        .collect();
+                    cycle.push(inner.to_string());
+                    cycles.push(cycle.join(" -> "));
+                    result.push_str(reference);
+                    continue;
+                }
+                if let Some(value) = self.get_raw(&key.0, &key.1) {
+                    stack.push(key.clone());
+                    result.push_str(&self.interpolate(&value, stack, cycles, None));
+                    stack.pop();
+                }
+                if let Some(references) = references.as_mut() {
+                    stack.push(key.clone());
+                    let sources = self.get_sources_internal(&key.0, &key.1, stack);
+                    stack.pop();
+                    references.push(Reference::Config {
+                        section: Text::from(key.0),
+                        name: Text::from(key.1),
+                        sources,
+                    });
+                }
+            } else {
+                result.push_str(reference);
+            }
+        }
+        result.push_str(rest);
+        result
     }
 
     /// Set a config item directly. `section`, `name` locates the config. `value` is the new value.
@@ -192,6 +387,7 @@ impl ConfigSet {
                     value,
                     location,
                     source: opts.source.clone(),
+                    interpolation: None,
                 })
         }
     }
@@ -243,6 +439,46 @@ impl ConfigSet {
         // reported in `errors`.
     }
 
+    fn load_include(
+        &mut self,
+        base: &Path,
+        include_path: &Path,
+        opts: &Options,
+        visited: &mut HashSet<PathBuf>,
+        errors: &mut Vec<Error>,
+    ) {
+        let full_include_path = base.join(include_path);
+        let include_str = include_path.to_string_lossy();
+        if include_str.contains(&['*', '?', '['][..]) {
+            // Glob patterns. Escape the base directory so only the include path is a pattern.
+            let pattern = if include_path.is_absolute() {
+                include_str.to_string()
+            } else {
+                let base = glob::Pattern::escape(&base.to_string_lossy());
+                Path::new(&base)
+                    .join(include_path)
+                    .to_string_lossy()
+                    .to_string()
+            };
+            match glob::glob(&pattern) {
+                Ok(paths) => {
+                    let mut paths: Vec<PathBuf> = paths.filter_map(|p| p.ok()).collect();
+                    paths.sort();
+                    for path in paths {
+                        self.load_file(&path, opts, visited, errors);
+                    }
+                }
+                Err(error) => errors.push(Error::Parse(
+                    full_include_path,
+                    format!("invalid include pattern: {}", error),
+                )),
+            }
+            return;
+        }
+
+        self.load_file(&full_include_path, opts, visited, errors);
+    }
+
     fn load_file_content(
         &mut self,
         path: &Path,
@@ -252,6 +488,7 @@ impl ConfigSet {
         errors: &mut Vec<Error>,
     ) {
         let mut section = Text::new();
+        let mut section_enabled = true;
         let shared_path = Arc::new(path.to_path_buf()); // use Arc to do shallow copy
         let skip_include = path.parent().is_none(); // skip handling %include if path is empty
 
@@ -301,26 +538,50 @@ impl ConfigSet {
             unreachable!();
         };
 
-        let handle_section = |pair: Pair, section: &mut Text| {
-            let pairs = pair.into_inner();
-            for pair in pairs {
-                if let Rule::section_name = pair.as_rule() {
-                    *section = extract(&buf, pair.as_span());
-                    return;
+        let handle_section =
+            |pair: Pair, section: &mut Text, enabled: &mut bool, errors: &mut Vec<Error>| {
+                let pairs = pair.into_inner();
+                for pair in pairs {
+                    if let Rule::section_name = pair.as_rule() {
+                        let section_name = extract(&buf, pair.as_span());
+                        // "[name if key=value ...]" is a conditional section.
+                        match section_name.find(" if ") {
+                            Some(index) => {
+                                *section = strip_whitespace(&section_name, 0, index);
+                                let spec = &section_name[index + 4..];
+                                let conditions = match opts.conditions {
+                                    Some(ref conditions) => conditions.matches(spec),
+                                    None => Conditions::from_system().matches(spec),
+                                };
+                                *enabled = match conditions {
+                                    Ok(matched) => matched,
+                                    Err(message) => {
+                                        let message = format!("[{}]: {}", &*section_name, message);
+                                        errors.push(Error::Parse(path.to_path_buf(), message));
+                                        false
+                                    }
+                                };
+                            }
+                            None => {
+                                *section = section_name;
+                                *enabled = true;
+                            }
+                        }
+                        return;
+                    }
                 }
-            }
-            unreachable!();
-        };
+                unreachable!();
+            };
 
         let mut handle_include = |this: &mut ConfigSet, pair: Pair, errors: &mut Vec<Error>| {
             let pairs = pair.into_inner();
+            // "%include?" is the same as "%include". Missing files are ignored by both.
             for pair in pairs {
                 if let Rule::line = pair.as_rule() {
                     if !skip_include {
-                        let include_path = pair.as_str();
-                        let full_include_path =
-                            path.parent().unwrap().join(expand_path(include_path));
-                        this.load_file(&full_include_path, opts, visited, errors);
+                        let include_path = expand_path(pair.as_str());
+                        let base = path.parent().unwrap();
+                        this.load_include(base, &include_path, opts, visited, errors);
                     }
                 }
             }
@@ -365,9 +626,14 @@ impl ConfigSet {
 
         for pair in pairs {
             match pair.as_rule() {
-                Rule::config_item => handle_config_item(self, pair, section.clone()),
-                Rule::section => handle_section(pair, &mut section),
-                Rule::directive => handle_directive(self, pair, &section, errors),
+                Rule::config_item if section_enabled => {
+                    handle_config_item(self, pair, section.clone())
+                }
+                Rule::directive if section_enabled => {
+                    handle_directive(self, pair, &section, errors)
+                }
+                Rule::config_item | Rule::directive => {}
+                Rule::section => handle_section(pair, &mut section, &mut section_enabled, errors),
                 Rule::blank_line | Rule::comment_line | Rule::new_line | Rule::EOI => {}
 
                 Rule::comment_start
@@ -378,6 +644,7 @@ impl ConfigSet {
                 | Rule::include
                 | Rule::left_bracket
                 | Rule::line
+                | Rule::optional_include
                 | Rule::right_bracket
                 | Rule::section_name
                 | Rule::space
@@ -539,6 +806,24 @@ impl ConfigSet {
     }
 }
 
+/// Whether `name` can be used in a `${env:NAME}` reference.
+fn is_env_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whether `reference` can be used as a `${section.name}` reference. Other `${...}`, like
+/// shell parameter expansions in hooks and aliases, are left as-is.
+fn is_config_reference(reference: &str) -> bool {
+    match reference.find('.') {
+        Some(dot) => {
+            dot > 0
+                && dot + 1 < reference.len()
+                && !reference.contains(|c: char| c.is_whitespace() || c == '$' || c == '{')
+        }
+        None => false,
+    }
+}
+
 impl ValueSource {
     /// Return the actual value stored in this config value, or `None` if uset.
     pub fn value(&self) -> &Option<Text> {
@@ -562,6 +847,12 @@ impl ValueSource {
         }
     }
 
+    /// Return how `${...}` references in the value were expanded, or `None` if the value
+    /// does not use interpolation.
+    pub fn interpolation(&self) -> Option<&Interpolation> {
+        self.interpolation.as_ref().map(|i| i.as_ref())
+    }
+
     /// Return the file content. Or `None` if there is no such information.
     pub fn file_content(&self) -> Option<Text> {
         match self.location {
@@ -571,6 +862,18 @@ impl ValueSource {
     }
 }
 
+impl Interpolation {
+    /// Return the value before expanding `${...}` references.
+    pub fn raw(&self) -> &Text {
+        &self.raw
+    }
+
+    /// Return the references used by the value, in order.
+    pub fn references(&self) -> &[Reference] {
+        &self.references
+    }
+}
+
 impl Options {
     /// Create a default `Options`.
     pub fn new() -> Self {
@@ -591,6 +894,13 @@ impl Options {
         self
     }
 
+    /// Set the [`Conditions`] that `[section if key=value]` headers are tested against.
+    /// If not set, [`Conditions::from_system`] is used.
+    pub fn conditions(mut self, conditions: Conditions) -> Self {
+        self.conditions = Some(Arc::new(conditions));
+        self
+    }
+
     /// Set `source` information. It is about who initialized the config loading.  For example,
     /// "user_hgrc" indicates it is from the user config file, "--config" indicates it is from the
     /// global "--config" command line flag, "env" indicates it is translated from an environment
@@ -1083,6 +1393,196 @@ pub(crate) mod tests {
         assert_eq!(cfg.get("y", "b"), Some(Text::from("2")));
     }
 
+    #[test]
+    fn test_parse_include_optional_and_glob() {
+        let dir = TempDir::new("test_parse_include_optional_and_glob").unwrap();
+        write_file(
+            dir.path().join("rootrc"),
+            "%include? missing.rc\n\
+             %include rc.d/*.rc\n\
+             %include nothing.d/*.rc\n",
+        );
+        write_file(dir.path().join("rc.d/2.rc"), "[x]\na=2\nb=2");
+        write_file(dir.path().join("rc.d/1.rc"), "[x]\na=1\nc=1");
+        write_file(dir.path().join("rc.d/3.txt"), "[x]\na=3");
+
+        let mut cfg = ConfigSet::new();
+        let errors = cfg.load_path(dir.path().join("rootrc"), &"test".into());
+        assert!(errors.is_empty());
+        assert_eq!(cfg.get("x", "a"), Some(Text::from("2")));
+        assert_eq!(cfg.get("x", "b"), Some(Text::from("2")));
+        assert_eq!(cfg.get("x", "c"), Some(Text::from("1")));
+
+        // Like hg, a missing include is ignored without "?" too.
+        write_file(
+            dir.path().join("missingrc"),
+            "%include missing.rc\n[x]\na=1",
+        );
+        let mut cfg = ConfigSet::new();
+        let errors = cfg.load_path(dir.path().join("missingrc"), &"test".into());
+        assert!(errors.is_empty());
+        assert_eq!(cfg.get("x", "a"), Some(Text::from("1")));
+    }
+
+    #[test]
+    fn test_parse_conditional_sections() {
+        let dir = TempDir::new("test_parse_conditional_sections").unwrap();
+        write_file(dir.path().join("included.rc"), "[y]\na=1");
+        write_file(
+            dir.path().join("rootrc"),
+            "[x if platform=linux,windows]\n\
+             a=1\n\
+             %unset b\n\
+             [x if platform=osx]\n\
+             a=2\n\
+             %include included.rc\n\
+             [x if repo=foo* hostname=DEV*]\n\
+             c=3\n\
+             [x if color=red]\n\
+             d=4\n\
+             [x]\n\
+             e=5\n",
+        );
+
+        let conditions = Conditions::new()
+            .with_platform("linux")
+            .with_hostname("devvm1")
+            .with_repo("foobar");
+        let opts = Options::new().conditions(conditions);
+        let mut cfg = ConfigSet::new();
+        cfg.set("x", "b", Some("0"), &opts);
+        let errors = cfg.load_path(dir.path().join("rootrc"), &opts);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0]
+            .to_string()
+            .contains("unknown condition \"color\""));
+
+        assert_eq!(cfg.sections(), vec![Text::from("x")]);
+        assert_eq!(cfg.get("x", "a"), Some(Text::from("1")));
+        assert_eq!(cfg.get("x", "b"), None);
+        assert_eq!(cfg.get("x", "c"), Some(Text::from("3")));
+        assert_eq!(cfg.get("x", "d"), None);
+        assert_eq!(cfg.get("x", "e"), Some(Text::from("5")));
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let _guard = crate::ENV_LOCK.lock();
+        env::set_var("TEST_CONFIG_INTERPOLATION", "env");
+        env::remove_var("TEST_CONFIG_INTERPOLATION_UNSET");
+
+        let mut cfg = ConfigSet::new();
+        cfg.parse(
+            "[x]\n\
+             a = 1\n\
+             b = ${x.a}-${x.c}\n\
+             c = 3\n\
+             d = ${env:TEST_CONFIG_INTERPOLATION}/${env:TEST_CONFIG_INTERPOLATION_UNSET}\n\
+             e = $${x.a} ${x.unset} ${noop} ${x.a\n\
+             [cycle]\n\
+             a = ${cycle.b}!\n\
+             b = ${cycle.a}?\n\
+             c = ${cycle.c}\n",
+            &"test".into(),
+        );
+
+        assert_eq!(cfg.get("x", "b"), Some(Text::from("1-3")));
+        assert_eq!(cfg.get_raw("x", "b"), Some(Text::from("${x.a}-${x.c}")));
+        assert_eq!(cfg.get("x", "d"), Some(Text::from("env/")));
+        assert_eq!(cfg.get("x", "e"), Some(Text::from("${x.a}  ${noop} ${x.a")));
+
+        // Values are expanded lazily.
+        cfg.set("x", "a", Some("2"), &"set".into());
+        assert_eq!(cfg.get("x", "b"), Some(Text::from("2-3")));
+
+        // Cycles are left unexpanded.
+        assert_eq!(cfg.get("cycle", "a"), Some(Text::from("${cycle.a}?!")));
+        assert_eq!(cfg.get("cycle", "c"), Some(Text::from("${cycle.c}")));
+        let errors: Vec<String> = cfg
+            .check_interpolation()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "cycle.a: reference cycle: cycle.a -> cycle.b -> cycle.a",
+                "cycle.b: reference cycle: cycle.b -> cycle.a -> cycle.b",
+                "cycle.c: reference cycle: cycle.c -> cycle.c",
+            ]
+        );
+
+        // Serialization keeps raw values.
+        assert!(cfg.to_string().contains("b=${x.a}-${x.c}\n"));
+    }
+
+    #[test]
+    fn test_interpolation_passthrough() {
+        let mut cfg = ConfigSet::new();
+        let text = "[hooks]\n\
+                    a = echo ${HG_NODE} ${HG_PARENT1:-none} $HG_URL\n\
+                    b = python:hgext.foo.hook\n\
+                    c = echo ${ x.a } ${env:} ${env:A-B} ${.a} ${a.}\n\
+                    [alias]\n\
+                    d = !echo ${1} $@ \"${HG_ARGS}\"\n\
+                    [templatealias]\n\
+                    e = '{node|short} ${branch}\\n'\n";
+        cfg.parse(text, &"test".into());
+        for (section, name) in [
+            ("hooks", "a"),
+            ("hooks", "b"),
+            ("hooks", "c"),
+            ("alias", "d"),
+            ("templatealias", "e"),
+        ]
+        .iter()
+        {
+            assert_eq!(cfg.get(section, name), cfg.get_raw(section, name));
+            let sources = cfg.get_sources(section, name);
+            let interpolation = sources[0].interpolation();
+            assert!(interpolation.map_or(true, |i| i.references().is_empty()));
+        }
+        assert!(cfg.check_interpolation().is_empty());
+    }
+
+    #[test]
+    fn test_interpolation_sources() {
+        let mut cfg = ConfigSet::new();
+        cfg.parse("[x]\na = 1\nb = ${x.a}${x.c}\n", &"file".into());
+        cfg.set("x", "c", Some("${x.a}"), &"set".into());
+
+        let sources = cfg.get_sources("x", "b");
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].value(), &Some(Text::from("11")));
+        let interpolation = sources[0].interpolation().unwrap();
+        assert_eq!(interpolation.raw(), &Text::from("${x.a}${x.c}"));
+        let references = interpolation.references();
+        assert_eq!(references.len(), 2);
+        match &references[0] {
+            Reference::Config {
+                section,
+                name,
+                sources,
+            } => {
+                assert_eq!((section.as_ref(), name.as_ref()), ("x", "a"));
+                assert_eq!(sources[0].source(), &Text::from("file"));
+                assert!(sources[0].interpolation().is_none());
+            }
+            reference => panic!("unexpected reference: {:?}", reference),
+        }
+        match &references[1] {
+            Reference::Config { sources, .. } => {
+                assert_eq!(sources[0].source(), &Text::from("set"));
+                assert_eq!(sources[0].value(), &Some(Text::from("1")));
+                let nested = sources[0].interpolation().unwrap().references();
+                assert_eq!(nested.len(), 1);
+            }
+            reference => panic!("unexpected reference: {:?}", reference),
+        }
+
+        assert!(cfg.get_sources("x", "a")[0].interpolation().is_none());
+    }
+
     #[test]
     fn test_serialize() {
         let mut cfg = ConfigSet::new();
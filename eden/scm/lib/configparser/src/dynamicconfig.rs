@@ -137,28 +137,8 @@ impl Generator {
     pub fn new(repo_name: String, repo_path: PathBuf, user_name: String) -> Result<Self> {
         let repo = Repo::from_str(&repo_name)?;
 
-        let mut tiers: HashSet<String> = if Path::new("/etc/smc.tiers").exists() {
-            fs::read_to_string("/etc/smc.tiers")?
-                .split_whitespace()
-                .filter(|s| s.len() > 0)
-                .map(|s| s.to_string())
-                .collect()
-        } else {
-            HashSet::new()
-        };
-
-        if Path::new("/etc/fbitwhoami").exists() {
-            let raw_json = fs::read_to_string("/etc/fbitwhoami")?;
-            let value: Value = serde_json::from_str(raw_json.as_ref())?;
-            if let Some(Some(tier)) = value.get("tier").map(|v| v.as_str()) {
-                tiers.insert(tier.to_string());
-            }
-        }
-
-        let hostname = hostname::get()?
-            .to_string_lossy()
-            .to_string()
-            .to_lowercase();
+        let tiers = get_tiers()?;
+        let hostname = get_hostname()?;
 
         let shard = get_shard(&hostname);
         let user_shard = get_shard(&user_name);
@@ -319,6 +299,35 @@ impl Generator {
     }
 }
 
+pub(crate) fn get_tiers() -> Result<HashSet<String>> {
+    let mut tiers: HashSet<String> = if Path::new("/etc/smc.tiers").exists() {
+        fs::read_to_string("/etc/smc.tiers")?
+            .split_whitespace()
+            .filter(|s| s.len() > 0)
+            .map(|s| s.to_string())
+            .collect()
+    } else {
+        HashSet::new()
+    };
+
+    if Path::new("/etc/fbitwhoami").exists() {
+        let raw_json = fs::read_to_string("/etc/fbitwhoami")?;
+        let value: Value = serde_json::from_str(raw_json.as_ref())?;
+        if let Some(Some(tier)) = value.get("tier").map(|v| v.as_str()) {
+            tiers.insert(tier.to_string());
+        }
+    }
+
+    Ok(tiers)
+}
+
+pub(crate) fn get_hostname() -> Result<String> {
+    Ok(hostname::get()?
+        .to_string_lossy()
+        .to_string()
+        .to_lowercase())
+}
+
 fn get_shard(input: &str) -> u8 {
     let mut hasher = DefaultHasher::new();
     input.hash(&mut hasher);
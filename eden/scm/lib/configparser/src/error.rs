@@ -34,6 +34,10 @@ pub enum Error {
 
     #[error("{0:?}: {1}")]
     Utf8Path(CString, #[source] str::Utf8Error),
+
+    /// A config value cannot be interpolated. For example, it refers to itself.
+    #[error("{0}: {1}")]
+    Interpolation(String, String),
 }
 
 #[derive(Error, Debug)]
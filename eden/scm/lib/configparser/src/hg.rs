@@ -23,6 +23,7 @@ use minibytes::Text;
 use tempfile::tempfile_in;
 use util::{path::expand_path, run_background};
 
+use crate::condition::Conditions;
 use crate::config::{ConfigSet, Options};
 use crate::dynamicconfig::Generator;
 use crate::error::{Error, Errors};
@@ -99,6 +100,21 @@ pub trait ConfigSetHgExt {
     fn get_or_default<T: Default + FromConfigValue>(&self, section: &str, name: &str) -> Result<T> {
         self.get_or(section, name, Default::default)
     }
+
+    /// Get a config item. Convert to type `T`.
+    ///
+    /// If the config item is not set, convert the default value of the item
+    /// registered using [`crate::schema::register`] instead. It is an error
+    /// if the item has no registered default value.
+    fn get_or_schema_default<T: FromConfigValue>(&self, section: &str, name: &str) -> Result<T> {
+        match self.get_opt(section, name)? {
+            Some(value) => Ok(value),
+            None => match crate::schema::registered_default(section, name) {
+                Some(value) => T::try_from_str(&value),
+                None => bail!("config {}.{} has no default value", section, name),
+            },
+        }
+    }
 }
 
 pub trait FromConfigValue: Sized {
@@ -246,7 +262,11 @@ impl ConfigSetHgExt for ConfigSet {
     ) -> Result<()> {
         let mut errors = vec![];
 
-        let mut opts = Options::new();
+        let mut conditions = Conditions::from_system();
+        if let Some(repo_name) = repo_path.and_then(read_repo_name) {
+            conditions = conditions.with_repo(repo_name);
+        }
+        let mut opts = Options::new().conditions(conditions);
         if let Some(readonly_items) = readonly_items {
             opts = opts.readonly_items(readonly_items);
         }
@@ -264,6 +284,7 @@ impl ConfigSetHgExt for ConfigSet {
         if let Some(repo_path) = repo_path {
             errors.append(&mut self.load_repo(&repo_path, opts.clone()));
         }
+        errors.append(&mut self.check_interpolation());
 
         if !errors.is_empty() {
             return Err(Errors(errors).into());
@@ -453,6 +474,16 @@ impl ConfigSetHgExt for ConfigSet {
     }
 }
 
+/// Read the repo name used by `[section if repo=...]` conditions from the repo hgrc.
+fn read_repo_name(repo_path: &Path) -> Option<String> {
+    let mut temp_config = ConfigSet::new();
+    let opts = Options::new().source("temp");
+    temp_config.load_path(repo_path.join("hgrc"), &opts);
+    temp_config
+        .get("remotefilelog", "reponame")
+        .map(|name| name.to_string())
+}
+
 impl ConfigSet {
     // For easier testing.
     pub(crate) fn load_user_internal(&mut self, paths: &[PathBuf], opts: Options) -> Vec<Error> {
@@ -46,6 +46,53 @@
 //! file being parsed. If it's a directory, files with names ending
 //! with `.rc` in it will be read.
 //!
+//! Including a file that does not exist is not an error. `%include?`
+//! can be used to make that explicit:
+//!
+//! ```plain,ignore
+//! %include? ~/.hgrc.d/local.rc
+//! ```
+//!
+//! If the include path contains `*`, `?` or `[`, it is a glob pattern.
+//! Matched files are included in sorted order:
+//!
+//! ```plain,ignore
+//! %include hgrc.d/*.rc
+//! ```
+//!
+//! ### Conditional sections
+//!
+//! A section header can have conditions. Config items and directives in
+//! the section are ignored unless all conditions match:
+//!
+//! ```plain,ignore
+//! [ui if platform=linux,osx hostname=devvm*]
+//! editor = vim
+//! ```
+//!
+//! Supported keys are `platform`, `hostname`, `repo` and `tier`. Values
+//! are comma-separated glob patterns, matched case-insensitively. See
+//! [`condition::Conditions`] for details.
+//!
+//! ### Interpolation
+//!
+//! Use `${section.name}` to refer to another config, and `${env:NAME}`
+//! to refer to an environment variable:
+//!
+//! ```plain,ignore
+//! [paths]
+//! base = ${env:HOME}/repos
+//! default = ${paths.base}/main
+//! ```
+//!
+//! References are expanded when reading a config, so they use the
+//! latest values. Unset configs or environment variables expand to an
+//! empty string. References forming a cycle are left unexpanded. Use
+//! `$${` for a literal `${`.
+//!
+//! Other `${...}` forms, like `${HG_NODE}` or `${1}` used by shell hooks
+//! and aliases, are kept as-is.
+//!
 //! ### Unset a config
 //!
 //! Use `%unset` to unset a config:
@@ -67,11 +114,13 @@
 //! ```
 
 pub mod c_api;
+pub mod condition;
 pub mod config;
 pub mod dynamicconfig;
 pub mod error;
 pub mod hg;
 pub mod parser;
+pub mod schema;
 
 pub use error::{Error, Errors};
 
@@ -16,7 +16,7 @@
 // However, `#[grammar = "spec.pest"]` does not play well with Buck build,
 // because pest_derive cannot find "spec.pest" in buck build environment.
 // Therefore this file is @generated. @no-lint.
-// pest-checksum: af79d1287503f19c331643cd2e28d30f6312592f.
+// pest-checksum: 789bc80d0b5415fd2ae3a3c8ed5c615ca5d88753.
 
 
 #[allow(dead_code, non_camel_case_types)]
@@ -39,6 +39,7 @@ pub enum Rule {
     blank_line,
     directive,
     include,
+    optional_include,
     unset,
     compound,
     file,
@@ -454,26 +455,46 @@ impl ::pest::Parser<Rule> for ConfigParser {
                                                                                {
                                                                                    state.match_string("include").and_then(|state|
                                                                                                                               {
-                                                                                                                                  state.sequence(|state|
+                                                                                                                                  state.optional(|state|
                                                                                                                                                      {
-                                                                                                                                                         self::space(state).and_then(|state|
-                                                                                                                                                                                         {
-                                                                                                                                                                                             state.repeat(|state|
-                                                                                                                                                                                                              {
-                                                                                                                                                                                                                  self::space(state)
-                                                                                                                                                                                                              })
-                                                                                                                                                                                         })
+                                                                                                                                                         self::optional_include(state)
                                                                                                                                                      })
                                                                                                                               }).and_then(|state|
                                                                                                                                               {
-                                                                                                                                                  self::line(state)
-                                                                                                                                              })
+                                                                                                                                                  state.sequence(|state|
+                                                                                                                                                                     {
+                                                                                                                                                                         self::space(state).and_then(|state|
+                                                                                                                                                                                                         {
+                                                                                                                                                                                                             state.repeat(|state|
+                                                                                                                                                                                                                              {
+                                                                                                                                                                                                                                  self::space(state)
+                                                                                                                                                                                                                              })
+                                                                                                                                                                                                         })
+                                                                                                                                                                     })
+                                                                                                                                              }).and_then(|state|
+                                                                                                                                                              {
+                                                                                                                                                                  self::line(state)
+                                                                                                                                                              })
                                                                                })
                                                         })
                                      })
                 }
                 #[inline]
                 #[allow(non_snake_case, unused_variables)]
+                pub fn optional_include(state: Box<::pest::ParserState<Rule>>)
+                 -> ::pest::ParseResult<Box<::pest::ParserState<Rule>>> {
+                    state.rule(Rule::optional_include,
+                               |state|
+                                   {
+                                       state.atomic(::pest::Atomicity::Atomic,
+                                                    |state|
+                                                        {
+                                                            state.match_string("?")
+                                                        })
+                                   })
+                }
+                #[inline]
+                #[allow(non_snake_case, unused_variables)]
                 pub fn unset(state: Box<::pest::ParserState<Rule>>)
                  -> ::pest::ParseResult<Box<::pest::ParserState<Rule>>> {
                     state.atomic(::pest::Atomicity::CompoundAtomic,
@@ -641,6 +662,8 @@ impl ::pest::Parser<Rule> for ConfigParser {
                                   rules::blank_line(state),
                                   Rule::directive => rules::directive(state),
                                   Rule::include => rules::include(state),
+                                  Rule::optional_include =>
+                                  rules::optional_include(state),
                                   Rule::unset => rules::unset(state),
                                   Rule::compound => rules::compound(state),
                                   Rule::file => rules::file(state),
@@ -0,0 +1,700 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Typed descriptions of known config items.
+//!
+//! Crates describe the configs they read using [`ConfigItem`] and add them to
+//! the process-wide registry using [`register`]. [`ConfigSet::validate`]
+//! checks a loaded config against a [`Schema`] so typos in section or config
+//! names, and values that cannot be parsed, are reported instead of being
+//! silently ignored.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+
+use indexmap::IndexMap;
+use lazy_static::lazy_static;
+use minibytes::Text;
+use parking_lot::RwLock;
+
+use crate::config::{ConfigSet, ValueSource};
+use crate::hg::{ByteCount, FromConfigValue};
+
+lazy_static! {
+    static ref REGISTRY: RwLock<Schema> = RwLock::new(builtin_schema());
+}
+
+/// Add items to the process-wide schema. Items registered later replace
+/// items with the same section and name.
+pub fn register(items: impl IntoIterator<Item = ConfigItem>) {
+    let mut registry = REGISTRY.write();
+    for item in items {
+        registry.register(item);
+    }
+}
+
+/// Mark sections in the process-wide schema as strict.
+/// See [`Schema::strict_section`].
+pub fn register_strict_sections(sections: impl IntoIterator<Item = &'static str>) {
+    let mut registry = REGISTRY.write();
+    for section in sections {
+        registry.strict_section(section);
+    }
+}
+
+/// Return a snapshot of the process-wide schema.
+pub fn registered() -> Schema {
+    REGISTRY.read().clone()
+}
+
+/// Deprecated names of `section.name` in the process-wide schema.
+pub(crate) fn registered_aliases(section: &str, name: &str) -> Vec<(Text, Text)> {
+    let key = (Text::copy_from_slice(section), Text::copy_from_slice(name));
+    REGISTRY
+        .read()
+        .items
+        .get(&key)
+        .map(|item| item.aliases.clone())
+        .unwrap_or_default()
+}
+
+/// Default value of `section.name` in the process-wide schema.
+pub(crate) fn registered_default(section: &str, name: &str) -> Option<Text> {
+    let key = (Text::copy_from_slice(section), Text::copy_from_slice(name));
+    REGISTRY
+        .read()
+        .items
+        .get(&key)
+        .and_then(|item| item.default.clone())
+}
+
+/// Configs read by this crate.
+fn builtin_schema() -> Schema {
+    let mut schema = Schema::new();
+    schema
+        .register(
+            ConfigItem::new("configs", "generationtime", ValueType::Int)
+                .doc("seconds before the dynamic config is regenerated in the background"),
+        )
+        .register(
+            ConfigItem::new("remotefilelog", "reponame", ValueType::String)
+                .doc("name of the repo, used by dynamic config and `[section if repo=...]`"),
+        )
+        .register(
+            ConfigItem::new("ui", "merge", ValueType::String)
+                .doc("the merge tool used for interactive merges"),
+        )
+        .register(
+            ConfigItem::new("ui", "username", ValueType::String)
+                .doc("the author name used for commits"),
+        );
+    schema
+}
+
+/// The type of a config value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueType {
+    Bool,
+    Int,
+    Float,
+    String,
+    /// A size like `1.5 MB`. See [`ByteCount`].
+    ByteCount,
+    Path,
+    /// Comma or space separated strings. See [`crate::hg::parse_list`].
+    List,
+}
+
+impl ValueType {
+    /// Name of the type.
+    pub fn name(self) -> &'static str {
+        match self {
+            ValueType::Bool => "bool",
+            ValueType::Int => "int",
+            ValueType::Float => "float",
+            ValueType::String => "string",
+            ValueType::ByteCount => "bytecount",
+            ValueType::Path => "path",
+            ValueType::List => "list",
+        }
+    }
+
+    /// Check whether `value` can be converted to this type.
+    fn check(self, value: &str) -> Result<(), String> {
+        let result = match self {
+            ValueType::Bool => bool::try_from_str(value).map(|_| ()),
+            ValueType::Int => i64::try_from_str(value).map(|_| ()),
+            ValueType::Float => f64::try_from_str(value).map(|_| ()),
+            ValueType::ByteCount => ByteCount::try_from_str(value).map(|_| ()),
+            ValueType::String | ValueType::Path | ValueType::List => Ok(()),
+        };
+        result.map_err(|e| format!("invalid {} {:?}: {}", self.name(), value, e))
+    }
+}
+
+/// Description of a config item.
+#[derive(Clone, Debug)]
+pub struct ConfigItem {
+    section: Text,
+    name: Text,
+    value_type: ValueType,
+    default: Option<Text>,
+    allowed: Vec<Text>,
+    doc: Text,
+    aliases: Vec<(Text, Text)>,
+}
+
+impl ConfigItem {
+    /// Describe the config `section.name` of the given type.
+    pub fn new(section: impl Into<Text>, name: impl Into<Text>, value_type: ValueType) -> Self {
+        Self {
+            section: section.into(),
+            name: name.into(),
+            value_type,
+            default: None,
+            allowed: Vec::new(),
+            doc: Text::new(),
+            aliases: Vec::new(),
+        }
+    }
+
+    /// Set the default value, used when the config is not set.
+    pub fn default(mut self, value: impl Into<Text>) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    /// Restrict the value to the given choices. Choices are compared case-insensitively.
+    pub fn allowed<T: Into<Text>>(mut self, values: impl IntoIterator<Item = T>) -> Self {
+        self.allowed = values.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the documentation.
+    pub fn doc(mut self, doc: impl Into<Text>) -> Self {
+        self.doc = doc.into();
+        self
+    }
+
+    /// Add a deprecated name for this config. Once the item is [`register`]ed,
+    /// values set using the deprecated name are still effective, but
+    /// [`ConfigSet::validate`] warns about them.
+    pub fn alias(mut self, section: impl Into<Text>, name: impl Into<Text>) -> Self {
+        self.aliases.push((section.into(), name.into()));
+        self
+    }
+
+    pub fn section(&self) -> &Text {
+        &self.section
+    }
+
+    pub fn name(&self) -> &Text {
+        &self.name
+    }
+
+    pub fn value_type(&self) -> ValueType {
+        self.value_type
+    }
+
+    pub fn default_value(&self) -> Option<&Text> {
+        self.default.as_ref()
+    }
+
+    pub fn allowed_values(&self) -> &[Text] {
+        &self.allowed
+    }
+
+    pub fn documentation(&self) -> &Text {
+        &self.doc
+    }
+
+    pub fn aliases(&self) -> &[(Text, Text)] {
+        &self.aliases
+    }
+
+    /// Get the effective value sources, including values set using deprecated
+    /// names. The last item is the effective value.
+    pub fn sources(&self, config: &ConfigSet) -> Vec<ValueSource> {
+        // Aliases are deprecated. Values set using the real name win.
+        let mut sources = Vec::new();
+        for (section, name) in self.aliases.iter() {
+            sources.extend(config.get_sources(section, name));
+        }
+        sources.extend(config.get_sources(&self.section, &self.name));
+        sources
+    }
+
+    /// Check a (non-empty) value against the type and allowed values.
+    fn check(&self, value: &str) -> Result<(), WarningKind> {
+        if !self.allowed.is_empty()
+            && !self
+                .allowed
+                .iter()
+                .any(|v| v.to_lowercase() == value.to_lowercase())
+        {
+            return Err(WarningKind::NotAllowed(
+                Text::copy_from_slice(value),
+                self.allowed.clone(),
+            ));
+        }
+        self.value_type.check(value).map_err(WarningKind::Invalid)
+    }
+}
+
+/// A collection of [`ConfigItem`]s.
+#[derive(Clone, Debug, Default)]
+pub struct Schema {
+    items: IndexMap<(Text, Text), ConfigItem>,
+    aliases: HashMap<(Text, Text), (Text, Text)>,
+    strict_sections: HashSet<Text>,
+}
+
+impl Schema {
+    /// Return an empty `Schema`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add an item. It replaces an existing item with the same section and name.
+    pub fn register(&mut self, item: ConfigItem) -> &mut Self {
+        let key = (item.section.clone(), item.name.clone());
+        for alias in item.aliases.iter() {
+            self.aliases.insert(alias.clone(), key.clone());
+        }
+        self.items.insert(key, item);
+        self
+    }
+
+    /// Mark a section as strict: all configs in it are described by the schema.
+    ///
+    /// Other sections can have configs that are not described by the schema
+    /// (for example, configs only read by Python). In those sections, only
+    /// unknown names that look like a typo of a known name are reported.
+    pub fn strict_section(&mut self, section: impl Into<Text>) -> &mut Self {
+        self.strict_sections.insert(section.into());
+        self
+    }
+
+    /// Look up an item by its name. Deprecated names are not resolved.
+    pub fn get(&self, section: &str, name: &str) -> Option<&ConfigItem> {
+        self.items
+            .get(&(Text::copy_from_slice(section), Text::copy_from_slice(name)))
+    }
+
+    /// Iterate through items, in registration order.
+    pub fn items(&self) -> impl Iterator<Item = &ConfigItem> {
+        self.items.values()
+    }
+
+    fn sections(&self) -> HashSet<&str> {
+        self.items.keys().map(|(s, _)| s.as_ref()).collect()
+    }
+
+    fn resolve_alias(&self, section: &str, name: &str) -> Option<&ConfigItem> {
+        let key = (Text::copy_from_slice(section), Text::copy_from_slice(name));
+        self.aliases.get(&key).and_then(|key| self.items.get(key))
+    }
+}
+
+/// A problem found by [`ConfigSet::validate`].
+#[derive(Clone, Debug)]
+pub struct Warning {
+    section: Text,
+    name: Text,
+    kind: WarningKind,
+    source: Text,
+    location: Option<(PathBuf, usize)>,
+}
+
+/// Kinds of [`Warning`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum WarningKind {
+    /// The section is unknown, but looks like a typo of the given section.
+    UnknownSection(Text),
+
+    /// The config name is unknown. The name it might be a typo of, if any.
+    UnknownName(Option<Text>),
+
+    /// The value cannot be converted to the type of the config.
+    Invalid(String),
+
+    /// The value is not one of the allowed values.
+    NotAllowed(Text, Vec<Text>),
+
+    /// The name is deprecated. `(section, name)` should be used instead.
+    Deprecated(Text, Text),
+}
+
+impl Warning {
+    pub fn section(&self) -> &Text {
+        &self.section
+    }
+
+    pub fn name(&self) -> &Text {
+        &self.name
+    }
+
+    pub fn kind(&self) -> &WarningKind {
+        &self.kind
+    }
+
+    /// The "source" of the problematic value. See [`ValueSource::source`].
+    pub fn source(&self) -> &Text {
+        &self.source
+    }
+
+    /// The file path and 1-based line number of the problematic value, if known.
+    pub fn location(&self) -> Option<(PathBuf, usize)> {
+        self.location.clone()
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.location {
+            Some((ref path, line)) => write!(f, "{}:{}: ", path.display(), line)?,
+            None => write!(f, "{}: ", self.source.as_ref())?,
+        }
+        let section: &str = self.section.as_ref();
+        let name: &str = self.name.as_ref();
+        match &self.kind {
+            WarningKind::UnknownSection(suggestion) => write!(
+                f,
+                "unknown section [{}] (did you mean [{}]?)",
+                section,
+                suggestion.as_ref()
+            ),
+            WarningKind::UnknownName(Some(suggestion)) => write!(
+                f,
+                "unknown config {}.{} (did you mean {}.{}?)",
+                section,
+                name,
+                section,
+                suggestion.as_ref()
+            ),
+            WarningKind::UnknownName(None) => write!(f, "unknown config {}.{}", section, name),
+            WarningKind::Invalid(message) => write!(f, "{}.{}: {}", section, name, message),
+            WarningKind::NotAllowed(value, allowed) => {
+                let allowed: Vec<&str> = allowed.iter().map(|v| v.as_ref()).collect();
+                write!(
+                    f,
+                    "{}.{}: {:?} is not one of {}",
+                    section,
+                    name,
+                    value.as_ref(),
+                    allowed.join(", ")
+                )
+            }
+            WarningKind::Deprecated(new_section, new_name) => write!(
+                f,
+                "{}.{} is deprecated, use {}.{} instead",
+                section,
+                name,
+                new_section.as_ref(),
+                new_name.as_ref()
+            ),
+        }
+    }
+}
+
+impl ConfigSet {
+    /// Check configs against `schema`. Only effective (last set) values are checked.
+    ///
+    /// Report:
+    /// - Sections whose names look like typos of sections in `schema`.
+    /// - Unknown config names in strict sections, and unknown config names
+    ///   that look like typos of known names in other sections.
+    /// - Values that are invalid for the config type, or not allowed.
+    /// - Configs set using deprecated names.
+    pub fn validate(&self, schema: &Schema) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        let known_sections = schema.sections();
+
+        for section in self.sections() {
+            let section_str: &str = section.as_ref();
+            let section_known = known_sections.contains(section_str)
+                || schema.strict_sections.contains(section_str)
+                || schema.aliases.keys().any(|(s, _)| s == &section);
+            if !section_known {
+                if let Some(suggestion) = suggest(section_str, known_sections.iter().cloned()) {
+                    // One warning per section is enough.
+                    if let Some(name) = self.keys(section_str).first() {
+                        let kind = WarningKind::UnknownSection(Text::from(suggestion.to_string()));
+                        push_warning(self, &mut warnings, &section, name, kind);
+                    }
+                }
+                continue;
+            }
+
+            for name in self.keys(section_str) {
+                let name_str: &str = name.as_ref();
+                let item = match schema.get(section_str, name_str) {
+                    Some(item) => item,
+                    None => match schema.resolve_alias(section_str, name_str) {
+                        Some(item) => {
+                            let kind =
+                                WarningKind::Deprecated(item.section.clone(), item.name.clone());
+                            push_warning(self, &mut warnings, &section, &name, kind);
+                            item
+                        }
+                        None => {
+                            let names = schema
+                                .items()
+                                .filter(|i| i.section == section)
+                                .map(|i| i.name.as_ref());
+                            let suggestion = suggest(name_str, names);
+                            if suggestion.is_some() || schema.strict_sections.contains(section_str)
+                            {
+                                let suggestion = suggestion.map(|s| Text::from(s.to_string()));
+                                let kind = WarningKind::UnknownName(suggestion);
+                                push_warning(self, &mut warnings, &section, &name, kind);
+                            }
+                            continue;
+                        }
+                    },
+                };
+                if let Some(value) = self.get(section_str, name_str) {
+                    if !value.is_empty() {
+                        if let Err(kind) = item.check(&value) {
+                            push_warning(self, &mut warnings, &section, &name, kind);
+                        }
+                    }
+                }
+            }
+        }
+
+        warnings
+    }
+}
+
+fn push_warning(
+    config: &ConfigSet,
+    warnings: &mut Vec<Warning>,
+    section: &Text,
+    name: &Text,
+    kind: WarningKind,
+) {
+    let sources = config.get_sources(section, name);
+    let last = sources.last();
+    warnings.push(Warning {
+        section: section.clone(),
+        name: name.clone(),
+        kind,
+        source: last.map(|s| s.source().clone()).unwrap_or_default(),
+        location: last.and_then(line_location),
+    })
+}
+
+/// Convert the byte range of a value to a 1-based line number.
+pub fn line_location(source: &ValueSource) -> Option<(PathBuf, usize)> {
+    let (path, range) = source.location()?;
+    if path.as_os_str().is_empty() {
+        return None;
+    }
+    let content = source.file_content()?;
+    let content: &str = content.as_ref();
+    let line = content[..range.start.min(content.len())]
+        .bytes()
+        .filter(|&b| b == b'\n')
+        .count()
+        + 1;
+    Some((path, line))
+}
+
+/// Find a candidate that `name` is likely a typo of.
+fn suggest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    // Allow one edit for short names, two edits for longer names.
+    let max_distance = if name.len() > 6 { 2 } else { 1 };
+    candidates
+        .filter(|c| *c != name)
+        .map(|c| (edit_distance(name, c), c))
+        .filter(|(d, _)| *d <= max_distance)
+        .min()
+        .map(|(_, c)| c)
+}
+
+/// Levenshtein distance between two strings, compared case-insensitively.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == cb { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::tests::write_file;
+    use crate::hg::ConfigSetHgExt;
+    use tempdir::TempDir;
+
+    fn test_schema() -> Schema {
+        let mut schema = Schema::new();
+        schema
+            .register(
+                ConfigItem::new("pager", "interface", ValueType::String)
+                    .allowed(vec!["fullscreen", "hybrid"])
+                    .default("hybrid"),
+            )
+            .register(
+                ConfigItem::new("blackbox", "maxfiles", ValueType::Int)
+                    .alias("blackbox", "maxlogs")
+                    .doc("number of log files to keep"),
+            )
+            .register(ConfigItem::new("blackbox", "maxsize", ValueType::ByteCount))
+            .register(ConfigItem::new("ui", "paginate", ValueType::Bool))
+            .strict_section("blackbox");
+        schema
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", "abc"), 0);
+        assert_eq!(edit_distance("maxfile", "maxfiles"), 1);
+        assert_eq!(edit_distance("pagre", "pager"), 2);
+        assert_eq!(edit_distance("Kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_validate() {
+        let dir = TempDir::new("test_validate").unwrap();
+        let path = dir.path().join("hgrc");
+        write_file(
+            path.clone(),
+            "[pager]\n\
+             interface = FullScreen\n\
+             [pagr]\n\
+             interface = hybrid\n\
+             [blackbox]\n\
+             maxfile = 3\n\
+             maxlogs = many\n\
+             maxsize = 1.5 MB\n\
+             unrelated = 1\n\
+             [ui]\n\
+             paginat = true\n\
+             verbose = true\n",
+        );
+        let mut cfg = ConfigSet::new();
+        assert!(cfg.load_path(&path, &"test".into()).is_empty());
+        cfg.set("ui", "paginate", Some("maybe"), &"--config".into());
+
+        let warnings: Vec<String> = cfg
+            .validate(&test_schema())
+            .iter()
+            .map(|w| w.to_string().replace(&path.display().to_string(), "hgrc"))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "hgrc:4: unknown section [pagr] (did you mean [pager]?)",
+                "hgrc:6: unknown config blackbox.maxfile (did you mean blackbox.maxfiles?)",
+                "hgrc:7: blackbox.maxlogs is deprecated, use blackbox.maxfiles instead",
+                "hgrc:7: blackbox.maxlogs: invalid int \"many\": invalid digit found in string",
+                "hgrc:9: unknown config blackbox.unrelated",
+                "hgrc:11: unknown config ui.paginat (did you mean ui.paginate?)",
+                "--config: ui.paginate: invalid bool \"maybe\": invalid bool: maybe",
+            ]
+        );
+
+        cfg.set("pager", "interface", Some("less"), &"--config".into());
+        let warnings = cfg.validate(&test_schema());
+        assert_eq!(
+            warnings[0].kind(),
+            &WarningKind::NotAllowed(
+                Text::from("less"),
+                vec![Text::from("fullscreen"), Text::from("hybrid")]
+            )
+        );
+        assert_eq!(warnings[0].location(), None);
+    }
+
+    #[test]
+    fn test_alias_sources() {
+        let schema = test_schema();
+        let item = schema.get("blackbox", "maxfiles").unwrap();
+        let mut cfg = ConfigSet::new();
+        cfg.set("blackbox", "maxlogs", Some("2"), &"old".into());
+        let sources = item.sources(&cfg);
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].value(), &Some(Text::from("2")));
+
+        cfg.set("blackbox", "maxfiles", Some("4"), &"new".into());
+        let sources = item.sources(&cfg);
+        assert_eq!(sources.last().unwrap().source(), &Text::from("new"));
+    }
+
+    #[test]
+    fn test_get_through_alias() {
+        register(vec![ConfigItem::new("test_alias", "new", ValueType::Int)
+            .alias("test_alias", "older")
+            .alias("test_alias_old", "old")]);
+        let mut cfg = ConfigSet::new();
+        assert_eq!(cfg.get("test_alias", "new"), None);
+
+        cfg.set("test_alias", "older", Some("1"), &"a".into());
+        assert_eq!(cfg.get("test_alias", "new"), Some("1".into()));
+
+        cfg.set("test_alias_old", "old", Some("2"), &"b".into());
+        assert_eq!(cfg.get("test_alias", "new"), Some("2".into()));
+        assert_eq!(cfg.get_or::<i64>("test_alias", "new", || 0).unwrap(), 2);
+
+        // The real name wins, even if it is unset.
+        cfg.set("test_alias", "new", Some("3"), &"c".into());
+        assert_eq!(cfg.get("test_alias", "new"), Some("3".into()));
+        cfg.set("test_alias", "new", None, &"d".into());
+        assert_eq!(cfg.get("test_alias", "new"), None);
+
+        // Deprecated names are not resolved the other way around.
+        assert_eq!(cfg.get("test_alias", "older"), Some("1".into()));
+    }
+
+    #[test]
+    fn test_get_schema_default() {
+        register(vec![
+            ConfigItem::new("test_default", "size", ValueType::ByteCount).default("4 KB"),
+            ConfigItem::new("test_default", "count", ValueType::Int),
+        ]);
+        let mut cfg = ConfigSet::new();
+        let size: ByteCount = cfg.get_or_schema_default("test_default", "size").unwrap();
+        assert_eq!(size.value(), 4096);
+        assert!(cfg
+            .get_or_schema_default::<i64>("test_default", "count")
+            .is_err());
+
+        cfg.set("test_default", "size", Some("1 MB"), &"test".into());
+        cfg.set("test_default", "count", Some("2"), &"test".into());
+        let size: ByteCount = cfg.get_or_schema_default("test_default", "size").unwrap();
+        assert_eq!(size.value(), 1 << 20);
+        assert_eq!(
+            cfg.get_or_schema_default::<i64>("test_default", "count")
+                .unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_register() {
+        register(vec![ConfigItem::new(
+            "test_register",
+            "x",
+            ValueType::Float,
+        )]);
+        let schema = registered();
+        assert!(schema.get("test_register", "x").is_some());
+        assert!(schema.get("remotefilelog", "reponame").is_some());
+    }
+}
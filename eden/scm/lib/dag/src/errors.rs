@@ -26,6 +26,11 @@ pub enum DagError {
     #[error("ProgrammingError: {0}")]
     Programming(String),
 
+    /// A revset expression cannot be parsed or evaluated. For example, it
+    /// has syntax errors, or calls unknown functions.
+    #[error("invalid revset: {0}")]
+    InvalidRevset(String),
+
     /// Logic error in this crate. A bug in this crate or the backend data.
     #[error("bug: {0}")]
     Bug(String),
@@ -44,7 +44,7 @@ impl IdDagStore for IndexedLogStore {
         let key = Self::serialize_head_level_lookup_key(head, level);
         match self.log.lookup(Self::INDEX_LEVEL_HEAD, &key)?.nth(0) {
             None => Ok(None),
-            Some(bytes) => Ok(Some(Segment(self.log.slice_to_bytes(bytes?)))),
+            Some(bytes) => Ok(Some(Segment(self.log.slice_to_bytes(&bytes?)))),
         }
     }
 
@@ -59,7 +59,7 @@ impl IdDagStore for IndexedLogStore {
             let (_, entries) = entry?;
             for entry in entries {
                 let entry = entry?;
-                let seg = Segment(self.log.slice_to_bytes(entry));
+                let seg = Segment(self.log.slice_to_bytes(&entry));
                 if seg.span()?.low > id {
                     return Ok(None);
                 }
@@ -95,7 +95,7 @@ impl IdDagStore for IndexedLogStore {
                 // break the logic here. If perf is really needed, we can change
                 // logic here to not checking values.
                 if let Some(bytes) = values.next() {
-                    let seg = Segment(self.log.slice_to_bytes(bytes?));
+                    let seg = Segment(self.log.slice_to_bytes(&bytes?));
                     Ok(seg.high()? + 1)
                 } else {
                     bug(format!("key {:?} should have values in next_free_id", key))
@@ -114,7 +114,7 @@ impl IdDagStore for IndexedLogStore {
         {
             let (_, values) = entry?;
             for value in values {
-                result.push(Segment(self.log.slice_to_bytes(value?)));
+                result.push(Segment(self.log.slice_to_bytes(&value?)));
             }
         }
         Ok(result)
@@ -137,7 +137,7 @@ impl IdDagStore for IndexedLogStore {
                     .into_iter()
                     .map(|value| {
                         let value = value?;
-                        Ok(Segment(self.log.slice_to_bytes(value)))
+                        Ok(Segment(self.log.slice_to_bytes(&value)))
                     })
                     .collect(),
                 Err(err) => vec![Err(err.into())],
@@ -161,7 +161,7 @@ impl IdDagStore for IndexedLogStore {
                 Ok((_key, values)) => values
                     .map(|value| {
                         let value = value?;
-                        Ok(Segment(self.log.slice_to_bytes(value)))
+                        Ok(Segment(self.log.slice_to_bytes(&value)))
                     })
                     .collect(),
                 Err(err) => vec![Err(err.into())],
@@ -181,7 +181,7 @@ impl IdDagStore for IndexedLogStore {
         let iter = self.log.lookup(Self::INDEX_PARENT, &key)?;
         let iter = iter.map(move |result| {
             match result {
-                Ok(bytes) => Ok(Segment(self.log.slice_to_bytes(bytes))),
+                Ok(bytes) => Ok(Segment(self.log.slice_to_bytes(&bytes))),
                 Err(err) => Err(err.into()),
             }
         });
@@ -199,7 +199,7 @@ impl IdDagStore for IndexedLogStore {
             let iter = self.log.lookup(Self::INDEX_PARENT, &key)?;
             let iter = iter.map(move |result| {
                 match result {
-                    Ok(bytes) => Ok(Segment(self.log.slice_to_bytes(bytes))),
+                    Ok(bytes) => Ok(Segment(self.log.slice_to_bytes(&bytes))),
                     Err(err) => Err(err.into()),
                 }
             });
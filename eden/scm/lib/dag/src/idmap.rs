@@ -246,10 +246,19 @@ mod tests {
         assert!(map.vertexes_by_hex_prefix(b"6b", 1).unwrap().is_empty());
 
         for _ in 0..=1 {
-            assert_eq!(map.find_name_by_id(Id(1)).unwrap().unwrap(), b"abc");
-            assert_eq!(map.find_name_by_id(Id(2)).unwrap().unwrap(), b"def");
+            assert_eq!(
+                map.find_name_by_id(Id(1)).unwrap().unwrap().as_ref(),
+                b"abc"
+            );
+            assert_eq!(
+                map.find_name_by_id(Id(2)).unwrap().unwrap().as_ref(),
+                b"def"
+            );
             assert!(map.find_name_by_id(Id(3)).unwrap().is_none());
-            assert_eq!(map.find_name_by_id(Id(10)).unwrap().unwrap(), b"ghi");
+            assert_eq!(
+                map.find_name_by_id(Id(10)).unwrap().unwrap().as_ref(),
+                b"ghi"
+            );
 
             assert_eq!(map.find_id_by_name(b"abc").unwrap().unwrap().0, 1);
             assert_eq!(map.find_id_by_name(b"def").unwrap().unwrap().0, 2);
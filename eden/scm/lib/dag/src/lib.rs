@@ -28,6 +28,7 @@ pub mod nameset;
 pub mod ops;
 pub mod protocol;
 pub mod render;
+pub mod revset;
 pub mod segment;
 pub mod spanset;
 pub mod utils;
@@ -27,6 +27,7 @@ use crate::ops::DagAddHeads;
 use crate::ops::DagAlgorithm;
 use crate::ops::DagImportCloneData;
 use crate::ops::DagPersistent;
+use crate::ops::DagStrip;
 use crate::ops::IdConvert;
 use crate::ops::IdMapSnapshot;
 use crate::ops::Open;
@@ -167,6 +168,71 @@ where
     }
 }
 
+impl<IS, M, P, S> DagStrip for AbstractNameDag<IdDag<IS>, M, P, S>
+where
+    IS: IdDagStore + Persist,
+    IdDag<IS>: TryClone,
+    M: TryClone + IdMapAssignHead + Persist,
+    P: TryClone,
+    S: TryClone + Persist,
+{
+    /// Remove non-master vertexes and their descendants.
+    ///
+    /// The remaining non-master vertexes get their ids and segments
+    /// re-assigned. Master vertexes are not touched so this is proportional
+    /// to the size of the non-master group, not the size of the DAG.
+    fn strip(&mut self, set: &NameSet) -> Result<()> {
+        if !self.pending_heads.is_empty() {
+            return programming(format!(
+                "ProgrammingError: strip called with pending heads ({:?})",
+                &self.pending_heads,
+            ));
+        }
+
+        // Take lock. Resolve the set after reloading so vertexes added by
+        // other processes are considered.
+        let locked = self.state.prepare_filesystem_sync()?;
+        let mut map = self.map.prepare_filesystem_sync()?;
+        let mut dag = self.dag.prepare_filesystem_sync()?;
+
+        let mut ids = Vec::new();
+        for name in set.iter()? {
+            let name = name?;
+            if let Some(id) = map.vertex_id_optional(&name)? {
+                if id.group() == Group::MASTER {
+                    return programming(format!(
+                        "ProgrammingError: cannot strip master vertex {:?}",
+                        &name
+                    ));
+                }
+                ids.push(id);
+            }
+        }
+        let strip_ids = dag.descendants(SpanSet::from_spans(ids))?;
+        if strip_ids.is_empty() {
+            return Ok(());
+        }
+        let strip_names = strip_ids
+            .iter()
+            .map(|id| map.vertex_name(id))
+            .collect::<Result<HashSet<_>>>()?;
+
+        // Rebuild the non-master group without the stripped vertexes.
+        let mut parents = non_master_parent_names(&map, &dag)?;
+        parents.retain(|name, _| !strip_names.contains(name));
+        rebuild_non_master_with_parents(&mut map, &mut dag, parents)?;
+
+        // Write to disk. `state` (the MultiLog meta for `NameDag`) is written
+        // last so the change becomes visible atomically.
+        map.sync()?;
+        dag.sync()?;
+        locked.sync()?;
+
+        self.invalidate_snapshot();
+        Ok(())
+    }
+}
+
 impl<IS, M, P, S> DagAddHeads for AbstractNameDag<IdDag<IS>, M, P, S>
 where
     IS: IdDagStore,
@@ -584,6 +650,19 @@ where
 {
     // backup part of the named graph in memory.
     let parents = non_master_parent_names(map, dag)?;
+    rebuild_non_master_with_parents(map, dag, parents)
+}
+
+/// Replace the non-master group with vertexes in `parents`.
+fn rebuild_non_master_with_parents<M, S>(
+    map: &mut Locked<M>,
+    dag: &mut Locked<IdDag<S>>,
+    parents: HashMap<VertexName, Vec<VertexName>>,
+) -> Result<()>
+where
+    M: IdMapAssignHead + Persist,
+    S: IdDagStore + Persist,
+{
     let mut heads = parents
         .keys()
         .collect::<HashSet<_>>()
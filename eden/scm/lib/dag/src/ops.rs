@@ -208,6 +208,16 @@ pub trait DagPersistent {
     }
 }
 
+/// Remove vertexes from the DAG.
+pub trait DagStrip {
+    /// Remove vertexes in `set` and their descendants, and write the change
+    /// to disk.
+    ///
+    /// Only vertexes in the non-master group can be removed. Vertexes in
+    /// `set` that are not in the DAG are ignored.
+    fn strip(&mut self, set: &NameSet) -> Result<()>;
+}
+
 /// Import ASCII graph to DAG.
 pub trait ImportAscii {
     /// Import vertexes described in an ASCII graph.
@@ -0,0 +1,205 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::fmt::{Debug, Write};
+use std::marker::PhantomData;
+
+use super::output::OutputRendererOptions;
+use super::render::{Ancestor, GraphRow, NodeLine, Renderer};
+
+/// Distance between columns and rows, in points.
+const SPACING: u64 = 36;
+
+/// Renders graph rows as Graphviz DOT.
+///
+/// Nodes are pinned (`pos="x,y!"`) to the column and row they have in the
+/// text graph, so `neato -n` draws the same layout as `hg log -G`.  Nodes are
+/// named using their `Debug` representation.  Each call to `next_row`
+/// returns the statements for that row, and [`DotRenderer::finish`] returns
+/// the complete graph.
+pub struct DotRenderer<N, R>
+where
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    inner: R,
+    row: u64,
+    body: String,
+    _phantom: PhantomData<N>,
+}
+
+impl<N, R> DotRenderer<N, R>
+where
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    pub(crate) fn new(inner: R, _options: OutputRendererOptions) -> Self {
+        DotRenderer {
+            inner,
+            row: 0,
+            body: String::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns the DOT graph containing all rendered rows.
+    pub fn finish(self) -> String {
+        format!(
+            concat!(
+                "digraph {{\n",
+                "  node [shape=circle, fixedsize=true, width=0.25];\n",
+                "  edge [arrowhead=none];\n",
+                "{}}}\n"
+            ),
+            self.body
+        )
+    }
+}
+
+impl<N, R> Renderer<N> for DotRenderer<N, R>
+where
+    N: Clone + Eq + Debug,
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    type Output = String;
+
+    fn width(&self, node: Option<&N>, parents: Option<&Vec<Ancestor<N>>>) -> u64 {
+        self.inner.width(node, parents)
+    }
+
+    fn reserve(&mut self, node: N) {
+        self.inner.reserve(node);
+    }
+
+    fn next_row(
+        &mut self,
+        node: N,
+        parents: Vec<Ancestor<N>>,
+        glyph: String,
+        message: String,
+    ) -> String {
+        let edges: Vec<_> = parents
+            .iter()
+            .map(|parent| match parent {
+                Ancestor::Parent(p) => Some((format!("{:?}", p), false)),
+                Ancestor::Ancestor(p) => Some((format!("{:?}", p), true)),
+                Ancestor::Anonymous => None,
+            })
+            .collect();
+        let row = self.inner.next_row(node, parents, glyph, message);
+        let name = format!("{:?}", &row.node);
+        let column = row
+            .node_line
+            .iter()
+            .position(|n| *n == NodeLine::Node)
+            .unwrap_or_default() as u64;
+        let y = -((self.row * SPACING) as i64);
+        self.row += 1;
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "  {} [pos=\"{},{}!\", xlabel={}, tooltip={}, label={}];",
+            quote(&name),
+            column * SPACING,
+            y,
+            quote(row.message.lines().next().unwrap_or_default()),
+            quote(&row.message),
+            quote(&row.glyph),
+        );
+
+        // Anonymous parents end in terminators below their columns.
+        let mut term_columns = row
+            .term_line
+            .iter()
+            .flatten()
+            .enumerate()
+            .filter(|(_, term)| **term)
+            .map(|(i, _)| i as u64);
+        for (i, edge) in edges.into_iter().enumerate() {
+            match edge {
+                Some((parent, ancestor)) => {
+                    let style = if ancestor { " [style=dashed]" } else { "" };
+                    let _ = writeln!(out, "  {} -> {}{};", quote(&name), quote(&parent), style);
+                }
+                None => {
+                    let term = quote(&format!("{}~{}", name, i));
+                    let term_column = term_columns.next().unwrap_or(column);
+                    let _ = writeln!(
+                        out,
+                        "  {} [shape=plaintext, label=\"~\", pos=\"{},{}!\"];",
+                        term,
+                        term_column * SPACING,
+                        y - (SPACING / 2) as i64
+                    );
+                    let _ = writeln!(out, "  {} -> {};", quote(&name), term);
+                }
+            }
+        }
+
+        self.body.push_str(&out);
+        out
+    }
+}
+
+/// Quote text as a DOT string.
+fn quote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_fixtures::{self, TestFixture};
+    use super::super::test_utils::render_rows;
+    use crate::render::GraphRowRenderer;
+
+    fn render(fixture: &TestFixture) -> String {
+        let mut renderer = GraphRowRenderer::new().output().build_dot();
+        render_rows(fixture, &mut renderer);
+        renderer.finish()
+    }
+
+    #[test]
+    fn basic() {
+        assert_eq!(
+            render(&test_fixtures::BASIC),
+            r#"digraph {
+  node [shape=circle, fixedsize=true, width=0.25];
+  edge [arrowhead=none];
+  "C" [pos="0,0!", xlabel="C", tooltip="C", label="o"];
+  "C" -> "B";
+  "B" [pos="0,-36!", xlabel="B", tooltip="B", label="o"];
+  "B" -> "A";
+  "A" [pos="0,-72!", xlabel="A", tooltip="A", label="o"];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn ancestors_and_terminations() {
+        let out = render(&test_fixtures::ANCESTORS);
+        assert!(out.contains("[style=dashed];"));
+
+        let out = render(&test_fixtures::TERMINATIONS);
+        assert_eq!(out.matches("label=\"~\"").count(), 3);
+    }
+
+    #[test]
+    fn quote() {
+        assert_eq!(super::quote("a \"b\"\n\\"), r#""a \"b\"\n\\""#);
+    }
+}
@@ -0,0 +1,152 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use serde::Serialize;
+
+use super::output::OutputRendererOptions;
+use super::render::{Ancestor, GraphRow, LinkLine, NodeLine, PadLine, Renderer};
+
+/// Renders each graph row as a single line of JSON.
+///
+/// The row model is the same as [`GraphRow`], so a frontend drawing it gets
+/// the same column layout as the text renderers. Nodes are named using their
+/// `Debug` representation.
+pub struct JsonRenderer<N, R>
+where
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    inner: R,
+    options: OutputRendererOptions,
+    row: usize,
+    _phantom: PhantomData<N>,
+}
+
+#[derive(Serialize)]
+struct JsonRow<'a> {
+    row: usize,
+    node: String,
+    column: usize,
+    glyph: &'a str,
+    message: &'a str,
+    merge: bool,
+    height: usize,
+    node_line: &'a [NodeLine],
+    link_line: Option<&'a [LinkLine]>,
+    term_line: Option<&'a [bool]>,
+    pad_lines: &'a [PadLine],
+}
+
+impl<N, R> JsonRenderer<N, R>
+where
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    pub(crate) fn new(inner: R, options: OutputRendererOptions) -> Self {
+        JsonRenderer {
+            inner,
+            options,
+            row: 0,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<N, R> Renderer<N> for JsonRenderer<N, R>
+where
+    N: Clone + Eq + Debug,
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    type Output = String;
+
+    fn width(&self, node: Option<&N>, parents: Option<&Vec<Ancestor<N>>>) -> u64 {
+        self.inner.width(node, parents)
+    }
+
+    fn reserve(&mut self, node: N) {
+        self.inner.reserve(node);
+    }
+
+    fn next_row(
+        &mut self,
+        node: N,
+        parents: Vec<Ancestor<N>>,
+        glyph: String,
+        message: String,
+    ) -> String {
+        let line = self.inner.next_row(node, parents, glyph, message);
+        let column = line
+            .node_line
+            .iter()
+            .position(|n| *n == NodeLine::Node)
+            .unwrap_or_default();
+        let row = JsonRow {
+            row: self.row,
+            node: format!("{:?}", &line.node),
+            column,
+            glyph: &line.glyph,
+            message: &line.message,
+            merge: line.merge,
+            height: line
+                .message
+                .lines()
+                .count()
+                .max(self.options.min_row_height),
+            node_line: &line.node_line,
+            link_line: line.link_line.as_deref(),
+            term_line: line.term_line.as_deref(),
+            pad_lines: &line.pad_lines,
+        };
+        self.row += 1;
+        let mut out = serde_json::to_string(&row).expect("graph rows are serializable");
+        out.push('\n');
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_fixtures::{self, TestFixture};
+    use super::super::test_utils::render_rows;
+    use crate::render::GraphRowRenderer;
+
+    fn render(fixture: &TestFixture) -> String {
+        let mut renderer = GraphRowRenderer::new().output().build_json();
+        render_rows(fixture, &mut renderer)
+    }
+
+    #[test]
+    fn basic() {
+        assert_eq!(
+            render(&test_fixtures::BASIC),
+            r#"{"row":0,"node":"C","column":0,"glyph":"o","message":"C","merge":false,"height":2,"node_line":["node"],"link_line":null,"term_line":null,"pad_lines":["parent"]}
+{"row":1,"node":"B","column":0,"glyph":"o","message":"B","merge":false,"height":2,"node_line":["node"],"link_line":null,"term_line":null,"pad_lines":["parent"]}
+{"row":2,"node":"A","column":0,"glyph":"o","message":"A","merge":false,"height":2,"node_line":["node"],"link_line":null,"term_line":null,"pad_lines":["blank"]}
+"#
+        );
+    }
+
+    #[test]
+    fn link_and_term_lines() {
+        let out = render(&test_fixtures::BRANCHES_AND_MERGES);
+        let rows: Vec<serde_json::Value> = out
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        let v = &rows[1];
+        assert_eq!(v["node"], "V");
+        assert_eq!(v["merge"], true);
+        assert_eq!(
+            v["link_line"].to_string(),
+            r#"[["parent","child","right_merge"],["left_fork"]]"#
+        );
+
+        let out = render(&test_fixtures::TERMINATIONS);
+        assert!(out.contains(r#""term_line":[true"#));
+    }
+}
@@ -9,10 +9,13 @@ mod ascii;
 mod ascii_large;
 mod box_drawing;
 mod column;
+mod dot;
+mod json;
 mod output;
 #[allow(clippy::module_inception)]
 mod render;
 mod render_utils;
+mod svg;
 
 #[cfg(test)]
 mod test_fixtures;
@@ -23,5 +26,10 @@ mod test_utils;
 pub use self::ascii::AsciiRenderer;
 pub use self::ascii_large::AsciiLargeRenderer;
 pub use self::box_drawing::BoxDrawingRenderer;
-pub use self::render::{Ancestor, GraphRowRenderer, LinkLine, NodeLine, PadLine, Renderer};
+pub use self::dot::DotRenderer;
+pub use self::json::JsonRenderer;
+pub use self::render::{
+    Ancestor, GraphRow, GraphRowRenderer, LinkLine, NodeLine, PadLine, Renderer,
+};
 pub use self::render_utils::render_namedag;
+pub use self::svg::SvgRenderer;
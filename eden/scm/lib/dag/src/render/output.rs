@@ -10,7 +10,10 @@ use std::marker::PhantomData;
 use super::ascii::AsciiRenderer;
 use super::ascii_large::AsciiLargeRenderer;
 use super::box_drawing::BoxDrawingRenderer;
+use super::dot::DotRenderer;
+use super::json::JsonRenderer;
 use super::render::{GraphRow, Renderer};
+use super::svg::SvgRenderer;
 
 pub(crate) struct OutputRendererOptions {
     pub(crate) min_row_height: usize,
@@ -53,4 +56,16 @@ where
     pub fn build_box_drawing(self) -> BoxDrawingRenderer<N, R> {
         BoxDrawingRenderer::new(self.inner, self.options)
     }
+
+    pub fn build_svg(self) -> SvgRenderer<N, R> {
+        SvgRenderer::new(self.inner, self.options)
+    }
+
+    pub fn build_dot(self) -> DotRenderer<N, R> {
+        DotRenderer::new(self.inner, self.options)
+    }
+
+    pub fn build_json(self) -> JsonRenderer<N, R> {
+        JsonRenderer::new(self.inner, self.options)
+    }
 }
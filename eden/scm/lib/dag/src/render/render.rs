@@ -9,6 +9,7 @@ use std::cmp::{max, min};
 use std::collections::BTreeMap;
 
 use bitflags::bitflags;
+use serde::{Serialize, Serializer};
 
 use super::column::{Column, ColumnsExt};
 use super::output::OutputRendererBuilder;
@@ -107,7 +108,8 @@ impl<N> Column<N> {
 }
 
 /// A column in the node row.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum NodeLine {
     /// Blank.
     Blank,
@@ -123,7 +125,8 @@ pub enum NodeLine {
 }
 
 /// A column in a padding row.
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum PadLine {
     /// Blank.
     Blank,
@@ -172,6 +175,33 @@ bitflags! {
     }
 }
 
+impl LinkLine {
+    /// Names of the individual flags set in this cell.
+    pub fn names(self) -> Vec<&'static str> {
+        const NAMES: [(LinkLine, &str); 8] = [
+            (LinkLine::HORIZONTAL, "horizontal"),
+            (LinkLine::PARENT, "parent"),
+            (LinkLine::ANCESTOR, "ancestor"),
+            (LinkLine::CHILD, "child"),
+            (LinkLine::LEFT_FORK, "left_fork"),
+            (LinkLine::RIGHT_FORK, "right_fork"),
+            (LinkLine::LEFT_MERGE, "left_merge"),
+            (LinkLine::RIGHT_MERGE, "right_merge"),
+        ];
+        NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+impl Serialize for LinkLine {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.names().serialize(serializer)
+    }
+}
+
 /// An output graph row.
 #[derive(Debug)]
 pub struct GraphRow<N> {
@@ -0,0 +1,418 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::cmp::max;
+use std::fmt::{Debug, Write};
+use std::marker::PhantomData;
+
+use itertools::Itertools;
+
+use super::output::OutputRendererOptions;
+use super::render::{Ancestor, GraphRow, LinkLine, NodeLine, PadLine, Renderer};
+
+/// Renders graph rows as SVG.
+///
+/// Each text line of the box drawing renderer becomes a line of cells, so
+/// nodes and edges are laid out the same way as `hg log -G`.  Each call to
+/// `next_row` returns the `<g>` element for that row, and
+/// [`SvgRenderer::finish`] returns the complete document.
+pub struct SvgRenderer<N, R>
+where
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    inner: R,
+    options: OutputRendererOptions,
+    column_width: u64,
+    line_height: u64,
+    line: u64,
+    width: u64,
+    extra_pad_line: Option<Vec<PadLine>>,
+    last_pad_lines: Vec<PadLine>,
+    body: String,
+    _phantom: PhantomData<N>,
+}
+
+/// Path data for the edges in a row.
+#[derive(Default)]
+struct Edges {
+    parent: String,
+    ancestor: String,
+}
+
+impl Edges {
+    fn push(&mut self, ancestor: bool, from: (u64, u64), to: (u64, u64)) {
+        let path = if ancestor {
+            &mut self.ancestor
+        } else {
+            &mut self.parent
+        };
+        let _ = write!(path, "M{} {}L{} {}", from.0, from.1, to.0, to.1);
+    }
+}
+
+/// Coordinates of a cell: left, center and right x, top, middle and
+/// bottom y.
+struct Cell {
+    x0: u64,
+    cx: u64,
+    x1: u64,
+    y0: u64,
+    my: u64,
+    y1: u64,
+}
+
+impl<N, R> SvgRenderer<N, R>
+where
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    pub(crate) fn new(inner: R, options: OutputRendererOptions) -> Self {
+        SvgRenderer {
+            inner,
+            options,
+            column_width: 16,
+            line_height: 20,
+            line: 0,
+            width: 0,
+            extra_pad_line: None,
+            last_pad_lines: Vec::new(),
+            body: String::new(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Set the size of a graph cell in pixels.
+    pub fn with_cell_size(mut self, column_width: u64, line_height: u64) -> Self {
+        self.column_width = column_width;
+        self.line_height = line_height;
+        self
+    }
+
+    /// Returns the SVG document containing all rendered rows.
+    pub fn finish(self) -> String {
+        let height = self.line * self.line_height;
+        format!(
+            concat!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" ",
+                "viewBox=\"0 0 {w} {h}\" font-family=\"monospace\" font-size=\"{f}\">\n",
+                "{body}</svg>\n"
+            ),
+            w = self.width,
+            h = height,
+            f = self.font_size(),
+            body = self.body,
+        )
+    }
+
+    fn font_size(&self) -> u64 {
+        self.line_height * 3 / 5
+    }
+
+    fn cell(&self, column: usize, line: u64) -> Cell {
+        let x0 = column as u64 * self.column_width;
+        let y0 = line * self.line_height;
+        Cell {
+            x0,
+            cx: x0 + self.column_width / 2,
+            x1: x0 + self.column_width,
+            y0,
+            my: y0 + self.line_height / 2,
+            y1: y0 + self.line_height,
+        }
+    }
+
+    fn render_pad_line(&self, edges: &mut Edges, pad_lines: &[PadLine], line: u64) {
+        for (i, pad) in pad_lines.iter().enumerate() {
+            if *pad != PadLine::Blank {
+                let c = self.cell(i, line);
+                edges.push(*pad == PadLine::Ancestor, (c.cx, c.y0), (c.cx, c.y1));
+            }
+        }
+    }
+
+    fn render_message(&mut self, out: &mut String, columns: usize, line: u64, msg: &str) {
+        if msg.is_empty() {
+            return;
+        }
+        let c = self.cell(columns, line);
+        let char_width = self.font_size() * 3 / 5;
+        self.width = max(self.width, c.cx + msg.chars().count() as u64 * char_width);
+        let _ = writeln!(
+            out,
+            "<text x=\"{}\" y=\"{}\" dominant-baseline=\"middle\">{}</text>",
+            c.cx,
+            c.my,
+            escape(msg)
+        );
+    }
+}
+
+impl<N, R> Renderer<N> for SvgRenderer<N, R>
+where
+    N: Clone + Eq + Debug,
+    R: Renderer<N, Output = GraphRow<N>> + Sized,
+{
+    type Output = String;
+
+    fn width(&self, node: Option<&N>, parents: Option<&Vec<Ancestor<N>>>) -> u64 {
+        self.inner.width(node, parents)
+    }
+
+    fn reserve(&mut self, node: N) {
+        self.inner.reserve(node);
+    }
+
+    fn next_row(
+        &mut self,
+        node: N,
+        parents: Vec<Ancestor<N>>,
+        glyph: String,
+        message: String,
+    ) -> String {
+        let row = self.inner.next_row(node, parents, glyph, message);
+        let columns = row.node_line.len();
+        let mut edges = Edges::default();
+        let mut nodes = String::new();
+        let mut texts = String::new();
+        let mut line = self.line;
+        let mut message_lines = row
+            .message
+            .lines()
+            .pad_using(self.options.min_row_height, |_| "");
+        let mut need_extra_pad_line = false;
+        self.width = max(self.width, columns as u64 * self.column_width);
+
+        // Render the previous extra pad line
+        if let Some(extra_pad_line) = self.extra_pad_line.take() {
+            self.render_pad_line(&mut edges, &extra_pad_line, line);
+            line += 1;
+        }
+
+        // Render the node line
+        for (i, entry) in row.node_line.iter().enumerate() {
+            let c = self.cell(i, line);
+            match entry {
+                NodeLine::Node => {
+                    let above = self.last_pad_lines.get(i).cloned();
+                    if let Some(pad) = above.filter(|pad| *pad != PadLine::Blank) {
+                        edges.push(pad == PadLine::Ancestor, (c.cx, c.y0), (c.cx, c.my));
+                    }
+                    let below = match (&row.link_line, &row.term_line) {
+                        (Some(link_line), _) => link_top(link_line[i]),
+                        (None, Some(term_line)) => {
+                            term_line[i] || row.pad_lines[i] != PadLine::Blank
+                        }
+                        (None, None) => row.pad_lines[i] != PadLine::Blank,
+                    };
+                    if below {
+                        let ancestor = row.pad_lines[i] == PadLine::Ancestor;
+                        edges.push(ancestor, (c.cx, c.my), (c.cx, c.y1));
+                    }
+                    let _ = writeln!(
+                        nodes,
+                        concat!(
+                            "<circle class=\"node\" cx=\"{}\" cy=\"{}\" r=\"{}\" ",
+                            "fill=\"white\" stroke=\"black\" ",
+                            "data-node=\"{}\" data-glyph=\"{}\"><title>{}</title></circle>"
+                        ),
+                        c.cx,
+                        c.my,
+                        max(self.column_width.min(self.line_height) / 4, 1),
+                        escape(&format!("{:?}", &row.node)),
+                        escape(&row.glyph),
+                        escape(&row.message),
+                    );
+                }
+                NodeLine::Parent => edges.push(false, (c.cx, c.y0), (c.cx, c.y1)),
+                NodeLine::Ancestor => edges.push(true, (c.cx, c.y0), (c.cx, c.y1)),
+                NodeLine::Blank => {}
+            }
+        }
+        if let Some(msg) = message_lines.next() {
+            self.render_message(&mut texts, columns, line, msg);
+        }
+        line += 1;
+
+        // Render the link line
+        if let Some(link_line) = &row.link_line {
+            for (i, cur) in link_line.iter().enumerate() {
+                let c = self.cell(i, line);
+                if link_top(*cur) {
+                    let ancestor = cur.contains(LinkLine::ANCESTOR)
+                        && !cur.intersects(LinkLine::PARENT | LinkLine::CHILD);
+                    edges.push(ancestor, (c.cx, c.y0), (c.cx, c.my));
+                }
+                if cur.intersects(
+                    LinkLine::PARENT | LinkLine::ANCESTOR | LinkLine::CHILD | LinkLine::ANY_FORK,
+                ) {
+                    let ancestor = row.pad_lines.get(i) == Some(&PadLine::Ancestor);
+                    edges.push(ancestor, (c.cx, c.my), (c.cx, c.y1));
+                }
+                if cur.intersects(LinkLine::HORIZONTAL | LinkLine::LEFT_MERGE | LinkLine::LEFT_FORK)
+                {
+                    edges.push(false, (c.x0, c.my), (c.cx, c.my));
+                }
+                if cur
+                    .intersects(LinkLine::HORIZONTAL | LinkLine::RIGHT_MERGE | LinkLine::RIGHT_FORK)
+                {
+                    edges.push(false, (c.cx, c.my), (c.x1, c.my));
+                }
+            }
+            if let Some(msg) = message_lines.next() {
+                self.render_message(&mut texts, columns, line, msg);
+            }
+            line += 1;
+        }
+
+        // Render the term lines
+        if let Some(term_line) = &row.term_line {
+            for term_index in 0..2 {
+                for (i, term) in term_line.iter().enumerate() {
+                    let c = self.cell(i, line);
+                    if !*term {
+                        if row.pad_lines[i] != PadLine::Blank {
+                            let ancestor = row.pad_lines[i] == PadLine::Ancestor;
+                            edges.push(ancestor, (c.cx, c.y0), (c.cx, c.y1));
+                        }
+                    } else if term_index == 0 {
+                        edges.push(false, (c.cx, c.y0), (c.cx, c.y1));
+                    } else {
+                        let _ = writeln!(
+                            nodes,
+                            concat!(
+                                "<text class=\"term\" x=\"{}\" y=\"{}\" ",
+                                "text-anchor=\"middle\" dominant-baseline=\"middle\">~</text>"
+                            ),
+                            c.cx, c.my
+                        );
+                    }
+                }
+                if let Some(msg) = message_lines.next() {
+                    self.render_message(&mut texts, columns, line, msg);
+                }
+                line += 1;
+            }
+            need_extra_pad_line = true;
+        }
+
+        // Render any pad lines
+        for msg in message_lines {
+            self.render_pad_line(&mut edges, &row.pad_lines, line);
+            self.render_message(&mut texts, columns, line, msg);
+            line += 1;
+            need_extra_pad_line = false;
+        }
+
+        if need_extra_pad_line {
+            self.extra_pad_line = Some(row.pad_lines.clone());
+        }
+        self.last_pad_lines = row.pad_lines;
+        self.line = line;
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "<g class=\"row\" data-node=\"{}\">",
+            escape(&format!("{:?}", &row.node))
+        );
+        if !edges.parent.is_empty() {
+            let _ = writeln!(
+                out,
+                "<path class=\"parent\" d=\"{}\" stroke=\"black\" fill=\"none\"/>",
+                edges.parent
+            );
+        }
+        if !edges.ancestor.is_empty() {
+            let _ = writeln!(
+                out,
+                concat!(
+                    "<path class=\"ancestor\" d=\"{}\" stroke=\"black\" fill=\"none\" ",
+                    "stroke-dasharray=\"2 2\"/>"
+                ),
+                edges.ancestor
+            );
+        }
+        out.push_str(&nodes);
+        out.push_str(&texts);
+        out.push_str("</g>\n");
+        self.body.push_str(&out);
+        out
+    }
+}
+
+/// Returns true if the link cell connects to the line above it.
+fn link_top(cell: LinkLine) -> bool {
+    cell.intersects(LinkLine::PARENT | LinkLine::ANCESTOR | LinkLine::CHILD | LinkLine::ANY_MERGE)
+}
+
+/// Escape text for use in XML content and attribute values.
+pub(crate) fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_fixtures::{self, TestFixture};
+    use super::super::test_utils::render_rows;
+    use crate::render::GraphRowRenderer;
+
+    fn render(fixture: &TestFixture) -> String {
+        let mut renderer = GraphRowRenderer::new().output().build_svg();
+        render_rows(fixture, &mut renderer);
+        renderer.finish()
+    }
+
+    #[test]
+    fn basic() {
+        assert_eq!(
+            render(&test_fixtures::BASIC),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="31" height="120" viewBox="0 0 31 120" font-family="monospace" font-size="12">
+<g class="row" data-node="C">
+<path class="parent" d="M8 10L8 20M8 20L8 40" stroke="black" fill="none"/>
+<circle class="node" cx="8" cy="10" r="4" fill="white" stroke="black" data-node="C" data-glyph="o"><title>C</title></circle>
+<text x="24" y="10" dominant-baseline="middle">C</text>
+</g>
+<g class="row" data-node="B">
+<path class="parent" d="M8 40L8 50M8 50L8 60M8 60L8 80" stroke="black" fill="none"/>
+<circle class="node" cx="8" cy="50" r="4" fill="white" stroke="black" data-node="B" data-glyph="o"><title>B</title></circle>
+<text x="24" y="50" dominant-baseline="middle">B</text>
+</g>
+<g class="row" data-node="A">
+<path class="parent" d="M8 80L8 90" stroke="black" fill="none"/>
+<circle class="node" cx="8" cy="90" r="4" fill="white" stroke="black" data-node="A" data-glyph="o"><title>A</title></circle>
+<text x="24" y="90" dominant-baseline="middle">A</text>
+</g>
+</svg>
+"#
+        );
+    }
+
+    #[test]
+    fn terminations_and_ancestors() {
+        let out = render(&test_fixtures::TERMINATIONS);
+        assert_eq!(out.matches("<circle").count(), 8);
+        assert_eq!(out.matches(">~</text>").count(), 3);
+
+        let out = render(&test_fixtures::ANCESTORS);
+        assert!(out.contains("class=\"ancestor\""));
+    }
+
+    #[test]
+    fn escape() {
+        assert_eq!(super::escape("<a & 'b'>"), "&lt;a &amp; &apos;b&apos;&gt;");
+    }
+}
@@ -28,6 +28,56 @@ pub(crate) fn render_string_with_order(
     renderer: &mut dyn Renderer<VertexName, Output = String>,
     order: Option<&[&str]>,
 ) -> String {
+    let mut out = String::new();
+    for (node, parents, name, message) in fixture_rows(fixture, renderer, order) {
+        let width = renderer.width(Some(&node), Some(&parents));
+        let row = renderer.next_row(node, parents, String::from("o"), message);
+        let row_indent = row
+            .lines()
+            .filter_map(|line| line.find(&name).map(|offset| &line[..offset]))
+            .next()
+            .expect("name should be in the output");
+        assert_eq!(
+            row_indent.width() as u64,
+            width,
+            "indent '{}' for row for {} is the wrong width",
+            row_indent,
+            name
+        );
+
+        out.push_str(&row);
+    }
+
+    format!(
+        "\n{}",
+        out.trim_end()
+            .lines()
+            .map(|l| format!("            {}", l))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+/// Render the fixture without checking the graph width of each row, for
+/// renderers that do not produce text graphs.
+pub(crate) fn render_rows(
+    fixture: &TestFixture,
+    renderer: &mut dyn Renderer<VertexName, Output = String>,
+) -> String {
+    let mut out = String::new();
+    for (node, parents, _name, message) in fixture_rows(fixture, renderer, None) {
+        out.push_str(&renderer.next_row(node, parents, String::from("o"), message));
+    }
+    out
+}
+
+/// Reserve columns in `renderer` and return the rows to render for the
+/// fixture as `(node, parents, name, message)`.
+fn fixture_rows(
+    fixture: &TestFixture,
+    renderer: &mut dyn Renderer<VertexName, Output = String>,
+    order: Option<&[&str]>,
+) -> Vec<(VertexName, Vec<Ancestor<VertexName>>, String, String)> {
     let TestFixture {
         dag: ascii,
         messages,
@@ -66,7 +116,7 @@ pub(crate) fn render_string_with_order(
         Some(order) => order.iter().map(|name| v(name)).collect(),
     };
 
-    let mut out = String::new();
+    let mut rows = Vec::new();
     for node in iter {
         if missing.contains(&node) {
             continue;
@@ -90,30 +140,7 @@ pub(crate) fn render_string_with_order(
             Some(message) => format!("{}\n{}", name, message),
             None => name.clone(),
         };
-        let width = renderer.width(Some(&node), Some(&parents));
-        let row = renderer.next_row(node, parents, String::from("o"), message);
-        let row_indent = row
-            .lines()
-            .filter_map(|line| line.find(&name).map(|offset| &line[..offset]))
-            .next()
-            .expect("name should be in the output");
-        assert_eq!(
-            row_indent.width() as u64,
-            width,
-            "indent '{}' for row for {} is the wrong width",
-            row_indent,
-            name
-        );
-
-        out.push_str(&row);
+        rows.push((node, parents, name, message));
     }
-
-    format!(
-        "\n{}",
-        out.trim_end()
-            .lines()
-            .map(|l| format!("            {}", l))
-            .collect::<Vec<_>>()
-            .join("\n")
-    )
+    rows
 }
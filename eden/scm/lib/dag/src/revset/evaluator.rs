@@ -0,0 +1,419 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Revset evaluation.
+
+use super::parser::parse;
+use super::parser::Expr;
+use crate::errors::DagError;
+use crate::DagAlgorithm;
+use crate::NameSet;
+use crate::Result;
+use crate::VertexName;
+use indexmap::IndexSet;
+use std::collections::HashMap;
+
+/// Resolves a symbol to a set. Returns `None` if the symbol is unknown.
+pub type SymbolFunc<'a> = Box<dyn Fn(&str) -> Result<Option<NameSet>> + 'a>;
+
+/// A revset function. Takes unevaluated arguments.
+pub type RevsetFunc<'a> = Box<dyn Fn(&Evaluator<'a>, &[Expr]) -> Result<NameSet> + 'a>;
+
+/// Evaluates revset expressions using [`DagAlgorithm`].
+///
+/// Symbols and functions can be extended. For example, to resolve bookmarks,
+/// or to filter by phases.
+pub struct Evaluator<'a> {
+    dag: &'a dyn DagAlgorithm,
+    symbol_func: Option<SymbolFunc<'a>>,
+    functions: HashMap<String, RevsetFunc<'a>>,
+}
+
+impl<'a> Evaluator<'a> {
+    /// Creates an [`Evaluator`] with builtin functions.
+    pub fn new(dag: &'a dyn DagAlgorithm) -> Self {
+        Self {
+            dag,
+            symbol_func: None,
+            functions: HashMap::new(),
+        }
+    }
+
+    /// Sets the function to resolve symbols.
+    ///
+    /// The function is tried first. If it returns `None`, the symbol is
+    /// looked up as a vertex name in the graph.
+    pub fn with_symbol_func(mut self, func: impl Fn(&str) -> Result<Option<NameSet>> + 'a) -> Self {
+        self.symbol_func = Some(Box::new(func));
+        self
+    }
+
+    /// Adds a function. It overrides the builtin function with the same name.
+    ///
+    /// The function takes unevaluated arguments. Use [`Evaluator::eval`] to
+    /// evaluate them as sets, or [`Expr::as_str`] to use them as strings.
+    pub fn with_function(
+        mut self,
+        name: impl ToString,
+        func: impl Fn(&Evaluator<'a>, &[Expr]) -> Result<NameSet> + 'a,
+    ) -> Self {
+        self.functions.insert(name.to_string(), Box::new(func));
+        self
+    }
+
+    /// Get the graph used for evaluation.
+    pub fn dag(&self) -> &'a dyn DagAlgorithm {
+        self.dag
+    }
+
+    /// Parses and evaluates a revset expression.
+    pub fn eval_str(&self, text: &str) -> Result<NameSet> {
+        self.eval(&parse(text)?)
+    }
+
+    /// Evaluates a parsed revset expression.
+    pub fn eval(&self, expr: &Expr) -> Result<NameSet> {
+        let dag = self.dag;
+        match expr {
+            Expr::Symbol(name) | Expr::String(name) => self.resolve_symbol(name),
+            Expr::Not(e) => Ok(dag.all()? - self.eval(e)?),
+            Expr::And(l, r) => Ok(self.eval(l)? & self.eval(r)?),
+            Expr::Or(l, r) => Ok(self.eval(l)? | self.eval(r)?),
+            Expr::Difference(l, r) => Ok(self.eval(l)? - self.eval(r)?),
+            Expr::Only(l, r) => dag.only(self.eval(l)?, self.eval(r)?),
+            Expr::Range(None, None) => dag.all(),
+            Expr::Range(Some(l), None) => dag.descendants(self.eval(l)?),
+            Expr::Range(None, Some(r)) => dag.ancestors(self.eval(r)?),
+            Expr::Range(Some(l), Some(r)) => dag.range(self.eval(l)?, self.eval(r)?),
+            Expr::Parent(e, n) => self.nth_parent(self.eval(e)?, *n),
+            Expr::Ancestor(e, n) => {
+                map_vertexes(self.eval(e)?, |v| dag.first_ancestor_nth(v, *n).map(Some))
+            }
+            Expr::Func(name, args) => match self.functions.get(name) {
+                Some(func) => func(self, args),
+                None => self.eval_builtin(name, args),
+            },
+        }
+    }
+
+    fn resolve_symbol(&self, name: &str) -> Result<NameSet> {
+        if let Some(func) = &self.symbol_func {
+            if let Some(set) = func(name)? {
+                return Ok(set);
+            }
+        }
+        let vertex = VertexName::copy_from(name.as_bytes());
+        if self.dag.all()?.contains(&vertex)? {
+            Ok(NameSet::from(vertex))
+        } else {
+            vertex.not_found()
+        }
+    }
+
+    /// `x^n`. `n` starts from 1. `x^0` is `x`.
+    fn nth_parent(&self, set: NameSet, n: u64) -> Result<NameSet> {
+        if n == 0 {
+            return Ok(set);
+        }
+        map_vertexes(set, |v| {
+            let parents = self.dag.parent_names(v)?;
+            Ok(parents.get(n as usize - 1).cloned())
+        })
+    }
+
+    fn eval_builtin(&self, name: &str, args: &[Expr]) -> Result<NameSet> {
+        let dag = self.dag;
+        match name {
+            "all" => {
+                check_arg_count(name, args, 0, 0)?;
+                dag.all()
+            }
+            "ancestors" => dag.ancestors(self.eval_single_arg(name, args)?),
+            "descendants" => dag.descendants(self.eval_single_arg(name, args)?),
+            "parents" => dag.parents(self.eval_single_arg(name, args)?),
+            "children" => dag.children(self.eval_single_arg(name, args)?),
+            "p1" => self.nth_parent(self.eval_single_arg(name, args)?, 1),
+            "p2" => self.nth_parent(self.eval_single_arg(name, args)?, 2),
+            "heads" => dag.heads(self.eval_single_arg(name, args)?),
+            "roots" => dag.roots(self.eval_single_arg(name, args)?),
+            "reverse" => {
+                let set = self.eval_single_arg(name, args)?;
+                let names = set.iter_rev()?.collect::<Result<Vec<_>>>()?;
+                Ok(NameSet::from_static_names(names))
+            }
+            "only" => {
+                check_arg_count(name, args, 1, 2)?;
+                let set = self.eval(&args[0])?;
+                let exclude = match args.get(1) {
+                    Some(arg) => self.eval(arg)?,
+                    // Heads that are not ancestors of `set`.
+                    None => dag.heads(dag.all()?)? - dag.ancestors(set.clone())?,
+                };
+                dag.only(set, exclude)
+            }
+            "ancestor" => {
+                check_arg_count(name, args, 1, usize::MAX)?;
+                let mut set = NameSet::empty();
+                for arg in args {
+                    set = set | self.eval(arg)?;
+                }
+                Ok(match dag.gca_one(set)? {
+                    Some(vertex) => NameSet::from(vertex),
+                    None => NameSet::empty(),
+                })
+            }
+            "limit" | "first" | "last" => {
+                let max_args = if name == "limit" { 3 } else { 2 };
+                check_arg_count(name, args, 1, max_args)?;
+                let set = self.eval(&args[0])?;
+                let n = match args.get(1) {
+                    Some(arg) => parse_int(name, arg)?,
+                    None => 1,
+                };
+                let offset = match args.get(2) {
+                    Some(arg) => parse_int(name, arg)?,
+                    None => 0,
+                };
+                let iter = if name == "last" {
+                    set.iter_rev()?
+                } else {
+                    set.iter()?
+                };
+                let mut names = iter
+                    .skip(offset as usize)
+                    .take(n as usize)
+                    .collect::<Result<Vec<_>>>()?;
+                if name == "last" {
+                    names.reverse();
+                }
+                Ok(NameSet::from_static_names(names))
+            }
+            "sort" => {
+                // hg sorts by revision number if the key is omitted. There are
+                // no revision numbers here, so require an explicit key.
+                check_arg_count(name, args, 2, 2)?;
+                let set = self.eval(&args[0])?;
+                let key = match args[1].as_str() {
+                    Some(key) => key,
+                    None => return invalid(format!("{} expects a string key", name)),
+                };
+                let sorted = dag.sort(&set)?;
+                match key {
+                    "topo" => Ok(sorted),
+                    "-topo" => {
+                        let names = sorted.iter_rev()?.collect::<Result<Vec<_>>>()?;
+                        Ok(NameSet::from_static_names(names))
+                    }
+                    _ => invalid(format!("unknown sort key {:?}", key)),
+                }
+            }
+            _ => invalid(format!("unknown function {:?}", name)),
+        }
+    }
+
+    fn eval_single_arg(&self, name: &str, args: &[Expr]) -> Result<NameSet> {
+        check_arg_count(name, args, 1, 1)?;
+        self.eval(&args[0])
+    }
+}
+
+fn invalid<T>(message: impl ToString) -> Result<T> {
+    Err(DagError::InvalidRevset(message.to_string()))
+}
+
+fn check_arg_count(name: &str, args: &[Expr], min: usize, max: usize) -> Result<()> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max {
+            format!("{}", min)
+        } else if max == usize::MAX {
+            format!("at least {}", min)
+        } else {
+            format!("{} to {}", min, max)
+        };
+        return invalid(format!(
+            "{} takes {} arguments, got {}",
+            name,
+            expected,
+            args.len()
+        ));
+    }
+    Ok(())
+}
+
+fn parse_int(name: &str, arg: &Expr) -> Result<u64> {
+    match arg.as_str().and_then(|s| s.parse::<u64>().ok()) {
+        Some(n) => Ok(n),
+        None => invalid(format!("{} expects a non-negative integer", name)),
+    }
+}
+
+/// Map vertexes in `set` using `func`. Preserve order. Remove duplicates.
+fn map_vertexes(
+    set: NameSet,
+    func: impl Fn(VertexName) -> Result<Option<VertexName>>,
+) -> Result<NameSet> {
+    let mut result = IndexSet::new();
+    for vertex in set.iter()? {
+        if let Some(vertex) = func(vertex?)? {
+            result.insert(vertex);
+        }
+    }
+    Ok(NameSet::from_static_names(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::namedag::MemNameDag;
+    use crate::ops::ImportAscii;
+
+    fn get_dag() -> MemNameDag {
+        let mut dag = MemNameDag::new();
+        dag.import_ascii(
+            r#"
+            A---B---C---F---G
+             \         /
+              D-------E---H"#,
+        )
+        .unwrap();
+        dag
+    }
+
+    /// Evaluate and format the result. Sort names so order does not matter.
+    fn r(evaluator: &Evaluator, text: &str) -> String {
+        match evaluator.eval_str(text) {
+            Ok(set) => {
+                let mut names = set
+                    .iter()
+                    .unwrap()
+                    .map(|n| String::from_utf8_lossy(n.unwrap().as_ref()).to_string())
+                    .collect::<Vec<String>>();
+                names.sort();
+                names.join(" ")
+            }
+            Err(err) => err.to_string(),
+        }
+    }
+
+    /// Evaluate and format the result. Preserve order.
+    fn ordered(evaluator: &Evaluator, text: &str) -> String {
+        let set = evaluator.eval_str(text).unwrap();
+        let names = set
+            .iter()
+            .unwrap()
+            .map(|n| String::from_utf8_lossy(n.unwrap().as_ref()).to_string())
+            .collect::<Vec<String>>();
+        names.join(" ")
+    }
+
+    #[test]
+    fn test_eval_operators() {
+        let dag = get_dag();
+        let e = Evaluator::new(&dag);
+        assert_eq!(r(&e, "A + C | E"), "A C E");
+        assert_eq!(r(&e, "::C & D::"), "");
+        assert_eq!(r(&e, "::F and not ::C"), "D E F");
+        assert_eq!(r(&e, "!(::G)"), "H");
+        assert_eq!(r(&e, "::F - ::C"), "D E F");
+        assert_eq!(r(&e, "G % H"), "B C F G");
+        assert_eq!(r(&e, "B::G"), "B C F G");
+        assert_eq!(r(&e, "D..G"), "D E F G");
+        assert_eq!(r(&e, "E::"), "E F G H");
+        assert_eq!(r(&e, "::"), "A B C D E F G H");
+        assert_eq!(r(&e, "F^ + H^"), "C E");
+        assert_eq!(r(&e, "F^2"), "E");
+        assert_eq!(r(&e, "F^0"), "F");
+        assert_eq!(r(&e, "A^"), "");
+        assert_eq!(r(&e, "G~3"), "B");
+        assert_eq!(r(&e, "G~1^2"), "E");
+        assert_eq!(r(&e, "'G'"), "G");
+    }
+
+    #[test]
+    fn test_eval_functions() {
+        let dag = get_dag();
+        let e = Evaluator::new(&dag);
+        assert_eq!(r(&e, "all()"), "A B C D E F G H");
+        assert_eq!(r(&e, "ancestors(E)"), "A D E");
+        assert_eq!(r(&e, "descendants(C)"), "C F G");
+        assert_eq!(r(&e, "parents(F + E)"), "C D E");
+        assert_eq!(r(&e, "children(E)"), "F H");
+        assert_eq!(r(&e, "p1(F)"), "C");
+        assert_eq!(r(&e, "p2(F + C)"), "E");
+        assert_eq!(r(&e, "heads(all())"), "G H");
+        assert_eq!(r(&e, "roots(B + C + E + F)"), "B E");
+        assert_eq!(r(&e, "only(G, H)"), "B C F G");
+        assert_eq!(r(&e, "only(H)"), "H");
+        assert_eq!(r(&e, "ancestor(C, H)"), "A");
+        assert_eq!(r(&e, "ancestor(G, H)"), "E");
+        assert_eq!(r(&e, "ancestor(C)"), "C");
+        assert_eq!(ordered(&e, "sort(A + G + C, topo)"), "G C A");
+        assert_eq!(ordered(&e, "sort(A + G + C, '-topo')"), "A C G");
+        assert_eq!(ordered(&e, "reverse(sort(A + G + C, topo))"), "A C G");
+        assert_eq!(ordered(&e, "limit(sort(::G, topo), 2)"), "G F");
+        assert_eq!(ordered(&e, "limit(sort(::G, topo), 2, 1)"), "F E");
+        assert_eq!(ordered(&e, "first(sort(::G, topo))"), "G");
+        assert_eq!(ordered(&e, "last(sort(::G, topo), 2)"), "B A");
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let dag = get_dag();
+        let e = Evaluator::new(&dag);
+        assert_eq!(r(&e, "X"), "X cannot be found");
+        assert_eq!(r(&e, "foo()"), "invalid revset: unknown function \"foo\"");
+        assert_eq!(
+            r(&e, "heads(A, B)"),
+            "invalid revset: heads takes 1 arguments, got 2"
+        );
+        assert_eq!(
+            r(&e, "limit(A, B)"),
+            "invalid revset: limit expects a non-negative integer"
+        );
+        assert_eq!(
+            r(&e, "sort(A, date)"),
+            "invalid revset: unknown sort key \"date\""
+        );
+        assert_eq!(
+            r(&e, "sort(A)"),
+            "invalid revset: sort takes 2 arguments, got 1"
+        );
+        assert_eq!(
+            r(&e, "ancestor()"),
+            "invalid revset: ancestor takes at least 1 arguments, got 0"
+        );
+    }
+
+    #[test]
+    fn test_eval_extensions() {
+        let dag = get_dag();
+        let bookmarks: HashMap<&str, &str> = vec![("master", "G"), ("feature", "H")]
+            .into_iter()
+            .collect();
+        let e = Evaluator::new(&dag)
+            .with_symbol_func(|name| {
+                Ok(bookmarks
+                    .get(name)
+                    .map(|v| NameSet::from(VertexName::copy_from(v.as_bytes()))))
+            })
+            .with_function("bookmark", |e, args| {
+                check_arg_count("bookmark", args, 1, 1)?;
+                e.eval(&args[0])
+            })
+            .with_function("draft", |e, args| {
+                check_arg_count("draft", args, 0, 0)?;
+                Ok(e.dag().all()? - e.dag().ancestors("C".into())?)
+            })
+            // Override a builtin function.
+            .with_function("all", |e, _args| e.eval_str("::master"));
+        assert_eq!(r(&e, "master % feature"), "B C F G");
+        assert_eq!(r(&e, "bookmark(feature)^"), "E");
+        assert_eq!(r(&e, "draft() & ::master"), "D E F G");
+        assert_eq!(r(&e, "all()"), "A B C D E F G");
+        assert_eq!(r(&e, "A::"), "A B C D E F G H");
+    }
+}
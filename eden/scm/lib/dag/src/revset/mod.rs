@@ -0,0 +1,51 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! # revset
+//!
+//! Parse and evaluate revset expressions using [`DagAlgorithm`].
+//!
+//! Supported operators, from loosest to tightest binding:
+//!
+//! - `x or y`, `x | y`, `x + y`: Union.
+//! - `x and y`, `x & y`: Intersection.
+//!   `x - y`: Difference.
+//!   `x % y`: `only(x, y)`.
+//! - `not x`, `!x`: Vertexes not in `x`.
+//! - `x::y`, `::y`, `x::`, `::`: Range, ancestors, descendants, all.
+//!   `..` is an alias of `::`.
+//! - `x^n`, `x^`: The n-th parent (`x^` is `x^1`).
+//!   `x~n`: The n-th first ancestor.
+//!
+//! Builtin functions: `all()`, `ancestors(set)`, `descendants(set)`,
+//! `parents(set)`, `children(set)`, `p1(set)`, `p2(set)`, `heads(set)`,
+//! `roots(set)`, `only(set[, exclude])`, `ancestor(set, ...)`,
+//! `limit(set[, n[, offset]])`, `first(set[, n])`, `last(set[, n])`,
+//! `sort(set, topo|-topo)`, `reverse(set)`.
+//!
+//! Unlike hg, `sort` requires a key. `topo` puts heads first, `-topo` puts
+//! roots first.
+//!
+//! Symbols and functions can be extended using [`Evaluator`].
+
+mod evaluator;
+mod parser;
+
+pub use evaluator::Evaluator;
+pub use evaluator::RevsetFunc;
+pub use evaluator::SymbolFunc;
+pub use parser::parse;
+pub use parser::Expr;
+
+use crate::DagAlgorithm;
+use crate::NameSet;
+use crate::Result;
+
+/// Evaluates a revset expression using builtin functions.
+pub fn eval(dag: &dyn DagAlgorithm, text: &str) -> Result<NameSet> {
+    Evaluator::new(dag).eval_str(text)
+}
@@ -0,0 +1,467 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Revset tokenizer and parser.
+//!
+//! The parser is a Pratt parser. Binding powers follow Mercurial's revset
+//! grammar so expressions are grouped the same way.
+
+use crate::errors::DagError;
+use crate::Result;
+
+/// Parsed revset expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    /// A name, like `foo`, or `1`.
+    Symbol(String),
+
+    /// A quoted string, like `"foo"`.
+    String(String),
+
+    /// `not x`, or `!x`.
+    Not(Box<Expr>),
+
+    /// `x and y`, or `x & y`.
+    And(Box<Expr>, Box<Expr>),
+
+    /// `x or y`, `x | y`, or `x + y`.
+    Or(Box<Expr>, Box<Expr>),
+
+    /// `x - y`.
+    Difference(Box<Expr>, Box<Expr>),
+
+    /// `x % y`.
+    Only(Box<Expr>, Box<Expr>),
+
+    /// `x::y`, `::y`, `x::`, or `::`. `..` is an alias of `::`.
+    Range(Option<Box<Expr>>, Option<Box<Expr>>),
+
+    /// `x^n`. `x^` is `x^1`.
+    Parent(Box<Expr>, u64),
+
+    /// `x~n`.
+    Ancestor(Box<Expr>, u64),
+
+    /// `name(args)`.
+    Func(String, Vec<Expr>),
+}
+
+impl Expr {
+    /// Get the string content of a symbol or a quoted string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Expr::Symbol(s) | Expr::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// Parse a revset expression.
+pub fn parse(text: &str) -> Result<Expr> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.parse_expr(0)?;
+    match parser.peek() {
+        Token::End => Ok(expr),
+        _ => parser.unexpected(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Symbol(String),
+    String(String),
+    Op(&'static str),
+    End,
+}
+
+/// Operators, longest first so `::` is not tokenized as `:`.
+const OPS: &[&str] = &[
+    "::", "..", "(", ")", ",", "^", "~", "-", "!", "&", "|", "+", "%",
+];
+
+fn is_symbol_char(ch: char) -> bool {
+    ch.is_alphanumeric() || "._/@".contains(ch) || ch as u32 > 127
+}
+
+/// Split `text` into tokens. Each token is paired with its byte offset.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    'outer: while let Some(&(pos, ch)) = chars.peek() {
+        if ch.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if ch == '"' || ch == '\'' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    None => return syntax_error(pos, "unterminated string"),
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c)) => s.push(c),
+                        None => return syntax_error(pos, "unterminated string"),
+                    },
+                    Some((_, c)) if c == ch => break,
+                    Some((_, c)) => s.push(c),
+                }
+            }
+            tokens.push((Token::String(s), pos));
+            continue;
+        }
+        if is_symbol_char(ch) && !text[pos..].starts_with("..") {
+            let mut s = String::new();
+            while let Some(&(_, c)) = chars.peek() {
+                // '-' is part of a symbol, unless it starts the symbol.
+                // Use spaces around '-' for differences, like `x - y`.
+                if is_symbol_char(c) || (c == '-' && !s.is_empty()) {
+                    // '..' ends a symbol.
+                    if c == '.' && text[pos + s.len() + 1..].starts_with('.') {
+                        break;
+                    }
+                    s.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            let token = match s.as_str() {
+                "and" => Token::Op("and"),
+                "or" => Token::Op("or"),
+                "not" => Token::Op("not"),
+                _ => Token::Symbol(s),
+            };
+            tokens.push((token, pos));
+            continue;
+        }
+        for op in OPS {
+            if text[pos..].starts_with(op) {
+                for _ in 0..op.len() {
+                    chars.next();
+                }
+                tokens.push((Token::Op(op), pos));
+                continue 'outer;
+            }
+        }
+        return syntax_error(pos, format!("unexpected character {:?}", ch));
+    }
+    tokens.push((Token::End, text.len()));
+    Ok(tokens)
+}
+
+fn syntax_error<T>(pos: usize, message: impl ToString) -> Result<T> {
+    Err(DagError::InvalidRevset(format!(
+        "syntax error at {}: {}",
+        pos,
+        message.to_string()
+    )))
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+/// Binding powers. Higher binds tighter.
+fn infix_binding_power(op: &str) -> Option<u8> {
+    let power = match op {
+        "^" | "~" => 18,
+        "::" | ".." => 17,
+        "-" | "and" | "&" | "%" => 5,
+        "or" | "|" | "+" => 4,
+        _ => return None,
+    };
+    Some(power)
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn unexpected<T>(&self) -> Result<T> {
+        let (token, pos) = &self.tokens[self.pos];
+        match token {
+            Token::End => syntax_error(*pos, "unexpected end of expression"),
+            Token::Symbol(s) | Token::String(s) => {
+                syntax_error(*pos, format!("unexpected {:?}", s))
+            }
+            Token::Op(op) => syntax_error(*pos, format!("unexpected {:?}", op)),
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<()> {
+        match self.peek() {
+            Token::Op(o) if *o == op => {
+                self.next();
+                Ok(())
+            }
+            _ => self.unexpected(),
+        }
+    }
+
+    /// Test if the next token can start an expression. This decides whether
+    /// `^` and `::` are postfix or infix operators.
+    fn peek_starts_expr(&self) -> bool {
+        match self.peek() {
+            Token::Symbol(_) | Token::String(_) => true,
+            Token::Op(op) => ["(", "not", "!", "::", ".."].contains(op),
+            Token::End => false,
+        }
+    }
+
+    fn peek_is_int(&self) -> bool {
+        match self.peek() {
+            Token::Symbol(s) => s.chars().all(|c| c.is_ascii_digit()),
+            _ => false,
+        }
+    }
+
+    fn parse_expr(&mut self, min_power: u8) -> Result<Expr> {
+        let mut lhs = self.parse_prefix()?;
+        while let Token::Op(op) = self.peek() {
+            let op = *op;
+            let power = match infix_binding_power(op) {
+                Some(power) if power > min_power => power,
+                _ => break,
+            };
+            self.next();
+            lhs = match op {
+                "^" => {
+                    // `x^::y` is `(x^)::y`. Only integers are taken as `n`.
+                    let n = if self.peek_is_int() {
+                        self.parse_int(power)?
+                    } else {
+                        1
+                    };
+                    Expr::Parent(Box::new(lhs), n)
+                }
+                "~" => Expr::Ancestor(Box::new(lhs), self.parse_int(power)?),
+                "::" | ".." => {
+                    let rhs = if self.peek_starts_expr() {
+                        Some(Box::new(self.parse_expr(power)?))
+                    } else {
+                        None
+                    };
+                    Expr::Range(Some(Box::new(lhs)), rhs)
+                }
+                _ => {
+                    let rhs = Box::new(self.parse_expr(power)?);
+                    let lhs = Box::new(lhs);
+                    match op {
+                        "-" => Expr::Difference(lhs, rhs),
+                        "and" | "&" => Expr::And(lhs, rhs),
+                        "%" => Expr::Only(lhs, rhs),
+                        "or" | "|" | "+" => Expr::Or(lhs, rhs),
+                        _ => unreachable!(),
+                    }
+                }
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn parse_prefix(&mut self) -> Result<Expr> {
+        match self.peek().clone() {
+            Token::Symbol(name) => {
+                self.next();
+                if self.peek() == &Token::Op("(") {
+                    self.next();
+                    let args = self.parse_args()?;
+                    Ok(Expr::Func(name, args))
+                } else {
+                    Ok(Expr::Symbol(name))
+                }
+            }
+            Token::String(s) => {
+                self.next();
+                Ok(Expr::String(s))
+            }
+            Token::Op("(") => {
+                self.next();
+                let expr = self.parse_expr(0)?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Op("not") | Token::Op("!") => {
+                self.next();
+                Ok(Expr::Not(Box::new(self.parse_expr(10)?)))
+            }
+            Token::Op("::") | Token::Op("..") => {
+                self.next();
+                let rhs = if self.peek_starts_expr() {
+                    Some(Box::new(self.parse_expr(17)?))
+                } else {
+                    None
+                };
+                Ok(Expr::Range(None, rhs))
+            }
+            _ => self.unexpected(),
+        }
+    }
+
+    /// Parse function arguments after `(`, until `)`.
+    fn parse_args(&mut self) -> Result<Vec<Expr>> {
+        let mut args = Vec::new();
+        if self.peek() == &Token::Op(")") {
+            self.next();
+            return Ok(args);
+        }
+        loop {
+            args.push(self.parse_expr(2)?);
+            match self.peek() {
+                Token::Op(",") => self.next(),
+                Token::Op(")") => {
+                    self.next();
+                    break;
+                }
+                _ => return self.unexpected(),
+            };
+        }
+        Ok(args)
+    }
+
+    /// Parse a non-negative integer, as the right side of `^` or `~`.
+    fn parse_int(&mut self, power: u8) -> Result<u64> {
+        let pos = self.tokens[self.pos].1;
+        match self.parse_expr(power)? {
+            Expr::Symbol(s) => match s.parse::<u64>() {
+                Ok(n) => Ok(n),
+                Err(_) => syntax_error(pos, format!("{:?} is not a non-negative integer", s)),
+            },
+            _ => syntax_error(pos, "expect a non-negative integer"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse and format the result in a compact S-expression form.
+    fn p(text: &str) -> String {
+        match parse(text) {
+            Ok(expr) => fmt(&expr),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    fn fmt(expr: &Expr) -> String {
+        let opt = |e: &Option<Box<Expr>>| match e {
+            Some(e) => fmt(e),
+            None => "_".to_string(),
+        };
+        match expr {
+            Expr::Symbol(s) => s.clone(),
+            Expr::String(s) => format!("{:?}", s),
+            Expr::Not(e) => format!("(not {})", fmt(e)),
+            Expr::And(l, r) => format!("(and {} {})", fmt(l), fmt(r)),
+            Expr::Or(l, r) => format!("(or {} {})", fmt(l), fmt(r)),
+            Expr::Difference(l, r) => format!("(- {} {})", fmt(l), fmt(r)),
+            Expr::Only(l, r) => format!("(% {} {})", fmt(l), fmt(r)),
+            Expr::Range(l, r) => format!("(:: {} {})", opt(l), opt(r)),
+            Expr::Parent(e, n) => format!("(^ {} {})", fmt(e), n),
+            Expr::Ancestor(e, n) => format!("(~ {} {})", fmt(e), n),
+            Expr::Func(name, args) => {
+                let args: Vec<String> = args.iter().map(fmt).collect();
+                format!("{}({})", name, args.join(", "))
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_symbols() {
+        assert_eq!(p("a"), "a");
+        assert_eq!(p(" foo-bar.baz "), "foo-bar.baz");
+        assert_eq!(p("'a b'"), "\"a b\"");
+        assert_eq!(p(r#""a\"b""#), "\"a\\\"b\"");
+        assert_eq!(p("(a)"), "a");
+    }
+
+    #[test]
+    fn test_parse_operators() {
+        assert_eq!(p("a + b & c"), "(or a (and b c))");
+        assert_eq!(p("a | b or c"), "(or (or a b) c)");
+        assert_eq!(p("not a and b"), "(and (not a) b)");
+        assert_eq!(p("!a - b"), "(- (not a) b)");
+        assert_eq!(p("a - b - c"), "(- (- a b) c)");
+        assert_eq!(p("a % b"), "(% a b)");
+        assert_eq!(p("a::b"), "(:: a b)");
+        assert_eq!(p("a..b"), "(:: a b)");
+        assert_eq!(p("::a"), "(:: _ a)");
+        assert_eq!(p("a::"), "(:: a _)");
+        assert_eq!(p("::"), "(:: _ _)");
+        assert_eq!(p("a:: & b"), "(and (:: a _) b)");
+        assert_eq!(p("a::b & c"), "(and (:: a b) c)");
+        assert_eq!(p("a^"), "(^ a 1)");
+        assert_eq!(p("a^2"), "(^ a 2)");
+        assert_eq!(p("a^^"), "(^ (^ a 1) 1)");
+        assert_eq!(p("a~3^"), "(^ (~ a 3) 1)");
+        assert_eq!(p("a^::b~1"), "(:: (^ a 1) (~ b 1))");
+        assert_eq!(p("(a + b)^"), "(^ (or a b) 1)");
+    }
+
+    #[test]
+    fn test_parse_functions() {
+        assert_eq!(p("all()"), "all()");
+        assert_eq!(p("heads(a::b)"), "heads((:: a b))");
+        assert_eq!(
+            p("limit(sort(a + b, '-topo'), 1, 2)"),
+            "limit(sort((or a b), \"-topo\"), 1, 2)"
+        );
+        assert_eq!(p("f(a)^"), "(^ f(a) 1)");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            p(""),
+            "invalid revset: syntax error at 0: unexpected end of expression"
+        );
+        assert_eq!(
+            p("a b"),
+            "invalid revset: syntax error at 2: unexpected \"b\""
+        );
+        assert_eq!(
+            p("(a"),
+            "invalid revset: syntax error at 2: unexpected end of expression"
+        );
+        assert_eq!(
+            p("f(a b)"),
+            "invalid revset: syntax error at 4: unexpected \"b\""
+        );
+        assert_eq!(
+            p("a & & b"),
+            "invalid revset: syntax error at 4: unexpected \"&\""
+        );
+        assert_eq!(
+            p("'a"),
+            "invalid revset: syntax error at 0: unterminated string"
+        );
+        assert_eq!(
+            p("a # b"),
+            "invalid revset: syntax error at 2: unexpected character '#'"
+        );
+        assert_eq!(
+            p("a~b"),
+            "invalid revset: syntax error at 2: \"b\" is not a non-negative integer"
+        );
+        assert_eq!(
+            p("a~f()"),
+            "invalid revset: syntax error at 2: expect a non-negative integer"
+        );
+    }
+}
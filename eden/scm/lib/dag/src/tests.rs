@@ -8,6 +8,7 @@
 use crate::id::{Group, VertexName};
 use crate::ops::DagAddHeads;
 use crate::ops::DagPersistent;
+use crate::ops::DagStrip;
 use crate::ops::ImportAscii;
 use crate::render::render_namedag;
 use crate::DagAlgorithm;
@@ -705,12 +706,24 @@ Lv1: R0-6[]"#
         Id(8)
     );
     assert_eq!(
-        built.name_dag.map.find_name_by_id(Id(8)).unwrap().unwrap(),
+        built
+            .name_dag
+            .map
+            .find_name_by_id(Id(8))
+            .unwrap()
+            .unwrap()
+            .as_ref(),
         b"m"
     );
     let id = Group::NON_MASTER.min_id() + 5;
     assert_eq!(
-        built.name_dag.map.find_name_by_id(id).unwrap().unwrap(),
+        built
+            .name_dag
+            .map
+            .find_name_by_id(id)
+            .unwrap()
+            .unwrap()
+            .as_ref(),
         b"q"
     );
 
@@ -780,6 +793,47 @@ fn test_namedag_reassign_non_master() {
     assert_eq!(format!("{:?}", z_vertex), "Z");
 }
 
+#[test]
+fn test_namedag_strip() {
+    let mut t = TestDag::new();
+
+    // A, B: master; C, D, E, F, G: non-master.
+    t.drawdag("A--B--C--D--E", &["B"]);
+    t.drawdag("C--F--G", &[]);
+
+    // Strip D and its descendants.
+    t.dag.strip(&"D".into()).unwrap();
+    assert_eq!(
+        t.render_graph(),
+        r#"
+            G  N2
+            │
+            F  N1
+            │
+            C  N0
+            │
+            B  1
+            │
+            A  0"#
+    );
+    assert!(!t.dag.contains_vertex_name(&"E".into()).unwrap());
+
+    // The change is persisted.
+    let dag = NameDag::open(t.dir.path().join("n")).unwrap();
+    assert_eq!(
+        format!("{:?}", dag.all().unwrap()),
+        "<spans [C:G+N0:N2, A:B+0:1]>"
+    );
+
+    // Stripping missing vertexes is a no-op.
+    t.dag.strip(&"E".into()).unwrap();
+
+    // Master vertexes cannot be stripped.
+    let err = t.dag.strip(&"B".into()).unwrap_err();
+    assert!(err.to_string().contains("cannot strip master vertex B"));
+    assert!(t.dag.contains_vertex_name(&"C".into()).unwrap());
+}
+
 #[test]
 fn test_segment_ancestors_example1() {
     // DAG from segmented-changelog.pdf
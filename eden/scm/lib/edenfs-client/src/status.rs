@@ -175,7 +175,8 @@ Your running Eden server is more than 45 days old.  You should run
 
 const NULL_COMMIT: [u8; 20] = [0; 20];
 
-fn needs_morestatus_extension(hg_dir: &Path, p2: &[u8; 20]) -> bool {
+/// Whether the Python `status` is needed to report an unfinished operation or merge.
+pub fn needs_morestatus_extension(hg_dir: &Path, p2: &[u8; 20]) -> bool {
     if p2 != &NULL_COMMIT {
         return true;
     }
@@ -401,6 +402,50 @@ impl PrintConfig {
         io: &mut IO,
     ) -> Result<u8> {
         let groups = group_entries(&repo_root, &status, &dirstate_data)?;
+        self.print_groups(&groups, &dirstate_data.copymap, relativizer, use_color, io)?;
+
+        if status.errors.is_empty() {
+            Ok(0)
+        } else {
+            io.write_err("Encountered errors computing status for some paths:\n")?;
+            for (path_str, error) in &status.errors {
+                let path = Path::new(str::from_utf8(path_str)?);
+                io.write_err(format!(
+                    "  {}: {}\n",
+                    &relativizer.relativize(&path.to_path_buf()).display(),
+                    error,
+                ))?;
+            }
+            Ok(1)
+        }
+    }
+
+    /// Print the status of a working copy that is not backed by EdenFS, where `groups` was
+    /// computed by the caller. `copymap` maps copied files to their copy sources.
+    pub fn print_grouped_status(
+        &self,
+        repo_root: &Path,
+        cwd: &Path,
+        groups: &GroupedEntries,
+        copymap: &HashMap<PathBuf, PathBuf>,
+        io: &mut IO,
+    ) -> Result<u8> {
+        let stdout = io::stdout();
+        let use_color = should_colorize_output(&stdout);
+        let relativizer = PathRelativizer::new(cwd, repo_root);
+        let relativizer = HgStatusPathRelativizer::new(self.root_relative, relativizer);
+        self.print_groups(groups, copymap, &relativizer, use_color, io)?;
+        Ok(0)
+    }
+
+    fn print_groups(
+        &self,
+        groups: &GroupedEntries,
+        copymap: &HashMap<PathBuf, PathBuf>,
+        relativizer: &HgStatusPathRelativizer,
+        use_color: bool,
+        io: &mut IO,
+    ) -> Result<(), io::Error> {
         let endl = self.endl;
 
         let mut print_group =
@@ -437,7 +482,7 @@ impl PrintConfig {
                         endl
                     ))?;
                     if self.copies {
-                        if let Some(ref p) = dirstate_data.copymap.get(path) {
+                        if let Some(ref p) = copymap.get(path) {
                             io.write(format!(
                                 "  {}{}",
                                 &relativizer.relativize(p).display(),
@@ -477,20 +522,7 @@ impl PrintConfig {
         )?;
         print_group(PrintGroup::Clean, self.status_types.clean, &groups.clean)?;
 
-        if status.errors.is_empty() {
-            Ok(0)
-        } else {
-            io.write_err("Encountered errors computing status for some paths:\n")?;
-            for (path_str, error) in &status.errors {
-                let path = Path::new(str::from_utf8(path_str)?);
-                io.write_err(format!(
-                    "  {}: {}\n",
-                    &relativizer.relativize(&path.to_path_buf()).display(),
-                    error,
-                ))?;
-            }
-            Ok(1)
-        }
+        Ok(())
     }
 }
 
@@ -515,15 +547,16 @@ enum PrintGroup {
     Clean,
 }
 
+/// Paths relative to the repository root, grouped by status.
 #[derive(Default)]
-struct GroupedEntries {
-    modified: Vec<PathBuf>,
-    added: Vec<PathBuf>,
-    removed: Vec<PathBuf>,
-    deleted: Vec<PathBuf>,
-    unknown: Vec<PathBuf>,
-    ignored: Vec<PathBuf>,
-    clean: Vec<PathBuf>,
+pub struct GroupedEntries {
+    pub modified: Vec<PathBuf>,
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+    pub unknown: Vec<PathBuf>,
+    pub ignored: Vec<PathBuf>,
+    pub clean: Vec<PathBuf>,
 }
 
 fn group_entries(
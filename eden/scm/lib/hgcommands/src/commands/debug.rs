@@ -14,7 +14,9 @@ pub use super::IO;
 
 commands! {
     mod args;
+    mod blackboxquery;
     mod causerusterror;
+    mod configschema;
     mod dumpindexedlog;
     mod dumptrace;
     mod dynamicconfig;
@@ -0,0 +1,126 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use super::define_flags;
+use super::Repo;
+use super::Result;
+use super::IO;
+use blackbox::query::{write_csv, write_jsonl, Order, Query};
+use clidispatch::errors;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+define_flags! {
+    pub struct BlackboxQueryOpts {
+        /// time range
+        #[short('t')]
+        time_range: String = "since 15 minutes ago",
+
+        /// event types to include (ex. network, finish)
+        #[short('e')]
+        event_type: Vec<String>,
+
+        /// minimal duration in milliseconds
+        min_duration: i64,
+
+        /// JSON pattern to match, can use ["capture", NAME, PATTERN]
+        #[short('p')]
+        pattern: String,
+
+        /// sort order (time, -time, -duration)
+        sort: String = "time",
+
+        /// maximum number of entries (0: unlimited)
+        #[short('l')]
+        limit: i64,
+
+        /// output format (jsonl, csv), default: decided by output path
+        format: String,
+
+        /// output path (.jsonl, .csv)
+        #[short('o')]
+        output_path: String,
+    }
+}
+
+pub fn run(opts: BlackboxQueryOpts, io: &mut IO, _repo: Repo) -> Result<u8> {
+    let range = match hgtime::HgTime::parse_range(&opts.time_range) {
+        Some(range) => range,
+        None => return Err(errors::Abort("invalid --time-range".into()).into()),
+    };
+    // Blackbox uses milliseconds. HgTime uses seconds. Both range ends are
+    // exclusive, so the last second is fully included.
+    let ratio = 1000;
+    let mut query = Query::new()
+        .time_range(
+            (range.start.unixtime.max(0) as u64).saturating_mul(ratio)
+                ..(range.end.unixtime.max(0) as u64).saturating_mul(ratio),
+        )
+        .event_types(&opts.event_type);
+    if opts.min_duration > 0 {
+        query = query.min_duration_ms(opts.min_duration as u64);
+    }
+    if !opts.pattern.is_empty() {
+        let pattern = match blackbox::serde_json::from_str(&opts.pattern) {
+            Ok(pattern) => pattern,
+            Err(e) => return Err(errors::Abort(format!("invalid --pattern: {}", e).into()).into()),
+        };
+        query = query.pattern(pattern);
+    }
+    query = query.order(match opts.sort.as_str() {
+        "time" => Order::Time,
+        "-time" => Order::TimeReversed,
+        "-duration" => Order::Duration,
+        _ => return Err(errors::Abort(format!("invalid --sort: {}", opts.sort).into()).into()),
+    });
+    if opts.limit > 0 {
+        query = query.limit(opts.limit as usize);
+    }
+
+    let rows = blackbox::SINGLETON.lock().query(&query);
+
+    let path = &opts.output_path;
+    let format = match opts.format.as_str() {
+        "" if path.ends_with(".csv") => "csv",
+        "" => "jsonl",
+        format => format,
+    };
+    let mut out: Box<dyn Write> = if path == "-" || path.is_empty() {
+        Box::new(&mut io.output)
+    } else {
+        Box::new(BufWriter::new(File::create(&path)?))
+    };
+    match format {
+        "jsonl" => write_jsonl(&rows, &mut out)?,
+        "csv" => write_csv(&rows, &mut out)?,
+        _ => return Err(errors::Abort(format!("invalid --format: {}", format).into()).into()),
+    }
+    out.flush()?;
+
+    Ok(0)
+}
+
+pub fn name() -> &'static str {
+    "debugblackboxquery"
+}
+
+pub fn doc() -> &'static str {
+    r#"query and export blackbox events
+
+    Events logged in the time range are filtered by event types, duration,
+    and JSON patterns. See :hg:`help blackbox` for the pattern syntax.
+    Values captured by ``["capture", NAME, PATTERN]`` are included in the
+    output.
+
+    For example, network operations slower than 5 seconds in the last week::
+
+        hg debugblackboxquery -t 'since 7 days ago' -e network \
+          --min-duration 5000 --sort -duration -o slow.csv
+
+    The output is JSON lines by default, or CSV if the output path ends
+    with ``.csv`` or ``--format csv`` is used."#
+}
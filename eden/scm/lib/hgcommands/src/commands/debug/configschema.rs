@@ -0,0 +1,88 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use super::define_flags;
+use super::ConfigSet;
+use super::Result;
+use super::IO;
+use configparser::schema::{self, line_location};
+
+define_flags! {
+    pub struct DebugConfigSchemaOpts {
+        /// show types and documentation
+        doc: bool,
+
+        /// report configs that do not match the schema
+        check: bool,
+    }
+}
+
+pub fn run(opts: DebugConfigSchemaOpts, io: &mut IO, config: ConfigSet) -> Result<u8> {
+    let schema = schema::registered();
+
+    if opts.check {
+        let warnings = config.validate(&schema);
+        for warning in warnings.iter() {
+            io.write(format!("{}\n", warning))?;
+        }
+        return Ok(if warnings.is_empty() { 0 } else { 1 });
+    }
+
+    for item in schema.items() {
+        let key = format!("{}.{}", item.section().as_ref(), item.name().as_ref());
+        let sources = item.sources(&config);
+        let (value, origin) = match sources.last() {
+            Some(source) => {
+                let origin = match line_location(source) {
+                    Some((path, line)) => format!("{}:{}", path.display(), line),
+                    None => source.source().to_string(),
+                };
+                match source.value() {
+                    Some(value) => (value.to_string(), origin),
+                    None => (String::new(), format!("unset by {}", origin)),
+                }
+            }
+            None => match item.default_value() {
+                Some(value) => (value.to_string(), "default".to_string()),
+                None => (String::new(), "not set".to_string()),
+            },
+        };
+        io.write(format!("{}={}  # {}\n", key, value, origin))?;
+
+        if opts.doc {
+            let mut attributes = vec![item.value_type().name().to_string()];
+            if let Some(default) = item.default_value() {
+                attributes.push(format!("default: {}", default.as_ref()));
+            }
+            if !item.allowed_values().is_empty() {
+                let allowed: Vec<&str> = item.allowed_values().iter().map(|v| v.as_ref()).collect();
+                attributes.push(format!("allowed: {}", allowed.join(", ")));
+            }
+            for (section, name) in item.aliases() {
+                attributes.push(format!(
+                    "deprecated: {}.{}",
+                    section.as_ref(),
+                    name.as_ref()
+                ));
+            }
+            io.write(format!("    ({})\n", attributes.join("; ")))?;
+            if !item.documentation().is_empty() {
+                io.write(format!("    {}\n", item.documentation().as_ref()))?;
+            }
+        }
+    }
+
+    Ok(0)
+}
+
+pub fn name() -> &'static str {
+    "debugconfigschema"
+}
+
+pub fn doc() -> &'static str {
+    "show known configs with their effective values and origins"
+}
@@ -22,7 +22,7 @@ define_flags! {
         #[short('s')]
         session_id: i64,
 
-        /// output path (.txt, .json, .json.gz, .spans.json)
+        /// output path (.txt, .json, .json.gz, .spans.json, .otlp.json, .folded, .pb.gz)
         #[short('o')]
         output_path: String,
     }
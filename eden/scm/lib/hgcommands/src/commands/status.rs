@@ -9,8 +9,26 @@ use crate::commands::{FormatterOpts, WalkOpts};
 use anyhow::Result;
 use clidispatch::{errors, io::IO, repo::Repo};
 use cliparser::define_flags;
-
-use edenfs_client::status::{maybe_status_fastpath, PrintConfig, PrintConfigStatusTypes};
+use configparser::hg::ConfigSetHgExt;
+use parking_lot::Mutex;
+use pathmatcher::{
+    expand_curly_brackets, normalize_glob, AlwaysMatcher, DifferenceMatcher, GitignoreMatcher,
+    IntersectMatcher, TreeMatcher, UnionMatcher,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use treestate::store::BlockId;
+use treestate::treestate::TreeState;
+use types::{HgId, RepoPathBuf};
+use workingcopy::filesystem::PhysicalFileSystem;
+
+use edenfs_client::status::{
+    maybe_status_fastpath, needs_morestatus_extension, GroupedEntries, PrintConfig,
+    PrintConfigStatusTypes,
+};
 
 define_flags! {
     pub struct StatusOpts {
@@ -82,21 +100,16 @@ define_flags! {
 pub fn run(opts: StatusOpts, io: &mut IO, repo: Repo) -> Result<u8> {
     let rev_check = opts.rev.is_empty() || (opts.rev.len() == 1 && opts.rev[0] == ".");
 
-    let args_check = opts.args.is_empty() || (opts.args.len() == 1 && opts.args[0] == "re:.");
-
-    if opts.all
-        || !opts.change.is_empty()
+    if !opts.change.is_empty()
         || !opts.terse.is_empty()
         || !rev_check
-        || !opts.walk_opts.include.is_empty()
-        || !opts.walk_opts.exclude.is_empty()
         || !opts.formatter_opts.template.is_empty()
-        || !args_check
     {
         return Err(errors::FallbackToPython.into());
     }
 
     let StatusOpts {
+        all,
         modified,
         added,
         removed,
@@ -107,7 +120,17 @@ pub fn run(opts: StatusOpts, io: &mut IO, repo: Repo) -> Result<u8> {
         ..
     } = opts;
 
-    let status_types = if modified || added || removed || deleted || clean || unknown || ignored {
+    let status_types = if all {
+        PrintConfigStatusTypes {
+            modified: true,
+            added: true,
+            removed: true,
+            deleted: true,
+            clean: true,
+            unknown: true,
+            ignored: true,
+        }
+    } else if modified || added || removed || deleted || clean || unknown || ignored {
         PrintConfigStatusTypes {
             modified,
             added,
@@ -131,13 +154,427 @@ pub fn run(opts: StatusOpts, io: &mut IO, repo: Repo) -> Result<u8> {
     let print_config = PrintConfig {
         status_types,
         no_status: opts.no_status,
-        copies: opts.copies,
+        // Note that if --no-status is specified, then it disables --copies.
+        copies: !opts.no_status && (opts.copies || all),
         endl: if opts.print0 { '\0' } else { '\n' },
         root_relative: opts.root_relative,
     };
 
     let cwd = std::env::current_dir()?;
-    maybe_status_fastpath(repo.path(), &cwd, print_config, io)
+    if repo.path().join(".eden").exists() {
+        let args_check = opts.args.is_empty() || (opts.args.len() == 1 && opts.args[0] == "re:.");
+        if all
+            || !opts.walk_opts.include.is_empty()
+            || !opts.walk_opts.exclude.is_empty()
+            || !args_check
+        {
+            return Err(errors::FallbackToPython.into());
+        }
+        return maybe_status_fastpath(repo.path(), &cwd, print_config, io);
+    }
+
+    treestate_status(&opts, &repo, &cwd, print_config, io)
+}
+
+/// Status for working copies that are not backed by EdenFS, using the treestate dirstate.
+///
+/// Falls back to Python for what is not implemented here: files that need their content
+/// compared with the parent, merges, unfinished operations, and sparse checkouts using
+/// `%include` profiles (they are read from the commit). Set `status.use-rust` to false to
+/// always use Python.
+fn treestate_status(
+    opts: &StatusOpts,
+    repo: &Repo,
+    cwd: &Path,
+    print_config: PrintConfig,
+    io: &mut IO,
+) -> Result<u8> {
+    if !repo.config().get_or("status", "use-rust", || true)? {
+        return Err(errors::FallbackToPython.into());
+    }
+    let root = repo.path();
+    let dot_hg = repo.dot_hg_path();
+    if !has_requirement(dot_hg, "treestate")? {
+        return Err(errors::FallbackToPython.into());
+    }
+    let dirstate = match read_dirstate(dot_hg)? {
+        Some(dirstate) => dirstate,
+        None => return Err(errors::FallbackToPython.into()),
+    };
+    if needs_morestatus_extension(dot_hg, &dirstate.p2) {
+        return Err(errors::FallbackToPython.into());
+    }
+    let matcher = match build_matcher(root, cwd, &opts.args, &opts.walk_opts)? {
+        Some(matcher) => matcher,
+        None => return Err(errors::FallbackToPython.into()),
+    };
+
+    let treestate_path = dot_hg.join("treestate").join(&dirstate.filename);
+    let treestate = TreeState::open(treestate_path, Some(BlockId(dirstate.root_id)))?;
+    // Let Python report a damaged dirstate.
+    let metadata = parse_metadata(treestate.get_metadata());
+    let p1 = HgId::from_slice(&dirstate.p1)?.to_hex();
+    if metadata.get("p1") != Some(&p1) {
+        return Err(errors::FallbackToPython.into());
+    }
+
+    let sparse = match sparse_matcher(repo)? {
+        Some(sparse) => sparse,
+        None => return Err(errors::FallbackToPython.into()),
+    };
+
+    // Like the sparse extension, treat files outside the sparse checkout as ignored.
+    let ignore_paths = global_ignore_paths(repo);
+    let ignore = UnionMatcher::new(
+        GitignoreMatcher::new(root, ignore_paths.iter().map(|p| p.as_path()).collect()),
+        DifferenceMatcher::new(AlwaysMatcher::new(), sparse),
+    );
+    let filesystem = PhysicalFileSystem::new(root.to_path_buf())?;
+    let status_types = &print_config.status_types;
+    let status = filesystem.status(
+        Arc::new(Mutex::new(treestate)),
+        matcher,
+        Arc::new(ignore),
+        status_types.ignored,
+        status_types.clean,
+        0u32.into(),
+    )?;
+
+    // Python compares the content and records clean files in the dirstate, so the next
+    // status can be answered here.
+    if !status.lookups.is_empty() {
+        return Err(errors::FallbackToPython.into());
+    }
+
+    let to_paths = |paths: Vec<RepoPathBuf>| -> Vec<PathBuf> {
+        paths.iter().map(|p| p.as_str().into()).collect()
+    };
+    let copymap: HashMap<PathBuf, PathBuf> = status
+        .copies
+        .iter()
+        .map(|(dest, source)| (dest.as_str().into(), source.as_str().into()))
+        .collect();
+    let groups = GroupedEntries {
+        modified: to_paths(status.modified),
+        added: to_paths(status.added),
+        removed: to_paths(status.removed),
+        deleted: to_paths(status.deleted),
+        unknown: to_paths(status.unknown),
+        ignored: to_paths(status.ignored),
+        clean: to_paths(status.clean),
+    };
+    print_config.print_grouped_status(root, cwd, &groups, &copymap, io)
+}
+
+const TREESTATE_HEADER: &[u8] = b"\ntreestate\n\0";
+
+/// Content of `.hg/dirstate` pointing to the treestate file.
+struct TreeStateDirstate {
+    p1: [u8; 20],
+    p2: [u8; 20],
+    filename: String,
+    root_id: u64,
+}
+
+/// Returns `None` if the dirstate is missing or not using treestate.
+fn read_dirstate(dot_hg: &Path) -> Result<Option<TreeStateDirstate>> {
+    let content = match fs::read(dot_hg.join("dirstate")) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let header_end = 40 + TREESTATE_HEADER.len();
+    if content.len() < header_end || &content[40..header_end] != TREESTATE_HEADER {
+        return Ok(None);
+    }
+    let metadata = parse_metadata(&content[header_end..]);
+    let (filename, root_id) = match (metadata.get("filename"), metadata.get("rootid")) {
+        (Some(filename), Some(root_id)) => (filename.clone(), root_id.parse::<u64>()?),
+        _ => return Ok(None),
+    };
+    if root_id == 0 {
+        return Ok(None);
+    }
+    let mut p1 = [0; 20];
+    p1.copy_from_slice(&content[..20]);
+    let mut p2 = [0; 20];
+    p2.copy_from_slice(&content[20..40]);
+    Ok(Some(TreeStateDirstate {
+        p1,
+        p2,
+        filename,
+        root_id,
+    }))
+}
+
+/// Parse "key=value" entries separated by NUL.
+fn parse_metadata(data: &[u8]) -> HashMap<String, String> {
+    String::from_utf8_lossy(data)
+        .split('\0')
+        .filter_map(|entry| {
+            let mut parts = entry.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => Some((key.to_string(), value.to_string())),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn has_requirement(dot_hg: &Path, requirement: &str) -> Result<bool> {
+    let requires = match fs::read_to_string(dot_hg.join("requires")) {
+        Ok(requires) => requires,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(requires.lines().any(|line| line == requirement))
+}
+
+/// Global ignore files from `ui.ignore` and `ui.ignore.*`.
+fn global_ignore_paths(repo: &Repo) -> Vec<PathBuf> {
+    let config = repo.config();
+    config
+        .keys("ui")
+        .into_iter()
+        .filter(|name| &**name == "ignore" || name.starts_with("ignore."))
+        .filter_map(|name| config.get("ui", name))
+        .map(|path| repo.path().join(util::path::expand_path(path)))
+        .collect()
+}
+
+/// The files included by `.hg/sparse` and `.hg/tempsparse`. Returns `None` if the sparse
+/// config is not supported here.
+fn sparse_matcher(repo: &Repo) -> Result<Option<TreeMatcher>> {
+    let config = repo.config();
+    let enabled =
+        ["sparse", "hgext.sparse"]
+            .iter()
+            .any(|name| match config.get("extensions", name) {
+                Some(value) => !value.starts_with('!'),
+                None => false,
+            });
+    let dot_hg = repo.dot_hg_path();
+    let content = match fs::read_to_string(dot_hg.join("sparse")) {
+        Ok(content) if enabled => content,
+        Ok(_) => return Ok(Some(TreeMatcher::always())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Some(TreeMatcher::always())),
+        Err(e) => return Err(e.into()),
+    };
+
+    // See `readsparseconfig` in the sparse extension.
+    let mut includes = Vec::new();
+    let mut excludes = Vec::new();
+    let mut section = "[include]";
+    for line in content.lines() {
+        let stripped = line.trim();
+        if stripped.is_empty() || stripped.starts_with('#') || stripped.starts_with(';') {
+            continue;
+        }
+        match stripped {
+            // Python aborts with an error message.
+            "[include]" if section == "[exclude]" => return Ok(None),
+            "[include]" | "[exclude]" | "[metadata]" => {
+                section = stripped;
+                continue;
+            }
+            _ => {}
+        }
+        if section == "[metadata]" {
+            continue;
+        }
+        // Profiles are read from the commit. Python warns about paths starting with "/".
+        // Python keeps surrounding spaces in patterns.
+        if stripped.starts_with("%include") || stripped.starts_with('/') || stripped != line {
+            return Ok(None);
+        }
+        if section == "[include]" {
+            includes.push(line);
+        } else {
+            excludes.push(line);
+        }
+    }
+
+    let mut rules = Vec::new();
+    if includes.is_empty() {
+        rules.push("**".to_string());
+    } else {
+        includes.push(".hg*");
+    }
+    for include in includes {
+        match pattern_to_rules(repo.path(), "", include, "glob") {
+            Some(include_rules) => rules.extend(include_rules),
+            None => return Ok(None),
+        }
+    }
+    for exclude in excludes {
+        match pattern_to_rules(repo.path(), "", exclude, "glob") {
+            Some(exclude_rules) => rules.extend(exclude_rules.iter().map(|r| format!("!{}", r))),
+            None => return Ok(None),
+        }
+    }
+    // Temporarily included files are included even if they are excluded above.
+    match fs::read_to_string(dot_hg.join("tempsparse")) {
+        Ok(content) => rules.extend(
+            content
+                .split('\n')
+                .filter(|path| !path.is_empty())
+                .map(escape_glob),
+        ),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    Ok(Some(TreeMatcher::from_rules(rules.iter())?))
+}
+
+type StatusMatcher = DifferenceMatcher<IntersectMatcher<TreeMatcher, TreeMatcher>, TreeMatcher>;
+
+/// Build the matcher for file patterns and `-I`/`-X`. Returns `None` if a pattern is not
+/// supported here.
+fn build_matcher(
+    root: &Path,
+    cwd: &Path,
+    args: &[String],
+    walk_opts: &WalkOpts,
+) -> Result<Option<StatusMatcher>> {
+    let cwd = match cwd.strip_prefix(root) {
+        Ok(cwd) => cwd,
+        Err(_) => return Ok(None),
+    };
+    let cwd = match cwd.to_str() {
+        Some(cwd) => cwd.replace(std::path::MAIN_SEPARATOR, "/"),
+        None => return Ok(None),
+    };
+    let build = |patterns: &[String], default_kind: &str, empty: TreeMatcher| {
+        if patterns.is_empty() {
+            return Ok(Some(empty));
+        }
+        let mut rules = Vec::with_capacity(patterns.len());
+        for pattern in patterns {
+            match pattern_to_rules(root, &cwd, pattern, default_kind) {
+                Some(pattern_rules) => rules.extend(pattern_rules),
+                None => return Ok(None),
+            }
+        }
+        TreeMatcher::from_rules(rules.iter()).map(Some)
+    };
+    let patterns = build(args, "relpath", TreeMatcher::always())?;
+    let include = build(&walk_opts.include, "glob", TreeMatcher::always())?;
+    let exclude = build(&walk_opts.exclude, "glob", TreeMatcher::never())?;
+    Ok(match (patterns, include, exclude) {
+        (Some(patterns), Some(include), Some(exclude)) => Some(DifferenceMatcher::new(
+            IntersectMatcher::new(patterns, include),
+            exclude,
+        )),
+        _ => None,
+    })
+}
+
+const PATTERN_KINDS: &[&str] = &[
+    "re",
+    "glob",
+    "path",
+    "relglob",
+    "relpath",
+    "relre",
+    "listfile",
+    "listfile0",
+    "set",
+    "include",
+    "subinclude",
+    "rootfilesin",
+];
+
+/// Convert a pattern to recursive `TreeMatcher` rules. Only `path:`, `relpath:` and `glob:`
+/// are supported.
+fn pattern_to_rules(
+    root: &Path,
+    cwd: &str,
+    pattern: &str,
+    default_kind: &str,
+) -> Option<Vec<String>> {
+    if pattern == "re:." {
+        return Some(vec!["**".to_string()]);
+    }
+    let (kind, pattern) = match pattern.find(':') {
+        Some(index) if PATTERN_KINDS.contains(&&pattern[..index]) => {
+            (&pattern[..index], &pattern[index + 1..])
+        }
+        _ => (default_kind, pattern),
+    };
+    let rules = match kind {
+        "path" => vec![escape_glob(&canonical_path(root, "", pattern)?)],
+        "relpath" => vec![escape_glob(&canonical_path(root, cwd, pattern)?)],
+        "glob" => {
+            // TreeMatcher treats "{" and "}" literally. Expand alternatives like "a/{b,c}" to
+            // separate rules. Escaped brackets are left to Python.
+            let mut rules = Vec::new();
+            for pattern in expand_curly_brackets(pattern) {
+                if pattern.contains('{') || pattern.contains('}') {
+                    return None;
+                }
+                rules.push(normalize_glob(&canonical_path(
+                    root,
+                    &escape_glob(cwd),
+                    &pattern,
+                )?));
+            }
+            if rules.is_empty() {
+                // Unbalanced brackets.
+                return None;
+            }
+            rules
+        }
+        _ => return None,
+    };
+    let rules = rules
+        .into_iter()
+        .map(|rule| {
+            if rule.is_empty() {
+                "**".to_string()
+            } else {
+                format!("{}/**", rule)
+            }
+        })
+        .collect();
+    Some(rules)
+}
+
+/// Escape a path so it can be used as a `TreeMatcher` rule. Unlike `plain_to_glob`, "{" and
+/// "}" are not escaped, since `TreeMatcher` treats them literally.
+fn escape_glob(path: &str) -> String {
+    let mut result = String::with_capacity(path.len());
+    if path.starts_with('!') {
+        result.push('\\');
+    }
+    for ch in path.chars() {
+        match ch {
+            '\\' | '*' | '?' | '[' | ']' => result.push('\\'),
+            _ => {}
+        }
+        result.push(ch);
+    }
+    result
+}
+
+/// Join `pattern` to `base` and normalize it to a path relative to the repository root.
+/// Returns `None` if the result is outside the repository.
+fn canonical_path(root: &Path, base: &str, pattern: &str) -> Option<String> {
+    let (base, pattern) = if Path::new(pattern).is_absolute() {
+        ("", Path::new(pattern).strip_prefix(root).ok()?.to_str()?)
+    } else {
+        (base, pattern)
+    };
+    let mut components: Vec<&str> = base.split('/').filter(|c| !c.is_empty()).collect();
+    for component in pattern.split(|c| c == '/' || c == std::path::MAIN_SEPARATOR) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop()?;
+            }
+            component => components.push(component),
+        }
+    }
+    Some(components.join("/"))
 }
 
 pub fn name() -> &'static str {
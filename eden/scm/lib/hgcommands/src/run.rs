@@ -11,6 +11,7 @@ use blackbox::serde_json;
 use clidispatch::global_flags::HgGlobalOpts;
 use clidispatch::{dispatch, errors};
 use parking_lot::Mutex;
+use std::collections::HashMap;
 use std::env;
 use std::fs::File;
 use std::io;
@@ -218,12 +219,21 @@ pub(crate) fn write_trace(
         TraceEventJSON,
         TraceEventGzip,
         SpansJSON,
+        OtlpJSON,
+        FoldedStacks,
+        PprofGzip,
     }
 
     let format = if path.ends_with(".txt") {
         Format::ASCII
     } else if path.ends_with("spans.json") {
         Format::SpansJSON
+    } else if path.ends_with(".otlp.json") {
+        Format::OtlpJSON
+    } else if path.ends_with(".folded") {
+        Format::FoldedStacks
+    } else if path.ends_with(".pb.gz") {
+        Format::PprofGzip
     } else if path.ends_with(".json") {
         Format::TraceEventJSON
     } else if path.ends_with(".gz") {
@@ -263,6 +273,24 @@ pub(crate) fn write_trace(
             data.write_trace_event_json(&mut out, Default::default())?;
             out.flush()?;
         }
+        Format::OtlpJSON => {
+            let mut resource_attributes = HashMap::new();
+            resource_attributes.insert("service.name".to_string(), "hg".to_string());
+            data.write_otlp_json(&mut out, resource_attributes)?;
+            out.flush()?;
+        }
+        Format::FoldedStacks => {
+            out.write_all(data.folded_stacks().as_bytes())?;
+            out.flush()?;
+        }
+        Format::PprofGzip => {
+            let mut out = Box::new(flate2::write::GzEncoder::new(
+                out,
+                flate2::Compression::new(6), // 6 is the default value
+            ));
+            data.write_pprof(&mut out)?;
+            out.finish()?.flush()?;
+        }
     }
 
     Ok(())
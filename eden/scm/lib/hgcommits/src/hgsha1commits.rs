@@ -18,6 +18,7 @@ use dag::delegate;
 use dag::ops::DagAddHeads;
 use dag::ops::DagAlgorithm;
 use dag::ops::DagPersistent;
+use dag::ops::DagStrip;
 use dag::Dag;
 use dag::Set;
 use dag::Vertex;
@@ -181,6 +182,10 @@ impl StreamCommitText for HgCommits {
 
 impl StripCommits for HgCommits {
     fn strip_commits(&mut self, set: Set) -> Result<()> {
+        if strip::is_non_master(&self.dag, &set)? {
+            self.dag.strip(&set)?;
+            return Ok(());
+        }
         let old_path = &self.dag_path;
         let new_path = self.dag_path.join("strip");
         let mut new = Self::new(&new_path, &self.commits_path)?;
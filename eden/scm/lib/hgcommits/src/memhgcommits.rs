@@ -16,6 +16,8 @@ use crate::StreamCommitText;
 use crate::StripCommits;
 use dag::delegate;
 use dag::ops::DagAddHeads;
+use dag::ops::DagAlgorithm;
+use dag::ops::DagStrip;
 use dag::MemDag;
 use dag::Set;
 use dag::Vertex;
@@ -113,6 +115,14 @@ impl StreamCommitText for MemHgCommits {
 
 impl StripCommits for MemHgCommits {
     fn strip_commits(&mut self, set: Set) -> Result<()> {
+        if strip::is_non_master(&self.dag, &set)? {
+            let stripped = self.dag.descendants(set.clone())?;
+            self.dag.strip(&set)?;
+            for vertex in stripped.iter()? {
+                self.commits.remove(&vertex?);
+            }
+            return Ok(());
+        }
         let mut new = Self::new()?;
         strip::migrate_commits(self, &mut new, set)?;
         *self = new;
@@ -9,19 +9,37 @@ use crate::AppendCommits;
 use crate::HgCommit;
 use crate::ReadCommitText;
 use crate::Result;
+use dag::ops::IdConvert;
 use dag::DagAlgorithm;
+use dag::Group;
 use dag::Set;
 use dag::Vertex;
 use std::fs;
 use std::path::Path;
 
 pub trait StripCommits {
-    /// Strip commits. This is for legacy tests only that wouldn't be used
-    /// much in production. The callsite should take care of locking or
+    /// Strip commits and their descendants.
+    ///
+    /// Non-master commits are removed from the segmented changelog directly.
+    /// Stripping master commits is for legacy tests only that wouldn't be
+    /// used much in production. The callsite should take care of locking or
     /// otherwise risk data race and loss.
     fn strip_commits(&mut self, set: Set) -> Result<()>;
 }
 
+/// Test if `set` only contains non-master commits, which can be stripped
+/// without migrating commits.
+pub(crate) fn is_non_master(dag: &dyn IdConvert, set: &Set) -> Result<bool> {
+    for vertex in set.iter()? {
+        if let Some(id) = dag.vertex_id_optional(&vertex?)? {
+            if id.group() == Group::MASTER {
+                return Ok(false);
+            }
+        }
+    }
+    Ok(true)
+}
+
 /// Enumerate all commits in `orig`, re-insert them to `new` except for `strip_set::`.
 pub(crate) fn migrate_commits(
     orig: &(impl ReadCommitText + DagAlgorithm),
@@ -0,0 +1,186 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Per-entry zstd compression.
+//!
+//! A compressed entry content is `LEN(DATA) + ZSTD(DATA)`. The uncompressed
+//! length is stored explicitly so decompression can allocate the output
+//! buffer upfront.
+
+use minibytes::Bytes;
+use once_cell::sync::OnceCell;
+use std::fmt;
+use std::io::{self, Cursor, Write};
+use std::ops::Deref;
+use std::sync::Arc;
+use vlqencoding::{VLQDecode, VLQEncode};
+use zstd_safe::{CDict, DDict};
+
+/// A zstd dictionary.
+///
+/// Loading a dictionary is much slower than compressing or decompressing a
+/// small entry. The loaded (prepared) dictionaries are created on first use
+/// and shared by clones, so entries of a [`Log`](crate::log::Log) do not
+/// load the dictionary again.
+#[derive(Clone)]
+pub(crate) struct Dictionary(Arc<DictionaryInner>);
+
+struct DictionaryInner {
+    data: Bytes,
+    cdict: OnceCell<(i32, CDict<'static>)>,
+    ddict: OnceCell<DDict<'static>>,
+}
+
+impl Dictionary {
+    pub(crate) fn new(data: Bytes) -> Self {
+        Self(Arc::new(DictionaryInner {
+            data,
+            cdict: OnceCell::new(),
+            ddict: OnceCell::new(),
+        }))
+    }
+
+    fn with_cdict<R>(&self, level: i32, f: impl FnOnce(&CDict) -> R) -> R {
+        let (cdict_level, cdict) = self
+            .0
+            .cdict
+            .get_or_init(|| (level, zstd_safe::create_cdict(&self.0.data, level)));
+        if *cdict_level == level {
+            f(cdict)
+        } else {
+            // The level is decided by OpenOptions, and is usually the same
+            // for all writers of a log.
+            f(&zstd_safe::create_cdict(&self.0.data, level))
+        }
+    }
+
+    fn ddict(&self) -> &DDict<'static> {
+        self.0
+            .ddict
+            .get_or_init(|| zstd_safe::create_ddict(&self.0.data))
+    }
+}
+
+impl Deref for Dictionary {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0.data
+    }
+}
+
+impl PartialEq for Dictionary {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.data == other.0.data
+    }
+}
+
+impl Eq for Dictionary {}
+
+impl fmt::Debug for Dictionary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.data.fmt(f)
+    }
+}
+
+fn map_error_code(code: usize) -> io::Error {
+    let msg = zstd_safe::get_error_name(code);
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Compress `data` at the given zstd `level`, optionally using a dictionary.
+pub(crate) fn compress(data: &[u8], level: i32, dict: Option<&Dictionary>) -> io::Result<Vec<u8>> {
+    let compressed = match dict {
+        Some(dict) => {
+            let mut compressed = vec![0; zstd_safe::compress_bound(data.len())];
+            let mut cctx = zstd_safe::create_cctx();
+            let len = dict
+                .with_cdict(level, |cdict| {
+                    zstd_safe::compress_using_cdict(&mut cctx, &mut compressed, data, cdict)
+                })
+                .map_err(map_error_code)?;
+            compressed.truncate(len);
+            compressed
+        }
+        None => zstd::block::Compressor::new().compress(data, level)?,
+    };
+    let mut buf = Vec::with_capacity(compressed.len() + 4);
+    buf.write_vlq(data.len())?;
+    buf.write_all(&compressed)?;
+    Ok(buf)
+}
+
+/// Decompress content produced by [`compress`]. `dict` must match the
+/// dictionary used for compression.
+pub(crate) fn decompress(content: &[u8], dict: Option<&Dictionary>) -> io::Result<Vec<u8>> {
+    let mut cur = Cursor::new(content);
+    let len: usize = cur.read_vlq()?;
+    let compressed = &content[cur.position() as usize..];
+    let data = match dict {
+        Some(dict) => {
+            let mut data = vec![0; len];
+            let mut dctx = zstd_safe::create_dctx();
+            let decompressed_len =
+                zstd_safe::decompress_using_ddict(&mut dctx, &mut data, compressed, dict.ddict())
+                    .map_err(map_error_code)?;
+            data.truncate(decompressed_len);
+            data
+        }
+        None => zstd::block::Decompressor::new().decompress(compressed, len)?,
+    };
+    if data.len() != len {
+        let msg = format!(
+            "decompressed entry has {} bytes, expected {} bytes",
+            data.len(),
+            len
+        );
+        return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+    }
+    Ok(data)
+}
+
+/// Train a zstd dictionary from sample entries.
+///
+/// The result can be passed to [`OpenOptions::compression_dictionary`](
+/// crate::log::OpenOptions::compression_dictionary). Samples should be
+/// representative of the entries that will be appended to the log.
+pub fn train_dictionary<T: AsRef<[u8]>>(samples: &[T], max_size: usize) -> crate::Result<Vec<u8>> {
+    zstd::dict::from_samples(samples, max_size).map_err(|e| {
+        crate::Error::wrap(Box::new(e), || {
+            format!(
+                "cannot train dictionary (samples = {}, max_size = {})",
+                samples.len(),
+                max_size
+            )
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dictionary_roundtrip() {
+        let samples: Vec<Vec<u8>> = (0..1000)
+            .map(|i| format!("key={} value={}", i, i * 3).into_bytes())
+            .collect();
+        let dict = Dictionary::new(train_dictionary(&samples, 1024).unwrap().into());
+        let data = b"key=1234 value=3702";
+
+        // Clones share the prepared dictionary.
+        let cloned = dict.clone();
+        assert!(std::ptr::eq(dict.ddict(), cloned.ddict()));
+
+        for &level in &[3, 1, 3, 19] {
+            let compressed = compress(data, level, Some(&cloned)).unwrap();
+            assert_eq!(decompress(&compressed, Some(&dict)).unwrap(), data);
+        }
+        let compressed = compress(data, 3, None).unwrap();
+        assert_eq!(decompress(&compressed, None).unwrap(), data);
+    }
+}
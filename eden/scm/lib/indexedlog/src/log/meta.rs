@@ -6,7 +6,9 @@
  */
 
 use crate::errors::IoResultExt;
+use crate::log::compression::Dictionary;
 use crate::utils::{self, atomic_read, atomic_write, xxhash};
+use minibytes::Bytes;
 use std::collections::BTreeMap;
 use std::io::{self, Cursor, Read, Write};
 use std::path::Path;
@@ -24,6 +26,15 @@ pub struct LogMetadata {
     /// Used to detect non-append-only changes.
     /// Conceptually similar to "create time".
     pub(crate) epoch: u64,
+
+    /// zstd dictionary used by compressed entries.
+    /// Decided when the log is created, and does not change afterwards.
+    pub(crate) compression_dict: Option<Dictionary>,
+
+    /// Suffix of the primary log and index file names. Bumped when those
+    /// files are rewritten, so the new files can be published by writing
+    /// the metadata once.
+    pub(crate) generation: u64,
 }
 
 impl LogMetadata {
@@ -69,10 +80,25 @@ impl LogMetadata {
         // format. So not being able to read it (because EOF) is not fatal.
         let epoch = reader.read_vlq().unwrap_or_default();
 
+        // 'compression_dict' is optional, for the same reason.
+        let dict_len: usize = reader.read_vlq().unwrap_or_default();
+        let compression_dict = if dict_len > 0 {
+            let mut dict = vec![0; dict_len];
+            reader.read_exact(&mut dict)?;
+            Some(Dictionary::new(Bytes::from(dict)))
+        } else {
+            None
+        };
+
+        // 'generation' is optional, for the same reason.
+        let generation = reader.read_vlq().unwrap_or_default();
+
         Ok(Self {
             primary_len,
             indexes,
             epoch,
+            compression_dict,
+            generation,
         })
     }
 
@@ -88,6 +114,14 @@ impl LogMetadata {
             buf.write_vlq(*len)?;
         }
         buf.write_vlq(self.epoch)?;
+        match self.compression_dict {
+            Some(ref dict) => {
+                buf.write_vlq(dict.len())?;
+                buf.write_all(dict)?;
+            }
+            None => buf.write_vlq(0)?,
+        }
+        buf.write_vlq(self.generation)?;
         writer.write_all(Self::HEADER)?;
         writer.write_vlq(xxhash(&buf))?;
         writer.write_vlq(buf.len())?;
@@ -119,9 +153,25 @@ impl LogMetadata {
             primary_len: len,
             indexes: BTreeMap::new(),
             epoch: utils::epoch(),
+            compression_dict: None,
+            generation: 0,
+        }
+    }
+
+    /// Use `dict` instead of the dictionary read from disk if they are the
+    /// same, so the prepared dictionary is kept after reloading.
+    pub(crate) fn reuse_compression_dict(&mut self, dict: Option<Dictionary>) {
+        if self.compression_dict == dict {
+            self.compression_dict = dict;
         }
     }
 
+    /// Name of a file in this generation of the log, given its name in
+    /// generation 0.
+    pub(crate) fn generation_file_name(&self, name: &str) -> String {
+        generation_file_name(name, self.generation)
+    }
+
     /// Test if two Metadata is compatible, aka. having the same length
     /// and epoch.
     pub(crate) fn is_compatible_with(&self, other: &Self) -> bool {
@@ -129,25 +179,42 @@ impl LogMetadata {
     }
 }
 
+/// Name of a file in the given generation of a log, given its name in
+/// generation 0.
+pub(crate) fn generation_file_name(name: &str, generation: u64) -> String {
+    if generation == 0 {
+        name.to_string()
+    } else {
+        format!("{}.{}", name, generation)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use quickcheck::quickcheck;
     use tempfile::tempdir;
 
+    fn normalize_dict(dict: Option<Vec<u8>>) -> Option<Dictionary> {
+        dict.filter(|d| !d.is_empty())
+            .map(|d| Dictionary::new(Bytes::from(d)))
+    }
+
     quickcheck! {
-        fn test_roundtrip_meta(primary_len: u64, indexes: BTreeMap<String, u64>, epoch: u64) -> bool {
+        fn test_roundtrip_meta(primary_len: u64, indexes: BTreeMap<String, u64>, epoch: u64, dict: Option<Vec<u8>>, generation: u64) -> bool {
             let mut buf = Vec::new();
-            let meta = LogMetadata { primary_len, indexes, epoch,  };
+            let compression_dict = normalize_dict(dict);
+            let meta = LogMetadata { primary_len, indexes, epoch, compression_dict, generation };
             meta.write(&mut buf).expect("write");
             let mut cur = Cursor::new(buf);
             let meta_read = LogMetadata::read(&mut cur).expect("read");
             meta_read == meta
         }
 
-        fn test_roundtrip_meta_file(primary_len: u64, indexes: BTreeMap<String, u64>, epoch: u64) -> bool {
+        fn test_roundtrip_meta_file(primary_len: u64, indexes: BTreeMap<String, u64>, epoch: u64, dict: Option<Vec<u8>>, generation: u64) -> bool {
             let dir = tempdir().unwrap();
-            let meta = LogMetadata { primary_len, indexes, epoch,  };
+            let compression_dict = normalize_dict(dict);
+            let meta = LogMetadata { primary_len, indexes, epoch, compression_dict, generation };
             let path = dir.path().join("meta");
             meta.write_file(&path, false).expect("write_file");
             let meta_read = LogMetadata::read_file(&path).expect("read_file");
@@ -19,11 +19,14 @@
 //   ENTRY_LIST := '' | ENTRY_LIST + ENTRY
 //   ENTRY := ENTRY_FLAGS + LEN(CONTENT) + CHECKSUM + CONTENT
 //   CHECKSUM := '' | XXHASH64(CONTENT) | XXHASH32(CONTENT)
+//   CONTENT := ENTRY_DATA | LEN(ENTRY_DATA) + ZSTD(ENTRY_DATA)
+//              (the latter if ENTRY_FLAGS has ENTRY_FLAG_ZSTD, compressed
+//              using DICT from metadata, if present)
 //
 // Metadata:
 //   META := HEADER + XXHASH64(DATA) + LEN(DATA) + DATA
 //   HEADER := 'meta\0'
-//   DATA := LEN(LOG) + LEN(INDEXES) + INDEXES
+//   DATA := LEN(LOG) + LEN(INDEXES) + INDEXES + EPOCH + LEN(DICT) + DICT
 //   INDEXES := '' | INDEXES + INDEX
 //   INDEX := LEN(NAME) + NAME + INDEX_LOGIC_LEN
 //
@@ -51,6 +54,7 @@ use tracing::debug_span;
 use tracing::trace;
 use vlqencoding::{VLQDecodeAt, VLQEncode};
 
+mod compression;
 mod meta;
 mod open_options;
 mod path;
@@ -58,6 +62,7 @@ mod repair;
 #[cfg(test)]
 mod tests;
 
+pub use self::compression::train_dictionary;
 pub use self::meta::LogMetadata;
 pub use open_options::{
     ChecksumType, FlushFilterContext, FlushFilterFunc, FlushFilterOutput, IndexDef, IndexOutput,
@@ -73,6 +78,7 @@ pub(crate) const META_FILE: &str = "meta";
 
 const ENTRY_FLAG_HAS_XXHASH64: u32 = 1;
 const ENTRY_FLAG_HAS_XXHASH32: u32 = 2;
+const ENTRY_FLAG_ZSTD: u32 = 4;
 
 // 1MB index checksum. This makes checksum file within one block (4KB) for 512MB index.
 const INDEX_CHECKSUM_CHUNK_SIZE_LOGARITHM: u32 = 20;
@@ -211,6 +217,22 @@ impl Log {
         let result: crate::Result<_> = (|| {
             let data = data.as_ref();
 
+            // Only keep the compressed content if it saves space.
+            let compressed = match self.open_options.compression_level {
+                Some(level) => {
+                    let dict = self.meta.compression_dict.as_ref();
+                    let compressed = compression::compress(data, level, dict)
+                        .map_err(|e| crate::Error::wrap(Box::new(e), "cannot compress entry"))?;
+                    if compressed.len() < data.len() {
+                        Some(compressed)
+                    } else {
+                        None
+                    }
+                }
+                None => None,
+            };
+            let content: &[u8] = compressed.as_deref().unwrap_or(data);
+
             let checksum_type = if self.open_options.checksum_type == ChecksumType::Auto {
                 // xxhash64 is slower for smaller data. A quick benchmark on x64 platform shows:
                 //
@@ -229,7 +251,7 @@ impl Log {
                 //  120       3000      3428
                 //  128       3459      4266
                 const XXHASH64_THRESHOLD: usize = 88;
-                if data.len() >= XXHASH64_THRESHOLD {
+                if content.len() >= XXHASH64_THRESHOLD {
                     ChecksumType::Xxhash64
                 } else {
                     ChecksumType::Xxhash32
@@ -240,39 +262,43 @@ impl Log {
 
             let offset = self.meta.primary_len + self.mem_buf.len() as u64;
 
-            // Design note: Currently checksum_type is the only thing that decides
-            // entry_flags.  Entry flags is not designed to just cover different
-            // checksum types.  For example, if we'd like to introduce transparent
-            // compression (maybe not a good idea since it can be more cleanly built
-            // at an upper layer), or some other ways to store data (ex. reference
-            // to other data, or fixed length data), they can probably be done by
-            // extending the entry type.
+            // Design note: Entry flags decide the checksum type, and whether
+            // the content is compressed. Other ways to store data (ex.
+            // reference to other data, or fixed length data) can probably be
+            // done by extending the entry type.
             let mut entry_flags = 0;
             entry_flags |= match checksum_type {
                 ChecksumType::Xxhash64 => ENTRY_FLAG_HAS_XXHASH64,
                 ChecksumType::Xxhash32 => ENTRY_FLAG_HAS_XXHASH32,
                 ChecksumType::Auto => unreachable!(),
             };
+            if compressed.is_some() {
+                entry_flags |= ENTRY_FLAG_ZSTD;
+            }
 
             self.mem_buf.write_vlq(entry_flags).infallible()?;
-            self.mem_buf.write_vlq(data.len()).infallible()?;
+            self.mem_buf.write_vlq(content.len()).infallible()?;
 
             match checksum_type {
                 ChecksumType::Xxhash64 => {
                     self.mem_buf
-                        .write_u64::<LittleEndian>(xxhash(data))
+                        .write_u64::<LittleEndian>(xxhash(content))
                         .infallible()?;
                 }
                 ChecksumType::Xxhash32 => {
                     self.mem_buf
-                        .write_u32::<LittleEndian>(xxhash32(data))
+                        .write_u32::<LittleEndian>(xxhash32(content))
                         .infallible()?;
                 }
                 ChecksumType::Auto => unreachable!(),
             };
-            let data_offset = self.meta.primary_len + self.mem_buf.len() as u64;
+            let data_offset = match compressed {
+                // Index keys cannot refer to the uncompressed data.
+                Some(_) => None,
+                None => Some(self.meta.primary_len + self.mem_buf.len() as u64),
+            };
 
-            self.mem_buf.write_all(data).infallible()?;
+            self.mem_buf.write_all(content).infallible()?;
             self.update_indexes_for_in_memory_entry(data, offset, data_offset)?;
 
             if let Some(threshold) = self.open_options.auto_sync_threshold {
@@ -425,7 +451,7 @@ impl Log {
 
             // Read-only fast path - no need to take directory lock.
             if self.mem_buf.is_empty() {
-                if let Ok(meta) = Self::load_or_create_meta(&self.dir, false) {
+                if let Ok(meta) = Self::load_or_create_meta(&self.dir, None) {
                     let changed = self.meta != meta;
                     let truncated = self.meta.epoch != meta.epoch;
                     if !truncated {
@@ -437,11 +463,13 @@ impl Log {
                         // Indexes can be reused, since they do not have new in-memory
                         // entries, and the on-disk primary log is append-only (so data
                         // already present in the indexes is valid).
+                        let compression_dict = self.meta.compression_dict.clone();
                         *self = self.open_options.clone().open_internal(
                             &self.dir,
                             if truncated { None } else { Some(&self.indexes) },
                             None,
                         )?;
+                        self.meta.reuse_compression_dict(compression_dict);
                     }
                 } else {
                     // If meta can not be read, do not error out.
@@ -461,7 +489,8 @@ impl Log {
             let lock = ScopedDirLock::new(&dir)?;
 
             // Step 1: Reload metadata to get the latest view of the files.
-            let mut meta = Self::load_or_create_meta(&self.dir, false)?;
+            let mut meta = Self::load_or_create_meta(&self.dir, None)?;
+            meta.reuse_compression_dict(self.meta.compression_dict.clone());
             let changed = self.meta != meta;
             let truncated = self.meta.epoch != meta.epoch;
             if !truncated {
@@ -483,11 +512,11 @@ impl Log {
                     let content = entry?;
                     let context = FlushFilterContext { log: &log };
                     // Re-insert entries to that clean log.
-                    match filter(&context, content)
+                    match filter(&context, &content)
                         .map_err(|err| crate::Error::wrap(err, "failed to run filter function"))?
                     {
                         FlushFilterOutput::Drop => {}
-                        FlushFilterOutput::Keep => log.append(content)?,
+                        FlushFilterOutput::Keep => log.append(&content)?,
                         FlushFilterOutput::Replace(content) => log.append(content)?,
                     }
                 }
@@ -517,7 +546,11 @@ impl Log {
             }
 
             // Step 2: Append to the primary log.
-            let primary_path = self.dir.as_opt_path().unwrap().join(PRIMARY_FILE);
+            let primary_path = self
+                .dir
+                .as_opt_path()
+                .unwrap()
+                .join(meta.generation_file_name(PRIMARY_FILE));
             let mut primary_file = fs::OpenOptions::new()
                 .read(true)
                 .write(true)
@@ -609,6 +642,9 @@ impl Log {
             // Step 5: Write the updated meta file.
             self.dir.write_meta(&self.meta, self.open_options.fsync)?;
 
+            // Step 6: Retry removing files left behind by a previous compact.
+            self.remove_stale_generations(&lock);
+
             Ok(self.meta.primary_len)
         })();
 
@@ -617,6 +653,60 @@ impl Log {
             .context(|| format!("  Log.dir = {:?}", self.dir))
     }
 
+    /// Remove the primary log and index files of generations other than the
+    /// current one. Other readers might still have them mmaped (which
+    /// prevents removal on Windows), so failures are ignored. Files left
+    /// behind are retried by the next `sync` or `compact`.
+    fn remove_stale_generations(&self, _lock: &ScopedDirLock) {
+        let dir = match self.dir.as_opt_path() {
+            Some(dir) => dir,
+            None => return,
+        };
+        if self.meta.generation == 0 {
+            // Never compacted.
+            return;
+        }
+        let names: Vec<String> = std::iter::once(PRIMARY_FILE.to_string())
+            .chain(
+                self.open_options
+                    .index_defs
+                    .iter()
+                    .map(|def| def.filename()),
+            )
+            .collect();
+        let current: Vec<String> = names
+            .iter()
+            .map(|name| self.meta.generation_file_name(name))
+            .collect();
+        let is_stale = |file_name: &str| -> bool {
+            !current.iter().any(|name| name == file_name)
+                && names
+                    .iter()
+                    .any(|name| match file_name.strip_prefix(name.as_str()) {
+                        Some("") => true,
+                        Some(suffix) => suffix
+                            .strip_prefix('.')
+                            .and_then(|generation| generation.parse::<u64>().ok())
+                            .is_some(),
+                        None => false,
+                    })
+        };
+        for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+            let file_name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            if is_stale(&file_name) {
+                let removed = fs::remove_file(entry.path()).is_ok();
+                trace!(
+                    name = "Log::remove_stale_generation",
+                    file_name = file_name.as_str(),
+                    removed = removed,
+                );
+            }
+        }
+    }
+
     /// Write (updated) lagging indexes back to disk.
     /// Usually called after `update_indexes_for_on_disk_entries`.
     /// This function might change `self.meta`. Be sure to write `self.meta` to
@@ -706,7 +796,7 @@ impl Log {
 
                 let _lock = ScopedDirLock::new(&dir)?;
 
-                let meta = Self::load_or_create_meta(&self.dir, false)?;
+                let meta = Self::load_or_create_meta(&self.dir, None)?;
                 if self.meta != meta {
                     return Err(crate::Error::programming(
                         "race detected, callsite responsible for preventing races",
@@ -820,7 +910,7 @@ impl Log {
                             &mut index,
                             def,
                             &self.disk_buf,
-                            self.meta.primary_len,
+                            &self.meta,
                         )?;
                         index.flush()?
                     };
@@ -835,7 +925,7 @@ impl Log {
 
                     let _ = utils::fix_perm_file(tmp.as_file(), false);
 
-                    let path = dir.join(def.filename());
+                    let path = dir.join(self.meta.generation_file_name(&def.filename()));
                     tmp.persist(&path).map_err(|e| {
                         crate::Error::wrap(Box::new(e), || {
                             format!("cannot persist tempfile to replace index {:?}", name)
@@ -854,6 +944,134 @@ impl Log {
         Ok(message)
     }
 
+    /// Rewrite the log so it only keeps entries for which `filter` returns
+    /// `true`. Indexes are rebuilt.
+    ///
+    /// In-memory entries are written to disk first. Entries appended by other
+    /// processes are also subject to `filter`.
+    ///
+    /// The primary log and indexes are written to new files, which are then
+    /// published by a single metadata write that also bumps the epoch.
+    /// Existing readers keep their mmaped view of the old files, and will
+    /// reload everything on `sync`. Old files that cannot be removed yet are
+    /// removed by a later `sync` or `compact`.
+    ///
+    /// Return the number of entries removed. If an error happens, `self` is
+    /// left unchanged.
+    pub fn compact(
+        &mut self,
+        mut filter: impl FnMut(&[u8]) -> crate::Result<bool>,
+    ) -> crate::Result<usize> {
+        let result: crate::Result<_> = (|| {
+            let span = debug_span!("Log::compact", primary_len = self.meta.primary_len);
+            if let Some(dir) = &self.dir.as_opt_path() {
+                span.record("dir", &dir.to_string_lossy().as_ref());
+            }
+            let _guard = span.enter();
+
+            let dir = match self.dir.as_opt_path() {
+                Some(dir) => dir.to_path_buf(),
+                None => {
+                    // In-memory Log. Re-insert entries to an empty Log.
+                    let mut log = self.open_options.clone().open(())?;
+                    let mut removed = 0;
+                    for entry in self.iter() {
+                        let content = entry?;
+                        if filter(&content)? {
+                            log.append(&content)?;
+                        } else {
+                            removed += 1;
+                        }
+                    }
+                    *self = log;
+                    return Ok(removed);
+                }
+            };
+
+            self.sync()?;
+            let path = self.dir.clone();
+            let open_options = self.open_options.clone();
+            let lock = ScopedDirLock::new(&dir)?;
+
+            // Step 1: Reload to get the latest view of the files.
+            let log = open_options.open_with_lock(&path, &lock)?;
+
+            // Step 2: Copy entries to keep. Entries are copied as-is, so
+            // compressed entries stay compressed.
+            let mut primary_buf = Vec::with_capacity(log.meta.primary_len as usize);
+            primary_buf.extend_from_slice(PRIMARY_HEADER);
+            let mut removed = 0;
+            let mut iter = log.iter();
+            let mut offset = iter.next_offset;
+            while let Some(entry) = iter.next() {
+                let content = entry?;
+                let next_offset = iter.next_offset;
+                if filter(&content)? {
+                    primary_buf
+                        .extend_from_slice(&log.disk_buf[offset as usize..next_offset as usize]);
+                } else {
+                    removed += 1;
+                }
+                offset = next_offset;
+            }
+
+            if removed == 0 {
+                *self = log;
+                self.remove_stale_generations(&lock);
+                return Ok(0);
+            }
+
+            // Step 3: Write the primary log and indexes of a new generation.
+            // Readers do not look at these files until the metadata points to
+            // them. Bump epoch since this is a non-append-only change.
+            let mut meta = log.meta.clone();
+            meta.primary_len = primary_buf.len() as u64;
+            meta.indexes.clear();
+            meta.epoch = meta.epoch.wrapping_add(1);
+            meta.generation = meta.generation.wrapping_add(1);
+            let primary_path = dir.join(meta.generation_file_name(PRIMARY_FILE));
+            utils::atomic_write_plain(&primary_path, &primary_buf, open_options.fsync)?;
+            let disk_buf = Bytes::from(primary_buf);
+            for def in open_options.index_defs.iter() {
+                let name = def.name;
+                let tmp = tempfile::NamedTempFile::new_in(&dir).context(&dir, || {
+                    format!("cannot create tempfile for compacting index {:?}", name)
+                })?;
+                let index_len = {
+                    let mut index = index::OpenOptions::new()
+                        .key_buf(Some(Arc::new(disk_buf.clone())))
+                        .open(tmp.path())?;
+                    Self::update_index_for_on_disk_entry_unchecked(
+                        &path, &mut index, def, &disk_buf, &meta,
+                    )?;
+                    index.flush()?
+                };
+                let _ = utils::fix_perm_file(tmp.as_file(), false);
+                let index_path = dir.join(meta.generation_file_name(&def.filename()));
+                tmp.persist(&index_path).map_err(|e| {
+                    crate::Error::wrap(Box::new(e), || {
+                        format!("cannot persist tempfile to compact index {:?}", name)
+                    })
+                })?;
+                meta.indexes.insert(def.metaname(), index_len);
+            }
+
+            // Step 4: Publish the new generation.
+            path.write_meta(&meta, open_options.fsync)?;
+
+            // Step 5: Reload, then remove files of the old generation.
+            drop(log);
+            *self = open_options.open_with_lock(&path, &lock)?;
+            self.remove_stale_generations(&lock);
+
+            Ok(removed)
+        })();
+
+        result
+            .context("in Log::compact")
+            .context(|| format!("  Log.dir = {:?}", self.dir))
+    }
+
     /// Look up an entry using the given index. The `index_id` is the index of
     /// `index_defs` passed to [`Log::open`].
     ///
@@ -1017,12 +1235,13 @@ impl Log {
     ///
     /// `offset` is the logical start offset of the entry.
     /// `data_offset` is the logical start offset of the real data (skips
-    /// length, and checksum header in the entry).
+    /// length, and checksum header in the entry), or `None` if the entry is
+    /// compressed.
     fn update_indexes_for_in_memory_entry(
         &mut self,
         data: &[u8],
         offset: u64,
-        data_offset: u64,
+        data_offset: Option<u64>,
     ) -> crate::Result<()> {
         let result = self.update_indexes_for_in_memory_entry_unchecked(data, offset, data_offset);
         self.maybe_set_index_error(result)
@@ -1032,16 +1251,23 @@ impl Log {
         &mut self,
         data: &[u8],
         offset: u64,
-        data_offset: u64,
+        data_offset: Option<u64>,
     ) -> crate::Result<()> {
         for (index, def) in self.indexes.iter_mut().zip(&self.open_options.index_defs) {
             for index_output in (def.func)(data) {
                 match index_output {
                     IndexOutput::Reference(range) => {
                         assert!(range.start <= range.end && range.end <= data.len() as u64);
-                        let start = range.start + data_offset;
-                        let end = range.end + data_offset;
-                        let key = InsertKey::Reference((start, end - start));
+                        let key = match data_offset {
+                            Some(data_offset) => {
+                                let start = range.start + data_offset;
+                                let end = range.end + data_offset;
+                                InsertKey::Reference((start, end - start))
+                            }
+                            None => {
+                                InsertKey::Embed(&data[range.start as usize..range.end as usize])
+                            }
+                        };
                         index.insert_advanced(key, InsertValue::Prepend(offset))?;
                     }
                     IndexOutput::Owned(key) => {
@@ -1076,7 +1302,7 @@ impl Log {
                 index,
                 def,
                 &self.disk_buf,
-                self.meta.primary_len,
+                &self.meta,
             )?;
         }
         Ok(())
@@ -1087,7 +1313,7 @@ impl Log {
         index: &mut Index,
         def: &IndexDef,
         disk_buf: &Bytes,
-        primary_len: u64,
+        meta: &LogMetadata,
     ) -> crate::Result<usize> {
         // The index meta is used to store the next offset the index should be built.
         let mut offset = Self::get_index_log_len(index, true)?;
@@ -1103,14 +1329,19 @@ impl Log {
             })?
         {
             count += 1;
-            let data = entry_result.data;
+            let data = entry_result.uncompressed_data(path, meta)?;
+            let data: &[u8] = &data;
             for index_output in (def.func)(data) {
                 match index_output {
                     IndexOutput::Reference(range) => {
                         assert!(range.start <= range.end && range.end <= data.len() as u64);
-                        let start = range.start + entry_result.data_offset;
-                        let end = range.end + entry_result.data_offset;
-                        let key = InsertKey::Reference((start, end - start));
+                        let key = if entry_result.compressed {
+                            InsertKey::Embed(&data[range.start as usize..range.end as usize])
+                        } else {
+                            let start = range.start + entry_result.data_offset;
+                            let end = range.end + entry_result.data_offset;
+                            InsertKey::Reference((start, end - start))
+                        };
 
                         index.insert_advanced(key, InsertValue::Prepend(offset))?;
                     }
@@ -1129,30 +1360,30 @@ impl Log {
             offset = entry_result.next_offset;
         }
         // The index now contains all entries. Write "next_offset" as the index meta.
-        Self::set_index_log_len(std::iter::once(index), primary_len);
+        Self::set_index_log_len(std::iter::once(index), meta.primary_len);
 
         Ok(count)
     }
 
-    /// Read [`LogMetadata`] from the given directory. If `create` is `true`,
-    /// create an empty one on demand.
+    /// Read [`LogMetadata`] from the given directory. If `create` is not
+    /// `None`, create an empty one on demand using the given options.
     ///
     /// The caller should ensure the directory exists and take a lock on it to
     /// avoid filesystem races.
     pub(crate) fn load_or_create_meta(
         path: &GenericPath,
-        create: bool,
+        create: Option<&OpenOptions>,
     ) -> crate::Result<LogMetadata> {
         Self::load_or_create_meta_internal(path, create)
     }
 
     pub(crate) fn load_or_create_meta_internal(
         path: &GenericPath,
-        create: bool,
+        create: Option<&OpenOptions>,
     ) -> crate::Result<LogMetadata> {
         match path.read_meta() {
-            Err(err) => {
-                if err.io_error_kind() == io::ErrorKind::NotFound && create {
+            Err(err) => match create {
+                Some(open_options) if err.io_error_kind() == io::ErrorKind::NotFound => {
                     let dir = path.as_opt_path().unwrap();
                     // Create (and truncate) the primary log and indexes.
                     let primary_path = dir.join(PRIMARY_FILE);
@@ -1163,14 +1394,13 @@ impl Log {
                         .context(&primary_path, "cannot write")?;
                     let _ = utils::fix_perm_file(&primary_file, false);
                     // Start from empty file and indexes.
-                    let meta = LogMetadata::new_with_primary_len(PRIMARY_START_OFFSET);
+                    let meta = open_options.new_meta(PRIMARY_START_OFFSET);
                     // An empty meta file is easy to recreate. No need to use fsync.
                     path.write_meta(&meta, false)?;
                     Ok(meta)
-                } else {
-                    Err(err)
                 }
-            }
+                _ => Err(err),
+            },
             Ok(meta) => Ok(meta),
         }
     }
@@ -1192,7 +1422,10 @@ impl Log {
         fsync: bool,
     ) -> crate::Result<(Bytes, Vec<Index>)> {
         let primary_buf = match dir.as_opt_path() {
-            Some(dir) => mmap_path(&dir.join(PRIMARY_FILE), meta.primary_len)?,
+            Some(dir) => mmap_path(
+                &dir.join(meta.generation_file_name(PRIMARY_FILE)),
+                meta.primary_len,
+            )?,
             None => Bytes::new(),
         };
 
@@ -1212,6 +1445,7 @@ impl Log {
                     let index_len = meta.indexes.get(&def.metaname()).cloned().unwrap_or(0);
                     indexes.push(Self::load_index(
                         dir,
+                        meta,
                         &def,
                         index_len,
                         key_buf.clone(),
@@ -1228,7 +1462,7 @@ impl Log {
                 for (index, def) in indexes.iter().zip(index_defs) {
                     let index_len = meta.indexes.get(&def.metaname()).cloned().unwrap_or(0);
                     let index = if index_len > Self::get_index_log_len(index, true).unwrap_or(0) {
-                        Self::load_index(dir, &def, index_len, key_buf.clone(), fsync)?
+                        Self::load_index(dir, meta, &def, index_len, key_buf.clone(), fsync)?
                     } else {
                         let mut index = index.try_clone()?;
                         index.key_buf = key_buf.clone();
@@ -1250,6 +1484,7 @@ impl Log {
     /// Load a single index.
     fn load_index(
         dir: &GenericPath,
+        meta: &LogMetadata,
         def: &IndexDef,
         len: u64,
         buf: Arc<dyn ReadonlyBuffer + Send + Sync>,
@@ -1257,7 +1492,7 @@ impl Log {
     ) -> crate::Result<Index> {
         match dir.as_opt_path() {
             Some(dir) => {
-                let path = dir.join(def.filename());
+                let path = dir.join(meta.generation_file_name(&def.filename()));
                 index::OpenOptions::new()
                     .checksum_chunk_size_logarithm(INDEX_CHECKSUM_CHUNK_SIZE_LOGARITHM)
                     .logical_len(Some(len))
@@ -1380,6 +1615,7 @@ impl Log {
                 data,
                 data_offset: offset,
                 next_offset: end,
+                compressed: entry_flags & ENTRY_FLAG_ZSTD != 0,
             }))
         } else {
             Err(data_error(format!("integrity check failed at {}", offset)))
@@ -1494,6 +1730,8 @@ struct EntryResult<'a> {
     data: &'a [u8],
     data_offset: u64,
     next_offset: u64,
+    // Whether the entry is compressed. If so, `data` is the stored content.
+    compressed: bool,
 }
 
 impl<'a> EntryResult<'a> {
@@ -1505,12 +1743,36 @@ impl<'a> EntryResult<'a> {
             // So it does not need to be changed.
             data_offset: self.data_offset,
             next_offset: self.next_offset + offset,
+            compressed: self.compressed,
+        }
+    }
+
+    /// Decompress the stored content if the entry is compressed.
+    fn uncompressed_data(
+        &self,
+        path: &GenericPath,
+        meta: &LogMetadata,
+    ) -> crate::Result<Cow<'a, [u8]>> {
+        if !self.compressed {
+            return Ok(Cow::Borrowed(self.data));
+        }
+        let dict = meta.compression_dict.as_ref();
+        match compression::decompress(self.data, dict) {
+            Ok(data) => Ok(Cow::Owned(data)),
+            Err(io_err) => {
+                let msg = format!("cannot decompress entry data at {}", self.data_offset);
+                let err = match path.as_opt_path() {
+                    Some(path) => crate::Error::corruption(path, msg),
+                    None => crate::Error::path(Path::new("<memory>"), msg),
+                };
+                Err(err.source(io_err))
+            }
         }
     }
 }
 
 impl<'a> Iterator for LogLookupIter<'a> {
-    type Item = crate::Result<&'a [u8]>;
+    type Item = crate::Result<Cow<'a, [u8]>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.errored {
@@ -1527,7 +1789,11 @@ impl<'a> Iterator for LogLookupIter<'a> {
                 .read_entry(offset)
                 .context("in LogLookupIter::next")
             {
-                Ok(Some(entry)) => Some(Ok(entry.data)),
+                Ok(Some(entry)) => Some(
+                    entry
+                        .uncompressed_data(&self.log.dir, &self.log.meta)
+                        .context("in LogLookupIter::next"),
+                ),
                 Ok(None) => None,
                 Err(err) => {
                     // Do not set this iterator to an error state. It's possible
@@ -1546,13 +1812,13 @@ impl<'a> Iterator for LogLookupIter<'a> {
 
 impl<'a> LogLookupIter<'a> {
     /// A convenient way to get data.
-    pub fn into_vec(self) -> crate::Result<Vec<&'a [u8]>> {
+    pub fn into_vec(self) -> crate::Result<Vec<Cow<'a, [u8]>>> {
         self.collect()
     }
 }
 
 impl<'a> Iterator for LogIter<'a> {
-    type Item = crate::Result<&'a [u8]>;
+    type Item = crate::Result<Cow<'a, [u8]>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.errored {
@@ -1570,7 +1836,16 @@ impl<'a> Iterator for LogIter<'a> {
             Ok(Some(entry_result)) => {
                 assert!(entry_result.next_offset > self.next_offset);
                 self.next_offset = entry_result.next_offset;
-                Some(Ok(entry_result.data))
+                match entry_result
+                    .uncompressed_data(&self.log.dir, &self.log.meta)
+                    .context("in LogIter::next")
+                {
+                    Ok(data) => Some(Ok(data)),
+                    Err(e) => {
+                        self.errored = true;
+                        Some(Err(e))
+                    }
+                }
             }
             Ok(None) => None,
         }
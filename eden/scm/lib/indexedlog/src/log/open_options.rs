@@ -8,7 +8,9 @@
 use crate::errors::ResultExt;
 use crate::index::Index;
 use crate::lock::ScopedDirLock;
+use crate::log::compression::Dictionary;
 use crate::log::{GenericPath, Log, LogMetadata, PRIMARY_START_OFFSET};
+use minibytes::Bytes;
 use std::borrow::Cow;
 use std::fmt::{self, Debug};
 use std::ops::Range;
@@ -115,6 +117,8 @@ pub struct OpenOptions {
     pub(crate) flush_filter: Option<FlushFilterFunc>,
     pub(crate) fsync: bool,
     pub(crate) auto_sync_threshold: Option<u64>,
+    pub(crate) compression_level: Option<i32>,
+    pub(crate) compression_dict: Option<Bytes>,
 }
 
 pub type FlushFilterFunc =
@@ -222,6 +226,7 @@ impl OpenOptions {
     /// `fsync` is initially `false`.
     /// `index_defs` is initially empty.
     /// `auto_sync_threshold` is initially `None`.
+    /// `compression_level` is initially `None`.
     pub fn new() -> Self {
         Self {
             create: false,
@@ -230,6 +235,8 @@ impl OpenOptions {
             flush_filter: None,
             fsync: false,
             auto_sync_threshold: None,
+            compression_level: None,
+            compression_dict: None,
         }
     }
 
@@ -287,6 +294,33 @@ impl OpenOptions {
         self
     }
 
+    /// Sets whether to compress new entries using zstd.
+    /// - `None`: Store entries as-is.
+    /// - `Some(level)`: Compress entries at the given zstd level. Entries
+    ///   that do not become smaller are still stored as-is.
+    ///
+    /// Compression is transparent: [`Log::lookup`] and [`Log::iter`] return
+    /// uncompressed data, and index functions take uncompressed data as
+    /// input. Reading compressed entries does not require this option, and
+    /// entries written without compression remain readable.
+    pub fn compression_level(mut self, level: impl Into<Option<i32>>) -> Self {
+        self.compression_level = level.into();
+        self
+    }
+
+    /// Sets the zstd dictionary used to compress entries.
+    ///
+    /// The dictionary is stored in the metadata when a [`Log`] is created,
+    /// and is used for all compressed entries of that [`Log`]. Existing
+    /// [`Log`]s keep using the dictionary they were created with.
+    ///
+    /// Use [`train_dictionary`](crate::log::train_dictionary) to create a
+    /// dictionary from sample entries.
+    pub fn compression_dictionary(mut self, dict: Option<Vec<u8>>) -> Self {
+        self.compression_dict = dict.filter(|d| !d.is_empty()).map(Bytes::from);
+        self
+    }
+
     /// Sets the flush filter function.
     ///
     /// The function will be called at [`Log::sync`] time, if there are
@@ -349,7 +383,7 @@ impl OpenOptions {
     pub(crate) fn create_in_memory(&self, dir: GenericPath) -> crate::Result<Log> {
         assert!(dir.as_opt_path().is_none());
         let result: crate::Result<_> = (|| {
-            let meta = LogMetadata::new_with_primary_len(PRIMARY_START_OFFSET);
+            let meta = self.new_meta(PRIMARY_START_OFFSET);
             let mem_buf = Box::pin(Vec::new());
             let (disk_buf, indexes) = Log::load_log_and_indexes(
                 &dir,
@@ -393,17 +427,17 @@ impl OpenOptions {
         let create = self.create;
 
         // Do a lock-less load_or_create_meta to avoid the flock overhead.
-        let meta = Log::load_or_create_meta(dir, false).or_else(|err| {
+        let meta = Log::load_or_create_meta(dir, None).or_else(|err| {
             if create {
                 dir.mkdir()
                     .context("cannot mkdir after failing to read metadata")
                     .source(err)?;
                 // Make sure check and write happens atomically.
                 if lock.is_some() {
-                    Log::load_or_create_meta(dir, true)
+                    Log::load_or_create_meta(dir, Some(self))
                 } else {
                     let _lock = dir.lock()?;
-                    Log::load_or_create_meta(dir, true)
+                    Log::load_or_create_meta(dir, Some(self))
                 }
             } else {
                 Err(err).context(|| format!("cannot open Log at {:?}", &dir))
@@ -447,6 +481,15 @@ impl OpenOptions {
     }
 }
 
+impl OpenOptions {
+    /// Create metadata for a new [`Log`] with the given primary length.
+    pub(crate) fn new_meta(&self, primary_len: u64) -> LogMetadata {
+        let mut meta = LogMetadata::new_with_primary_len(primary_len);
+        meta.compression_dict = self.compression_dict.clone().map(Dictionary::new);
+        meta
+    }
+}
+
 impl IndexOutput {
     pub(crate) fn into_cow(self, data: &[u8]) -> crate::Result<Cow<[u8]>> {
         Ok(match self {
@@ -489,6 +532,12 @@ impl fmt::Debug for OpenOptions {
         write!(f, "create: {}, ", self.create)?;
         write!(f, "checksum_type: {:?}, ", self.checksum_type)?;
         write!(f, "auto_sync_threshold: {:?}, ", self.auto_sync_threshold)?;
+        write!(f, "compression_level: {:?}, ", self.compression_level)?;
+        let compression_dict_desc = match self.compression_dict {
+            Some(ref dict) => format!("Some(<{} bytes>)", dict.len()),
+            None => "None".to_string(),
+        };
+        write!(f, "compression_dict: {}, ", compression_dict_desc)?;
         let flush_filter_desc = match self.flush_filter {
             Some(ref _buf) => "Some(_)",
             None => "None",
@@ -7,6 +7,7 @@
 
 use crate::errors::{IoResultExt, ResultExt};
 use crate::lock::ScopedDirLock;
+use crate::log::meta::generation_file_name;
 use crate::log::{
     GenericPath, LogMetadata, OpenOptions, META_FILE, PRIMARY_FILE, PRIMARY_HEADER,
     PRIMARY_START_OFFSET,
@@ -43,9 +44,17 @@ impl OpenOptions {
 
             let lock = ScopedDirLock::new(dir)?;
 
-            let primary_path = dir.join(PRIMARY_FILE);
             let meta_path = dir.join(META_FILE);
 
+            // A compacted log keeps its files under the generation recorded in
+            // the metadata. If the metadata cannot be read, use the newest
+            // generation on disk.
+            let generation = match LogMetadata::read_file(&meta_path) {
+                Ok(meta) => meta.generation,
+                Err(_) => latest_generation(dir),
+            };
+            let primary_path = dir.join(generation_file_name(PRIMARY_FILE, generation));
+
             // Make sure the header of the primary log file is okay.
             (|| -> crate::Result<()> {
                 #[allow(clippy::never_loop)]
@@ -111,7 +120,10 @@ impl OpenOptions {
                     }
                     Err(meta_err) => {
                         // Attempt to rebuild metadata.
-                        let meta = LogMetadata::new_with_primary_len(primary_len);
+                        // Compressed entries can only be read if the
+                        // dictionary matches the one they were written with.
+                        let mut meta = self.new_meta(primary_len);
+                        meta.generation = generation;
                         meta.write_file(&meta_path, self.fsync)
                             .context("while recreating meta")
                             .source(meta_err)?;
@@ -228,6 +240,20 @@ impl OpenOptions {
     }
 }
 
+/// Find the newest generation of the primary log in `dir`.
+fn latest_generation(dir: &Path) -> u64 {
+    let prefix = format!("{}.", PRIMARY_FILE);
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            name.strip_prefix(&prefix)?.parse::<u64>().ok()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
 impl OpenOptionsRepair for OpenOptions {
     fn open_options_repair(&self, dir: impl AsRef<Path>) -> crate::Result<String> {
         OpenOptions::repair(self, dir.as_ref())
@@ -253,7 +279,7 @@ impl OpenOptions {
             let lock = ScopedDirLock::new(dir)?;
 
             // Replace the metadata to an empty state.
-            let meta = LogMetadata::new_with_primary_len(PRIMARY_START_OFFSET);
+            let meta = self.new_meta(PRIMARY_START_OFFSET);
             let meta_path = dir.join(META_FILE);
             meta.write_file(&meta_path, self.fsync)?;
 
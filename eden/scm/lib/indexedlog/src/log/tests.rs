@@ -25,6 +25,11 @@ impl fmt::Display for DummyError {
 
 impl std::error::Error for DummyError {}
 
+/// Collect entries as owned bytes, so they can be compared with literals.
+fn owned<'a>(iter: impl Iterator<Item = crate::Result<Cow<'a, [u8]>>>) -> Vec<Vec<u8>> {
+    iter.map(|v| v.unwrap().into_owned()).collect()
+}
+
 #[test]
 fn test_empty_log() {
     let dir = tempdir().unwrap();
@@ -148,6 +153,310 @@ fn test_checksum_type() {
     );
 }
 
+#[test]
+fn test_compression() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("log");
+
+    let open = |level: Option<i32>, lag_threshold: u64| {
+        OpenOptions::new()
+            .compression_level(level)
+            .index_defs(vec![
+                IndexDef::new("ref", index_ref).lag_threshold(lag_threshold)
+            ])
+            .create(true)
+            .open(&log_path)
+            .unwrap()
+    };
+    let read_entries =
+        |log: &Log| -> Vec<Vec<u8>> { log.iter().map(|v| v.unwrap().to_vec()).collect() };
+
+    // Write some entries without compression.
+    let mut expected: Vec<Vec<u8>> = vec![vec![b'a'; 1000], b"short".to_vec()];
+    let mut log = open(None, 0);
+    for entry in &expected {
+        log.append(entry).unwrap();
+    }
+    let uncompressed_len = log.sync().unwrap();
+
+    // Append compressible entries. "short" is not compressible and stays
+    // uncompressed.
+    let mut log = open(Some(3), 0);
+    for entry in vec![vec![b'b'; 1000], vec![b'c'; 2000], b"short2".to_vec()] {
+        log.append(&entry).unwrap();
+        expected.push(entry);
+    }
+
+    // In-memory entries are readable and indexed.
+    assert_eq!(read_entries(&log), expected);
+    for entry in &expected {
+        let found = owned(log.lookup(0, entry).unwrap());
+        assert_eq!(found, vec![&entry[..]]);
+    }
+    let len = log.sync().unwrap();
+    assert!(len - uncompressed_len < 100);
+
+    // Entries are readable and indexed after reloading, with or without
+    // compression options, and with on-disk or in-memory indexes.
+    for &(level, lag_threshold) in &[(Some(3), 0), (None, 0), (None, 1 << 20)] {
+        let log = open(level, lag_threshold);
+        assert_eq!(read_entries(&log), expected);
+        for entry in &expected {
+            let found = owned(log.lookup(0, entry).unwrap());
+            assert_eq!(found, vec![&entry[..]]);
+        }
+    }
+
+    // Compressed entries are decompressed into owned buffers, so nothing is
+    // kept around after they are dropped. Other entries are borrowed.
+    let log = open(None, 0);
+    let entries: Vec<_> = log.iter().map(|v| v.unwrap()).collect();
+    assert!(matches!(entries[0], Cow::Borrowed(_)));
+    assert!(matches!(entries[2], Cow::Owned(_)));
+    drop(entries);
+
+    // Rebuilding indexes works with compressed entries.
+    log.rebuild_indexes(true).unwrap();
+    let log = open(None, 0);
+    let found = owned(log.lookup(0, &expected[3]).unwrap());
+    assert_eq!(found, vec![&expected[3][..]]);
+}
+
+#[test]
+fn test_compression_dictionary() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("log");
+
+    let samples: Vec<Vec<u8>> = (0..1000)
+        .map(|i| {
+            format!(
+                "commit {} author=alice date={} parents=[{}]",
+                i,
+                i * 7,
+                i - 1
+            )
+            .into_bytes()
+        })
+        .collect();
+    let dict = train_dictionary(&samples, 4096).unwrap();
+
+    let opts = OpenOptions::new()
+        .compression_level(3)
+        .compression_dictionary(Some(dict.clone()))
+        .create(true);
+    let mut log = opts.open(&log_path).unwrap();
+    let entry = b"commit 1234 author=alice date=8638 parents=[1233]".to_vec();
+    log.append(&entry).unwrap();
+    log.sync().unwrap();
+
+    // The dictionary is stored in meta and is used for reading, even if
+    // options do not specify it.
+    let log = Log::open(&log_path, Vec::new()).unwrap();
+    assert_eq!(log.meta.compression_dict.as_deref(), Some(&dict[..]));
+    assert_eq!(log.iter().next().unwrap().unwrap(), &entry[..]);
+
+    // A different dictionary in options does not replace the existing one.
+    let mut log = OpenOptions::new()
+        .compression_level(3)
+        .compression_dictionary(Some(b"other".to_vec()))
+        .open(&log_path)
+        .unwrap();
+    log.append(&entry).unwrap();
+    log.sync().unwrap();
+    let log = Log::open(&log_path, Vec::new()).unwrap();
+    assert_eq!(log.meta.compression_dict.as_deref(), Some(&dict[..]));
+    assert_eq!(log.iter().count(), 2);
+    assert!(log.iter().all(|e| e.unwrap() == &entry[..]));
+
+    // Reloading on sync keeps the loaded dictionary.
+    let mut log = log;
+    let dict_ptr = |log: &Log| log.meta.compression_dict.as_ref().unwrap().as_ptr();
+    let loaded_dict_ptr = dict_ptr(&log);
+    let mut other = opts.open(&log_path).unwrap();
+    other.append(&entry).unwrap();
+    other.sync().unwrap();
+    log.sync().unwrap();
+    assert_eq!(log.iter().count(), 3);
+    assert_eq!(dict_ptr(&log), loaded_dict_ptr);
+    log.append(&entry).unwrap();
+    log.sync().unwrap();
+    assert_eq!(dict_ptr(&log), loaded_dict_ptr);
+    assert!(log.iter().all(|e| e.unwrap() == &entry[..]));
+}
+
+#[test]
+fn test_compact() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("log");
+
+    let open = || {
+        OpenOptions::new()
+            .compression_level(3)
+            .index("first-byte", |_| vec![IndexOutput::Reference(0..1)])
+            .create(true)
+            .open(&log_path)
+            .unwrap()
+    };
+    let read_entries =
+        |log: &Log| -> Vec<Vec<u8>> { log.iter().map(|v| v.unwrap().to_vec()).collect() };
+    let lookup = |log: &Log, key: &[u8]| -> Vec<Vec<u8>> {
+        log.lookup(0, key)
+            .unwrap()
+            .map(|v| v.unwrap().to_vec())
+            .collect()
+    };
+
+    let mut log = open();
+    for &(byte, len) in &[(b'a', 10), (b'b', 1000), (b'a', 20), (b'c', 1)] {
+        log.append(vec![byte; len]).unwrap();
+    }
+    log.sync().unwrap();
+
+    // Readers and writers opened before compaction.
+    let reader = open();
+    let mut writer = open();
+    writer.append(b"d").unwrap();
+
+    // Entries not yet written are also subject to the filter.
+    log.append(b"bb").unwrap();
+    let removed = log.compact(|data| Ok(data[0] != b'b')).unwrap();
+    assert_eq!(removed, 2);
+    let expected = vec![vec![b'a'; 10], vec![b'a'; 20], vec![b'c'; 1]];
+    assert_eq!(read_entries(&log), expected);
+    assert_eq!(lookup(&log, b"a"), vec![vec![b'a'; 20], vec![b'a'; 10]]);
+    assert!(lookup(&log, b"b").is_empty());
+
+    // Compacted files are published as a new generation. Files of the old
+    // generation are removed.
+    assert_eq!(log.meta.generation, 1);
+    assert!(log_path.join("log.1").exists());
+    assert!(log_path.join("index2-first-byte.1").exists());
+    #[cfg(unix)]
+    assert!(!log_path.join(PRIMARY_FILE).exists());
+
+    // Existing readers are not affected until sync.
+    assert_eq!(read_entries(&reader).len(), 4);
+
+    // Writers with pending entries write them on top of the compacted log.
+    writer.sync().unwrap();
+    let mut expected = expected;
+    expected.push(b"d".to_vec());
+    assert_eq!(read_entries(&writer), expected);
+    assert_eq!(lookup(&writer, b"d"), vec![b"d".to_vec()]);
+
+    let mut reader = reader;
+    reader.sync().unwrap();
+    assert_eq!(read_entries(&reader), expected);
+    assert_eq!(lookup(&reader, b"c"), vec![b"c".to_vec()]);
+
+    // Keeping all entries is a no-op.
+    assert_eq!(log.compact(|_| Ok(true)).unwrap(), 0);
+    assert_eq!(read_entries(&log), expected);
+
+    // Compact removes everything.
+    assert_eq!(log.compact(|_| Ok(false)).unwrap(), 4);
+    assert!(read_entries(&open()).is_empty());
+    assert_eq!(log.meta.primary_len, PRIMARY_START_OFFSET);
+
+    // Errors in the filter function are propagated.
+    log.append(b"e").unwrap();
+    let err = log
+        .compact(|_| Err(crate::Error::programming("filter error")))
+        .unwrap_err();
+    assert!(err.to_string().contains("filter error"));
+    assert_eq!(read_entries(&log), vec![b"e".to_vec()]);
+    assert_eq!(lookup(&log, b"e"), vec![b"e".to_vec()]);
+}
+
+#[test]
+fn test_compact_removes_stale_generations() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("log");
+    let open_options = OpenOptions::new()
+        .index("first-byte", |_| vec![IndexOutput::Reference(0..1)])
+        .create(true);
+
+    let mut log = open_options.open(&log_path).unwrap();
+    for data in &[b"a1", b"b1", b"a2"] {
+        log.append(data).unwrap();
+    }
+    assert_eq!(log.compact(|data| Ok(data[0] == b'a')).unwrap(), 1);
+    assert_eq!(log.meta.generation, 1);
+
+    // Pretend removing files of older generations failed (ex. they were
+    // mmaped by a reader on Windows).
+    let stale = ["log", "index2-first-byte", "log.0", "index2-first-byte.0"];
+    let unrelated = ["log.bak", "index2-first-byte.x", "index2-other.0"];
+    for name in stale.iter().chain(unrelated.iter()) {
+        fs::write(log_path.join(name), b"stale").unwrap();
+    }
+
+    // The next sync removes them.
+    log.append(b"a3").unwrap();
+    log.sync().unwrap();
+    for name in &stale {
+        assert!(!log_path.join(name).exists(), "{} is not removed", name);
+    }
+    for name in &unrelated {
+        assert!(log_path.join(name).exists(), "{} is removed", name);
+    }
+    assert_eq!(owned(log.iter()), vec![b"a1", b"a2", b"a3"]);
+
+    // So does the next compact, even if it does not remove any entry.
+    fs::write(log_path.join("log"), b"stale").unwrap();
+    assert_eq!(log.compact(|_| Ok(true)).unwrap(), 0);
+    assert!(!log_path.join("log").exists());
+
+    // Compacting again moves to a new generation, and removes the old one.
+    assert_eq!(log.compact(|data| Ok(data != b"a1")).unwrap(), 1);
+    assert_eq!(log.meta.generation, 2);
+    assert!(!log_path.join("log.1").exists());
+    assert!(!log_path.join("index2-first-byte.1").exists());
+    let log = open_options.open(&log_path).unwrap();
+    assert_eq!(owned(log.iter()), vec![b"a2", b"a3"]);
+    assert_eq!(log.lookup(0, b"a").unwrap().count(), 2);
+}
+
+#[test]
+fn test_repair_compacted() {
+    let dir = tempdir().unwrap();
+    let log_path = dir.path().join("log");
+    let open_options = OpenOptions::new()
+        .index("first-byte", |_| vec![IndexOutput::Reference(0..1)])
+        .create(true);
+
+    let mut log = open_options.open(&log_path).unwrap();
+    for data in &[b"a1", b"b1", b"a2"] {
+        log.append(data).unwrap();
+    }
+    assert_eq!(log.compact(|data| Ok(data[0] == b'a')).unwrap(), 1);
+    drop(log);
+
+    // Repair finds the compacted primary log even if metadata is lost.
+    fs::write(log_path.join(META_FILE), b"corrupted").unwrap();
+    open_options.repair(&log_path).unwrap();
+    let log = open_options.open(&log_path).unwrap();
+    assert_eq!(log.meta.generation, 1);
+    let entries = owned(log.iter());
+    assert_eq!(entries, vec![b"a1", b"a2"]);
+    assert_eq!(log.lookup(0, b"a").unwrap().count(), 2);
+}
+
+#[test]
+fn test_compact_in_memory() {
+    let mut log = OpenOptions::new()
+        .index("first-byte", |_| vec![IndexOutput::Reference(0..1)])
+        .open(())
+        .unwrap();
+    for data in &[b"a1", b"b1", b"a2"] {
+        log.append(data).unwrap();
+    }
+    assert_eq!(log.compact(|data| Ok(data[0] == b'a')).unwrap(), 1);
+    let entries = owned(log.iter());
+    assert_eq!(entries, vec![b"a1", b"a2"]);
+    assert_eq!(log.lookup(0, b"a").unwrap().count(), 2);
+}
+
 #[test]
 fn test_iter_and_iter_dirty() {
     let dir = tempdir().unwrap();
@@ -158,38 +467,22 @@ fn test_iter_and_iter_dirty() {
     log.append(b"4").unwrap();
     log.append(b"3").unwrap();
 
-    assert_eq!(
-        log.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
-        vec![b"2", b"4", b"3"]
-    );
-    assert_eq!(
-        log.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
-        log.iter_dirty().collect::<crate::Result<Vec<_>>>().unwrap(),
-    );
+    assert_eq!(owned(log.iter()), vec![b"2", b"4", b"3"]);
+    assert_eq!(owned(log.iter()), owned(log.iter_dirty()),);
 
     log.sync().unwrap();
 
-    assert!(
-        log.iter_dirty()
-            .collect::<crate::Result<Vec<_>>>()
-            .unwrap()
-            .is_empty()
-    );
-    assert_eq!(
-        log.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
-        vec![b"2", b"4", b"3"]
-    );
+    assert!(log
+        .iter_dirty()
+        .collect::<crate::Result<Vec<_>>>()
+        .unwrap()
+        .is_empty());
+    assert_eq!(owned(log.iter()), vec![b"2", b"4", b"3"]);
 
     log.append(b"5").unwrap();
     log.append(b"1").unwrap();
-    assert_eq!(
-        log.iter_dirty().collect::<crate::Result<Vec<_>>>().unwrap(),
-        vec![b"5", b"1"]
-    );
-    assert_eq!(
-        log.iter().collect::<crate::Result<Vec<_>>>().unwrap(),
-        vec![b"2", b"4", b"3", b"5", b"1"]
-    );
+    assert_eq!(owned(log.iter_dirty()), vec![b"5", b"1"]);
+    assert_eq!(owned(log.iter()), vec![b"2", b"4", b"3", b"5", b"1"]);
 }
 
 fn get_index_defs(lag_threshold: u64) -> Vec<IndexDef> {
@@ -248,7 +541,8 @@ fn test_slice_to_bytes() {
     log.append(b"1231516").unwrap();
     log.sync().unwrap();
 
-    let slice = log.lookup(0, b"23").unwrap().into_vec().unwrap()[0];
+    let entries = log.lookup(0, b"23").unwrap().into_vec().unwrap();
+    let slice: &[u8] = &entries[0];
     assert_eq!(slice, b"1231516");
 
     // The bytes are zero-copy from the Log buffer.
@@ -340,19 +634,13 @@ fn test_index_manual() {
         }
 
         // Lookups via index 0
-        assert_eq!(
-            log.lookup(0, b"34").unwrap().into_vec().unwrap(),
-            [b"3456", b"2345"]
-        );
-        assert_eq!(log.lookup(0, b"56").unwrap().into_vec().unwrap(), [b"3456"]);
-        assert_eq!(log.lookup(0, b"78").unwrap().into_vec().unwrap(), [b"78"]);
+        assert_eq!(owned(log.lookup(0, b"34").unwrap()), [b"3456", b"2345"]);
+        assert_eq!(owned(log.lookup(0, b"56").unwrap()), [b"3456"]);
+        assert_eq!(owned(log.lookup(0, b"78").unwrap()), [b"78"]);
         assert!(log.lookup(0, b"89").unwrap().into_vec().unwrap().is_empty());
 
         // Lookups via index 1
-        assert_eq!(
-            log.lookup(1, b"345").unwrap().into_vec().unwrap(),
-            [b"3456", b"2345"]
-        );
+        assert_eq!(owned(log.lookup(1, b"345").unwrap()), [b"3456", b"2345"]);
 
         log.sync().unwrap();
 
@@ -361,7 +649,7 @@ fn test_index_manual() {
         for key in [b"34", b"35"].iter() {
             assert!(log.lookup(0, key).unwrap().into_vec().unwrap().is_empty());
         }
-        assert_eq!(log.lookup(0, b"56").unwrap().into_vec().unwrap(), [b"3456"]);
+        assert_eq!(owned(log.lookup(0, b"56").unwrap()), [b"3456"]);
 
         // Delete keys.
         let mut log = Log::open(dir.path(), get_index_defs(lag)).unwrap();
@@ -395,10 +683,7 @@ fn test_index_reorder() {
     let mut indexes = get_index_defs(0);
     indexes.reverse();
     log = Log::open(dir.path(), indexes).unwrap();
-    assert_eq!(
-        log.lookup(1, b"23").unwrap().into_vec().unwrap(),
-        [b"234", b"123"]
-    );
+    assert_eq!(owned(log.lookup(1, b"23").unwrap()), [b"234", b"123"]);
 }
 
 // This test rewrites mmaped files which is unsupoorted by Windows.
@@ -462,8 +747,8 @@ fn test_lookup_prefix_and_range() {
             .unwrap()
             .unwrap()
             .1
-            .collect::<Result<Vec<_>, _>>()
-            .unwrap(),
+            .map(|v| v.unwrap().into_owned())
+            .collect::<Vec<_>>(),
         vec![b"bb", b"bb"]
     );
     assert_eq!(iter.next().unwrap().unwrap().0.as_ref(), b"aa");
@@ -686,7 +971,7 @@ fn test_flush_filter() {
         .create(true)
         .flush_filter(Some(|ctx: &FlushFilterContext, bytes: &[u8]| {
             // "new" changes by log2 are visible.
-            assert_eq!(ctx.log.iter().nth(0).unwrap().unwrap(), b"log2");
+            assert_eq!(ctx.log.iter().nth(0).unwrap().unwrap().as_ref(), b"log2");
             Ok(match bytes.len() {
                 1 => FlushFilterOutput::Drop,
                 2 => FlushFilterOutput::Replace(b"cc".to_vec()),
@@ -826,12 +1111,12 @@ fn test_sync_missing_meta() {
 
 fn test_rebuild_indexes() {
     let dir = tempdir().unwrap();
-    let open_opts = OpenOptions::new().create(true).index_defs(vec![
-        IndexDef::new("key", |data| {
+    let open_opts = OpenOptions::new()
+        .create(true)
+        .index_defs(vec![IndexDef::new("key", |data| {
             vec![IndexOutput::Reference(0..data.len() as u64)]
         })
-        .lag_threshold(1),
-    ]);
+        .lag_threshold(1)]);
     let mut log = open_opts.clone().open(dir.path()).unwrap();
 
     log.append(b"abc").unwrap();
@@ -943,20 +1228,14 @@ fn test_repair() {
 
     // Reading entries is recovered. But we lost one entry.
     let mut log = Log::open(dir.path(), Vec::new()).unwrap();
-    assert_eq!(
-        log.iter().collect::<Result<Vec<_>, _>>().unwrap(),
-        vec![b"abc", b"def"]
-    );
+    assert_eq!(owned(log.iter()), vec![b"abc", b"def"]);
 
     // Writing is recovered.
     log.append(b"pqr").unwrap();
     log.flush().unwrap();
 
     let log = Log::open(dir.path(), Vec::new()).unwrap();
-    assert_eq!(
-        log.iter().collect::<Result<Vec<_>, _>>().unwrap(),
-        vec![b"abc", b"def", b"pqr"]
-    );
+    assert_eq!(owned(log.iter()), vec![b"abc", b"def", b"pqr"]);
 }
 
 #[test]
@@ -979,9 +1258,12 @@ fn test_repair_noop() {
 fn test_repair_and_delete_content() {
     let dir = tempdir().unwrap();
     let path = dir.path();
-    let open_opts = OpenOptions::new().create(true).index_defs(vec![
-        IndexDef::new("c", |_| vec![IndexOutput::Reference(0..1)]).lag_threshold(5000),
-    ]);
+    let open_opts = OpenOptions::new()
+        .create(true)
+        .index_defs(vec![IndexDef::new("c", |_| {
+            vec![IndexOutput::Reference(0..1)]
+        })
+        .lag_threshold(5000)]);
 
     let long_lived_log = RefCell::new(open_opts.open(()).unwrap());
     let open = || open_opts.open(path);
@@ -1405,10 +1687,7 @@ fn test_clear_dirty() {
         assert_eq!(log.lookup_range(0, ..).unwrap().count(), 2);
 
         log.clear_dirty().unwrap();
-        assert_eq!(
-            log.iter().collect::<Result<Vec<_>, _>>().unwrap(),
-            vec![[b'a'; 10]],
-        );
+        assert_eq!(owned(log.iter()), vec![[b'a'; 10]],);
         assert_eq!(log.lookup_range(0, ..).unwrap().count(), 1);
     }
 }
@@ -110,7 +110,7 @@ impl OpenOptions {
                 if !multimeta.metas.contains_key(name_ref) {
                     // Create a new Log if it does not exist in MultiMeta.
                     utils::mkdir_p(&fspath)?;
-                    let meta = log::Log::load_or_create_meta(&fspath.as_path().into(), true)?;
+                    let meta = log::Log::load_or_create_meta(&fspath.as_path().into(), Some(opts))?;
                     let meta = Arc::new(Mutex::new(meta));
                     multimeta.metas.insert(name.to_string(), meta);
                 }
@@ -14,6 +14,7 @@ use crate::repair::OpenOptionsRepair;
 use crate::utils;
 use minibytes::Bytes;
 use once_cell::sync::OnceCell;
+use std::borrow::Cow;
 use std::fmt;
 use std::fs;
 use std::io;
@@ -96,6 +97,22 @@ impl OpenOptions {
         self
     }
 
+    /// Sets whether to compress new entries using zstd.
+    ///
+    /// See [`log::OpenOptions::compression_level`] for details.
+    pub fn compression_level(mut self, level: impl Into<Option<i32>>) -> Self {
+        self.log_open_options = self.log_open_options.compression_level(level);
+        self
+    }
+
+    /// Sets the zstd dictionary used by newly created [`Log`]s.
+    ///
+    /// See [`log::OpenOptions::compression_dictionary`] for details.
+    pub fn compression_dictionary(mut self, dict: Option<Vec<u8>>) -> Self {
+        self.log_open_options = self.log_open_options.compression_dictionary(dict);
+        self
+    }
+
     /// Set whether create the [`RotateLog`] structure if it does not exist.
     pub fn create(mut self, create: bool) -> Self {
         self.log_open_options = self.log_open_options.create(create);
@@ -412,6 +429,8 @@ impl RotateLog {
                             latest,
                         )?);
                         self.latest = latest;
+                    } else {
+                        self.unload_changed_logs();
                     }
                     self.writable_log().sync()?;
                 } else {
@@ -440,11 +459,11 @@ impl RotateLog {
                         for entry in self.writable_log().iter_dirty() {
                             let content = entry?;
                             let context = FlushFilterContext { log };
-                            match filter(&context, content).map_err(|err| {
+                            match filter(&context, &content).map_err(|err| {
                                 crate::Error::wrap(err, "failed to run filter function")
                             })? {
                                 FlushFilterOutput::Drop => {}
-                                FlushFilterOutput::Keep => log.append(content)?,
+                                FlushFilterOutput::Keep => log.append(&content)?,
                                 FlushFilterOutput::Replace(content) => log.append(content)?,
                             }
                         }
@@ -458,6 +477,8 @@ impl RotateLog {
                     }
                     self.set_logs(new_logs);
                     self.latest = latest;
+                } else {
+                    self.unload_changed_logs();
                 }
 
                 let size = self.writable_log().flush()?;
@@ -513,6 +534,68 @@ impl RotateLog {
         self.sync()
     }
 
+    /// Rewrite all [`Log`]s so they only keep entries for which `filter`
+    /// returns `true`.
+    ///
+    /// In-memory entries are written to disk first. See [`Log::compact`] for
+    /// details.
+    ///
+    /// Return the number of entries removed.
+    pub fn compact(
+        &mut self,
+        mut filter: impl FnMut(&[u8]) -> crate::Result<bool>,
+    ) -> crate::Result<usize> {
+        let result: crate::Result<_> = (|| {
+            if self.dir.is_none() {
+                return self.writable_log().compact(&mut filter);
+            }
+
+            self.sync()?;
+
+            // Take the directory lock so logs won't be rotated or removed.
+            let dir = self.dir.clone().unwrap();
+            let _lock = ScopedDirLock::new(&dir)?;
+            let latest = read_latest(&dir)?;
+            let mut logs = read_logs(&dir, &self.open_options, latest)?;
+
+            let mut removed = 0;
+            for (index, cell) in logs.iter_mut().enumerate() {
+                let mut log = match cell.take() {
+                    Some(log) => log,
+                    None => {
+                        let id = latest.wrapping_sub(index as u8);
+                        let open_options = self
+                            .open_options
+                            .log_open_options
+                            .clone()
+                            .with_zero_index_lag();
+                        load_log(&dir, id, open_options)?
+                    }
+                };
+                removed += log.compact(&mut filter)?;
+                *cell = create_log_cell(log);
+            }
+
+            self.set_logs(logs);
+            self.latest = latest;
+            Ok(removed)
+        })();
+
+        result
+            .context("in RotateLog::compact")
+            .context(|| format!("  RotateLog.dir = {:?}", self.dir))
+    }
+
+    /// Unload non-writable [`Log`]s that are changed on disk (ex. by
+    /// [`RotateLog::compact`]), so they will be reloaded on demand.
+    fn unload_changed_logs(&mut self) {
+        for cell in self.logs.iter_mut().skip(1) {
+            if cell.get().map(|log| log.is_changed()).unwrap_or(false) {
+                *cell = OnceCell::new();
+            }
+        }
+    }
+
     fn set_logs(&mut self, logs: Vec<OnceCell<Log>>) {
         self.logs_len = AtomicUsize::new(logs.len());
         self.logs = logs;
@@ -595,13 +678,13 @@ impl RotateLog {
     /// Iterate over all the entries.
     ///
     /// The entries are returned in FIFO order.
-    pub fn iter(&self) -> impl Iterator<Item = crate::Result<&[u8]>> {
+    pub fn iter(&self) -> impl Iterator<Item = crate::Result<Cow<[u8]>>> {
         let logs = self.logs();
         logs.into_iter().rev().flat_map(|log| log.iter())
     }
 
     /// Iterate over all dirty entries.
-    pub fn iter_dirty(&mut self) -> impl Iterator<Item = crate::Result<&[u8]>> {
+    pub fn iter_dirty(&mut self) -> impl Iterator<Item = crate::Result<Cow<[u8]>>> {
         self.writable_log().iter_dirty()
     }
 }
@@ -679,7 +762,7 @@ pub struct RotateLogLookupIter<'a> {
 }
 
 impl<'a> Iterator for RotateLogLookupIter<'a> {
-    type Item = crate::Result<&'a [u8]>;
+    type Item = crate::Result<Cow<'a, [u8]>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.end {
@@ -858,29 +941,24 @@ mod tests {
 
         assert!(OpenOptions::new().create(false).open(&path).is_err());
         assert!(OpenOptions::new().create(true).open(&path).is_ok());
-        assert!(
-            OpenOptions::new()
-                .checksum_type(log::ChecksumType::Xxhash64)
-                .create(false)
-                .open(&path)
-                .is_ok()
-        );
+        assert!(OpenOptions::new()
+            .checksum_type(log::ChecksumType::Xxhash64)
+            .create(false)
+            .open(&path)
+            .is_ok());
     }
 
     // lookup via index 0
-    fn lookup<'a>(rotate: &'a RotateLog, key: &[u8]) -> Vec<&'a [u8]> {
+    fn lookup(rotate: &RotateLog, key: &[u8]) -> Vec<Vec<u8>> {
         rotate
             .lookup(0, key.to_vec())
             .unwrap()
-            .collect::<crate::Result<Vec<&[u8]>>>()
-            .unwrap()
+            .map(|v| v.unwrap().into_owned())
+            .collect()
     }
 
-    fn iter(rotate: &RotateLog) -> Vec<&[u8]> {
-        rotate
-            .iter()
-            .collect::<crate::Result<Vec<&[u8]>>>()
-            .unwrap()
+    fn iter(rotate: &RotateLog) -> Vec<Vec<u8>> {
+        rotate.iter().map(|v| v.unwrap().into_owned()).collect()
     }
 
     #[test]
@@ -1140,7 +1218,7 @@ mod tests {
             .max_bytes_per_log(100)
             .flush_filter(Some(|ctx, bytes| {
                 // 'aa' is not inserted yet. It should not exist in the log.
-                assert!(!ctx.log.iter().any(|x| x.unwrap() == b"aa"));
+                assert!(!ctx.log.iter().any(|x| x.unwrap().as_ref() == b"aa"));
                 Ok(match bytes.len() {
                     1 => FlushFilterOutput::Replace(b"xx".to_vec()),
                     _ => FlushFilterOutput::Keep,
@@ -1218,6 +1296,50 @@ mod tests {
         rotate.lookup_latest(0, b"a").unwrap(); // flush_filter is not set
     }
 
+    #[test]
+    fn test_compact() {
+        let dir = tempdir().unwrap();
+        let open = || {
+            OpenOptions::new()
+                .create(true)
+                .max_bytes_per_log(100)
+                .max_log_count(4)
+                .index("first-byte", |_| vec![IndexOutput::Reference(0..1)])
+                .open(&dir)
+                .unwrap()
+        };
+
+        let mut rotate = open();
+        for i in 0..6u8 {
+            rotate.append(vec![b'a' + i % 2; 60]).unwrap();
+            rotate.sync().unwrap();
+        }
+        rotate.append(vec![b'a'; 1]).unwrap();
+        assert_eq!(rotate.logs().len(), 4);
+
+        let mut reader = open();
+        assert_eq!(reader.iter().count(), 6);
+
+        let removed = rotate.compact(|data| Ok(data[0] == b'a')).unwrap();
+        assert_eq!(removed, 3);
+        assert_eq!(
+            iter(&rotate),
+            vec![&[b'a'; 60][..], &[b'a'; 60], &[b'a'; 60], &[b'a'; 1]]
+        );
+        assert_eq!(lookup(&rotate, b"a").len(), 4);
+        assert!(lookup(&rotate, b"b").is_empty());
+
+        // Existing readers are not affected until sync.
+        assert_eq!(reader.iter().count(), 6);
+        reader.sync().unwrap();
+        assert_eq!(lookup(&reader, b"a").len(), 4);
+        assert!(lookup(&reader, b"b").is_empty());
+
+        // New RotateLogs see the compacted logs.
+        let rotate = open();
+        assert_eq!(rotate.iter().count(), 4);
+    }
+
     #[test]
     fn test_iter() {
         let dir = tempdir().unwrap();
@@ -1245,16 +1367,10 @@ mod tests {
             vec![&b[..], &a, &a]
         );
 
-        assert_eq!(
-            rotate.iter().map(|e| e.unwrap()).collect::<Vec<&[u8]>>(),
-            vec![&a[..], &b, &a, &a],
-        );
+        assert_eq!(iter(&rotate), vec![&a[..], &b, &a, &a],);
 
         rotate.sync().unwrap(); // trigger rotate
-        assert_eq!(
-            rotate.iter().map(|e| e.unwrap()).collect::<Vec<&[u8]>>(),
-            vec![&b[..], &a, &a],
-        );
+        assert_eq!(iter(&rotate), vec![&b[..], &a, &a],);
     }
 
     #[test]
@@ -1315,10 +1431,10 @@ mod tests {
         let dir = tempdir().unwrap();
         let opts = OpenOptions::new()
             .create(true)
-            .index_defs(vec![
-                IndexDef::new("idx", |_| vec![IndexOutput::Reference(0..2)])
-                    .lag_threshold(u64::max_value()),
-            ])
+            .index_defs(vec![IndexDef::new("idx", |_| {
+                vec![IndexOutput::Reference(0..2)]
+            })
+            .lag_threshold(u64::max_value())])
             .max_bytes_per_log(100)
             .max_log_count(3);
 
@@ -0,0 +1,200 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use crate::metalog::load_root;
+use crate::metalog::SerId20;
+use crate::CommitOptions;
+use crate::Id20;
+use crate::MetaLog;
+use crate::Result;
+use minibytes::Bytes;
+use std::collections::BTreeSet;
+use xdiff::{diff_unified_headerless, HeaderlessDiffOpts};
+
+/// How the value of a key differs between two roots.
+#[derive(Clone, Debug, PartialEq)]
+pub enum KeyChange {
+    Added(Bytes),
+    Removed(Bytes),
+    Changed { old: Bytes, new: Bytes },
+}
+
+/// A key that differs between two roots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyDiff {
+    pub key: String,
+    pub change: KeyChange,
+}
+
+impl KeyChange {
+    /// The value before the change. Empty if the key was added.
+    pub fn old_value(&self) -> &[u8] {
+        match self {
+            KeyChange::Added(_) => b"",
+            KeyChange::Removed(old) | KeyChange::Changed { old, .. } => old.as_ref(),
+        }
+    }
+
+    /// The value after the change. Empty if the key was removed.
+    pub fn new_value(&self) -> &[u8] {
+        match self {
+            KeyChange::Removed(_) => b"",
+            KeyChange::Added(new) | KeyChange::Changed { new, .. } => new.as_ref(),
+        }
+    }
+}
+
+impl KeyDiff {
+    /// Unified diff (without file headers) of the value.
+    ///
+    /// Return `None` if either side is not text. Values are considered text
+    /// if they are valid UTF-8 and do not contain NUL bytes.
+    pub fn text_diff(&self, context: usize) -> Option<String> {
+        let old = self.change.old_value();
+        let new = self.change.new_value();
+        if !is_text(old) || !is_text(new) {
+            return None;
+        }
+        let diff = diff_unified_headerless(&old, &new, HeaderlessDiffOpts { context });
+        String::from_utf8(diff).ok()
+    }
+}
+
+impl MetaLog {
+    /// Compare the key-value pairs of two roots.
+    ///
+    /// Return keys that are added, removed or changed from `root_a` to
+    /// `root_b`, sorted by key.
+    pub fn diff(&self, root_a: Id20, root_b: Id20) -> Result<Vec<KeyDiff>> {
+        let a = load_root(&self.blobs, root_a)?.map;
+        let b = load_root(&self.blobs, root_b)?.map;
+        let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+        let mut result = Vec::new();
+        for key in keys {
+            let change = match (a.get(key), b.get(key)) {
+                (Some(SerId20(old)), Some(SerId20(new))) if old == new => continue,
+                (Some(SerId20(old)), Some(SerId20(new))) => KeyChange::Changed {
+                    old: self.get_blob(*old)?,
+                    new: self.get_blob(*new)?,
+                },
+                (Some(SerId20(old)), None) => KeyChange::Removed(self.get_blob(*old)?),
+                (None, Some(SerId20(new))) => KeyChange::Added(self.get_blob(*new)?),
+                (None, None) => continue,
+            };
+            result.push(KeyDiff {
+                key: key.clone(),
+                change,
+            });
+        }
+        Ok(result)
+    }
+
+    /// Write a new root that has the same key-value pairs as `root_id`.
+    ///
+    /// The history is preserved: `root_id` and roots after it are not
+    /// removed, and the restored state is appended as the latest root.
+    /// Return the Id20 of the new root, which is the current root if it
+    /// already matches `root_id`.
+    ///
+    /// Fail if there are uncommitted changes.
+    pub fn restore(&mut self, root_id: Id20, options: CommitOptions) -> Result<Id20> {
+        if self.is_dirty() {
+            return Err(self.error("cannot restore with uncommitted changes"));
+        }
+        let root = load_root(&self.blobs, root_id)?;
+        self.root.map = root.map;
+        self.commit(options)
+    }
+
+    fn get_blob(&self, id: Id20) -> Result<Bytes> {
+        self.blobs
+            .get(id)?
+            .ok_or_else(|| self.error(format!("cannot read {:?}", id)))
+    }
+}
+
+fn is_text(data: &[u8]) -> bool {
+    !data.contains(&0) && std::str::from_utf8(data).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn commit_opt(message: &str, timestamp: u64) -> CommitOptions {
+        let mut opts = CommitOptions::default();
+        opts.message = message;
+        opts.timestamp = timestamp;
+        opts
+    }
+
+    #[test]
+    fn test_diff() {
+        let dir = TempDir::new().unwrap();
+        let mut metalog = MetaLog::open(&dir, None).unwrap();
+        metalog.set("a", b"1\n2\n3\n").unwrap();
+        metalog.set("b", b"x").unwrap();
+        metalog.set("c", b"\0").unwrap();
+        let root1 = metalog.commit(commit_opt("commit 1", 1)).unwrap();
+
+        metalog.set("a", b"1\n3\n4\n").unwrap();
+        metalog.remove("b").unwrap();
+        metalog.set("c", b"\0\0").unwrap();
+        metalog.set("d", b"y\n").unwrap();
+        let root2 = metalog.commit(commit_opt("commit 2", 2)).unwrap();
+
+        assert!(metalog.diff(root1, root1).unwrap().is_empty());
+
+        let diff = metalog.diff(root1, root2).unwrap();
+        let keys: Vec<&str> = diff.iter().map(|d| d.key.as_str()).collect();
+        assert_eq!(keys, ["a", "b", "c", "d"]);
+        assert_eq!(diff[1].change, KeyChange::Removed(Bytes::from_static(b"x")));
+        assert_eq!(diff[3].change, KeyChange::Added(Bytes::from_static(b"y\n")));
+        assert_eq!(
+            diff[0].text_diff(1).unwrap(),
+            "@@ -1,3 +1,3 @@\n 1\n-2\n 3\n+4\n"
+        );
+        assert_eq!(diff[2].text_diff(1), None);
+        assert_eq!(diff[3].text_diff(1).unwrap(), "@@ -1,0 +1,1 @@\n+y\n");
+    }
+
+    #[test]
+    fn test_restore() {
+        let dir = TempDir::new().unwrap();
+        let mut metalog = MetaLog::open(&dir, None).unwrap();
+        metalog.set("a", b"1").unwrap();
+        let root1 = metalog.commit(commit_opt("commit 1", 1)).unwrap();
+        metalog.set("a", b"2").unwrap();
+        metalog.set("b", b"2").unwrap();
+        let root2 = metalog.commit(commit_opt("commit 2", 2)).unwrap();
+
+        metalog.set("b", b"3").unwrap();
+        assert!(metalog.restore(root1, commit_opt("", 0)).is_err());
+        metalog.remove("b").unwrap();
+        metalog.set("b", b"2").unwrap();
+
+        let root3 = metalog.restore(root1, commit_opt("restore 1", 3)).unwrap();
+        assert_ne!(root3, root1);
+        assert!(metalog.diff(root1, root3).unwrap().is_empty());
+        assert_eq!(metalog.get("a").unwrap().unwrap(), b"1");
+        assert_eq!(metalog.get("b").unwrap(), None);
+        assert_eq!(metalog.message(), "restore 1");
+        assert_eq!(
+            MetaLog::list_roots(&dir).unwrap()[1..],
+            [root1, root2, root3]
+        );
+
+        // Restoring to the current state is a no-op.
+        let root4 = metalog.restore(root1, commit_opt("restore 1", 4)).unwrap();
+        assert_eq!(root4, root3);
+
+        let metalog = MetaLog::open(&dir, None).unwrap();
+        assert_eq!(metalog.root_id(), root3);
+        assert_eq!(metalog.get("a").unwrap().unwrap(), b"1");
+    }
+}
@@ -9,10 +9,12 @@
 //!
 //! See [`MetaLog`] for the main structure.
 
+mod diff;
 mod errors;
 mod export;
 mod metalog;
 
+pub use crate::diff::{KeyChange, KeyDiff};
 pub use crate::metalog::{resolver, CommitOptions, Id20, MetaLog};
 pub use errors::{Error, Result};
 pub use indexedlog::Repair;
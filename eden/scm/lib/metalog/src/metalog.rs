@@ -58,7 +58,7 @@ pub struct MetaLog {
     pub(crate) orig_root_id: Id20,
 
     /// The current (possibly modified) root.
-    root: Root,
+    pub(crate) root: Root,
 }
 
 /// Options used by the `commit` API.
@@ -131,23 +131,22 @@ impl MetaLog {
             .find(|epoch| fs::create_dir(metalog.path.join(epoch.to_string())).is_ok())
             .ok_or_else(|| "Failed to create compaction directory".to_string())?;
         {
-            // the metalog open (create) happens directly against the *new* store sub-directory,
-            // bypassing the current "pointer" resolution and defeating the write locking
-            // (this function took the needed lock).
-            let mut compact_metalog = Self::open(metalog.path.join(next_epoch.to_string()), None)?;
-            for key in metalog.keys() {
-                if let Some(value) = metalog.get(&key)? {
-                    compact_metalog.set(&key, &value)?;
-                }
+            // Write the new store sub-directory directly, bypassing the current
+            // "pointer" resolution (this function took the needed lock).
+            // Only blobs reachable from the current root are kept, and the
+            // root id is preserved.
+            let store_path = metalog.path.join(next_epoch.to_string());
+            let mut live_ids: Vec<Id20> = metalog.root.map.values().map(|id| id.0).collect();
+            live_ids.push(metalog.orig_root_id);
+            metalog
+                .blobs
+                .gc(&live_ids, store_path.join("blobs"))
+                .with_context(|| "Failed to write compacted metalog blobs")?;
+            let mut log = Self::ilog_open_options().open(store_path.join("roots"))?;
+            if metalog.orig_root_id != *EMPTY_ROOT_ID {
+                log.append(metalog.orig_root_id.as_ref())?;
             }
-            let opts = CommitOptions {
-                timestamp: metalog.root.timestamp,
-                message: &metalog.root.message,
-                ..Default::default()
-            };
-            compact_metalog
-                .commit(opts)
-                .with_context(|| "Failed to commit to compacted metalog")?;
+            log.sync()?;
         }
         indexedlog::utils::atomic_write(
             metalog.path.join("current"),
@@ -175,7 +174,7 @@ impl MetaLog {
         let result = std::iter::once(EMPTY_ROOT_ID.clone())
             .chain(
                 log.iter()
-                    .map(|e| e.ok().and_then(|e| Id20::from_slice(e).ok()))
+                    .map(|e| e.ok().and_then(|e| Id20::from_slice(&e).ok()))
                     .take_while(|s| s.is_some())
                     .map(|s| s.unwrap()),
             )
@@ -415,7 +414,7 @@ fn find_last_root_id(log: &ilog::Log) -> Result<Id20> {
     for entry in log.lookup(INDEX_REVERSE, INDEX_REVERSE_KEY)? {
         // The linked list in the index is in the reversed order.
         // So the first entry contains the last root id.
-        return Ok(Id20::from_slice(entry?)?);
+        return Ok(Id20::from_slice(&entry?)?);
     }
     Ok(EMPTY_ROOT_ID.clone())
 }
@@ -695,6 +694,11 @@ mod tests {
         assert_eq!(metalog2.get("11b").unwrap().unwrap(), b"ij");
         assert_eq!(MetaLog::list_roots(&dir).unwrap().len(), 2);
 
+        // Blobs only reachable from old roots are dropped.
+        assert!(!metalog2.blobs.contains(zstore::sha1(b"ab")).unwrap());
+        assert!(!metalog2.blobs.contains(zstore::sha1(b"ef")).unwrap());
+        assert!(!metalog2.blobs.contains(metalog_stale.orig_root_id).unwrap());
+
         assert_eq!(
             MetaLog::open(&dir, None).unwrap().orig_root_id,
             metalog.orig_root_id
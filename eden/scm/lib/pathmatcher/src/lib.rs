@@ -84,6 +84,155 @@ impl Matcher for NeverMatcher {
     }
 }
 
+/// Matches files and directories matched by both matchers.
+#[derive(Clone)]
+pub struct IntersectMatcher<A, B> {
+    left: A,
+    right: B,
+}
+
+impl<A: Matcher, B: Matcher> IntersectMatcher<A, B> {
+    pub fn new(left: A, right: B) -> Self {
+        IntersectMatcher { left, right }
+    }
+}
+
+impl<A: Matcher, B: Matcher> Matcher for IntersectMatcher<A, B> {
+    fn matches_directory(&self, path: &RepoPath) -> DirectoryMatch {
+        match (
+            self.left.matches_directory(path),
+            self.right.matches_directory(path),
+        ) {
+            (DirectoryMatch::Nothing, _) | (_, DirectoryMatch::Nothing) => DirectoryMatch::Nothing,
+            (DirectoryMatch::Everything, DirectoryMatch::Everything) => DirectoryMatch::Everything,
+            _ => DirectoryMatch::ShouldTraverse,
+        }
+    }
+
+    fn matches_file(&self, path: &RepoPath) -> bool {
+        self.left.matches_file(path) && self.right.matches_file(path)
+    }
+}
+
+/// Matches files and directories matched by the first matcher but not by the second one.
+#[derive(Clone)]
+pub struct DifferenceMatcher<A, B> {
+    include: A,
+    exclude: B,
+}
+
+impl<A: Matcher, B: Matcher> DifferenceMatcher<A, B> {
+    pub fn new(include: A, exclude: B) -> Self {
+        DifferenceMatcher { include, exclude }
+    }
+}
+
+impl<A: Matcher, B: Matcher> Matcher for DifferenceMatcher<A, B> {
+    fn matches_directory(&self, path: &RepoPath) -> DirectoryMatch {
+        match (
+            self.include.matches_directory(path),
+            self.exclude.matches_directory(path),
+        ) {
+            (DirectoryMatch::Nothing, _) | (_, DirectoryMatch::Everything) => {
+                DirectoryMatch::Nothing
+            }
+            (DirectoryMatch::Everything, DirectoryMatch::Nothing) => DirectoryMatch::Everything,
+            _ => DirectoryMatch::ShouldTraverse,
+        }
+    }
+
+    fn matches_file(&self, path: &RepoPath) -> bool {
+        self.include.matches_file(path) && !self.exclude.matches_file(path)
+    }
+}
+
+/// Matches files and directories matched by either matcher.
+#[derive(Clone)]
+pub struct UnionMatcher<A, B> {
+    left: A,
+    right: B,
+}
+
+impl<A: Matcher, B: Matcher> UnionMatcher<A, B> {
+    pub fn new(left: A, right: B) -> Self {
+        UnionMatcher { left, right }
+    }
+}
+
+impl<A: Matcher, B: Matcher> Matcher for UnionMatcher<A, B> {
+    fn matches_directory(&self, path: &RepoPath) -> DirectoryMatch {
+        match (
+            self.left.matches_directory(path),
+            self.right.matches_directory(path),
+        ) {
+            (DirectoryMatch::Everything, _) | (_, DirectoryMatch::Everything) => {
+                DirectoryMatch::Everything
+            }
+            (DirectoryMatch::Nothing, DirectoryMatch::Nothing) => DirectoryMatch::Nothing,
+            _ => DirectoryMatch::ShouldTraverse,
+        }
+    }
+
+    fn matches_file(&self, path: &RepoPath) -> bool {
+        self.left.matches_file(path) || self.right.matches_file(path)
+    }
+}
+
 pub use gitignore_matcher::GitignoreMatcher;
 pub use tree_matcher::TreeMatcher;
 pub use utils::{expand_curly_brackets, normalize_glob, plain_to_glob};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(s: &str) -> &RepoPath {
+        RepoPath::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_intersect_matcher() {
+        let left = TreeMatcher::from_rules(["a/**"].iter()).unwrap();
+        let right = TreeMatcher::from_rules(["a/b/**", "c/**"].iter()).unwrap();
+        let m = IntersectMatcher::new(left, right);
+        assert_eq!(
+            m.matches_directory(path("a")),
+            DirectoryMatch::ShouldTraverse
+        );
+        assert_eq!(m.matches_directory(path("a/b")), DirectoryMatch::Everything);
+        assert_eq!(m.matches_directory(path("c")), DirectoryMatch::Nothing);
+        assert!(m.matches_file(path("a/b/x")));
+        assert!(!m.matches_file(path("a/x")));
+        assert!(!m.matches_file(path("c/x")));
+    }
+
+    #[test]
+    fn test_difference_matcher() {
+        let include = TreeMatcher::from_rules(["a/**"].iter()).unwrap();
+        let exclude = TreeMatcher::from_rules(["a/b/**", "c/**"].iter()).unwrap();
+        let m = DifferenceMatcher::new(include, exclude);
+        assert_eq!(
+            m.matches_directory(path("a")),
+            DirectoryMatch::ShouldTraverse
+        );
+        assert_eq!(m.matches_directory(path("a/b")), DirectoryMatch::Nothing);
+        assert_eq!(m.matches_directory(path("a/c")), DirectoryMatch::Everything);
+        assert_eq!(m.matches_directory(path("c")), DirectoryMatch::Nothing);
+        assert!(m.matches_file(path("a/x")));
+        assert!(!m.matches_file(path("a/b/x")));
+        assert!(!m.matches_file(path("c/x")));
+    }
+
+    #[test]
+    fn test_union_matcher() {
+        let left = TreeMatcher::from_rules(["a/**"].iter()).unwrap();
+        let right = TreeMatcher::from_rules(["a/b/**", "c/**"].iter()).unwrap();
+        let m = UnionMatcher::new(left, right);
+        assert_eq!(m.matches_directory(path("a")), DirectoryMatch::Everything);
+        assert_eq!(m.matches_directory(path("c")), DirectoryMatch::Everything);
+        assert_eq!(m.matches_directory(path("d")), DirectoryMatch::Nothing);
+        assert!(m.matches_file(path("a/x")));
+        assert!(m.matches_file(path("c/x")));
+        assert!(!m.matches_file(path("d/x")));
+    }
+}
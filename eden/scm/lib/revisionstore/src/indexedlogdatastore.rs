@@ -110,7 +110,7 @@ impl Entry {
             Some(buf) => buf?,
         };
 
-        Entry::from_slice(buf).map(Some)
+        Entry::from_slice(&buf).map(Some)
     }
 
     /// Write an entry to the IndexedLog. See [`from_log`] for the detail about the on-disk format.
@@ -286,7 +286,7 @@ impl ToKeys for IndexedLogHgIdDataStore {
             .read()
             .log
             .iter()
-            .map(|entry| Entry::from_slice(entry?))
+            .map(|entry| Entry::from_slice(&entry?))
             .map(|entry| Ok(entry?.key))
             .collect()
     }
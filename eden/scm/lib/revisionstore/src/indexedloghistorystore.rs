@@ -148,7 +148,7 @@ impl Entry {
             Some(buf) => buf?,
         };
 
-        Self::from_slice(buf).map(Some)
+        Self::from_slice(&buf).map(Some)
     }
 
     /// Write an entry to the `IndexedLog`. See [`from_slice`] for the detail about the on-disk
@@ -274,7 +274,7 @@ impl ToKeys for IndexedLogHgIdHistoryStore {
             .unwrap()
             .log
             .iter()
-            .map(|entry| Entry::from_slice(entry?))
+            .map(|entry| Entry::from_slice(&entry?))
             .map(|entry| Ok(entry?.key))
             .collect()
     }
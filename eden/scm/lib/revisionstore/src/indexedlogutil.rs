@@ -5,7 +5,7 @@
  * GNU General Public License version 2.
  */
 
-use std::path::Path;
+use std::{borrow::Cow, path::Path};
 
 use anyhow::Result;
 
@@ -71,7 +71,7 @@ pub enum LookupIter<'a> {
 }
 
 impl<'a> Iterator for LookupIter<'a> {
-    type Item = Result<&'a [u8]>;
+    type Item = Result<Cow<'a, [u8]>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self {
@@ -186,7 +186,7 @@ mod tests {
 
         assert_eq!(
             store.lookup(0, b"aa")?.collect::<Result<Vec<_>>>()?,
-            vec![b"aabcd"]
+            vec![Cow::Borrowed(&b"aabcd"[..])]
         );
         Ok(())
     }
@@ -203,7 +203,7 @@ mod tests {
 
         assert_eq!(
             store.lookup(0, b"aa")?.collect::<Result<Vec<_>>>()?,
-            vec![b"aabcd"]
+            vec![Cow::Borrowed(&b"aabcd"[..])]
         );
         Ok(())
     }
@@ -235,7 +235,7 @@ impl LfsPointersStore {
             Some(buf) => buf?,
         };
 
-        Self::get_from_slice(buf).map(Some)
+        Self::get_from_slice(&buf).map(Some)
     }
 
     /// Find the pointer corresponding to the passed in `Key`.
@@ -304,7 +304,7 @@ impl LfsIndexedLogBlobsStore {
         let store = self.inner.read();
         let chunks_iter = store
             .lookup(0, hash)?
-            .map(|data| Ok(deserialize::<LfsIndexedLogBlobsEntry>(data?)?));
+            .map(|data| Ok(deserialize::<LfsIndexedLogBlobsEntry>(&data?)?));
 
         // Filter errors. It's possible that one entry is corrupted, or for whatever reason can't
         // be deserialized, whenever this blob/entry is refetched, the corrupted entry will still be
@@ -8,8 +8,9 @@
 use crate::log;
 use indexmap::{IndexMap, IndexSet};
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::hash::Hash;
 use std::io;
@@ -1715,6 +1716,426 @@ impl<S: Eq + Hash> TreeSpan<S> {
     }
 }
 
+// -------- Convert to OpenTelemetry (OTLP JSON) --------
+
+/// Top-level object of OTLP JSON (`ExportTraceServiceRequest`).
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OtlpTraces<'a> {
+    resource_spans: Vec<OtlpResourceSpans<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpResourceSpans<'a> {
+    resource: OtlpResource<'a>,
+    scope_spans: Vec<OtlpScopeSpans<'a>>,
+}
+
+#[derive(Serialize)]
+struct OtlpResource<'a> {
+    attributes: Vec<OtlpKeyValue<'a>>,
+}
+
+#[derive(Serialize)]
+struct OtlpScopeSpans<'a> {
+    scope: OtlpScope,
+    spans: Vec<OtlpSpan<'a>>,
+}
+
+#[derive(Serialize)]
+struct OtlpScope {
+    name: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpSpan<'a> {
+    trace_id: String,
+    span_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_span_id: Option<String>,
+    name: &'a str,
+    /// `SPAN_KIND_INTERNAL`.
+    kind: u8,
+    start_time_unix_nano: String,
+    end_time_unix_nano: String,
+    attributes: Vec<OtlpKeyValue<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    events: Vec<OtlpEvent<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpEvent<'a> {
+    time_unix_nano: String,
+    name: &'a str,
+    attributes: Vec<OtlpKeyValue<'a>>,
+}
+
+#[derive(Serialize)]
+struct OtlpKeyValue<'a> {
+    key: Cow<'a, str>,
+    value: OtlpAnyValue<'a>,
+}
+
+/// Note: OTLP JSON encodes 64-bit integers as strings.
+#[derive(Serialize)]
+enum OtlpAnyValue<'a> {
+    #[serde(rename = "stringValue")]
+    String(Cow<'a, str>),
+    #[serde(rename = "intValue")]
+    Int(String),
+    #[serde(rename = "boolValue")]
+    Bool(bool),
+}
+
+impl<'a> OtlpKeyValue<'a> {
+    fn new(key: impl Into<Cow<'a, str>>, value: OtlpAnyValue<'a>) -> Self {
+        Self {
+            key: key.into(),
+            value,
+        }
+    }
+}
+
+impl TracingData {
+    /// Write OTLP JSON that can be imported by OpenTelemetry tools (ex. the
+    /// "otlpjsonfile" receiver of the OpenTelemetry Collector).
+    ///
+    /// See https://opentelemetry.io/docs/specs/otlp/#json-protobuf-encoding.
+    pub fn write_otlp_json(
+        &self,
+        out: &mut dyn io::Write,
+        resource_attributes: HashMap<String, String>,
+    ) -> Result<(), serde_json::Error> {
+        serde_json::to_writer(out, &self.otlp(resource_attributes))
+    }
+
+    /// Convert to OTLP traces.
+    ///
+    /// Each process becomes a "resource" with `resource_attributes` and
+    /// `process.pid`. All spans share a trace id. A span's parent is the
+    /// innermost span covering it in the same thread. Events are attached to
+    /// their parent spans, or become zero-duration spans if they are not
+    /// inside any span.
+    pub fn otlp(&self, resource_attributes: HashMap<String, String>) -> OtlpTraces {
+        let base_nanos = self.unix_micros_base() * 1000;
+        let trace_id = format!("{:016x}{:016x}", base_nanos, self.default_process_id);
+        let mut resource_attributes: Vec<_> = resource_attributes.into_iter().collect();
+        resource_attributes.sort();
+
+        let mut next_span_id = 1u64;
+        let mut spans_by_pid = IndexMap::<u64, Vec<OtlpSpan>>::new();
+        for (&(pid, tid), eventus_list) in self.eventus_group_by_pid_tid().iter() {
+            let spans = spans_by_pid.entry(pid).or_default();
+            let tree_spans = self.build_tree_spans(eventus_list);
+            let last_timestamp = eventus_list.last().map(|e| e.timestamp.0).unwrap_or(0);
+
+            // (tree span index, index of the parent in `spans`)
+            let mut to_visit: Vec<(RawTreeSpanId, Option<usize>)> = tree_spans[0]
+                .children
+                .iter()
+                .rev()
+                .map(|&id| (id, None))
+                .collect();
+            while let Some((id, parent)) = to_visit.pop() {
+                let tree_span = &tree_spans[id];
+                let espan = match tree_span.espan_id.and_then(|id| self.get_espan(id)) {
+                    Some(espan) => espan,
+                    None => continue,
+                };
+                let mut name = "(unnamed)";
+                let mut attributes = Vec::with_capacity(espan.meta.len() + 1);
+                for (key, value) in espan.meta.iter() {
+                    let key = self.strings.get(*key);
+                    let value = self.strings.get(*value);
+                    if key == "name" {
+                        name = value;
+                    } else {
+                        attributes.push(OtlpKeyValue::new(key, OtlpAnyValue::String(value.into())));
+                    }
+                }
+                let start = base_nanos + tree_span.start_time * 1000;
+
+                if tree_span.is_event {
+                    if let Some(parent) = parent {
+                        spans[parent].events.push(OtlpEvent {
+                            time_unix_nano: start.to_string(),
+                            name,
+                            attributes,
+                        });
+                        to_visit.extend(
+                            tree_span
+                                .children
+                                .iter()
+                                .rev()
+                                .map(|&id| (id, Some(parent))),
+                        );
+                        continue;
+                    }
+                }
+
+                let end = if tree_span.is_incomplete() {
+                    attributes.push(OtlpKeyValue::new("incomplete", OtlpAnyValue::Bool(true)));
+                    base_nanos + last_timestamp * 1000
+                } else {
+                    start + tree_span.duration * 1000
+                };
+                attributes.push(OtlpKeyValue::new(
+                    "thread.id",
+                    OtlpAnyValue::Int(tid.to_string()),
+                ));
+                spans.push(OtlpSpan {
+                    trace_id: trace_id.clone(),
+                    span_id: format!("{:016x}", next_span_id),
+                    parent_span_id: parent.map(|i| spans[i].span_id.clone()),
+                    name,
+                    kind: 1,
+                    start_time_unix_nano: start.to_string(),
+                    end_time_unix_nano: end.to_string(),
+                    attributes,
+                    events: Vec::new(),
+                });
+                next_span_id += 1;
+                let index = spans.len() - 1;
+                to_visit.extend(tree_span.children.iter().rev().map(|&id| (id, Some(index))));
+            }
+        }
+
+        let resource_spans = spans_by_pid
+            .into_iter()
+            .map(|(pid, spans)| {
+                let mut attributes: Vec<_> = resource_attributes
+                    .iter()
+                    .map(|(k, v)| {
+                        OtlpKeyValue::new(k.clone(), OtlpAnyValue::String(v.clone().into()))
+                    })
+                    .collect();
+                attributes.push(OtlpKeyValue::new(
+                    "process.pid",
+                    OtlpAnyValue::Int(pid.to_string()),
+                ));
+                OtlpResourceSpans {
+                    resource: OtlpResource { attributes },
+                    scope_spans: vec![OtlpScopeSpans {
+                        scope: OtlpScope {
+                            name: "tracing-collector",
+                        },
+                        spans,
+                    }],
+                }
+            })
+            .collect();
+
+        OtlpTraces { resource_spans }
+    }
+
+    /// Start time in microseconds since epoch. 0 if the fake clock is used.
+    fn unix_micros_base(&self) -> u64 {
+        if self.test_clock_step > 0 {
+            0
+        } else {
+            self.start
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_micros() as u64)
+                .unwrap_or(0)
+        }
+    }
+}
+
+// -------- Profiles (folded stacks, pprof) --------
+
+/// Calls and time spent in spans, aggregated by stacks of span names.
+#[derive(Default, Clone, Copy, Debug, PartialEq)]
+struct StackStat {
+    calls: u64,
+    /// Time spent in the span itself, excluding child spans, in microseconds.
+    self_micros: u64,
+}
+
+impl TracingData {
+    /// Generate "folded stacks" that can be used by flamegraph.pl,
+    /// speedscope, or inferno.
+    ///
+    /// Each line contains span names from the outermost to the innermost,
+    /// separated by `;`, followed by the time spent in the innermost span
+    /// (excluding its child spans) in microseconds. Spans with the same name
+    /// in the same stack are aggregated, across threads and processes.
+    pub fn folded_stacks(&self) -> String {
+        let mut out = String::new();
+        for (stack, stat) in self.stack_stats() {
+            if stat.self_micros > 0 {
+                out += &format!("{} {}\n", stack.join(";"), stat.self_micros);
+            }
+        }
+        out
+    }
+
+    /// Write a pprof profile (uncompressed `profile.proto`), aggregated like
+    /// [`TracingData::folded_stacks`].
+    ///
+    /// The sample types are "calls" (count) and "wall" (microseconds). Use
+    /// `go tool pprof` or other compatible tools to view it.
+    pub fn write_pprof(&self, out: &mut dyn io::Write) -> io::Result<()> {
+        // See https://github.com/google/pprof/blob/master/proto/profile.proto.
+        let mut strings = InternedStrings::default();
+        strings.id("");
+        let mut value_type = |ty: &str, unit: &str| {
+            let mut msg = ProtoBuf::default();
+            msg.uint(1, strings.id(ty).0);
+            msg.uint(2, strings.id(unit).0);
+            msg
+        };
+        let mut profile = ProtoBuf::default();
+        profile.message(1, value_type("calls", "count"));
+        profile.message(1, value_type("wall", "microseconds"));
+        let period_type = value_type("wall", "microseconds");
+
+        // Functions and locations share ids: one per span name.
+        let mut function_ids = IndexMap::<String, u64>::new();
+        for (stack, stat) in self.stack_stats() {
+            let location_ids: Vec<u64> = stack
+                .iter()
+                .rev()
+                .map(|name| {
+                    let next_id = function_ids.len() as u64 + 1;
+                    *function_ids.entry(name.clone()).or_insert(next_id)
+                })
+                .collect();
+            let mut sample = ProtoBuf::default();
+            sample.packed(1, location_ids);
+            sample.packed(2, vec![stat.calls, stat.self_micros]);
+            profile.message(2, sample);
+        }
+        for id in function_ids.values() {
+            let mut line = ProtoBuf::default();
+            line.uint(1, *id);
+            let mut location = ProtoBuf::default();
+            location.uint(1, *id);
+            location.message(4, line);
+            profile.message(4, location);
+        }
+        for (name, id) in function_ids.iter() {
+            let mut function = ProtoBuf::default();
+            function.uint(1, *id);
+            function.uint(2, strings.id(name).0);
+            profile.message(5, function);
+        }
+        for s in strings.0.iter() {
+            profile.bytes(6, s.as_bytes());
+        }
+        let end_micros = self.eventus.last().map(|e| e.timestamp.0).unwrap_or(0);
+        profile.uint(9, self.unix_micros_base() * 1000);
+        profile.uint(10, end_micros * 1000);
+        profile.message(11, period_type);
+        profile.uint(12, 1);
+
+        out.write_all(&profile.0)
+    }
+
+    /// Aggregate spans by stacks of span names.
+    fn stack_stats(&self) -> BTreeMap<Vec<String>, StackStat> {
+        let mut result = BTreeMap::<Vec<String>, StackStat>::new();
+        for eventus_list in self.eventus_group_by_pid_tid().values() {
+            let tree_spans = self.build_tree_spans(eventus_list);
+            let last_timestamp = eventus_list.last().map(|e| e.timestamp.0).unwrap_or(0);
+            let duration = |span: &RawTreeSpan| -> u64 {
+                if span.is_event {
+                    0
+                } else if span.is_incomplete() {
+                    last_timestamp.saturating_sub(span.start_time)
+                } else {
+                    span.duration
+                }
+            };
+
+            // (tree span index, stack of names)
+            let mut to_visit: Vec<(RawTreeSpanId, Vec<String>)> = tree_spans[0]
+                .children
+                .iter()
+                .map(|&id| (id, Vec::new()))
+                .collect();
+            while let Some((id, mut stack)) = to_visit.pop() {
+                let tree_span = &tree_spans[id];
+                if tree_span.is_event {
+                    to_visit.extend(tree_span.children.iter().map(|&id| (id, stack.clone())));
+                    continue;
+                }
+                let name = tree_span
+                    .espan_id
+                    .and_then(|id| self.get_espan(id))
+                    .and_then(|espan| {
+                        espan.meta.iter().find_map(|(k, v)| {
+                            if self.strings.get(*k) == "name" {
+                                Some(self.strings.get(*v))
+                            } else {
+                                None
+                            }
+                        })
+                    })
+                    .unwrap_or("(unnamed)");
+                // ";" separates frames. Newlines separate stacks.
+                stack.push(name.replace(';', ":").replace('\n', " "));
+                let children_micros: u64 = tree_span
+                    .children
+                    .iter()
+                    .map(|&id| duration(&tree_spans[id]))
+                    .sum();
+                let stat = result.entry(stack.clone()).or_default();
+                stat.calls += 1;
+                stat.self_micros += duration(tree_span).saturating_sub(children_micros);
+                to_visit.extend(tree_span.children.iter().map(|&id| (id, stack.clone())));
+            }
+        }
+        result
+    }
+}
+
+/// Minimal protobuf encoder.
+#[derive(Default)]
+struct ProtoBuf(Vec<u8>);
+
+impl ProtoBuf {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn key(&mut self, field: u64, wire_type: u64) {
+        self.varint((field << 3) | wire_type);
+    }
+
+    /// Write a varint field. Zero (the default value) is omitted.
+    fn uint(&mut self, field: u64, value: u64) {
+        if value != 0 {
+            self.key(field, 0);
+            self.varint(value);
+        }
+    }
+
+    fn bytes(&mut self, field: u64, data: &[u8]) {
+        self.key(field, 2);
+        self.varint(data.len() as u64);
+        self.0.extend_from_slice(data);
+    }
+
+    fn message(&mut self, field: u64, message: ProtoBuf) {
+        self.bytes(field, &message.0);
+    }
+
+    fn packed(&mut self, field: u64, values: Vec<u64>) {
+        let mut packed = ProtoBuf::default();
+        for value in values {
+            packed.varint(value);
+        }
+        self.bytes(field, &packed.0);
+    }
+}
+
 // -------- Tests --------
 
 #[cfg(test)]
@@ -2131,4 +2552,139 @@ Start Dur.ms | Name
         assert_eq!(data1.ascii(&Default::default()), "");
         assert_eq!(data2.ascii(&Default::default()), "");
     }
+
+    fn profile_test_data() -> TracingData {
+        let mut data = TracingData::new_for_test();
+        let foo = data.add_espan(&meta("foo", "a.py", "10"), None);
+        let bar = data.add_espan(&meta("bar", "a.py", "20"), None);
+        let baz = data.add_espan(&meta("baz", "a.py", "30"), None);
+        let qux = data.add_espan(&meta("qux", "a.py", "40"), None);
+        let inc = data.add_espan(&meta("inc", "a.py", "50"), None);
+        data.add_action(foo, Action::EnterSpan);
+        data.add_action(bar, Action::EnterSpan);
+        data.add_action(bar, Action::ExitSpan);
+        data.add_action(baz, Action::Event);
+        data.add_action(foo, Action::ExitSpan);
+        data.add_action(qux, Action::Event);
+        data.add_action(inc, Action::EnterSpan);
+        data.add_action(bar, Action::EnterSpan);
+        data.add_action(bar, Action::ExitSpan);
+        data
+    }
+
+    #[test]
+    fn test_otlp() {
+        let data = profile_test_data();
+        let mut resource_attributes = HashMap::new();
+        resource_attributes.insert("service.name".to_string(), "hg".to_string());
+        let value = serde_json::to_value(data.otlp(resource_attributes)).unwrap();
+
+        let resource_spans = value["resourceSpans"].as_array().unwrap();
+        assert_eq!(resource_spans.len(), 1);
+        assert_eq!(
+            resource_spans[0]["resource"]["attributes"][0].to_string(),
+            r#"{"key":"service.name","value":{"stringValue":"hg"}}"#
+        );
+        assert_eq!(
+            resource_spans[0]["resource"]["attributes"][1]["value"]["intValue"],
+            data.process_id().to_string()
+        );
+
+        let spans = resource_spans[0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap();
+        let summary: Vec<String> = spans
+            .iter()
+            .map(|s| {
+                format!(
+                    "{} {} {} {}-{} events={}",
+                    s["name"].as_str().unwrap(),
+                    s["spanId"].as_str().unwrap(),
+                    s["parentSpanId"].as_str().unwrap_or("-"),
+                    s["startTimeUnixNano"].as_str().unwrap(),
+                    s["endTimeUnixNano"].as_str().unwrap(),
+                    s["events"],
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                r#"foo 0000000000000001 - 2000000-10000000 events=[{"attributes":[{"key":"module_path","value":{"stringValue":"a.py"}},{"key":"line","value":{"stringValue":"30"}}],"name":"baz","timeUnixNano":"8000000"}]"#,
+                "bar 0000000000000002 0000000000000001 4000000-6000000 events=null",
+                "qux 0000000000000003 - 12000000-12000000 events=null",
+                "inc 0000000000000004 - 14000000-18000000 events=null",
+                "bar 0000000000000005 0000000000000004 16000000-18000000 events=null",
+            ]
+        );
+        assert!(spans.iter().all(|s| s["traceId"] == spans[0]["traceId"]));
+        assert_eq!(spans[0]["traceId"].as_str().unwrap().len(), 32);
+        assert_eq!(
+            spans[3]["attributes"].to_string(),
+            format!(
+                r#"[{{"key":"module_path","value":{{"stringValue":"a.py"}}}},{{"key":"line","value":{{"stringValue":"50"}}}},{{"key":"incomplete","value":{{"boolValue":true}}}},{{"key":"thread.id","value":{{"intValue":"{}"}}}}]"#,
+                data.default_thread_id
+            )
+        );
+    }
+
+    #[test]
+    fn test_otlp_merged_processes() {
+        let mut data1 = TracingData::new_for_test();
+        let mut data2 = TracingData::new_for_test();
+        data2.default_process_id = data1.default_process_id + 1;
+        let span_id1 = data1.add_espan(&meta("foo", "a.py", "10"), None);
+        let span_id2 = data2.add_espan(&meta("bar", "b.py", "20"), None);
+        data1.add_action(span_id1, Action::EnterSpan);
+        data1.add_action(span_id1, Action::ExitSpan);
+        data2.add_action(span_id2, Action::EnterSpan);
+        data2.add_action(span_id2, Action::ExitSpan);
+        let pids = [data1.process_id(), data2.process_id()];
+
+        let data = TracingData::merge(vec![data1, data2]);
+        let value = serde_json::to_value(data.otlp(Default::default())).unwrap();
+        let resource_spans = value["resourceSpans"].as_array().unwrap();
+        assert_eq!(resource_spans.len(), 2);
+        for (resource_span, pid) in resource_spans.iter().zip(pids.iter()) {
+            assert_eq!(
+                resource_span["resource"]["attributes"][0]["value"]["intValue"],
+                pid.to_string()
+            );
+            assert_eq!(
+                resource_span["scopeSpans"][0]["spans"]
+                    .as_array()
+                    .unwrap()
+                    .len(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn test_folded_stacks() {
+        let data = profile_test_data();
+        assert_eq!(
+            data.folded_stacks(),
+            "foo 6000\nfoo;bar 2000\ninc 2000\ninc;bar 2000\n"
+        );
+    }
+
+    #[test]
+    fn test_pprof() {
+        let data = profile_test_data();
+        let mut out = Vec::new();
+        data.write_pprof(&mut out).unwrap();
+
+        let contains = |needle: &[u8]| out.windows(needle.len()).any(|w| w == needle);
+
+        // sample_type: {type: "calls", unit: "count"}
+        assert!(out.starts_with(b"\x0a\x04\x08\x01\x10\x02"));
+        // sample: {location_id: [1 (foo)], value: [1, 6000]}
+        assert!(contains(b"\x12\x08\x0a\x01\x01\x12\x03\x01\xf0\x2e"));
+        // sample: {location_id: [2 (bar), 1 (foo)], value: [1, 2000]}
+        assert!(contains(b"\x12\x09\x0a\x02\x02\x01\x12\x03\x01\xd0\x0f"));
+        for name in &["foo", "bar", "inc", "calls", "wall", "microseconds"] {
+            assert!(contains(name.as_bytes()));
+        }
+    }
 }
@@ -97,10 +97,16 @@ pub struct FileStateV2 {
 
     /// Size of the file.  Mercurial uses negative sizes for special values, so this must be
     /// signed.
-    pub size: i32,
+    pub size: i64,
 
-    /// Modification time of the file.
-    pub mtime: i32,
+    /// Modification time of the file, in seconds.  Mercurial uses negative mtimes for special
+    /// values, so this must be signed.
+    pub mtime: i64,
+
+    /// Nanoseconds part of the modification time.  0 means the sub-second part is unknown, for
+    /// example, because the entry was written by an older version, or the filesystem only
+    /// provides second-granularity timestamps.
+    pub mtime_nsec: u32,
 
     /// State of the file.
     pub state: StateFlags,
@@ -125,6 +131,7 @@ impl rand::distributions::Distribution<FileStateV2> for rand::distributions::Sta
         let mode = rng.gen();
         let size = rng.gen();
         let mtime = rng.gen();
+        let mtime_nsec = rng.gen_range(0, 1_000_000_000);
         let state = StateFlags::from_bits_truncate(rng.gen());
         let copied = if state.contains(StateFlags::COPIED) {
             Some(b"copied_source".to_vec().into_boxed_slice())
@@ -135,6 +142,7 @@ impl rand::distributions::Distribution<FileStateV2> for rand::distributions::Sta
             mode,
             size,
             mtime,
+            mtime_nsec,
             state,
             copied,
         }
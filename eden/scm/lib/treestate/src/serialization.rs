@@ -92,12 +92,18 @@ impl Serializable for Box<[u8]> {
     }
 }
 
+/// Marker bit in the serialized state flags of a `FileStateV2` entry.  Entries with this bit set
+/// are followed by 64-bit size and mtime fields and the nanoseconds part of mtime.  Entries
+/// written by older versions do not have it, and use 32-bit size and mtime fields.
+const EXTENDED_FORMAT: u16 = 0x8000;
+
 impl Serializable for FileStateV2 {
     fn serialize(&self, w: &mut dyn Write) -> Result<()> {
-        w.write_vlq(self.state.to_bits())?;
+        w.write_vlq(self.state.to_bits() | EXTENDED_FORMAT)?;
         w.write_vlq(self.mode)?;
         w.write_vlq(self.size)?;
         w.write_vlq(self.mtime)?;
+        w.write_vlq(self.mtime_nsec)?;
 
         if self.state.contains(StateFlags::COPIED) {
             if let &Some(ref copied) = &self.copied {
@@ -110,11 +116,16 @@ impl Serializable for FileStateV2 {
     }
 
     fn deserialize(r: &mut dyn Read) -> Result<FileStateV2> {
-        let state: u16 = r.read_vlq()?;
-        let state = StateFlags::from_bits_truncate(state);
+        let bits: u16 = r.read_vlq()?;
+        let state = StateFlags::from_bits_truncate(bits);
         let mode = r.read_vlq()?;
-        let size = r.read_vlq()?;
-        let mtime = r.read_vlq()?;
+        let (size, mtime, mtime_nsec) = if bits & EXTENDED_FORMAT != 0 {
+            (r.read_vlq()?, r.read_vlq()?, r.read_vlq()?)
+        } else {
+            let size: i32 = r.read_vlq()?;
+            let mtime: i32 = r.read_vlq()?;
+            (size as i64, mtime as i64, 0)
+        };
         let copied = if state.contains(StateFlags::COPIED) {
             Some(Box::<[u8]>::deserialize(r)?)
         } else {
@@ -126,6 +137,7 @@ impl Serializable for FileStateV2 {
             mode,
             size,
             mtime,
+            mtime_nsec,
             copied,
         })
     }
@@ -257,7 +269,7 @@ impl Serializable for TreeStateRoot {
 
         let mut cur = Cursor::new(buf);
         let version = cur.read_vlq()?;
-        if version != 0 {
+        if version > TreeStateRoot::CURRENT_VERSION {
             bail!(ErrorKind::UnsupportedTreeVersion(version));
         }
 
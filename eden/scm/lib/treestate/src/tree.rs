@@ -360,6 +360,18 @@ where
         self.write_entries(store)
     }
 
+    /// Load this node and all of its children, and mark them as modified, so the next delta
+    /// write writes all of them again.
+    fn mark_all_changed(&mut self, store: &dyn StoreView) -> Result<()> {
+        for (_name, entry) in self.load_entries(store)?.iter_mut() {
+            if let &mut NodeEntry::Directory(ref mut node) = entry {
+                node.mark_all_changed(store)?;
+            }
+        }
+        self.id = None;
+        Ok(())
+    }
+
     /// Perform a delta write of the node and its children to the store.  Entries that are
     /// already in the store will not be written again.
     fn write_delta<S: Store + StoreView>(&mut self, store: &mut S) -> Result<()> {
@@ -926,6 +938,11 @@ where
         Ok(self.root.id.unwrap())
     }
 
+    /// Mark all nodes as modified, so the next `write_delta` rewrites the entire tree.
+    pub fn mark_all_changed(&mut self, store: &dyn StoreView) -> Result<()> {
+        self.root.mark_all_changed(store)
+    }
+
     pub fn get<'a>(&'a mut self, store: &dyn StoreView, name: KeyRef) -> Result<Option<&'a T>> {
         Ok(self.root.get(store, name)?)
     }
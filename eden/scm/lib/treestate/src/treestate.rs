@@ -25,7 +25,6 @@ pub struct TreeState {
 }
 
 /// `TreeStateRoot` contains block id to the root `Tree`, and other metadata.
-#[derive(Default)]
 pub(crate) struct TreeStateRoot {
     pub version: u32,
     pub file_count: u32,
@@ -33,6 +32,27 @@ pub(crate) struct TreeStateRoot {
     pub metadata: Box<[u8]>,
 }
 
+impl TreeStateRoot {
+    /// Version of newly written trees.
+    ///
+    /// - 0: `FileStateV2` entries have 32-bit size and mtime.
+    /// - 1: `FileStateV2` entries have 64-bit size and mtime, and the nanoseconds part of mtime.
+    ///
+    /// Version 0 trees are upgraded to version 1 when they are written.
+    pub const CURRENT_VERSION: u32 = 1;
+}
+
+impl Default for TreeStateRoot {
+    fn default() -> Self {
+        TreeStateRoot {
+            version: TreeStateRoot::CURRENT_VERSION,
+            file_count: 0,
+            tree_block_id: BlockId::default(),
+            metadata: Box::default(),
+        }
+    }
+}
+
 impl TreeState {
     /// Read `TreeState` from a file, or create an empty new `TreeState` if `root_id` is None.
     pub fn open<P: AsRef<Path>>(path: P, root_id: Option<BlockId>) -> Result<Self> {
@@ -57,6 +77,11 @@ impl TreeState {
 
     /// Flush dirty entries. Return new `root_id` that can be passed to `open`.
     pub fn flush(&mut self) -> Result<BlockId> {
+        if self.root.version < TreeStateRoot::CURRENT_VERSION {
+            // Entries written in an older format cannot be referred to by a newer root. Rewrite
+            // all of them.
+            self.tree.mark_all_changed(&self.store)?;
+        }
         let tree_block_id = { self.tree.write_delta(&mut self.store)? };
         self.write_root(tree_block_id)
     }
@@ -71,6 +96,7 @@ impl TreeState {
     }
 
     fn write_root(&mut self, tree_block_id: BlockId) -> Result<BlockId> {
+        self.root.version = TreeStateRoot::CURRENT_VERSION;
         self.root.tree_block_id = tree_block_id;
         self.root.file_count = self.len() as u32;
 
@@ -297,6 +323,84 @@ mod tests {
         assert_eq!(state.len(), SAMPLE_PATHS.len());
     }
 
+    #[test]
+    fn test_large_size_and_mtime() {
+        let dir = TempDir::new("treestate").expect("tempdir");
+        let mut state = TreeState::open(dir.path().join("1"), None).expect("open");
+        let file = FileStateV2 {
+            mode: 0o100644,
+            size: 5 << 32,
+            mtime: 4_000_000_000,
+            mtime_nsec: 999_999_999,
+            state: StateFlags::EXIST_P1 | StateFlags::EXIST_NEXT,
+            copied: None,
+        };
+        state.insert(b"a/b", &file).expect("insert");
+        let block_id = state.flush().expect("flush");
+        let mut state = TreeState::open(dir.path().join("1"), block_id.into()).expect("open");
+        assert_eq!(state.get(b"a/b").unwrap().unwrap(), &file);
+    }
+
+    #[test]
+    fn test_legacy_entry_format() {
+        use vlqencoding::VLQEncode;
+
+        let mut buf = Vec::new();
+        buf.write_vlq(StateFlags::EXIST_P1.to_bits()).unwrap();
+        buf.write_vlq(0o100644u32).unwrap();
+        buf.write_vlq(-1i32).unwrap();
+        buf.write_vlq(-2i32).unwrap();
+        let file = FileStateV2::deserialize(&mut Cursor::new(buf)).expect("deserialize");
+        assert_eq!(
+            file,
+            FileStateV2 {
+                mode: 0o100644,
+                size: -1,
+                mtime: -2,
+                mtime_nsec: 0,
+                state: StateFlags::EXIST_P1,
+                copied: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_upgrade_legacy_version() {
+        let dir = TempDir::new("treestate").expect("tempdir");
+        let path = dir.path().join("1");
+        let mut state = new_treestate(&path);
+        state.set_metadata(b"foobar");
+        state.flush().expect("flush");
+
+        // Write a version 0 root pointing to the same tree.
+        let legacy_root = TreeStateRoot {
+            version: 0,
+            file_count: state.root.file_count,
+            tree_block_id: state.root.tree_block_id,
+            metadata: state.root.metadata.clone(),
+        };
+        let mut root_buf = Vec::new();
+        legacy_root.serialize(&mut root_buf).expect("serialize");
+        let root_id = state.store.append(&root_buf).expect("append");
+        state.store.flush().expect("flush");
+
+        let mut state = TreeState::open(&path, root_id.into()).expect("open");
+        assert_eq!(state.root.version, 0);
+        let block_id = state.flush().expect("flush");
+        assert_eq!(state.root.version, TreeStateRoot::CURRENT_VERSION);
+        assert_ne!(state.root.tree_block_id, legacy_root.tree_block_id);
+
+        let mut state = TreeState::open(&path, block_id.into()).expect("open");
+        assert_eq!(state.root.version, TreeStateRoot::CURRENT_VERSION);
+        assert_eq!(state.get_metadata(), b"foobar");
+        let mut rng = ChaChaRng::from_seed([0; 32]);
+        for path in &SAMPLE_PATHS {
+            let file: FileStateV2 = rng.gen();
+            assert_eq!(state.get(path).unwrap().unwrap(), &file);
+        }
+        assert_eq!(state.len(), SAMPLE_PATHS.len());
+    }
+
     #[test]
     fn test_has_dir() {
         let dir = TempDir::new("treestate").expect("tempdir");
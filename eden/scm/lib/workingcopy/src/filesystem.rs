@@ -6,71 +6,144 @@
  */
 
 use std::{
-    collections::HashSet,
+    collections::{BTreeSet, HashSet},
     convert::{TryFrom, TryInto},
     fs::Metadata,
     path::PathBuf,
     sync::Arc,
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 
 use anyhow::{Error, Result};
 use parking_lot::Mutex;
 
-use pathmatcher::Matcher;
-use treestate::filestate::StateFlags;
+use pathmatcher::{DirectoryMatch, Matcher};
+use treestate::filestate::{FileStateV2, StateFlags};
 use treestate::tree::VisitorResult;
 use treestate::treestate::TreeState;
 use types::{RepoPath, RepoPathBuf};
 use vfs::{is_executable, is_symlink, VFS};
 
+use crate::journal::{ChangeJournal, JournalCursor};
 use crate::walker::{WalkEntry, WalkError, Walker};
 
-/// Represents a file modification time in Mercurial, in seconds since the unix epoch.
-#[derive(PartialEq)]
-pub struct HgModifiedTime(u64);
+/// How long to wait for the watcher to record recent changes before falling back to a full walk.
+const JOURNAL_SYNC_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How far apart two nanosecond-precision modification times must be for a write at the later
+/// time to be guaranteed to produce a different mtime. This covers filesystems with coarse
+/// timestamp granularity and clocks that are updated once per scheduler tick.
+const MTIME_GRANULARITY_NSEC: u64 = 20_000_000;
+
+/// Represents a file modification time in Mercurial, in seconds since the unix epoch, with an
+/// optional nanoseconds part. A nanoseconds part of 0 means it is unknown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HgModifiedTime {
+    secs: u64,
+    nsec: u32,
+}
+
+impl HgModifiedTime {
+    /// Set the nanoseconds part of the modification time.
+    pub fn with_nsec(self, nsec: u32) -> Self {
+        HgModifiedTime { nsec, ..self }
+    }
+
+    fn as_nanos(&self) -> u64 {
+        self.secs
+            .saturating_mul(1_000_000_000)
+            .saturating_add(self.nsec.into())
+    }
+
+    /// Test if a file with this mtime has the mtime recorded in `recorded`. Only seconds are
+    /// compared if either side does not know the nanoseconds.
+    fn matches(&self, recorded: &HgModifiedTime) -> bool {
+        if self.nsec == 0 || recorded.nsec == 0 {
+            self.secs == recorded.secs
+        } else {
+            self == recorded
+        }
+    }
+
+    /// Test if a file with this mtime might have been modified again after `last_write` without
+    /// its mtime changing. Without nanoseconds, that is the case if both are in the same second.
+    fn is_ambiguous(&self, last_write: &HgModifiedTime) -> bool {
+        if self.nsec == 0 || last_write.nsec == 0 {
+            self.secs == last_write.secs
+        } else {
+            let (a, b) = (self.as_nanos(), last_write.as_nanos());
+            let distance = if a > b { a - b } else { b - a };
+            distance < MTIME_GRANULARITY_NSEC
+        }
+    }
+}
 
 impl From<u64> for HgModifiedTime {
     fn from(value: u64) -> Self {
-        HgModifiedTime(value)
+        HgModifiedTime {
+            secs: value,
+            nsec: 0,
+        }
     }
 }
 
 impl From<u32> for HgModifiedTime {
     fn from(value: u32) -> Self {
-        HgModifiedTime(value.into())
+        HgModifiedTime::from(u64::from(value))
     }
 }
 
 impl TryFrom<SystemTime> for HgModifiedTime {
     type Error = Error;
     fn try_from(value: SystemTime) -> Result<Self> {
-        Ok(value
-            .duration_since(SystemTime::UNIX_EPOCH)?
-            .as_secs()
-            .into())
+        let duration = value.duration_since(SystemTime::UNIX_EPOCH)?;
+        Ok(HgModifiedTime {
+            secs: duration.as_secs(),
+            nsec: duration.subsec_nanos(),
+        })
     }
 }
 
-impl TryFrom<i32> for HgModifiedTime {
+impl TryFrom<i64> for HgModifiedTime {
     type Error = Error;
-    fn try_from(value: i32) -> Result<Self> {
-        Ok(HgModifiedTime(value.try_into()?))
+    fn try_from(value: i64) -> Result<Self> {
+        Ok(HgModifiedTime::from(u64::try_from(value)?))
     }
 }
 
 pub struct PhysicalFileSystem {
     // TODO: Make this an Arc<Mutex<VFS>> so we can persist the vfs pathauditor cache
-    vfs: VFS,
+    pub(crate) vfs: VFS,
+    journal: Option<ChangeJournal>,
 }
 
 impl PhysicalFileSystem {
     pub fn new(root: PathBuf) -> Result<Self> {
         Ok(PhysicalFileSystem {
             vfs: VFS::new(root)?,
+            journal: None,
         })
     }
 
+    /// Use `journal` so `pending_changes` only looks at paths that changed since the journal
+    /// cursor stored in the treestate, instead of walking the whole working copy.
+    ///
+    /// Once `pending_changes` is exhausted, the files it reported are marked `NEED_CHECK` and the
+    /// new cursor is stored in the treestate root metadata. They are persisted when the caller
+    /// writes the treestate. Like with fsmonitor, commands that change the tracked state of a
+    /// file without touching it on disk are expected to mark it `NEED_CHECK`.
+    ///
+    /// The journal is only used with matchers matching the whole working copy, and no
+    /// directories are reported when only changed paths are looked at.
+    ///
+    /// `hg status` does not use a journal yet: its Rust implementation does not write the
+    /// treestate, so the cursor would never advance. Also, without fsmonitor, Python commands
+    /// changing the tracked state of files do not mark them `NEED_CHECK`.
+    pub fn with_journal(mut self, journal: ChangeJournal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
     pub fn pending_changes<M: Matcher + Clone>(
         &self,
         treestate: Arc<Mutex<TreeState>>,
@@ -78,6 +151,27 @@ impl PhysicalFileSystem {
         include_directories: bool,
         last_write: HgModifiedTime,
     ) -> PendingChanges<M> {
+        self.pending_changes_impl(treestate, matcher, include_directories, last_write, true)
+    }
+
+    pub(crate) fn pending_changes_impl<M: Matcher + Clone>(
+        &self,
+        treestate: Arc<Mutex<TreeState>>,
+        matcher: M,
+        include_directories: bool,
+        last_write: HgModifiedTime,
+        use_journal: bool,
+    ) -> PendingChanges<M> {
+        let journal = match self.journal {
+            Some(ref journal)
+                if use_journal
+                    && matcher.matches_directory(RepoPath::empty())
+                        == DirectoryMatch::Everything =>
+            {
+                JournalState::new(journal, &treestate)
+            }
+            _ => None,
+        };
         let walker = Walker::new(self.vfs.root().to_path_buf(), matcher.clone(), false);
         PendingChanges {
             vfs: self.vfs.clone(),
@@ -90,10 +184,33 @@ impl PhysicalFileSystem {
             lookups: vec![],
             tree_iter: None,
             last_write,
+            journal,
+            journal_iter: None,
+            reported: vec![],
+            stale: vec![],
         }
     }
 }
 
+/// Journal position the pending changes are computed at.
+struct JournalState {
+    cursor: JournalCursor,
+    /// Paths changed since the cursor stored in the treestate, if that cursor is still valid.
+    changes: Option<BTreeSet<RepoPathBuf>>,
+}
+
+impl JournalState {
+    fn new(journal: &ChangeJournal, treestate: &Mutex<TreeState>) -> Option<Self> {
+        // If no watcher is running or it is lagging behind, do a full walk and keep the old
+        // cursor.
+        let cursor = journal.sync(JOURNAL_SYNC_TIMEOUT).ok().flatten()?;
+        let previous = JournalCursor::from_metadata(treestate.lock().get_metadata());
+        let changes = previous
+            .and_then(|previous| journal.changes_between(&previous, &cursor).ok().flatten());
+        Some(JournalState { cursor, changes })
+    }
+}
+
 pub struct PendingChanges<M: Matcher + Clone> {
     vfs: VFS,
     walker: Walker<M>,
@@ -105,6 +222,12 @@ pub struct PendingChanges<M: Matcher + Clone> {
     lookups: Vec<RepoPathBuf>,
     tree_iter: Option<Box<dyn Iterator<Item = Result<PendingChangeResult>> + Send>>,
     last_write: HgModifiedTime,
+    journal: Option<JournalState>,
+    journal_iter: Option<std::vec::IntoIter<RepoPathBuf>>,
+    // Changed and deleted files reported so far, to be marked NEED_CHECK.
+    reported: Vec<RepoPathBuf>,
+    // Untracked treestate entries that no longer exist on disk.
+    stale: Vec<RepoPathBuf>,
 }
 
 #[derive(PartialEq)]
@@ -155,7 +278,7 @@ impl<M: Matcher + Clone> PendingChanges<M> {
         }
 
         // If working copy file size or flags are different from what is in treestate, it has changed.
-        // Note: state.size is i32 since Mercurial uses negative numbers to indicate special files.
+        // Note: state.size is signed since Mercurial uses negative numbers to indicate special files.
         // A -1 indicates the file is either in a merge state or a lookup state.
         // A -2 indicates the file comes from the other parent (and may or may not exist in the
         // current parent).
@@ -192,11 +315,12 @@ impl<M: Matcher + Clone> PendingChanges<M> {
             self.lookups.push(path.to_owned());
         } else {
             let state_mtime: Result<HgModifiedTime> = state.mtime.try_into();
-            let state_mtime =
-                state_mtime.map_err(|e| WalkError::InvalidMTime(path.to_owned(), e))?;
+            let state_mtime = state_mtime
+                .map_err(|e| WalkError::InvalidMTime(path.to_owned(), e))?
+                .with_nsec(state.mtime_nsec);
             let mtime: HgModifiedTime = metadata.modified()?.try_into()?;
 
-            if mtime != state_mtime || mtime == self.last_write {
+            if !mtime.matches(&state_mtime) || mtime.is_ambiguous(&self.last_write) {
                 self.lookups.push(path.to_owned());
             }
         }
@@ -324,6 +448,151 @@ impl<M: Matcher + Clone> PendingChanges<M> {
     fn next_lookup(&mut self) -> Option<Result<PendingChangeResult>> {
         None
     }
+
+    /// Whether only the paths recorded in the change journal are looked at.
+    fn is_incremental(&self) -> bool {
+        match self.journal {
+            Some(ref journal) => journal.changes.is_some(),
+            None => false,
+        }
+    }
+
+    fn next_journal(&mut self) -> Option<Result<PendingChangeResult>> {
+        if self.journal_iter.is_none() {
+            match self.get_journal_candidates() {
+                Ok(candidates) => self.journal_iter = Some(candidates.into_iter()),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        loop {
+            let path = self.journal_iter.as_mut().and_then(|iter| iter.next())?;
+            match self.check_journal_path(path) {
+                Ok(Some(change)) => return Some(Ok(PendingChangeResult::File(change))),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    /// Paths that might have changed since the previous journal cursor.
+    fn get_journal_candidates(&mut self) -> Result<Vec<RepoPathBuf>> {
+        let mut candidates = match self.journal {
+            Some(JournalState {
+                changes: Some(ref changes),
+                ..
+            }) => changes.clone(),
+            _ => return Ok(vec![]),
+        };
+        let prefixes: Vec<Vec<u8>> = candidates
+            .iter()
+            .map(|path| format!("{}/", path).into_bytes())
+            .collect();
+        let under_prefix = |path: &[u8]| prefixes.iter().any(|prefix| path.starts_with(prefix));
+
+        // Files reported by previous runs, and tracked files in directories that were removed
+        // or moved away, since the files in them are not recorded one by one.
+        self.treestate.lock().visit(
+            &mut |components, _| {
+                candidates.insert(RepoPathBuf::from_utf8(components.concat())?);
+                Ok(VisitorResult::NotChanged)
+            },
+            &|components, dir| {
+                let needs_check = match dir.get_aggregated_state() {
+                    None => true,
+                    Some(state) => state.union.intersects(StateFlags::NEED_CHECK),
+                };
+                let dir = components.concat();
+                needs_check
+                    || under_prefix(&dir)
+                    || prefixes.iter().any(|prefix| prefix.starts_with(&dir))
+            },
+            &|components, file| {
+                file.state.intersects(StateFlags::NEED_CHECK) || under_prefix(&components.concat())
+            },
+        )?;
+        Ok(candidates.into_iter().collect())
+    }
+
+    fn check_journal_path(&mut self, path: RepoPathBuf) -> Result<Option<ChangeType>> {
+        if !self.matcher.matches_file(&path) {
+            return Ok(None);
+        }
+        let flags = self.treestate.lock().get(&path)?.map(|state| state.state);
+        let flags = flags.unwrap_or_else(StateFlags::empty);
+        let tracked =
+            flags.intersects(StateFlags::EXIST_P1 | StateFlags::EXIST_P2 | StateFlags::EXIST_NEXT);
+
+        // Like the walk, ignore untracked files in directories the matcher excludes.
+        if !tracked
+            && path.parents().any(|dir| {
+                !dir.is_empty() && self.matcher.matches_directory(dir) == DirectoryMatch::Nothing
+            })
+        {
+            return Ok(None);
+        }
+
+        match self.vfs.metadata(&path) {
+            Ok(ref metadata) if metadata.is_file() || metadata.file_type().is_symlink() => {
+                if self.is_changed(&path, metadata)? {
+                    Ok(Some(ChangeType::Changed(path)))
+                } else {
+                    Ok(None)
+                }
+            }
+            _ => {
+                if flags.intersects(StateFlags::EXIST_P1) {
+                    Ok(Some(ChangeType::Deleted(path)))
+                } else {
+                    if !tracked && flags.intersects(StateFlags::NEED_CHECK) {
+                        self.stale.push(path);
+                    }
+                    Ok(None)
+                }
+            }
+        }
+    }
+
+    /// Mark the reported files `NEED_CHECK` so later incremental walks look at them again, and
+    /// store the new journal cursor in the treestate.
+    fn update_journal(&mut self) -> Result<()> {
+        let journal = match self.journal.take() {
+            Some(journal) => journal,
+            None => return Ok(()),
+        };
+        let mut treestate = self.treestate.lock();
+        for path in self.reported.iter().chain(self.lookups.iter()) {
+            let state = match treestate.get(path)? {
+                Some(state) if state.state.intersects(StateFlags::NEED_CHECK) => continue,
+                Some(state) => {
+                    let mut state = state.clone();
+                    state.state |= StateFlags::NEED_CHECK;
+                    state
+                }
+                None => FileStateV2 {
+                    mode: 0o666,
+                    size: -1,
+                    mtime: -1,
+                    mtime_nsec: 0,
+                    state: StateFlags::NEED_CHECK,
+                    copied: None,
+                },
+            };
+            treestate.insert(path, &state)?;
+        }
+        for path in self.stale.iter() {
+            treestate.remove(path)?;
+        }
+        let metadata = journal.cursor.update_metadata(treestate.get_metadata());
+        treestate.set_metadata(metadata);
+        Ok(())
+    }
+
+    /// Files whose size and flags match the treestate but whose content still needs to be
+    /// compared to decide whether they changed. Only complete once the iterator is exhausted.
+    pub fn lookups(&self) -> &[RepoPathBuf] {
+        &self.lookups
+    }
 }
 
 impl<M: Matcher + Clone> Iterator for PendingChanges<M> {
@@ -333,19 +602,29 @@ impl<M: Matcher + Clone> Iterator for PendingChanges<M> {
         // TODO: Try to make this into a chain instead of a manual state machine
         loop {
             let change = match self.stage {
+                PendingChangesStage::Walk if self.is_incremental() => self.next_journal(),
                 PendingChangesStage::Walk => self.next_walk(),
+                PendingChangesStage::IterateTree if self.is_incremental() => None,
                 PendingChangesStage::IterateTree => self.next_tree(),
                 PendingChangesStage::Lookups => self.next_lookup(),
                 PendingChangesStage::Finished => None,
             };
 
+            if let Some(Ok(PendingChangeResult::File(ref change))) = change {
+                if self.journal.is_some() {
+                    let path = match change {
+                        ChangeType::Changed(path) | ChangeType::Deleted(path) => path,
+                    };
+                    self.reported.push(path.clone());
+                }
+            }
             if change.is_some() {
                 return change;
             }
 
             self.stage = self.stage.next();
             if self.stage == PendingChangesStage::Finished {
-                return None;
+                return self.update_journal().err().map(Err);
             }
         }
     }
@@ -355,3 +634,42 @@ fn normalize(path: RepoPathBuf) -> RepoPathBuf {
     // TODO: Support path normalization on case insensitive file systems
     path
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mtime_matches() {
+        let recorded = HgModifiedTime::from(100u64);
+        assert!(HgModifiedTime::from(100u64).with_nsec(5).matches(&recorded));
+        assert!(!HgModifiedTime::from(101u64).matches(&recorded));
+
+        let recorded = recorded.with_nsec(5);
+        assert!(HgModifiedTime::from(100u64).with_nsec(5).matches(&recorded));
+        assert!(!HgModifiedTime::from(100u64).with_nsec(6).matches(&recorded));
+        assert!(HgModifiedTime::from(100u64).matches(&recorded));
+    }
+
+    #[test]
+    fn test_mtime_is_ambiguous() {
+        // Without nanoseconds, files modified in the same second as the last write are ambiguous.
+        let last_write = HgModifiedTime::from(100u64);
+        assert!(HgModifiedTime::from(100u64)
+            .with_nsec(1)
+            .is_ambiguous(&last_write));
+        assert!(!HgModifiedTime::from(99u64)
+            .with_nsec(1)
+            .is_ambiguous(&last_write));
+
+        // With nanoseconds, only files modified close to the last write are ambiguous.
+        let last_write = last_write.with_nsec(500_000_000);
+        let mtime = HgModifiedTime::from(100u64);
+        assert!(mtime.with_nsec(490_000_000).is_ambiguous(&last_write));
+        assert!(mtime.with_nsec(510_000_000).is_ambiguous(&last_write));
+        assert!(!mtime.with_nsec(100_000_000).is_ambiguous(&last_write));
+        assert!(!HgModifiedTime::from(99u64)
+            .with_nsec(500_000_000)
+            .is_ambiguous(&last_write));
+    }
+}
@@ -0,0 +1,342 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! Change journal recorded by a filesystem watcher.
+//!
+//! The journal lives in its own directory:
+//! - `lock`: exclusively locked by the watcher while it is running. A journal is only
+//!   trustworthy while it is locked, since changes are not recorded otherwise.
+//! - `id`: identifies one run of the watcher. It changes when the watcher restarts, loses
+//!   events or empties `changes`, which invalidates cursors taken before that.
+//! - `changes`: append-only list of changed paths relative to the working copy root, one per
+//!   line. Lines starting with `\0` are sync cookies, which never conflict with paths. The
+//!   watcher empties it and changes `id` when it gets too large.
+//!
+//! A [`JournalCursor`] is a position in the journal. Paths changed between two cursors are
+//! returned by [`ChangeJournal::changes_between`].
+//!
+//! This is a library for now. See [`PhysicalFileSystem::with_journal`] for why `hg status`
+//! does not use it.
+//!
+//! [`PhysicalFileSystem::with_journal`]: crate::filesystem::PhysicalFileSystem::with_journal
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use fs2::FileExt;
+use thiserror::Error;
+
+use types::RepoPathBuf;
+
+pub(crate) const LOCK_FILE: &str = "lock";
+pub(crate) const ID_FILE: &str = "id";
+pub(crate) const CHANGES_FILE: &str = "changes";
+pub(crate) const COOKIE_PREFIX: &str = "cookie-";
+
+/// Key of the journal cursor in the treestate root metadata.
+const METADATA_KEY: &str = "journal";
+
+#[derive(Error, Debug)]
+pub enum JournalError {
+    #[error("invalid journal cursor '{0}'")]
+    InvalidCursor(String),
+    #[error("invalid path in change journal: {0}")]
+    InvalidPath(String),
+}
+
+/// Position in a change journal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JournalCursor {
+    id: String,
+    offset: u64,
+}
+
+impl JournalCursor {
+    /// Read the cursor from treestate root metadata ("key=value" entries separated by NUL).
+    pub fn from_metadata(metadata: &[u8]) -> Option<Self> {
+        metadata
+            .split(|b| *b == 0)
+            .filter_map(|entry| std::str::from_utf8(entry).ok())
+            .filter_map(|entry| {
+                let mut parts = entry.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(METADATA_KEY), Some(value)) => value.parse().ok(),
+                    _ => None,
+                }
+            })
+            .next()
+    }
+
+    /// Return treestate root metadata with the cursor replaced by this one.
+    pub fn update_metadata(&self, metadata: &[u8]) -> Vec<u8> {
+        let prefix = format!("{}=", METADATA_KEY);
+        let mut entries: Vec<Vec<u8>> = metadata
+            .split(|b| *b == 0)
+            .filter(|entry| !entry.is_empty() && !entry.starts_with(prefix.as_bytes()))
+            .map(|entry| entry.to_vec())
+            .collect();
+        entries.push(format!("{}{}", prefix, self).into_bytes());
+        entries.join(&0)
+    }
+}
+
+impl fmt::Display for JournalCursor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.id, self.offset)
+    }
+}
+
+impl FromStr for JournalCursor {
+    type Err = JournalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || JournalError::InvalidCursor(s.to_string());
+        let index = s.rfind(':').ok_or_else(invalid)?;
+        let offset = s[index + 1..].parse().map_err(|_| invalid())?;
+        let id = s[..index].to_string();
+        if id.is_empty() || id.contains('\0') {
+            return Err(invalid());
+        }
+        Ok(JournalCursor { id, offset })
+    }
+}
+
+/// Reader side of a change journal.
+pub struct ChangeJournal {
+    dir: PathBuf,
+}
+
+impl ChangeJournal {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        ChangeJournal {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Whether a watcher is currently recording changes into this journal.
+    pub fn is_watched(&self) -> bool {
+        let file = match File::open(self.dir.join(LOCK_FILE)) {
+            Ok(file) => file,
+            Err(_) => return false,
+        };
+        match FileExt::try_lock_shared(&file) {
+            Ok(()) => {
+                let _ = FileExt::unlock(&file);
+                false
+            }
+            Err(_) => true,
+        }
+    }
+
+    /// Current end of the journal, or `None` if no watcher is recording changes.
+    ///
+    /// Changes that happened just before this call might not be recorded yet. Use
+    /// [`ChangeJournal::sync`] to wait for them.
+    pub fn cursor(&self) -> Result<Option<JournalCursor>> {
+        if !self.is_watched() {
+            return Ok(None);
+        }
+        let id = self.read_id()?;
+        let offset = match fs::metadata(self.dir.join(CHANGES_FILE)) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(id.map(|id| JournalCursor { id, offset }))
+    }
+
+    /// Wait until the watcher has recorded all changes made before this call, and return the
+    /// cursor at that point. Returns `None` if no watcher is recording changes, or if it did not
+    /// catch up within `timeout`.
+    pub fn sync(&self, timeout: Duration) -> Result<Option<JournalCursor>> {
+        let start = match self.cursor()? {
+            Some(cursor) => cursor,
+            None => return Ok(None),
+        };
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let name = format!("{}{}-{}", COOKIE_PREFIX, std::process::id(), nanos);
+        let cookie_path = self.dir.join(&name);
+        File::create(&cookie_path)?;
+        let result = self.wait_for_cookie(&start, &name, timeout);
+        let _ = fs::remove_file(&cookie_path);
+        result
+    }
+
+    fn wait_for_cookie(
+        &self,
+        start: &JournalCursor,
+        name: &str,
+        timeout: Duration,
+    ) -> Result<Option<JournalCursor>> {
+        let deadline = Instant::now() + timeout;
+        let cookie_line = format!("\0{}\n", name);
+        loop {
+            if self.read_id()?.as_ref() != Some(&start.id) {
+                return Ok(None);
+            }
+            let data = self.read_changes(start.offset, None)?;
+            if let Some(index) = find(&data, cookie_line.as_bytes()) {
+                let offset = start.offset + (index + cookie_line.len()) as u64;
+                return Ok(Some(JournalCursor {
+                    id: start.id.clone(),
+                    offset,
+                }));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Paths changed between two cursors. Returns `None` if the changes cannot be known from
+    /// the journal, for example because the watcher restarted in between.
+    pub fn changes_between(
+        &self,
+        from: &JournalCursor,
+        to: &JournalCursor,
+    ) -> Result<Option<BTreeSet<RepoPathBuf>>> {
+        if from.id != to.id || from.offset > to.offset {
+            return Ok(None);
+        }
+        if self.read_id()?.as_ref() != Some(&from.id) {
+            return Ok(None);
+        }
+        let data = self.read_changes(from.offset, Some(to.offset))?;
+        // The watcher might have restarted and truncated the journal while it was read.
+        if (data.len() as u64) < to.offset - from.offset
+            || self.read_id()?.as_ref() != Some(&from.id)
+        {
+            return Ok(None);
+        }
+
+        let mut paths = BTreeSet::new();
+        for line in data.split(|b| *b == b'\n') {
+            if line.is_empty() || line[0] == 0 {
+                continue;
+            }
+            let line = std::str::from_utf8(line)
+                .map_err(|_| JournalError::InvalidPath(String::from_utf8_lossy(line).into()))?;
+            let path = RepoPathBuf::from_string(line.to_string())
+                .map_err(|_| JournalError::InvalidPath(line.to_string()))?;
+            paths.insert(path);
+        }
+        Ok(Some(paths))
+    }
+
+    fn read_id(&self) -> Result<Option<String>> {
+        match fs::read_to_string(self.dir.join(ID_FILE)) {
+            Ok(id) => Ok(Some(id.trim().to_string())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn read_changes(&self, start: u64, end: Option<u64>) -> Result<Vec<u8>> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .open(self.dir.join(CHANGES_FILE))?;
+        file.seek(SeekFrom::Start(start))?;
+        let mut data = Vec::new();
+        match end {
+            Some(end) => {
+                file.take(end.saturating_sub(start))
+                    .read_to_end(&mut data)?;
+            }
+            None => {
+                file.read_to_end(&mut data)?;
+            }
+        }
+        Ok(data)
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    fn path(s: &str) -> RepoPathBuf {
+        RepoPathBuf::from_string(s.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_cursor_metadata() {
+        let cursor: JournalCursor = "abc-1:42".parse().unwrap();
+        assert_eq!(cursor.to_string(), "abc-1:42");
+        assert!("42".parse::<JournalCursor>().is_err());
+        assert!("abc:x".parse::<JournalCursor>().is_err());
+
+        let metadata = cursor.update_metadata(b"p1=1234\0p2=5678");
+        assert_eq!(&metadata[..], &b"p1=1234\0p2=5678\0journal=abc-1:42"[..]);
+        assert_eq!(JournalCursor::from_metadata(&metadata), Some(cursor));
+
+        let next: JournalCursor = "abc-1:50".parse().unwrap();
+        let metadata = next.update_metadata(&metadata);
+        assert_eq!(&metadata[..], &b"p1=1234\0p2=5678\0journal=abc-1:50"[..]);
+        assert_eq!(JournalCursor::from_metadata(b"p1=1234"), None);
+        assert_eq!(JournalCursor::from_metadata(b""), None);
+    }
+
+    #[test]
+    fn test_changes_between() -> Result<()> {
+        let dir = tempdir()?;
+        let journal = ChangeJournal::new(dir.path());
+        assert!(!journal.is_watched());
+        assert_eq!(journal.cursor()?, None);
+
+        let lock = File::create(dir.path().join(LOCK_FILE))?;
+        FileExt::lock_exclusive(&lock)?;
+        fs::write(dir.path().join(ID_FILE), "1")?;
+        fs::write(dir.path().join(CHANGES_FILE), "a\n\0cookie-1\nb/c\n")?;
+        assert!(journal.is_watched());
+
+        let start: JournalCursor = "1:0".parse()?;
+        let end = journal.cursor()?.unwrap();
+        assert_eq!(end.to_string(), "1:16");
+        let changes = journal.changes_between(&start, &end)?.unwrap();
+        assert_eq!(
+            changes.into_iter().collect::<Vec<_>>(),
+            vec![path("a"), path("b/c")]
+        );
+        let middle: JournalCursor = "1:2".parse()?;
+        let changes = journal.changes_between(&middle, &end)?.unwrap();
+        assert_eq!(changes.into_iter().collect::<Vec<_>>(), vec![path("b/c")]);
+
+        // Cursors from another watcher run cannot be used.
+        let other: JournalCursor = "0:0".parse()?;
+        assert_eq!(journal.changes_between(&other, &end)?, None);
+        fs::write(dir.path().join(ID_FILE), "2")?;
+        assert_eq!(journal.changes_between(&start, &end)?, None);
+
+        FileExt::unlock(&lock)?;
+        assert!(!journal.is_watched());
+        assert_eq!(journal.cursor()?, None);
+        Ok(())
+    }
+}
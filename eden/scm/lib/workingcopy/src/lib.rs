@@ -6,4 +6,8 @@
  */
 
 pub mod filesystem;
+pub mod journal;
+pub mod status;
 pub mod walker;
+#[cfg(target_os = "linux")]
+pub mod watcher;
@@ -0,0 +1,432 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use parking_lot::Mutex;
+
+use pathmatcher::{DirectoryMatch, Matcher};
+use treestate::filestate::StateFlags;
+use treestate::tree::{AggregatedState, VisitorResult};
+use treestate::treestate::TreeState;
+use types::{RepoPath, RepoPathBuf};
+
+use crate::filesystem::{ChangeType, HgModifiedTime, PendingChangeResult, PhysicalFileSystem};
+
+/// Status of the working copy relative to the first working parent.
+///
+/// Each list is sorted by path, the same way the Python `status` sorts them.
+#[derive(Default)]
+pub struct Status {
+    pub modified: Vec<RepoPathBuf>,
+    pub added: Vec<RepoPathBuf>,
+    pub removed: Vec<RepoPathBuf>,
+    pub deleted: Vec<RepoPathBuf>,
+    pub unknown: Vec<RepoPathBuf>,
+    pub ignored: Vec<RepoPathBuf>,
+    pub clean: Vec<RepoPathBuf>,
+    /// Copy sources of the added and modified files.
+    pub copies: HashMap<RepoPathBuf, RepoPathBuf>,
+    /// Files whose content needs to be compared with the parent to decide whether they are
+    /// modified or clean. They are not included in `modified` or `clean`.
+    pub lookups: Vec<RepoPathBuf>,
+}
+
+impl PhysicalFileSystem {
+    /// Compute the status of files selected by `matcher`.
+    ///
+    /// Untracked files matched by `ignore` are reported as ignored, and only when `list_ignored`
+    /// is set. Otherwise, ignored directories are not walked at all. Clean files are only
+    /// collected when `list_clean` is set.
+    ///
+    /// Merge state is not taken into account: the caller is expected to handle working copies
+    /// with a second parent differently.
+    pub fn status<M, I>(
+        &self,
+        treestate: Arc<Mutex<TreeState>>,
+        matcher: M,
+        ignore: Arc<I>,
+        list_ignored: bool,
+        list_clean: bool,
+        last_write: HgModifiedTime,
+    ) -> Result<Status>
+    where
+        M: Matcher + Clone,
+        I: Matcher,
+    {
+        let mut status = Status::default();
+
+        // Step 1: Files that are different from p1 on disk.
+        let walk_matcher = WalkMatcher {
+            matcher: matcher.clone(),
+            ignore: ignore.clone(),
+            list_ignored,
+        };
+        // Ignored files are not recorded in the treestate, so they can only be found by a full
+        // walk.
+        let mut pending = self.pending_changes_impl(
+            treestate.clone(),
+            walk_matcher,
+            false,
+            last_write,
+            !list_ignored,
+        );
+        for change in &mut pending {
+            let (path, exists) = match change? {
+                PendingChangeResult::File(ChangeType::Changed(path)) => (path, true),
+                PendingChangeResult::File(ChangeType::Deleted(path)) => (path, false),
+                PendingChangeResult::SeenDirectory(_) => continue,
+            };
+            let flags = treestate.lock().get(&path)?.map(|state| state.state);
+            let flags = flags.unwrap_or_else(StateFlags::empty);
+            if !flags
+                .intersects(StateFlags::EXIST_P1 | StateFlags::EXIST_P2 | StateFlags::EXIST_NEXT)
+            {
+                if ignore.matches_file(&path) {
+                    if list_ignored {
+                        status.ignored.push(path);
+                    }
+                } else {
+                    status.unknown.push(path);
+                }
+            } else if flags.contains(StateFlags::EXIST_P1 | StateFlags::EXIST_NEXT) {
+                if exists {
+                    status.modified.push(path);
+                } else {
+                    status.deleted.push(path);
+                }
+            }
+            // Added and removed files are handled below.
+        }
+        status.lookups = pending.lookups().to_vec();
+
+        let mut seen: HashSet<RepoPathBuf> = status
+            .modified
+            .iter()
+            .chain(status.deleted.iter())
+            .chain(status.lookups.iter())
+            .cloned()
+            .collect();
+
+        // Step 2: Files that are added, removed or marked as copied in the treestate.
+        let nonnormal = collect_files(
+            &treestate,
+            &|state: &AggregatedState| {
+                state.union.intersects(StateFlags::COPIED)
+                    || !state
+                        .intersection
+                        .contains(StateFlags::EXIST_P1 | StateFlags::EXIST_NEXT)
+            },
+            &|flags: StateFlags| is_nonnormal(flags) || flags.intersects(StateFlags::COPIED),
+        )?;
+        for (path, flags, copied) in nonnormal {
+            if !matcher.matches_file(&path) || seen.contains(&path) {
+                continue;
+            }
+            let added = flags.contains(StateFlags::EXIST_NEXT)
+                && !flags.intersects(StateFlags::EXIST_P1 | StateFlags::EXIST_P2);
+            if added {
+                if self.file_exists(&path) {
+                    status.added.push(path.clone());
+                } else {
+                    status.deleted.push(path.clone());
+                }
+            } else if !flags.contains(StateFlags::EXIST_NEXT)
+                && flags.intersects(StateFlags::EXIST_P1 | StateFlags::EXIST_P2)
+            {
+                status.removed.push(path.clone());
+            } else if flags
+                .contains(StateFlags::EXIST_P1 | StateFlags::EXIST_NEXT | StateFlags::COPIED)
+            {
+                // A clean file can be marked as copied retroactively. Report it as modified.
+                status.modified.push(path.clone());
+            } else {
+                continue;
+            }
+            if let Some(copied) = copied {
+                status.copies.insert(path.clone(), copied);
+            }
+            seen.insert(path);
+        }
+
+        // Copy sources are only relevant for added and modified files.
+        for path in status.modified.iter() {
+            if status.copies.contains_key(path) {
+                continue;
+            }
+            if let Some(copied) = copied_from(&treestate, path)? {
+                status.copies.insert(path.clone(), copied);
+            }
+        }
+        let added: HashSet<&RepoPathBuf> =
+            status.added.iter().chain(status.modified.iter()).collect();
+        status.copies.retain(|path, _| added.contains(path));
+
+        // Step 3: Tracked files that were not seen above are clean.
+        if list_clean {
+            let tracked = collect_files(
+                &treestate,
+                &|state: &AggregatedState| {
+                    state
+                        .union
+                        .contains(StateFlags::EXIST_P1 | StateFlags::EXIST_NEXT)
+                },
+                &|flags: StateFlags| flags.contains(StateFlags::EXIST_P1 | StateFlags::EXIST_NEXT),
+            )?;
+            for (path, _, _) in tracked {
+                if matcher.matches_file(&path) && !seen.contains(&path) {
+                    status.clean.push(path);
+                }
+            }
+        }
+
+        for list in [
+            &mut status.modified,
+            &mut status.added,
+            &mut status.removed,
+            &mut status.deleted,
+            &mut status.unknown,
+            &mut status.ignored,
+            &mut status.clean,
+            &mut status.lookups,
+        ]
+        .iter_mut()
+        {
+            list.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        }
+
+        Ok(status)
+    }
+
+    fn file_exists(&self, path: &RepoPath) -> bool {
+        match self.vfs.metadata(path) {
+            Ok(metadata) => metadata.is_file() || metadata.file_type().is_symlink(),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Added or removed.
+fn is_nonnormal(flags: StateFlags) -> bool {
+    flags.intersects(StateFlags::EXIST_P1 | StateFlags::EXIST_P2 | StateFlags::EXIST_NEXT)
+        && !flags.contains(StateFlags::EXIST_P1 | StateFlags::EXIST_NEXT)
+}
+
+fn collect_files(
+    treestate: &Arc<Mutex<TreeState>>,
+    visit_dir: &dyn Fn(&AggregatedState) -> bool,
+    visit_file: &dyn Fn(StateFlags) -> bool,
+) -> Result<Vec<(RepoPathBuf, StateFlags, Option<RepoPathBuf>)>> {
+    let mut result = Vec::new();
+    treestate.lock().visit(
+        &mut |components, state| {
+            let path = RepoPathBuf::from_utf8(components.concat())?;
+            let copied = match state.copied {
+                Some(ref copied) => Some(RepoPathBuf::from_utf8(copied.to_vec())?),
+                None => None,
+            };
+            result.push((path, state.state, copied));
+            Ok(VisitorResult::NotChanged)
+        },
+        &|_path, dir| match dir.get_aggregated_state() {
+            None => true,
+            Some(state) => visit_dir(&state),
+        },
+        &|_path, file| visit_file(file.state),
+    )?;
+    Ok(result)
+}
+
+fn copied_from(treestate: &Arc<Mutex<TreeState>>, path: &RepoPath) -> Result<Option<RepoPathBuf>> {
+    let mut treestate = treestate.lock();
+    match treestate.get(path)? {
+        Some(state) => match state.copied {
+            Some(ref copied) => Ok(Some(RepoPathBuf::from_utf8(copied.to_vec())?)),
+            None => Ok(None),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Matcher used for the walk. Files are selected by the user matcher only, so ignored files
+/// that are tracked are still compared. Ignored directories are skipped unless ignored files
+/// are listed.
+struct WalkMatcher<M, I> {
+    matcher: M,
+    ignore: Arc<I>,
+    list_ignored: bool,
+}
+
+impl<M: Clone, I> Clone for WalkMatcher<M, I> {
+    fn clone(&self) -> Self {
+        WalkMatcher {
+            matcher: self.matcher.clone(),
+            ignore: self.ignore.clone(),
+            list_ignored: self.list_ignored,
+        }
+    }
+}
+
+impl<M: Matcher, I: Matcher> Matcher for WalkMatcher<M, I> {
+    fn matches_directory(&self, path: &RepoPath) -> DirectoryMatch {
+        if !self.list_ignored
+            && !path.is_empty()
+            && self.ignore.matches_directory(path) == DirectoryMatch::Everything
+        {
+            return DirectoryMatch::Nothing;
+        }
+        self.matcher.matches_directory(path)
+    }
+
+    fn matches_file(&self, path: &RepoPath) -> bool {
+        self.matcher.matches_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::{create_dir_all, symlink_metadata, write};
+    use std::path::Path;
+    use std::time::UNIX_EPOCH;
+
+    use pathmatcher::{AlwaysMatcher, TreeMatcher};
+    use tempfile::tempdir;
+    use treestate::filestate::FileStateV2;
+
+    fn insert(
+        treestate: &mut TreeState,
+        root: &Path,
+        path: &str,
+        flags: StateFlags,
+        copied: Option<&str>,
+    ) -> Result<()> {
+        // Record the size and mtime of files on disk so they are considered clean.
+        let (size, mtime, mtime_nsec) = match symlink_metadata(root.join(path)) {
+            Ok(metadata) => {
+                let mtime = metadata.modified()?.duration_since(UNIX_EPOCH)?;
+                (
+                    metadata.len() as i64,
+                    mtime.as_secs() as i64,
+                    mtime.subsec_nanos(),
+                )
+            }
+            Err(_) => (0, 0, 0),
+        };
+        let state = FileStateV2 {
+            mode: 0o100644,
+            size,
+            mtime,
+            mtime_nsec,
+            state: flags,
+            copied: copied.map(|c| c.as_bytes().to_vec().into_boxed_slice()),
+        };
+        treestate.insert(path, &state)?;
+        Ok(())
+    }
+
+    fn names(paths: &[RepoPathBuf]) -> Vec<&str> {
+        paths.iter().map(|p| p.as_str()).collect()
+    }
+
+    #[test]
+    fn test_status() -> Result<()> {
+        let root = tempdir()?;
+        create_dir_all(root.path().join("dir"))?;
+        create_dir_all(root.path().join("build"))?;
+        write(root.path().join(".gitignore"), "build/\n*.log\n")?;
+        write(root.path().join("modified"), "")?;
+        write(root.path().join("added"), "")?;
+        write(root.path().join("dir/clean"), "")?;
+        write(root.path().join("dir/copied"), "")?;
+        write(root.path().join("unknown"), "")?;
+        write(root.path().join("out.log"), "")?;
+        write(root.path().join("build/out"), "")?;
+        write(root.path().join("build/tracked"), "")?;
+
+        let treestate_dir = tempdir()?;
+        let mut treestate = TreeState::open(treestate_dir.path().join("1"), None)?;
+        let root_path = root.path();
+        let normal = StateFlags::EXIST_P1 | StateFlags::EXIST_NEXT;
+        insert(&mut treestate, root_path, ".gitignore", normal, None)?;
+        insert(&mut treestate, root_path, "dir/clean", normal, None)?;
+        insert(&mut treestate, root_path, "build/tracked", normal, None)?;
+        insert(&mut treestate, root_path, "deleted", normal, None)?;
+        insert(
+            &mut treestate,
+            root_path,
+            "removed",
+            StateFlags::EXIST_P1,
+            None,
+        )?;
+        insert(
+            &mut treestate,
+            root_path,
+            "added",
+            StateFlags::EXIST_NEXT,
+            None,
+        )?;
+        let added = StateFlags::EXIST_NEXT;
+        insert(&mut treestate, root_path, "added_missing", added, None)?;
+        let copied = normal | StateFlags::COPIED;
+        insert(
+            &mut treestate,
+            root_path,
+            "dir/copied",
+            copied,
+            Some("dir/clean"),
+        )?;
+        insert(&mut treestate, root_path, "modified", normal, None)?;
+        write(root.path().join("modified"), "changed content")?;
+        let treestate = Arc::new(Mutex::new(treestate));
+
+        let ignore = Arc::new(pathmatcher::GitignoreMatcher::new(root.path(), Vec::new()));
+        let fs = PhysicalFileSystem::new(root.path().to_path_buf())?;
+        let status = fs.status(
+            treestate.clone(),
+            Arc::new(AlwaysMatcher::new()),
+            ignore.clone(),
+            true,
+            true,
+            0u32.into(),
+        )?;
+        assert_eq!(names(&status.modified), vec!["dir/copied", "modified"]);
+        assert_eq!(names(&status.added), vec!["added"]);
+        assert_eq!(names(&status.removed), vec!["removed"]);
+        assert_eq!(names(&status.deleted), vec!["added_missing", "deleted"]);
+        assert_eq!(names(&status.unknown), vec!["unknown"]);
+        assert_eq!(names(&status.ignored), vec!["build/out", "out.log"]);
+        assert_eq!(
+            names(&status.clean),
+            vec![".gitignore", "build/tracked", "dir/clean"]
+        );
+        assert_eq!(
+            status
+                .copies
+                .get(&RepoPathBuf::from_string("dir/copied".to_string())?),
+            Some(&RepoPathBuf::from_string("dir/clean".to_string())?)
+        );
+        assert!(status.lookups.is_empty());
+
+        // Ignored directories are not walked unless ignored files are listed, but tracked files
+        // inside them are still checked.
+        write(root.path().join("build/tracked"), "changed")?;
+        let matcher = TreeMatcher::from_rules(["build/**", "dir/**"].iter())?;
+        let status = fs.status(treestate, matcher, ignore, false, false, 0u32.into())?;
+        assert_eq!(names(&status.modified), vec!["build/tracked", "dir/copied"]);
+        assert!(status.unknown.is_empty());
+        assert!(status.ignored.is_empty());
+        assert!(status.clean.is_empty());
+
+        Ok(())
+    }
+}
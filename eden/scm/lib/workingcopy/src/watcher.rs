@@ -0,0 +1,563 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * This software may be used and distributed according to the terms of the
+ * GNU General Public License version 2.
+ */
+
+//! inotify based watcher recording working copy changes into a [`ChangeJournal`].
+//!
+//! [`JournalWatcher::spawn`] runs the watcher in a thread of the current process, and
+//! [`JournalWatcher::run`] can be used by a long running watcher process.
+//!
+//! [`ChangeJournal`]: crate::journal::ChangeJournal
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use fs2::FileExt;
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+
+use types::{RepoPath, RepoPathBuf};
+
+use crate::journal::{CHANGES_FILE, COOKIE_PREFIX, ID_FILE, LOCK_FILE};
+
+/// Size of the `changes` file above which a new journal is started, so the file does not grow
+/// forever. Cursors taken before that fall back to a full walk.
+const MAX_CHANGES_SIZE: u64 = 16 << 20;
+
+#[derive(Clone)]
+enum Watched {
+    Directory(RepoPathBuf),
+    Journal,
+}
+
+pub struct JournalWatcher {
+    root: PathBuf,
+    journal_dir: PathBuf,
+    inotify: Inotify,
+    watches: HashMap<WatchDescriptor, Watched>,
+    changes: File,
+    max_changes_size: u64,
+    // Held for the lifetime of the watcher so readers know the journal is up to date.
+    _lock: File,
+}
+
+impl JournalWatcher {
+    /// Start recording changes under `root` into the journal at `journal_dir`.
+    ///
+    /// Fails if another watcher is already recording into the same journal. Cursors taken
+    /// before this are invalidated.
+    pub fn new(root: impl AsRef<Path>, journal_dir: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        let journal_dir = journal_dir.as_ref().to_path_buf();
+        fs::create_dir_all(&journal_dir)?;
+        let lock = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(journal_dir.join(LOCK_FILE))?;
+        FileExt::try_lock_exclusive(&lock)?;
+        let changes = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(journal_dir.join(CHANGES_FILE))?;
+
+        let mut watcher = JournalWatcher {
+            root,
+            journal_dir,
+            inotify: Inotify::init()?,
+            watches: HashMap::new(),
+            changes,
+            max_changes_size: MAX_CHANGES_SIZE,
+            _lock: lock,
+        };
+        watcher.reset()?;
+        Ok(watcher)
+    }
+
+    /// Run the watcher in a thread of the current process. It stops when the returned handle
+    /// is dropped.
+    pub fn spawn(root: impl AsRef<Path>, journal_dir: impl AsRef<Path>) -> Result<WatcherHandle> {
+        let mut watcher = JournalWatcher::new(root, journal_dir)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = stop.clone();
+            thread::spawn(move || watcher.run(&stop))
+        };
+        Ok(WatcherHandle {
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Record changes until `stop` is set.
+    pub fn run(&mut self, stop: &AtomicBool) -> Result<()> {
+        while !stop.load(Ordering::Acquire) {
+            self.wait_for_events(100)?;
+            self.process_events()?;
+        }
+        Ok(())
+    }
+
+    /// Record the changes reported so far. Returns the number of inotify events processed.
+    pub fn process_events(&mut self) -> Result<usize> {
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut count = 0;
+        loop {
+            let mut lines = Vec::new();
+            let mut overflow = false;
+            let mut events = Vec::new();
+            for event in self.inotify.read_events(&mut buffer)? {
+                let name = event.name.map(|name| name.to_os_string());
+                events.push((event.wd, event.mask, name));
+            }
+            if events.is_empty() {
+                return Ok(count);
+            }
+            count += events.len();
+
+            for (wd, mask, name) in events {
+                if mask.contains(EventMask::Q_OVERFLOW) {
+                    overflow = true;
+                    continue;
+                }
+                if mask.contains(EventMask::IGNORED) {
+                    self.watches.remove(&wd);
+                    continue;
+                }
+                let (watched, name) = match (self.watches.get(&wd), name) {
+                    (Some(watched), Some(name)) => (watched.clone(), name),
+                    _ => continue,
+                };
+                let name = match name.to_str() {
+                    Some(name) => name.to_string(),
+                    // Such paths cannot be tracked either.
+                    None => continue,
+                };
+                match watched {
+                    Watched::Journal => {
+                        if mask.contains(EventMask::CREATE) && name.starts_with(COOKIE_PREFIX) {
+                            lines.push(format!("\0{}", name));
+                        }
+                    }
+                    Watched::Directory(dir) => {
+                        if dir.is_empty() && name == ".hg" {
+                            continue;
+                        }
+                        let component = match RepoPath::from_str(&name) {
+                            Ok(component) => component,
+                            Err(_) => continue,
+                        };
+                        let mut path = dir;
+                        path.push(component);
+                        lines.push(path.to_string());
+                        if mask.contains(EventMask::ISDIR) {
+                            if mask.intersects(EventMask::DELETE | EventMask::MOVED_FROM) {
+                                self.forget_directory(&path);
+                            }
+                            if mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+                                // Files might have been created before the directory is watched.
+                                self.watch_directory(path, Some(&mut lines))?;
+                            }
+                        }
+                    }
+                }
+            }
+
+            self.append(&lines)?;
+            if overflow {
+                // Events were lost. Start a new journal so readers do a full walk.
+                self.reset()?;
+            }
+        }
+    }
+
+    fn wait_for_events(&self, timeout_ms: i32) -> Result<()> {
+        let mut fds = libc::pollfd {
+            fd: self.inotify.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let result = unsafe { libc::poll(&mut fds, 1, timeout_ms) };
+        if result < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Start a new journal run with a new id, and watch the whole working copy again.
+    fn reset(&mut self) -> Result<()> {
+        for (wd, _) in self.watches.drain() {
+            let _ = self.inotify.rm_watch(wd);
+        }
+        self.start_journal()?;
+
+        let wd = self
+            .inotify
+            .add_watch(&self.journal_dir, WatchMask::CREATE)?;
+        self.watches.insert(wd, Watched::Journal);
+        self.watch_directory(RepoPathBuf::new(), None)
+    }
+
+    /// Empty the `changes` file and issue a new journal id, which invalidates existing cursors.
+    fn start_journal(&mut self) -> Result<()> {
+        self.changes.set_len(0)?;
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let id = format!("{}-{}", std::process::id(), nanos);
+        let tmp_path = self.journal_dir.join(format!("{}.tmp", ID_FILE));
+        fs::write(&tmp_path, id)?;
+        fs::rename(&tmp_path, self.journal_dir.join(ID_FILE))?;
+        Ok(())
+    }
+
+    /// Watch `dir` and its subdirectories. Entries found are added to `lines` if provided.
+    fn watch_directory(
+        &mut self,
+        dir: RepoPathBuf,
+        mut lines: Option<&mut Vec<String>>,
+    ) -> Result<()> {
+        let mask = WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MODIFY
+            | WatchMask::ATTRIB
+            | WatchMask::CLOSE_WRITE
+            | WatchMask::MOVED_FROM
+            | WatchMask::MOVED_TO
+            | WatchMask::ONLYDIR
+            | WatchMask::DONT_FOLLOW
+            | WatchMask::EXCL_UNLINK;
+
+        let mut pending = vec![dir];
+        while let Some(dir) = pending.pop() {
+            let abs_dir = self.root.join(dir.as_str());
+            match self.inotify.add_watch(&abs_dir, mask) {
+                Ok(wd) => {
+                    self.watches.insert(wd, Watched::Directory(dir.clone()));
+                }
+                // The directory was removed or replaced in the meantime.
+                Err(ref e) if is_gone(e) => continue,
+                Err(e) => return Err(e.into()),
+            }
+            let entries = match fs::read_dir(&abs_dir) {
+                Ok(entries) => entries,
+                Err(ref e) if is_gone(e) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            for entry in entries {
+                let entry = entry?;
+                let name = entry.file_name();
+                let name = match name.to_str() {
+                    Some(name) => name,
+                    None => continue,
+                };
+                if dir.is_empty() && name == ".hg" {
+                    continue;
+                }
+                let component = match RepoPath::from_str(name) {
+                    Ok(component) => component,
+                    Err(_) => continue,
+                };
+                let mut path = dir.clone();
+                path.push(component);
+                if let Some(ref mut lines) = lines {
+                    lines.push(path.to_string());
+                }
+                let file_type = match entry.file_type() {
+                    Ok(file_type) => file_type,
+                    Err(_) => continue,
+                };
+                // Like the walker, do not look into nested repositories.
+                if file_type.is_dir() && !entry.path().join(".hg").exists() {
+                    pending.push(path);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop tracking a directory that was removed or moved away.
+    fn forget_directory(&mut self, path: &RepoPath) {
+        let prefix = format!("{}/", path);
+        self.watches.retain(|_, watched| match watched {
+            Watched::Directory(dir) => {
+                dir.as_repo_path() != path && !dir.as_str().starts_with(&prefix)
+            }
+            Watched::Journal => true,
+        });
+    }
+
+    fn append(&mut self, lines: &[String]) -> Result<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let mut data = lines.join("\n");
+        data.push('\n');
+        self.changes.write_all(data.as_bytes())?;
+        if self.changes.metadata()?.len() > self.max_changes_size {
+            // Existing watches are still valid. Only the recorded changes are dropped.
+            self.start_journal()?;
+        }
+        Ok(())
+    }
+}
+
+fn is_gone(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::NotFound || error.raw_os_error() == Some(libc::ENOTDIR)
+}
+
+/// Handle of a watcher running in a thread. The watcher stops when this is dropped.
+pub struct WatcherHandle {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl WatcherHandle {
+    /// Stop the watcher and return the error that stopped it, if any.
+    pub fn stop(mut self) -> Result<()> {
+        self.stop_thread()
+    }
+
+    fn stop_thread(&mut self) -> Result<()> {
+        self.stop.store(true, Ordering::Release);
+        match self.thread.take() {
+            Some(thread) => match thread.join() {
+                Ok(result) => result,
+                Err(_) => Err(anyhow::anyhow!("journal watcher thread panicked")),
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for WatcherHandle {
+    fn drop(&mut self) {
+        let _ = self.stop_thread();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use parking_lot::Mutex;
+    use pathmatcher::AlwaysMatcher;
+    use tempfile::tempdir;
+    use treestate::filestate::{FileStateV2, StateFlags};
+    use treestate::treestate::TreeState;
+
+    use crate::filesystem::{ChangeType, PendingChangeResult, PhysicalFileSystem};
+    use crate::journal::{ChangeJournal, JournalCursor};
+
+    fn paths(changes: BTreeSet<RepoPathBuf>) -> Vec<String> {
+        changes.into_iter().map(|p| p.to_string()).collect()
+    }
+
+    fn pending_changes(
+        fs: &PhysicalFileSystem,
+        treestate: &Arc<Mutex<TreeState>>,
+    ) -> Result<Vec<String>> {
+        let mut changes = Vec::new();
+        for change in fs.pending_changes(
+            treestate.clone(),
+            Arc::new(AlwaysMatcher::new()),
+            false,
+            0u64.into(),
+        ) {
+            match change? {
+                PendingChangeResult::File(ChangeType::Changed(path)) => {
+                    changes.push(format!("changed {}", path))
+                }
+                PendingChangeResult::File(ChangeType::Deleted(path)) => {
+                    changes.push(format!("deleted {}", path))
+                }
+                PendingChangeResult::SeenDirectory(_) => {}
+            }
+        }
+        changes.sort();
+        Ok(changes)
+    }
+
+    #[test]
+    fn test_watcher() -> Result<()> {
+        let root = tempdir()?;
+        fs::create_dir_all(root.path().join(".hg/journal"))?;
+        fs::create_dir_all(root.path().join("a"))?;
+        fs::write(root.path().join("a/1"), "1")?;
+        let journal_dir = root.path().join(".hg/journal");
+        let journal = ChangeJournal::new(&journal_dir);
+        let timeout = Duration::from_secs(10);
+
+        let watcher = JournalWatcher::spawn(root.path(), &journal_dir)?;
+        assert!(journal.is_watched());
+        assert!(JournalWatcher::new(root.path(), &journal_dir).is_err());
+        let start = journal.sync(timeout)?.unwrap();
+
+        fs::write(root.path().join("a/1"), "2")?;
+        fs::write(root.path().join("b"), "")?;
+        fs::write(root.path().join(".hg/ignored"), "")?;
+        fs::create_dir_all(root.path().join("c/d"))?;
+        fs::write(root.path().join("c/d/e"), "")?;
+        let end = journal.sync(timeout)?.unwrap();
+        let changes = journal.changes_between(&start, &end)?.unwrap();
+        assert_eq!(paths(changes), vec!["a/1", "b", "c", "c/d", "c/d/e"]);
+
+        // New directories are watched.
+        fs::write(root.path().join("c/d/f"), "")?;
+        fs::rename(root.path().join("a"), root.path().join("g"))?;
+        let next = journal.sync(timeout)?.unwrap();
+        let changes = journal.changes_between(&end, &next)?.unwrap();
+        assert_eq!(paths(changes), vec!["a", "c/d/f", "g", "g/1"]);
+
+        // Moved directories are watched with their new path.
+        fs::remove_file(root.path().join("g/1"))?;
+        let last = journal.sync(timeout)?.unwrap();
+        let changes = journal.changes_between(&next, &last)?.unwrap();
+        assert_eq!(paths(changes), vec!["g/1"]);
+
+        watcher.stop()?;
+        assert!(!journal.is_watched());
+        assert_eq!(journal.sync(timeout)?, None);
+
+        // A new watcher invalidates previous cursors.
+        let _watcher = JournalWatcher::spawn(root.path(), &journal_dir)?;
+        let restarted = journal.sync(timeout)?.unwrap();
+        assert_eq!(journal.changes_between(&last, &restarted)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotate_changes() -> Result<()> {
+        let root = tempdir()?;
+        let journal_dir = root.path().join(".hg/journal");
+        let journal = ChangeJournal::new(&journal_dir);
+        let mut watcher = JournalWatcher::new(root.path(), &journal_dir)?;
+        watcher.max_changes_size = 20;
+        let start = journal.cursor()?.unwrap();
+
+        fs::write(root.path().join("a"), "1")?;
+        watcher.wait_for_events(1000)?;
+        watcher.process_events()?;
+        let small = journal.cursor()?.unwrap();
+        assert_eq!(
+            journal.changes_between(&start, &small)?.map(paths),
+            Some(vec!["a".to_string()])
+        );
+
+        // Going over the limit starts a new journal.
+        fs::write(root.path().join("long-file-name"), "1")?;
+        watcher.wait_for_events(1000)?;
+        watcher.process_events()?;
+        let rotated = journal.cursor()?.unwrap();
+        assert_eq!(journal.changes_between(&small, &rotated)?, None);
+        assert_eq!(fs::metadata(journal_dir.join(CHANGES_FILE))?.len(), 0);
+
+        // Directories are still watched after that.
+        fs::write(root.path().join("b"), "1")?;
+        watcher.wait_for_events(1000)?;
+        watcher.process_events()?;
+        let next = journal.cursor()?.unwrap();
+        assert_eq!(
+            journal.changes_between(&rotated, &next)?.map(paths),
+            Some(vec!["b".to_string()])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_incremental_pending_changes() -> Result<()> {
+        let root = tempdir()?;
+        let state_dir = tempdir()?;
+        let journal_dir = root.path().join(".hg/journal");
+        fs::create_dir_all(root.path().join("dir"))?;
+        for name in ["a", "x", "dir/b", "dir/c"].iter() {
+            fs::write(root.path().join(name), "1")?;
+        }
+        let mut treestate = TreeState::open(state_dir.path().join("tree"), None)?;
+        for name in ["a", "x", "dir/b", "dir/c"].iter() {
+            let mtime = fs::metadata(root.path().join(name))?
+                .modified()?
+                .duration_since(UNIX_EPOCH)?;
+            let state = FileStateV2 {
+                mode: 0o100644,
+                size: 1,
+                mtime: mtime.as_secs() as i64,
+                mtime_nsec: mtime.subsec_nanos(),
+                state: StateFlags::EXIST_P1 | StateFlags::EXIST_NEXT,
+                copied: None,
+            };
+            treestate.insert(name, &state)?;
+        }
+        let treestate = Arc::new(Mutex::new(treestate));
+
+        let _watcher = JournalWatcher::spawn(root.path(), &journal_dir)?;
+        let fs = PhysicalFileSystem::new(root.path().to_path_buf())?
+            .with_journal(ChangeJournal::new(&journal_dir));
+
+        // Without a cursor, the whole working copy is walked.
+        fs::write(root.path().join("a"), "22")?;
+        assert_eq!(pending_changes(&fs, &treestate)?, vec!["changed a"]);
+        let metadata = treestate.lock().get_metadata().to_vec();
+        assert!(JournalCursor::from_metadata(&metadata).is_some());
+        assert!(treestate
+            .lock()
+            .get("a")?
+            .unwrap()
+            .state
+            .intersects(StateFlags::NEED_CHECK));
+
+        // Only changed paths and files reported before are looked at. "x" looks modified in the
+        // treestate but did not change on disk.
+        let mut state = treestate.lock().get("x")?.unwrap().clone();
+        state.size = 2;
+        treestate.lock().insert("x", &state)?;
+        fs::write(root.path().join("u"), "")?;
+        fs::rename(root.path().join("dir"), root.path().join("moved"))?;
+        assert_eq!(
+            pending_changes(&fs, &treestate)?,
+            vec![
+                "changed a",
+                "changed moved/b",
+                "changed moved/c",
+                "changed u",
+                "deleted dir/b",
+                "deleted dir/c",
+            ]
+        );
+
+        // Untracked files that are gone are removed from the treestate.
+        fs::remove_file(root.path().join("u"))?;
+        assert_eq!(
+            pending_changes(&fs, &treestate)?,
+            vec![
+                "changed a",
+                "changed moved/b",
+                "changed moved/c",
+                "deleted dir/b",
+                "deleted dir/c",
+            ]
+        );
+        assert!(treestate.lock().get("u")?.is_none());
+        Ok(())
+    }
+}
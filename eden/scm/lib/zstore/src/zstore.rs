@@ -17,6 +17,7 @@ use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use sha1::{Digest, Sha1};
 use std::borrow::Cow;
+use std::collections::HashSet;
 use std::fmt;
 use std::path::{Path, PathBuf};
 use tracing::{debug_span, info_span, trace_span};
@@ -185,6 +186,50 @@ impl Zstore {
         })
     }
 
+    /// Write blobs listed in `live_ids` to a new store at `dir`.
+    ///
+    /// Other blobs are not copied. Since delta bases might be dropped, delta
+    /// chains are rebuilt using live blobs from the original chains as base
+    /// candidates, following [`DeltaOptions`]. Ids that are not in this store
+    /// are ignored.
+    ///
+    /// This store is not changed. The callsite is responsible for switching
+    /// to the new store.
+    pub fn gc(&self, live_ids: &[Id20], dir: impl AsRef<Path>) -> crate::Result<Zstore> {
+        info_span!("Zstore::gc", live_count = live_ids.len()).in_scope(|| {
+            let live: HashSet<Id20> = live_ids.iter().cloned().collect();
+            let mut new = Zstore::open(dir)?;
+            new.delta_opts = self.delta_opts.clone();
+
+            // Follow the log order so delta bases are inserted before blobs
+            // using them.
+            for entry in self.log.iter() {
+                let entry = entry?;
+                let delta: Delta = mincode::deserialize(&entry)?;
+                let id = delta.id;
+                if !live.contains(&id) || new.contains(id)? {
+                    continue;
+                }
+                let mut candidate_base_ids = Vec::new();
+                let mut base_id = delta.base_id;
+                while base_id != *EMPTY_ID20 {
+                    if live.contains(&base_id) {
+                        candidate_base_ids.push(base_id);
+                    }
+                    base_id = match self.get_delta(base_id)? {
+                        Some(base_delta) => base_delta.base_id,
+                        None => break,
+                    };
+                }
+                let data = self.resolve(delta)?;
+                new.insert(&data, &candidate_base_ids)?;
+            }
+
+            new.flush()?;
+            Ok(new)
+        })
+    }
+
     /// Create a new [`Delta`] using the specified delta base candidate.
     /// Satisfy limitations specified by [`DeltaOptions`].
     /// Return `None` if a suitable delta cannot be created.
@@ -283,10 +328,14 @@ impl Zstore {
         let mut results = self.log.lookup(0, id)?;
         match results.next() {
             None => Ok(None),
-            Some(Ok(bytes)) => {
+            Some(Ok(Cow::Borrowed(bytes))) => {
                 let result = mincode::deserialize(bytes)?;
                 Ok(Some(result))
             }
+            Some(Ok(Cow::Owned(bytes))) => {
+                let result: Delta = mincode::deserialize(&bytes)?;
+                Ok(Some(result.into_owned()))
+            }
             Some(Err(err)) => Err(err.into()),
         }
     }
@@ -384,7 +433,8 @@ impl Zstore {
         }
 
         for entry in self.log.iter() {
-            let id = &self.log.index_func(Self::ID20_INDEX, entry?)?[0];
+            let entry = entry?;
+            let id = &self.log.index_func(Self::ID20_INDEX, &entry)?[0];
             let mut id = Id20::from_slice(id).unwrap();
             let mut chain: Vec<Delta> = Vec::new();
             while id != *EMPTY_ID20 {
@@ -512,6 +562,7 @@ pub fn sha1(data: &[u8]) -> Id20 {
 ///
 /// In general, a larger `n` helps space usage for shorter chains,
 /// a larger `d` helps handling longer chains.
+#[derive(Clone)]
 pub struct DeltaOptions {
     /// Maximum depth of a delta.
     ///
@@ -600,6 +651,20 @@ struct Delta<'a> {
     data: Cow<'a, [u8]>,
 }
 
+impl<'a> Delta<'a> {
+    /// Detach the delta from the buffer it was deserialized from.
+    fn into_owned(self) -> Delta<'static> {
+        Delta {
+            id: self.id,
+            base_id: self.base_id,
+            depth: self.depth,
+            subchain_len: self.subchain_len,
+            chain_bytes: self.chain_bytes,
+            data: Cow::Owned(self.data.into_owned()),
+        }
+    }
+}
+
 // -------- Tests --------
 
 #[cfg(test)]
@@ -672,6 +737,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_gc() {
+        let dir = TempDir::new().unwrap();
+        let mut zstore = Zstore::open(dir.path().join("a")).unwrap();
+        let noise = generate_noise(4000);
+        let mut ids = Vec::new();
+        let mut base_ids = Vec::new();
+        for i in 0..6 {
+            let content = format!("{}{}{}", noise, i, noise);
+            let id = zstore.insert(content.as_bytes(), &base_ids).unwrap();
+            base_ids = vec![id];
+            ids.push(id);
+        }
+        let unrelated = zstore.insert(b"unrelated", &[]).unwrap();
+        let size = zstore.flush().unwrap();
+
+        // Keep 2 blobs. The delta base of ids[4] (ids[3]) is dropped.
+        let live = [ids[1], ids[4]];
+        let new = zstore.gc(&live, dir.path().join("b")).unwrap();
+        for (i, id) in ids.iter().enumerate() {
+            let content = format!("{}{}{}", noise, i, noise);
+            assert_eq!(zstore.get(*id).unwrap().unwrap(), content.as_bytes());
+            if live.contains(id) {
+                assert_eq!(new.get(*id).unwrap().unwrap(), content.as_bytes());
+            } else {
+                assert!(!new.contains(*id).unwrap());
+            }
+        }
+        assert!(!new.contains(unrelated).unwrap());
+
+        // ids[4] uses the live ids[1] as its delta base.
+        assert_eq!(new.get_delta(ids[4]).unwrap().unwrap().base_id, ids[1]);
+
+        // The new store is smaller and persisted.
+        let mut new = Zstore::open(dir.path().join("b")).unwrap();
+        assert!(new.flush().unwrap() < size);
+        assert_eq!(
+            new.get(ids[4]).unwrap().unwrap(),
+            zstore.get(ids[4]).unwrap().unwrap()
+        );
+    }
+
     /// Generate noise that is hard to compress.
     fn generate_noise(approximated_len: usize) -> String {
         (0..(approximated_len / 41))
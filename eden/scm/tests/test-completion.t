@@ -88,6 +88,7 @@ Show debug commands if there are no other candidates
   debugapplystreamclonebundle
   debugbenchmarkrevsets
   debugbindag
+  debugblackboxquery
   debugbuilddag
   debugbundle
   debugcapabilities
@@ -136,6 +137,8 @@ Show debug commands if there are no other candidates
   debugmanifestdirs
   debugmergestate
   debugmetalog
+  debugmetalogdiff
+  debugmetalogrestore
   debugmetalogroots
   debugmutation
   debugmutationfromobsmarkers
@@ -377,6 +380,7 @@ Show all commands + options
   debugapplystreamclonebundle: 
   debugbenchmarkrevsets: rev-x, rev-y, expr, default, multi-backend
   debugbindag: rev, output
+  debugblackboxquery: time-range, event-type, min-duration, pattern, sort, limit, format, output-path
   debugbuilddag: mergeable-file, overwritten-file, new-file
   debugbundle: all, part-type, spec
   debugcapabilities: 
@@ -424,6 +428,8 @@ Show all commands + options
   debugmanifestdirs: rev
   debugmergestate: 
   debugmetalog: time-range
+  debugmetalogdiff: stat, style, template
+  debugmetalogrestore: 
   debugmetalogroots: style, template
   debugmutation: rev, successors, time-range
   debugmutationfromobsmarkers: 
@@ -0,0 +1,48 @@
+#chg-compatible
+
+Show configs described by the config schema, with their effective values:
+
+  $ cat > $TESTTMP/hgrc << 'EOF'
+  > [blackbox]
+  > maxsize = 1 MB
+  > [pager]
+  > interface = fullscreen
+  > EOF
+  $ export HGRCPATH=$TESTTMP/hgrc
+
+  $ hg debugconfigschema --config permissions.chmod-file=420
+  configs.generationtime=  # not set
+  remotefilelog.reponame=  # not set
+  ui.merge=  # not set
+  ui.username=  # not set
+  blackbox.maxsize=1 MB  # $TESTTMP/hgrc:2
+  blackbox.maxfiles=3  # default
+  format.use-symlink-atomic-write=false  # default
+  pager.interface=fullscreen  # $TESTTMP/hgrc:4
+  pager.scroll-past-eof=false  # default
+  permissions.chmod-dir=-1  # default
+  permissions.chmod-file=420  # --config
+
+  $ hg debugconfigschema --doc | grep -A2 '^blackbox.maxsize'
+  blackbox.maxsize=1 MB  # $TESTTMP/hgrc:2
+      (bytecount; default: 4 KB)
+      maximum size of a blackbox log file before it gets rotated
+
+No warnings for configs matching the schema:
+
+  $ hg debugconfigschema --check
+
+Report typos and invalid values:
+
+  $ cat >> $TESTTMP/hgrc << 'EOF'
+  > scroll-past-eof = maybe
+  > [blackbox]
+  > maxfile = 2
+  > [pagr]
+  > interface = hybrid
+  > EOF
+  $ hg debugconfigschema --check
+  $TESTTMP/hgrc:7: unknown config blackbox.maxfile (did you mean blackbox.maxfiles?)
+  $TESTTMP/hgrc:5: pager.scroll-past-eof: invalid bool "maybe": invalid bool: maybe
+  $TESTTMP/hgrc:9: unknown section [pagr] (did you mean [pager]?)
+  [1]
@@ -0,0 +1,73 @@
+#chg-compatible
+
+Test undoing a pull using debugmetalogdiff and debugmetalogrestore.
+
+  $ setconfig experimental.metalog=1 metalog.track-config=0
+  $ configure narrowheads
+  $ enable remotenames
+  $ setconfig remotenames.selectivepull=1 remotenames.selectivepulldefault=master
+  $ setconfig "templatealias.names=\"{if(remotenames, ' {remotenames}')}{if(bookmarks, ' {bookmarks}')}\""
+
+  $ newrepo server
+  $ drawdag << 'EOS'
+  > B C
+  > |/
+  > A
+  > EOS
+  $ hg bookmark -r $A master
+
+  $ cd $TESTTMP
+  $ hg clone -q server client
+  $ cd client
+  $ hg bookmark -r $A local
+  $ hg log -G -T '{desc}{names}'
+  @  A default/master local
+
+  $ BEFORE=$(hg debugmetalogroots -T '{root}\n' | head -1)
+
+Pull moves master and adds a draft head:
+
+  $ hg --cwd $TESTTMP/server bookmark -fr $B master
+  $ hg pull -q -r $C
+  $ hg log -G -T '{desc}{names}'
+  o  C
+  │
+  │ o  B default/master
+  ├─╯
+  @  A local
+
+  $ AFTER=$(hg debugmetalogroots -T '{root}\n' | head -1)
+
+The pull changed remote bookmarks and visible heads, but not local bookmarks:
+
+  $ hg debugmetalogdiff --stat $BEFORE $AFTER
+  remotenames: changed
+  tip: changed
+  visibleheads: changed
+  $ hg debugmetalogdiff $BEFORE $AFTER | grep master
+  -* bookmarks default/master (glob)
+  +* bookmarks default/master (glob)
+
+Undo the pull:
+
+  $ hg debugmetalogrestore $BEFORE
+  restored metalog to * as * (glob)
+  $ hg log -G -T '{desc}{names}'
+  @  A default/master local
+
+  $ hg log -r 'heads(draft())' -T '{desc}\n'
+  $ hg debugmetalogdiff --stat $BEFORE $(hg debugmetalogroots -T '{root}\n' | head -1)
+
+The state after the restore is a new root, so the pull can be redone:
+
+  $ hg debugmetalogrestore $AFTER
+  restored metalog to * as * (glob)
+  $ hg log -G -T '{desc}{names}'
+  o  C
+  │
+  │ o  B default/master
+  ├─╯
+  @  A local
+
+  $ hg log -r 'heads(draft())' -T '{desc}\n'
+  C
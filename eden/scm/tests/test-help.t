@@ -936,6 +936,8 @@ Test list of internal help commands
    debugbenchmarkrevsets
                  benchmark revsets
    debugbindag   serialize dag to a compat binary format
+   debugblackboxquery
+                 query and export blackbox events
    debugbuilddag
                  builds a repo with a given DAG from scratch in the current
                  empty repo
@@ -1019,6 +1021,10 @@ Test list of internal help commands
    debugmergestate
                  print merge state
    debugmetalog  show changes in commit graph over time
+   debugmetalogdiff
+                 show changes between metalog roots
+   debugmetalogrestore
+                 restore metalog to the state of an older root
    debugmetalogroots
                  list roots stored in metalog
    debugmutation
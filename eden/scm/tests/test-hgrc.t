@@ -83,6 +83,9 @@ issue1829: wrong indentation
   $ echo '%include $FAKEPATH/no-such-file' > $HGRC
   $ hg version
   EdenSCM * (glob)
+  $ echo '%include? $FAKEPATH/no-such-file' > $HGRC
+  $ hg version
+  EdenSCM * (glob)
   $ unset FAKEPATH
 
 make sure global options given on the cmdline take precedence
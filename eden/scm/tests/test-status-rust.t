@@ -0,0 +1,157 @@
+#chg-compatible
+
+Compare the Rust status for non-EdenFS working copies with the Python status.
+"(python status)" is printed if the Rust status falls back to Python.
+
+  $ cat > $TESTTMP/pystatus.py << 'EOF'
+  > from edenscm.mercurial import commands, extensions
+  > def uisetup(ui):
+  >     def status(orig, ui, *args, **opts):
+  >         ui.write_err("(python status)\n")
+  >         return orig(ui, *args, **opts)
+  >     extensions.wrapcommand(commands.table, "status", status)
+  > EOF
+  $ setglobalconfig extensions.pystatus=$TESTTMP/pystatus.py
+
+  $ compare() {
+  >   hg status "$@" > $TESTTMP/rust.out
+  >   hg status --config status.use-rust=false "$@" > $TESTTMP/python.out 2> /dev/null
+  >   cat $TESTTMP/rust.out
+  >   cmp -s $TESTTMP/rust.out $TESTTMP/python.out || echo "(different from python status)"
+  > }
+
+Files with ambiguous mtimes are compared by Python. Use old mtimes so Python
+records them as clean:
+
+  $ newrepo
+  $ mkdir -p a/b c
+  $ echo 1 > a/b/x
+  $ echo 1 > a/y
+  $ echo 1 > c/z
+  $ echo 1 > top
+  $ echo 'ignored*' > .gitignore
+  $ hg commit -Aqm base
+  $ touch -t 200001010000 .gitignore a/b/x a/y c/z top
+  $ hg status --config status.use-rust=false 2> /dev/null
+
+  $ compare
+  $ compare -c
+  C .gitignore
+  C a/b/x
+  C a/y
+  C c/z
+  C top
+
+  $ echo 22 > a/y
+  $ echo 1 > new
+  $ hg add new
+  $ hg cp -q a/b/x a/b/copy
+  $ hg rm -q c/z
+  $ rm top
+  $ echo 1 > unknown
+  $ echo 1 > ignored1
+
+  $ compare
+  M a/y
+  A a/b/copy
+  A new
+  R c/z
+  ! top
+  ? unknown
+  $ compare -C
+  M a/y
+  A a/b/copy
+    a/b/x
+  A new
+  R c/z
+  ! top
+  ? unknown
+  $ compare -i
+  I ignored1
+  $ compare -A
+  M a/y
+  A a/b/copy
+    a/b/x
+  A new
+  R c/z
+  ! top
+  ? unknown
+  I ignored1
+  C .gitignore
+  C a/b/x
+  $ compare -n0 -ma | xargs -0 echo
+  a/y a/b/copy new
+
+Patterns, including glob alternatives:
+
+  $ compare a
+  M a/y
+  A a/b/copy
+  $ compare 'glob:a/{b,y}'
+  M a/y
+  A a/b/copy
+  $ compare -X a/b -X 'glob:{c,top}'
+  M a/y
+  A new
+  ? unknown
+  $ cd a
+  $ compare -I 'glob:{b,y}'
+  M y
+  A b/copy
+  $ compare b ../new
+  A b/copy
+  A ../new
+  $ cd ..
+
+Sparse checkouts: files outside the sparse profile are ignored.
+
+  $ newrepo
+  $ enable sparse
+  $ mkdir a b
+  $ echo 1 > a/x
+  $ echo 1 > b/y
+  $ hg commit -Aqm base
+  $ hg sparse include a
+  $ touch -t 200001010000 a/x
+  $ hg status --config status.use-rust=false 2> /dev/null
+  $ mkdir -p b
+  $ echo 1 > a/unknown
+  $ echo 1 > b/unknown
+
+  $ compare
+  ? a/unknown
+  $ compare -i
+  I b/unknown
+  $ compare -A
+  ? a/unknown
+  I b/unknown
+  C a/x
+
+  $ cat > .hg/sparse << 'EOF'
+  > [include]
+  > a
+  > [exclude]
+  > a/unknown
+  > EOF
+  $ compare -ui
+  I a/unknown
+  I b/unknown
+
+Temporarily included files are not ignored:
+
+  $ echo b/unknown > .hg/tempsparse
+  $ compare -ui
+  ? b/unknown
+  I a/unknown
+
+Sparse profiles are read from the commit, so Python handles them:
+
+  $ cat > .hg/sparse << 'EOF'
+  > %include profile
+  > [include]
+  > a
+  > EOF
+  $ rm .hg/tempsparse
+  $ compare
+  (python status)
+  ? a/unknown
@@ -186,6 +186,21 @@ class testtreestate(unittest.TestCase):
         self.assertTrue("b" in tree)
         self.assertEqual(tree.getmetadata(), b"2")
 
+    def testlargesizeandmtime(self):
+        treepath = os.path.join(testtmp, "large")
+        tree = treestate.treestate(treepath, 0)
+        size = 5 << 32
+        mtime = 1 << 33
+        tree.insert("a", treestate.EXIST_P1, 0o644, size, mtime, None, 123456789)
+        rootid = tree.flush()
+
+        tree = treestate.treestate(treepath, rootid)
+        self.assertEqual(
+            tree.get("a", None), (treestate.EXIST_P1, 0o644, size, mtime, None)
+        )
+        self.assertEqual(tree.getmtimensec("a"), 123456789)
+        self.assertEqual(tree.getmtimensec("b"), None)
+
     def testsaveas(self):
         treepath = os.path.join(testtmp, "saveas")
         tree = treestate.treestate(treepath, 0)